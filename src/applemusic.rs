@@ -0,0 +1,463 @@
+// Import necessary modules and crates
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::{Method, StatusCode, Url};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::auth::{generate_state, open_in_browser};
+use crate::backup::{save_backup, Backup, BackupItem};
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::http::send;
+use crate::matching::*;
+use crate::models::*;
+use crate::secrets::{load_secret, save_secret};
+use crate::service::{MusicService, NewPlaylist, ServicePlaylist};
+use crate::tokens::now;
+
+// Endpoint of the Apple Music API
+const APPLE_MUSIC_API_URL: &str = "https://api.music.apple.com";
+
+// MusicKit JS, loaded by the local authorization page
+const MUSICKIT_JS_URL: &str = "https://js-cdn.music.apple.com/musickit/v3/musickit.js";
+
+// Prefix of the track URIs, to tell Apple Music songs apart from Spotify tracks in the history
+const URI_PREFIX: &str = "applemusic:song:";
+
+// Lifetime of the developer tokens signed for a run, in seconds (Apple accepts up to six months)
+const DEVELOPER_TOKEN_LIFETIME: u64 = 12 * 60 * 60;
+
+// Number of songs of a library playlist read per call, the most the API allows
+const LIBRARY_PAGE_SIZE: &str = "100";
+
+// What a run needs on top of the developer token to act on behalf of the user
+struct Session {
+    user_token: String,
+    storefront: String,
+}
+
+// Apple Music, through the Apple Music API: songs are searched in the catalog and added to library playlists
+// The developer token is the context's access token, the user token and storefront are kept here once authorized.
+#[derive(Default)]
+pub struct AppleMusic {
+    session: OnceLock<Session>,
+}
+
+// Function to sign a developer token (an ES256 JWT) with the MusicKit private key of an Apple Developer account
+pub fn developer_token(team_id: &str, key_id: &str, private_key_pem: &[u8], issued_at: u64) -> Result<String, PilotError> {
+    let header = serde_json::json!({ "alg": "ES256", "kid": key_id });
+    let claims = serde_json::json!({ "iss": team_id, "iat": issued_at, "exp": issued_at + DEVELOPER_TOKEN_LIFETIME });
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header.to_string()),
+        URL_SAFE_NO_PAD.encode(claims.to_string()),
+    );
+
+    let key = PKey::private_key_from_pem(private_key_pem).map_err(|e| format!("Invalid MusicKit private key: {}", e))?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key).map_err(|e| e.to_string())?;
+    signer.update(signing_input.as_bytes()).map_err(|e| e.to_string())?;
+    let der = signer.sign_to_vec().map_err(|e| e.to_string())?;
+
+    // OpenSSL gives a DER structure, a JWT wants r and s side by side on 32 bytes each
+    let signature = EcdsaSig::from_der(&der).map_err(|e| e.to_string())?;
+    let mut raw = signature.r().to_vec_padded(32).map_err(|e| e.to_string())?;
+    raw.extend(signature.s().to_vec_padded(32).map_err(|e| e.to_string())?);
+    Ok(format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(raw)))
+}
+
+// Function to sign a developer token from the settings
+fn config_developer_token(config: &Config) -> Result<String, PilotError> {
+    let team_id = config.apple_team_id.as_deref().ok_or("apple team id not set")?;
+    let key_id = config.apple_key_id.as_deref().ok_or("apple key id not set")?;
+    let key_path = config.apple_private_key_path.as_deref().ok_or("apple private key path not set")?;
+    let pem = fs::read(key_path).map_err(|e| format!("{}: {}", key_path, e))?;
+    developer_token(team_id, key_id, &pem, now())
+}
+
+// Function to build the local page asking MusicKit JS for a Music-User-Token and sending it back to `/token`
+fn authorization_page(developer_token: &str, state: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>PlaylistPilot</title><script src="{}" async></script></head>
+<body>
+<button id="authorize" disabled>Authorize PlaylistPilot on Apple Music</button>
+<script>
+document.addEventListener('musickitloaded', async () => {{
+  const music = await MusicKit.configure({{ developerToken: '{}', app: {{ name: 'PlaylistPilot', build: '1' }} }});
+  const button = document.getElementById('authorize');
+  button.disabled = false;
+  button.onclick = async () => {{
+    const token = await music.authorize();
+    location.href = '/token?state={}&value=' + encodeURIComponent(token);
+  }};
+}});
+</script>
+</body>
+</html>"#,
+        MUSICKIT_JS_URL, developer_token, state
+    )
+}
+
+// Function to write a whole HTTP response to the browser
+fn respond(stream: &mut impl Write, status: &str, content_type: &str, body: &str) {
+    let _ = stream.write_all(
+        format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}", status, content_type, body.len(), body).as_bytes(),
+    );
+}
+
+// Function to get a Music-User-Token from the user through a local page running MusicKit JS
+// Apple has no OAuth flow for the Apple Music API: only MusicKit, in a browser, can ask the user for their authorization.
+fn authorize_user(config: &Config, developer_token: &str, headless: bool) -> Result<String, PilotError> {
+    let page_url = Url::parse(&config.apple_redirect_uri).map_err(|e| format!("Invalid Apple redirect URI: {}", e))?;
+    match page_url.host_str() {
+        Some("localhost") | Some("127.0.0.1") => {},
+        _ => return Err("The Apple redirect URI does not point to this machine.".into()),
+    }
+    let port = page_url.port_or_known_default().unwrap_or(80);
+    let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| format!("Could not listen on port {}: {}", port, e))?;
+    listener.set_nonblocking(true)?;
+
+    let auth = generate_state();
+    if headless {
+        println!("Open this URL to authorize Apple Music: {}", page_url);
+        println!("If this machine is remote, forward the port first (e.g. `ssh -L {}:localhost:{} <server>`).", port, port);
+    } else {
+        match open_in_browser(page_url.as_str()) {
+            Ok(_) => println!("Opened the Apple Music authorization page in your browser. If nothing happened, go to this URL: {}", page_url),
+            Err(_) => println!("Go to this URL to authorize Apple Music: {}", page_url),
+        }
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(300);
+    while Instant::now() < deadline {
+        let mut stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(200));
+                continue;
+            },
+            Err(e) => return Err(e.into()),
+        };
+        stream.set_nonblocking(false)?;
+
+        let mut request_line = String::new();
+        BufReader::new(&stream).read_line(&mut request_line)?;
+        let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+
+        if path == "/" {
+            respond(&mut stream, "200 OK", "text/html; charset=utf-8", &authorization_page(developer_token, &auth.state));
+            continue;
+        }
+        if !path.starts_with("/token?") {
+            respond(&mut stream, "404 Not Found", "text/plain", "");
+            continue;
+        }
+
+        let callback = Url::parse(&format!("http://localhost{}", path)).map_err(|e| e.to_string())?;
+        let param = |name: &str| callback.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.into_owned());
+        if param("state").as_deref() != Some(auth.state.as_str()) {
+            respond(&mut stream, "400 Bad Request", "text/plain", "State mismatch, the request did not come from this authorization.");
+            continue;
+        }
+        let Some(token) = param("value").filter(|token| !token.is_empty()) else {
+            respond(&mut stream, "400 Bad Request", "text/plain", "Apple Music did not give a token.");
+            continue;
+        };
+        let page = format!("Authorization received (code {}). You can close this tab.", auth.code);
+        respond(&mut stream, "200 OK", "text/plain; charset=utf-8", &page);
+        return Ok(token);
+    }
+    Err("Timed out waiting for the Apple Music authorization.".into())
+}
+
+// Function to describe a catalog song as a track
+fn catalog_track(song: AppleSong) -> Track {
+    let attributes = song.attributes;
+    Track {
+        name: attributes.name,
+        artists: vec![Artist { name: attributes.artist_name, id: None }],
+        uri: format!("{}{}", URI_PREFIX, song.id),
+        is_local: false,
+        external_ids: ExternalIds { isrc: attributes.isrc },
+        is_playable: None,
+        duration_ms: attributes.duration_in_millis,
+        popularity: None,
+        album: Album {
+            name: attributes.album_name,
+            release_date: attributes.release_date,
+            images: attributes
+                .artwork
+                .map(|artwork| Image { url: artwork.url.replace("{w}", "120").replace("{h}", "120"), width: Some(120) })
+                .into_iter()
+                .collect(),
+        },
+        explicit: attributes.content_rating.as_deref() == Some("explicit"),
+    }
+}
+
+// Function to describe a song of a library playlist as a track, None when it isn't in the catalog (e.g. an upload)
+// Library songs have their own IDs, the catalog one is used so they compare with search results.
+fn library_track(song: AppleSong) -> Option<Track> {
+    let catalog_id = song.attributes.play_params.as_ref()?.catalog_id.clone()?;
+    Some(catalog_track(AppleSong { id: catalog_id, ..song }))
+}
+
+// Function to get the catalog ID of a track URI
+fn song_id(uri: &str) -> &str {
+    uri.strip_prefix(URI_PREFIX).unwrap_or(uri)
+}
+
+impl AppleMusic {
+    // Function to prepare a call to the Apple Music API with the developer and user tokens
+    fn request(&self, ctx: &Context, method: Method, path: &str) -> RequestBuilder {
+        let user_token = self.session.get().map(|session| session.user_token.as_str()).unwrap_or_default();
+        ctx.client
+            .request(method, format!("{}{}", APPLE_MUSIC_API_URL, path))
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+            .header("Music-User-Token", user_token)
+    }
+
+    // Function to call the Apple Music API, None when the resource doesn't exist
+    fn get<T: serde::de::DeserializeOwned>(&self, ctx: &Context, path: &str, params: &[(&str, &str)]) -> Result<Option<T>, PilotError> {
+        let response = send(self.request(ctx, Method::GET, path).query(params))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(PilotError::from_status(response.status(), format!("Error calling Apple Music: {}", response.status())));
+        }
+        response.json().map(Some).map_err(|e| format!("Failed to parse the Apple Music response: {}", e).into())
+    }
+
+    // Function to get the storefront of the session, which catalog searches go to
+    fn storefront(&self) -> &str {
+        self.session.get().map(|session| session.storefront.as_str()).unwrap_or("us")
+    }
+
+    // Function to find the catalog song with an ISRC, if it is confident enough for the requested song
+    fn search_isrc(&self, ctx: &Context, song: &Song, isrc: &str, filter: &TrackFilter) -> Result<Option<TrackMatch>, PilotError> {
+        let path = format!("/v1/catalog/{}/songs", self.storefront());
+        let songs: Option<AppleSongs> = self.get(ctx, &path, &[("filter[isrc]", isrc.trim())])?;
+        let candidates: Vec<Track> = songs
+            .map(|songs| songs.data)
+            .unwrap_or_default()
+            .into_iter()
+            .map(catalog_track)
+            .filter(|candidate| filter.allows(candidate))
+            .collect();
+        Ok(best_candidate(&candidates, &song.artist, &song.name)
+            .filter(|found| found.confidence >= ctx.config.min_confidence)
+            .map(|found| TrackMatch { confidence: 1.0, ..found }))
+    }
+}
+
+// Function to check the answer of a write call
+fn check_write(response: Result<Response, reqwest::Error>, action: &str) -> Result<Response, PilotError> {
+    match response {
+        Ok(resp) if resp.status().is_success() => Ok(resp),
+        Ok(resp) => Err(PilotError::from_status(resp.status(), format!("Error {}: {}", action, resp.status()))),
+        Err(e) => Err(format!("Error {}: {}", action, e).into()),
+    }
+}
+
+impl MusicService for AppleMusic {
+    fn name(&self) -> &'static str {
+        "Apple Music"
+    }
+
+    // Reading a library playlist needs the user token too, so a dry run asks for it as well
+    // The user token is checked against the account's storefront, and asked for again once Apple stops accepting it.
+    fn access_token(&self, client: &Client, config: &Config, headless: bool, _read_only: bool) -> Result<String, PilotError> {
+        let developer_token = config_developer_token(config)?;
+        let secret_name = config.storage_key("apple-music-user-token");
+        let mut user_token = config.apple_music_user_token.clone().or_else(|| load_secret(&secret_name));
+
+        loop {
+            let token = match user_token.take() {
+                Some(token) => token,
+                None => {
+                    let token = authorize_user(config, &developer_token, headless)?;
+                    if let Err(e) = save_secret(&secret_name, &token) {
+                        warn!("Could not save the Apple Music user token: {}", e);
+                    }
+                    token
+                },
+            };
+
+            let response = send(
+                client
+                    .get(format!("{}/v1/me/storefront", APPLE_MUSIC_API_URL))
+                    .header("Authorization", format!("Bearer {}", developer_token))
+                    .header("Music-User-Token", &token)
+            )?;
+            match response.status() {
+                status if status.is_success() => {
+                    let storefronts: AppleStorefronts = response.json()?;
+                    let storefront = config
+                        .apple_storefront
+                        .clone()
+                        .or_else(|| storefronts.data.into_iter().next().map(|storefront| storefront.id))
+                        .unwrap_or_else(|| "us".to_string());
+                    let _ = self.session.set(Session { user_token: token, storefront });
+                    return Ok(developer_token);
+                },
+                StatusCode::UNAUTHORIZED => {
+                    return Err(PilotError::Auth("Apple Music refused the developer token, check the MusicKit key settings.".to_string()))
+                },
+                StatusCode::FORBIDDEN if config.apple_music_user_token.is_none() => {
+                    warn!("The Apple Music user token is no longer valid, authorizing again.");
+                },
+                status => return Err(PilotError::from_status(status, format!("Failed to check the Apple Music user token: {}", status))),
+            }
+        }
+    }
+
+    // Songs that aren't in the catalog (uploads, songs removed from Apple Music) are skipped
+    fn get_playlist(&self, ctx: &Context, playlist_id: &str) -> Result<ServicePlaylist, PilotError> {
+        let playlists: Option<AppleLibraryPlaylists> = self.get(ctx, &format!("/v1/me/library/playlists/{}", playlist_id), &[])?;
+        let name = playlists
+            .and_then(|playlists| playlists.data.into_iter().next())
+            .map(|playlist| playlist.attributes.name)
+            .ok_or_else(|| format!("Playlist not found: {}", playlist_id))?;
+
+        // The tracks of an empty playlist are answered with a 404
+        let mut songs = Vec::new();
+        let mut path = format!("/v1/me/library/playlists/{}/tracks", playlist_id);
+        loop {
+            let params: &[(&str, &str)] = if songs.is_empty() { &[("limit", LIBRARY_PAGE_SIZE)] } else { &[] };
+            let Some(page) = self.get::<AppleSongs>(ctx, &path, params)? else { break };
+            songs.extend(page.data);
+            match page.next {
+                Some(next) => path = next,
+                None => break,
+            }
+        }
+
+        let length = songs.len();
+        let tracks: Vec<Track> = songs.into_iter().filter_map(library_track).collect();
+        let skipped = length - tracks.len();
+        Ok(ServicePlaylist { name, snapshot_id: None, length, tracks, skipped })
+    }
+
+    // A song with an ISRC is looked up by it first, then the catalog is searched by artist and title
+    fn search_song(&self, ctx: &Context, song: &Song, filter: &TrackFilter) -> Result<TrackMatch, PilotError> {
+        if let Some(isrc) = &song.isrc {
+            if let Some(found) = self.search_isrc(ctx, song, isrc, filter)? {
+                return Ok(found);
+            }
+        }
+
+        let term = format!("{} {}", song.artist, song.name);
+        let path = format!("/v1/catalog/{}/search", self.storefront());
+        let results: Option<AppleSearchResponse> = self.get(ctx, &path, &[("term", &term), ("types", "songs"), ("limit", "10")])?;
+        let candidates: Vec<Track> = results
+            .and_then(|results| results.results.songs)
+            .map(|songs| songs.data)
+            .unwrap_or_default()
+            .into_iter()
+            .map(catalog_track)
+            .filter(|candidate| filter.allows(candidate) && !is_other_recording(candidate, &song.name))
+            .collect();
+        best_candidate(&candidates, &song.artist, &song.name).ok_or_else(|| {
+            if filter.is_active() {
+                "No result meeting the filters found for the specified artist and track.".into()
+            } else {
+                "No result found for the specified artist and track.".into()
+            }
+        })
+    }
+
+    fn create_playlist(&self, ctx: &Context, name: &str, description: &str) -> Result<NewPlaylist, PilotError> {
+        let body = AppleCreatePlaylistRequest {
+            attributes: ApplePlaylistAttributes { name: name.to_string(), description: description.to_string() },
+        };
+        let response = check_write(
+            send(self.request(ctx, Method::POST, "/v1/me/library/playlists").json(&body)),
+            "creating playlist",
+        )?;
+        let created: AppleLibraryPlaylists = response.json().map_err(|e| format!("Failed to parse the Apple Music response: {}", e))?;
+        let id = created.data.into_iter().next().map(|playlist| playlist.id).ok_or("Apple Music did not return the new playlist")?;
+        let url = format!("https://music.apple.com/library/playlist/{}", id);
+        Ok(NewPlaylist { id, url })
+    }
+
+    // Apple Music only appends to library playlists, --position is refused before the run starts
+    fn add_to_playlist(&self, ctx: &Context, playlist_id: &str, uris: Vec<String>, _position: Option<usize>) -> Result<(), PilotError> {
+        let body = AppleTracksRequest {
+            data: uris.iter().map(|uri| AppleResource { id: song_id(uri).to_string(), kind: "songs".to_string() }).collect(),
+        };
+        check_write(
+            send(self.request(ctx, Method::POST, &format!("/v1/me/library/playlists/{}/tracks", playlist_id)).json(&body)),
+            "adding tracks to playlist",
+        )?;
+        Ok(())
+    }
+
+    fn backup_playlist(&self, ctx: &Context, playlist_id: &str) -> Result<PathBuf, PilotError> {
+        let playlist = self.get_playlist(ctx, playlist_id)?;
+        save_backup(&Backup {
+            playlist_id: playlist_id.to_string(),
+            snapshot_id: String::new(),
+            created_at: now(),
+            items: playlist
+                .tracks
+                .into_iter()
+                .map(|track| BackupItem {
+                    uri: track.uri,
+                    name: track.name,
+                    artists: track.artists.into_iter().map(|a| a.name).collect(),
+                    is_local: false,
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::bn::BigNum;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::nid::Nid;
+    use openssl::sign::Verifier;
+
+    #[test]
+    fn developer_token_is_a_verifiable_es256_jwt() {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let pem = key.private_key_to_pem_pkcs8().unwrap();
+
+        let token = developer_token("TEAM", "KEY", &pem, 1_700_000_000).unwrap();
+        let parts: Vec<&str> = token.split('.').collect();
+        assert_eq!(parts.len(), 3);
+        let header: serde_json::Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[0]).unwrap()).unwrap();
+        assert_eq!((header["alg"].as_str(), header["kid"].as_str()), (Some("ES256"), Some("KEY")));
+        let claims: serde_json::Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[1]).unwrap()).unwrap();
+        assert_eq!(claims["iss"].as_str(), Some("TEAM"));
+
+        let raw = URL_SAFE_NO_PAD.decode(parts[2]).unwrap();
+        assert_eq!(raw.len(), 64);
+        let signature = EcdsaSig::from_private_components(
+            BigNum::from_slice(&raw[..32]).unwrap(),
+            BigNum::from_slice(&raw[32..]).unwrap(),
+        )
+        .unwrap();
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &key).unwrap();
+        verifier.update(format!("{}.{}", parts[0], parts[1]).as_bytes()).unwrap();
+        assert!(verifier.verify(&signature.to_der().unwrap()).unwrap());
+    }
+}
@@ -0,0 +1,181 @@
+// Import necessary modules and crates
+use serde::Serialize;
+use std::fmt::Debug;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context as LayerContext, Layer};
+
+use crate::error::PilotError;
+use crate::history::Suggestion;
+
+// Most warnings and errors kept for one run, the first ones
+const MAX_PROBLEMS: usize = 200;
+
+// Warnings and errors logged since the current run started, for its audit record
+static PROBLEMS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+// Layer of the logger keeping the warnings and errors for the audit record, whatever RUST_LOG shows
+pub struct AuditLayer;
+
+// Visitor getting the message of a log event
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{:?}", value);
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for AuditLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: LayerContext<'_, S>) {
+        // More verbose levels compare greater, so these are warnings and errors
+        if *event.metadata().level() > Level::WARN {
+            return;
+        }
+        let mut visitor = MessageVisitor(String::new());
+        event.record(&mut visitor);
+        if let Ok(mut problems) = PROBLEMS.lock() {
+            if problems.len() < MAX_PROBLEMS {
+                problems.push(visitor.0);
+            }
+        }
+    }
+}
+
+// Function to forget the warnings and errors logged so far, when a run starts
+pub fn clear_problems() {
+    if let Ok(mut problems) = PROBLEMS.lock() {
+        problems.clear();
+    }
+}
+
+// Function to put back warnings and errors taken earlier, before those logged since, for runs made of several parts
+pub fn restore_problems(earlier: Vec<String>) {
+    if let Ok(mut problems) = PROBLEMS.lock() {
+        let later = std::mem::replace(&mut *problems, earlier);
+        problems.extend(later);
+        problems.truncate(MAX_PROBLEMS);
+    }
+}
+
+// Function to take the warnings and errors logged since the run started
+pub fn take_problems() -> Vec<String> {
+    PROBLEMS.lock().map(|mut problems| std::mem::take(&mut *problems)).unwrap_or_default()
+}
+
+// The track a suggestion matched, as recorded in the audit log
+#[derive(Serialize)]
+pub struct AuditMatch {
+    pub uri: String,
+    pub name: String,
+    pub artists: Vec<String>,
+    pub confidence: f64,
+}
+
+// One suggestion of a run with the decision taken on it
+#[derive(Serialize)]
+pub struct AuditSuggestion {
+    pub name: String,
+    pub artist: String,
+    pub source: &'static str,
+    pub status: &'static str,
+    // Why it wasn't used, None for the songs that were
+    pub reason: Option<&'static str>,
+    pub matched: Option<AuditMatch>,
+}
+
+impl AuditSuggestion {
+    // Function to record a suggestion and why it was left out
+    pub fn new(suggestion: &Suggestion, reason: Option<&'static str>) -> AuditSuggestion {
+        AuditSuggestion {
+            name: suggestion.song.name.clone(),
+            artist: suggestion.song.artist.clone(),
+            source: suggestion.source.as_str(),
+            status: suggestion.status.as_str(),
+            reason,
+            matched: suggestion.found.as_ref().map(|found| AuditMatch {
+                uri: found.uri.clone(),
+                name: found.name.clone(),
+                artists: found.artists.clone(),
+                confidence: found.confidence,
+            }),
+        }
+    }
+}
+
+// Everything about one run, one line of the audit log
+#[derive(Default, Serialize)]
+pub struct AuditRecord {
+    // RFC 3339 dates in UTC
+    pub started_at: String,
+    pub finished_at: String,
+    pub profile: Option<String>,
+    // Command line of the run, without the program name
+    pub args: Vec<String>,
+    pub service: String,
+    // Names of the seeds
+    pub seeds: Vec<String>,
+    // Name of the playlist the songs were written to, and its ID once known
+    pub target: Option<String>,
+    pub playlist_id: Option<String>,
+    pub dry_run: bool,
+    // Fingerprint of the prompt sent to the LLM
+    pub prompt_hash: Option<String>,
+    pub suggestions: Vec<AuditSuggestion>,
+    // Warnings and errors logged during the run, failed API calls included
+    pub errors: Vec<String>,
+    // Snapshot of the playlist once the songs were written, None when nothing was or the service has no snapshots
+    pub snapshot_id: Option<String>,
+    // Why the run stopped, None when it finished
+    pub failure: Option<String>,
+}
+
+// Function to append a record to the audit log, one JSON object per line
+// The file is only ever appended to, so earlier records can't be lost by a failed write.
+pub fn append_record(path: &str, record: &AuditRecord) -> Result<(), PilotError> {
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| format!("Could not open the audit log {}: {}", path, e))?;
+    file.write_all(line.as_bytes()).map_err(|e| format!("Could not write to the audit log {}: {}", path, e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_are_appended_as_lines() {
+        let path = std::env::temp_dir().join(format!("playlistpilot-audit-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let record = |failure: Option<&str>| AuditRecord {
+            started_at: "2026-10-14T08:00:00Z".to_string(),
+            finished_at: "2026-10-14T08:01:00Z".to_string(),
+            profile: None,
+            args: vec!["--dry-run".to_string()],
+            service: "Spotify".to_string(),
+            seeds: vec!["Road Trip".to_string()],
+            target: Some("Road Trip".to_string()),
+            playlist_id: None,
+            dry_run: true,
+            prompt_hash: Some("0123456789abcdef".to_string()),
+            suggestions: Vec::new(),
+            errors: Vec::new(),
+            snapshot_id: None,
+            failure: failure.map(str::to_string),
+        };
+        append_record(path, &record(None)).unwrap();
+        append_record(path, &record(Some("LLM unavailable"))).unwrap();
+        let text = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let lines: Vec<serde_json::Value> = text.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["failure"], serde_json::Value::Null);
+        assert_eq!(lines[1]["failure"], "LLM unavailable");
+        assert_eq!(lines[1]["prompt_hash"], "0123456789abcdef");
+    }
+}
@@ -0,0 +1,411 @@
+// Import necessary modules and crates
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use reqwest::blocking::Client;
+use reqwest::Url;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::config::Config;
+use crate::error::PilotError;
+use crate::http::{send_with_policy, Endpoint};
+use crate::models::*;
+use crate::tokens::*;
+
+// Scopes requested from the user during authorization
+pub const SCOPES: &str = "playlist-read-private playlist-modify-public playlist-modify-private user-library-read user-library-modify user-read-recently-played user-top-read ugc-image-upload user-modify-playback-state user-follow-read";
+
+// Function to exchange the authorization code for an access token
+pub fn get_spotify_access(
+    client: &Client,
+    accounts_url: &str,
+    client_id: &str,
+    client_secret: &str,
+    code: &str,
+    redirect_uri: &str
+) -> Result<SpotifyAuthResponse, PilotError> {
+    let auth_url = format!("{}/api/token", accounts_url);
+
+    // Prepare the request body as a HashMap
+    let mut body = HashMap::new();
+    body.insert("grant_type", "authorization_code");
+    body.insert("code", code);
+    body.insert("redirect_uri", redirect_uri);
+    body.insert("client_id", client_id);
+    body.insert("client_secret", client_secret);
+
+    // Send POST request to the Spotify token endpoint
+    let response = send_with_policy(
+        Endpoint::Auth,
+        client
+            .post(auth_url)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .form(&body)
+    )?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let reason = match response.json::<SpotifyAuthError>() {
+            Ok(SpotifyAuthError { error, error_description: Some(description) }) => format!("{} ({})", error, description),
+            Ok(SpotifyAuthError { error, error_description: None }) => error,
+            Err(_) => status.to_string(),
+        };
+        return Err(PilotError::Auth(format!("Spotify refused the authorization code: {}", reason)));
+    }
+    Ok(response.json()?)
+}
+
+// Function to exchange a refresh token for a new access token
+pub fn refresh_spotify_access(
+    client: &Client,
+    accounts_url: &str,
+    client_id: &str,
+    client_secret: &str,
+    refresh_token: &str
+) -> Result<SpotifyAuthResponse, PilotError> {
+    let auth_url = format!("{}/api/token", accounts_url);
+
+    // Prepare the request body as a HashMap
+    let mut body = HashMap::new();
+    body.insert("grant_type", "refresh_token");
+    body.insert("refresh_token", refresh_token);
+    body.insert("client_id", client_id);
+    body.insert("client_secret", client_secret);
+
+    // Send POST request to the Spotify token endpoint
+    let response = send_with_policy(
+        Endpoint::Auth,
+        client
+            .post(auth_url)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .form(&body)
+    )?;
+
+    if !response.status().is_success() {
+        return Err(PilotError::Auth(format!("Failed to refresh access token: {}", response.status())));
+    }
+    Ok(response.json()?)
+}
+
+// Function to get an app-only access token with the Client Credentials grant
+// No user is involved, so the token can only read public data such as public playlists and search results.
+pub fn get_client_credentials_access(
+    client: &Client,
+    accounts_url: &str,
+    client_id: &str,
+    client_secret: &str
+) -> Result<SpotifyAuthResponse, PilotError> {
+    let auth_url = format!("{}/api/token", accounts_url);
+
+    // Prepare the request body as a HashMap
+    let mut body = HashMap::new();
+    body.insert("grant_type", "client_credentials");
+    body.insert("client_id", client_id);
+    body.insert("client_secret", client_secret);
+
+    // Send POST request to the Spotify token endpoint
+    let response = send_with_policy(
+        Endpoint::Auth,
+        client
+            .post(auth_url)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .form(&body)
+    )?;
+
+    if !response.status().is_success() {
+        return Err(PilotError::Auth(format!("Failed to get an app access token: {}", response.status())));
+    }
+    Ok(response.json()?)
+}
+
+// Random values of one authorization flow
+pub struct AuthState {
+    // Sent in the authorization URL and checked on the callback, protecting the flow against CSRF
+    pub state: String,
+    // Short, human-readable code shown in the terminal and on the callback page so the user can check both belong to the same authorization
+    // Generated apart from `state`, which must not be shown.
+    pub code: String,
+}
+
+// Function to generate random characters
+fn random_characters(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+// Function to generate the `state` value and confirmation code of a new authorization flow
+pub fn generate_state() -> AuthState {
+    let code = random_characters(8).to_uppercase();
+    AuthState { state: random_characters(16), code: format!("{}-{}", &code[..4], &code[4..]) }
+}
+
+// Function to generate the Spotify authorization URL
+pub fn get_authorization_url(accounts_url: &str, client_id: &str, redirect_uri: &str, state: &str) -> String {
+    Url::parse_with_params(
+        &format!("{}/authorize", accounts_url),
+        &[
+            ("response_type", "code"),
+            ("client_id", client_id),
+            ("scope", SCOPES),
+            ("redirect_uri", redirect_uri),
+            ("state", state),
+        ],
+    )
+    .expect("authorization endpoint is a valid URL")
+    .to_string()
+}
+
+// Function to read the authorization code out of the URL Spotify redirected to
+// The `state` returned by Spotify must match the one sent in the authorization URL, otherwise the code is rejected.
+pub fn parse_callback(callback_url: &str, expected_state: &str) -> Result<String, PilotError> {
+    let url = Url::parse(callback_url.trim())
+        .map_err(|_| "Please paste the whole URL you were redirected to, not only the code.".to_string())?;
+    let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+
+    if let Some(error) = params.get("error") {
+        return Err(PilotError::Auth(format!("Authorization denied: {}", error)));
+    }
+    match params.get("state") {
+        Some(state) if state == expected_state => {},
+        _ => return Err(PilotError::Auth("State mismatch: the authorization response did not come from this request. Aborting.".to_string())),
+    }
+    match params.get("code") {
+        Some(code) => Ok(code.clone()),
+        None => Err("No authorization code found in the URL.".into()),
+    }
+}
+
+// Function to open a URL in the OS default browser
+// Returns an error if no browser launcher could be started, so the caller can fall back to printing the URL.
+pub fn open_in_browser(url: &str) -> Result<(), PilotError> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = std::process::Command::new("open");
+        command.arg(url);
+        command
+    } else if cfg!(target_os = "windows") {
+        // Going through `cmd /C start` would split the URL on `&`, so hand it to the URL protocol handler directly
+        let mut command = std::process::Command::new("rundll32");
+        command.args(["url.dll,FileProtocolHandler", url]);
+        command
+    } else {
+        let mut command = std::process::Command::new("xdg-open");
+        command.arg(url);
+        command
+    };
+
+    match command.status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("Browser launcher exited with {}", status).into()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Function to poll a local listener on the redirect URI until Spotify's callback reaches it
+// Only works when the redirect URI points to this machine (e.g. http://localhost:3000), possibly through an SSH port forward.
+pub fn poll_for_callback(redirect_uri: &str, auth: &AuthState, timeout: Duration) -> Result<String, PilotError> {
+    let redirect = Url::parse(redirect_uri).map_err(|e| format!("Invalid redirect URI: {}", e))?;
+    match redirect.host_str() {
+        Some("localhost") | Some("127.0.0.1") => {},
+        _ => return Err("The redirect URI does not point to this machine.".into()),
+    }
+    let port = redirect.port_or_known_default().unwrap_or(80);
+    let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| format!("Could not listen on port {}: {}", port, e))?;
+    listener.set_nonblocking(true)?;
+
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        let mut stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(200));
+                continue;
+            },
+            Err(e) => return Err(e.into()),
+        };
+        stream.set_nonblocking(false)?;
+
+        // Read the request line, e.g. "GET /?code=...&state=... HTTP/1.1"
+        let mut request_line = String::new();
+        BufReader::new(&stream).read_line(&mut request_line)?;
+        let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+
+        // Ignore unrelated requests such as the browser asking for a favicon
+        if !path.contains("code=") && !path.contains("error=") {
+            let _ = stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+            continue;
+        }
+
+        let result = parse_callback(&format!("http://localhost{}", path), &auth.state);
+        let page = match &result {
+            Ok(_) => format!("Authorization received (code {}). You can close this tab.", auth.code),
+            Err(e) => e.to_string(),
+        };
+        let _ = stream.write_all(format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\n\r\n{}",
+            page.len(),
+            page
+        ).as_bytes());
+        return result;
+    }
+    Err(PilotError::Auth("Timed out waiting for the authorization callback.".to_string()))
+}
+
+// Function to read the redirect URL pasted by the user and extract its authorization code
+fn read_pasted_callback(state: &str) -> Result<String, PilotError> {
+    let mut callback_url = String::new();
+    println!("Enter the URL you were redirected to:");
+    std::io::stdin().read_line(&mut callback_url)?;
+    parse_callback(&callback_url, state)
+}
+
+// Function to have the user authorize on an authorization page and return the authorization code
+// In headless mode no browser is opened: the URL and a short code are printed, and the local callback is polled.
+pub fn authorization_code(auth_url: &str, redirect_uri: &str, auth: &AuthState, headless: bool) -> Result<String, PilotError> {
+    if headless {
+        println!("Open this URL on any device to authorize: {}", auth_url);
+        println!("Your confirmation code is {}, it will be shown again once authorization completes.", auth.code);
+        println!("If this machine is remote, forward the redirect port first (e.g. `ssh -L 3000:localhost:3000 <server>`).");
+        match poll_for_callback(redirect_uri, auth, Duration::from_secs(300)) {
+            Ok(code) => {
+                println!("Authorization received (code {}).", auth.code);
+                Ok(code)
+            },
+            Err(e) => {
+                println!("{}", e);
+                read_pasted_callback(&auth.state)
+            },
+        }
+    } else {
+        // Open the authorization page in the browser, printing the URL as a fallback
+        match open_in_browser(auth_url) {
+            Ok(_) => println!("Opened the authorization page in your browser. If nothing happened, go to this URL: {}", auth_url),
+            Err(_) => println!("Go to this URL to authorize: {}", auth_url),
+        }
+        read_pasted_callback(&auth.state)
+    }
+}
+
+// Function to run the whole user authorization flow and return the token response
+pub fn authorize_user(
+    client: &Client,
+    accounts_url: &str,
+    client_id: &str,
+    client_secret: &str,
+    redirect_uri: &str,
+    headless: bool
+) -> Result<SpotifyAuthResponse, PilotError> {
+    let auth = generate_state();
+    let auth_url = get_authorization_url(accounts_url, client_id, redirect_uri, &auth.state);
+    let code = authorization_code(&auth_url, redirect_uri, &auth, headless)?;
+
+    // Exchange the authorization code for tokens
+    get_spotify_access(client, accounts_url, client_id, client_secret, &code, redirect_uri)
+}
+
+// Function to check that the Spotify app settings are there, they are only needed when Spotify is used
+fn check_spotify_settings(config: &Config) -> Result<(), PilotError> {
+    for (value, what) in [
+        (&config.spotify_client_id, "spotify client id"),
+        (&config.spotify_client_secret, "spotify client secret key"),
+        (&config.spotify_redirect_uri, "spotify redirect uri"),
+    ] {
+        if value.is_empty() {
+            return Err(format!("{} not set", what).into());
+        }
+    }
+    Ok(())
+}
+
+// Function to get a usable access token, reusing the cached one when possible
+// A valid cached token is used as is, an expired one is refreshed, and only otherwise is the user asked to authorize.
+pub fn get_access_token(client: &Client, config: &Config, headless: bool) -> Result<String, PilotError> {
+    check_spotify_settings(config)?;
+    if let Some(access_token) = cached_access_token(client, config) {
+        return Ok(access_token);
+    }
+
+    let response = authorize_user(client, &config.spotify_accounts_url, &config.spotify_client_id, &config.spotify_client_secret, &config.spotify_redirect_uri, headless)?;
+    Ok(cache_token(&config.storage_key(&config.spotify_client_id), response, None))
+}
+
+// Function to get the cached user token, refreshed when needed, for callers that must never ask the user to authorize
+pub fn get_cached_access_token(client: &Client, config: &Config) -> Result<String, PilotError> {
+    check_spotify_settings(config)?;
+    cached_access_token(client, config)
+        .ok_or_else(|| PilotError::Auth("not authorized, run a command such as `playlistpilot stats` in a terminal first".to_string()))
+}
+
+// Function to get the cached user token, refreshing it when it expired, None when the user must authorize again
+fn cached_access_token(client: &Client, config: &Config) -> Option<String> {
+    let cache_key = config.storage_key(&config.spotify_client_id);
+    let token = load_token(&cache_key).filter(|token| token.scope == SCOPES)?;
+    if token.expires_at > now() + 60 {
+        return Some(token.access_token);
+    }
+    let refresh_token = token.refresh_token?;
+    match refresh_spotify_access(client, &config.spotify_accounts_url, &config.spotify_client_id, &config.spotify_client_secret, &refresh_token) {
+        Ok(response) => Some(cache_token(&cache_key, response, Some(refresh_token))),
+        Err(e) => {
+            warn!("{}, authorizing again.", e);
+            None
+        },
+    }
+}
+
+// Function to tell whether a user token is cached, so getting one won't ask the user to authorize (unless refreshing it fails)
+pub fn has_cached_token(config: &Config) -> bool {
+    load_token(&config.storage_key(&config.spotify_client_id))
+        .filter(|token| token.scope == SCOPES)
+        .is_some_and(|token| token.expires_at > now() + 60 || token.refresh_token.is_some())
+}
+
+// Function to get an access token for commands that only read public data
+// A still valid user token is reused when there is one; otherwise the Client Credentials grant is used,
+// so no user authorization is needed at all.
+pub fn get_read_only_token(client: &Client, config: &Config) -> Result<String, PilotError> {
+    check_spotify_settings(config)?;
+    let user_token = load_token(&config.storage_key(&config.spotify_client_id))
+        .filter(|token| token.expires_at > now() + 60);
+    if let Some(token) = user_token {
+        return Ok(token.access_token);
+    }
+
+    let cache_key = config.storage_key(&format!("app:{}", config.spotify_client_id));
+    if let Some(token) = load_token(&cache_key).filter(|token| token.expires_at > now() + 60) {
+        return Ok(token.access_token);
+    }
+
+    let response = get_client_credentials_access(client, &config.spotify_accounts_url, &config.spotify_client_id, &config.spotify_client_secret)?;
+    let token = CachedToken {
+        access_token: response.access_token,
+        refresh_token: None,
+        expires_at: now() + response.expires_in,
+        scope: String::new(),
+    };
+    if let Err(e) = save_token(&cache_key, &token) {
+        warn!("Could not save the access token: {}", e);
+    }
+    Ok(token.access_token)
+}
+
+// Function to store a token response in the cache and return its access token
+// Spotify may omit the refresh token when refreshing, in which case the previous one is kept.
+fn cache_token(cache_key: &str, response: SpotifyAuthResponse, previous_refresh_token: Option<String>) -> String {
+    let token = CachedToken {
+        access_token: response.access_token,
+        refresh_token: response.refresh_token.or(previous_refresh_token),
+        expires_at: now() + response.expires_in,
+        scope: SCOPES.to_string(),
+    };
+    if let Err(e) = save_token(cache_key, &token) {
+        warn!("Could not save the access token: {}", e);
+    }
+    token.access_token
+}
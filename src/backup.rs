@@ -0,0 +1,100 @@
+// Import necessary modules and crates
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::models::*;
+use crate::spotify::get_playlist;
+use crate::tokens::{data_dir, now};
+
+// One entry of a backed up playlist, in playlist order
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BackupItem {
+    pub uri: String,
+    pub name: String,
+    // Empty for podcast episodes
+    pub artists: Vec<String>,
+    #[serde(default)]
+    pub is_local: bool,
+}
+
+// Full content of a playlist at one point in time
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Backup {
+    pub playlist_id: String,
+    pub snapshot_id: String,
+    // Unix timestamp (seconds) of the backup
+    pub created_at: u64,
+    pub items: Vec<BackupItem>,
+}
+
+// Function to get the directory backups are written to
+pub fn backups_dir() -> Result<PathBuf, PilotError> {
+    Ok(data_dir()?.join("backups"))
+}
+
+// Function to turn the items of a playlist into backup entries, dropping removed tracks
+pub fn backup_items(items: Vec<TrackItem>) -> Vec<BackupItem> {
+    items
+        .into_iter()
+        .filter_map(|item| match item.track {
+            Some(PlaylistItem::Track(track)) => Some(BackupItem {
+                uri: track.uri,
+                name: track.name,
+                artists: track.artists.into_iter().map(|a| a.name).collect(),
+                is_local: track.is_local,
+            }),
+            Some(PlaylistItem::Episode(episode)) => Some(BackupItem {
+                uri: episode.uri,
+                name: episode.name,
+                artists: Vec::new(),
+                is_local: false,
+            }),
+            Some(PlaylistItem::Other) | None => None,
+        })
+        .collect()
+}
+
+// Function to save the current content of a playlist to a timestamped file, returning its path
+// Called before every write so a bad run can always be reverted.
+pub fn backup_playlist(ctx: &Context, playlist_id: &str) -> Result<PathBuf, PilotError> {
+    let playlist = get_playlist(ctx, playlist_id, None)?;
+    save_backup(&Backup {
+        playlist_id: playlist_id.to_string(),
+        snapshot_id: playlist.snapshot_id,
+        created_at: now(),
+        items: backup_items(playlist.tracks.items),
+    })
+}
+
+// Function to write a backup to a timestamped file in the backups directory, returning its path
+pub fn save_backup(backup: &Backup) -> Result<PathBuf, PilotError> {
+    let dir = backups_dir()?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let path = dir.join(format!("{}-{}.json", backup.playlist_id, backup.created_at));
+    let content = serde_json::to_string_pretty(backup).map_err(|e| e.to_string())?;
+    fs::write(&path, content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+// Function to find a backup file from a path or a file name in the backups directory
+pub fn find_backup(name: &str) -> Result<PathBuf, PilotError> {
+    let path = PathBuf::from(name);
+    if path.exists() {
+        return Ok(path);
+    }
+    let dir = backups_dir()?;
+    [name.to_string(), format!("{}.json", name)]
+        .into_iter()
+        .map(|file| dir.join(file))
+        .find(|path| path.exists())
+        .ok_or_else(|| format!("Backup not found: {}", name).into())
+}
+
+// Function to read a backup file
+pub fn load_backup(path: &PathBuf) -> Result<Backup, PilotError> {
+    let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid backup {}: {}", path.display(), e).into())
+}
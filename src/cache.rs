@@ -0,0 +1,169 @@
+// Import necessary modules and crates
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::db::open_database;
+use crate::error::PilotError;
+use crate::tokens::now;
+
+// How long a cached search result stays valid
+const SEARCH_CACHE_TTL_SECS: u64 = 30 * 24 * 60 * 60;
+
+// How long a cached embedding is kept, and how many are kept at most (the oldest go first)
+const EMBEDDING_CACHE_TTL_SECS: u64 = 90 * 24 * 60 * 60;
+const EMBEDDING_CACHE_MAX_ENTRIES: i64 = 50_000;
+
+// Cache of Spotify search results, mapping "artist + track" to the matched track URI
+pub struct SearchCache {
+    connection: Connection,
+}
+
+// Function to build the cache key of a search, ignoring case and surrounding spaces
+fn cache_key(artist: &str, track: &str) -> String {
+    format!("{}\u{1f}{}", artist.trim().to_lowercase(), track.trim().to_lowercase())
+}
+
+impl SearchCache {
+    // Function to open the search cache stored in the local database
+    pub fn open() -> Result<SearchCache, PilotError> {
+        Ok(SearchCache { connection: open_database()? })
+    }
+
+    // Function to get the cached URI of a search, if it was cached less than the TTL ago
+    pub fn get(&self, artist: &str, track: &str) -> Option<String> {
+        let oldest = now().saturating_sub(SEARCH_CACHE_TTL_SECS) as i64;
+        self.connection
+            .query_row(
+                "SELECT uri FROM search_cache WHERE query = ?1 AND cached_at >= ?2",
+                params![cache_key(artist, track), oldest],
+                |row| row.get(0),
+            )
+            .optional()
+            .ok()
+            .flatten()
+    }
+
+    // Function to store the URI a search matched
+    pub fn put(&self, artist: &str, track: &str, uri: &str) -> Result<(), PilotError> {
+        self.connection
+            .execute(
+                "INSERT OR REPLACE INTO search_cache (query, uri, cached_at) VALUES (?1, ?2, ?3)",
+                params![cache_key(artist, track), uri, now() as i64],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+// Cache of track embeddings, by track URI and embeddings model
+pub struct EmbeddingCache {
+    connection: Connection,
+}
+
+impl EmbeddingCache {
+    // Function to open the embedding cache stored in the local database, evicting old entries
+    pub fn open() -> Result<EmbeddingCache, PilotError> {
+        let cache = EmbeddingCache { connection: open_database()? };
+        cache.evict()?;
+        Ok(cache)
+    }
+
+    // Function to delete the entries older than the TTL, then the oldest ones over the size limit
+    fn evict(&self) -> Result<(), PilotError> {
+        let oldest = now().saturating_sub(EMBEDDING_CACHE_TTL_SECS) as i64;
+        self.connection
+            .execute("DELETE FROM embedding_cache WHERE cached_at < ?1", params![oldest])
+            .map_err(|e| e.to_string())?;
+        self.connection
+            .execute(
+                "DELETE FROM embedding_cache WHERE rowid NOT IN \
+                (SELECT rowid FROM embedding_cache ORDER BY cached_at DESC LIMIT ?1)",
+                params![EMBEDDING_CACHE_MAX_ENTRIES],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    // Function to get the cached embedding of a track for a model
+    pub fn get(&self, uri: &str, model: &str) -> Option<Vec<f64>> {
+        let vector: String = self
+            .connection
+            .query_row(
+                "SELECT vector FROM embedding_cache WHERE uri = ?1 AND model = ?2",
+                params![uri, model],
+                |row| row.get(0),
+            )
+            .optional()
+            .ok()
+            .flatten()?;
+        serde_json::from_str(&vector).ok()
+    }
+
+    // Function to store the embedding of a track for a model
+    pub fn put(&self, uri: &str, model: &str, vector: &[f64]) -> Result<(), PilotError> {
+        let vector = serde_json::to_string(vector).map_err(|e| e.to_string())?;
+        self.connection
+            .execute(
+                "INSERT OR REPLACE INTO embedding_cache (uri, model, vector, cached_at) VALUES (?1, ?2, ?3, ?4)",
+                params![uri, model, vector, now() as i64],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+// Function to fingerprint a text with 64-bit FNV-1a, which stays the same across builds unlike the standard hasher
+pub fn fingerprint(text: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in text.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+// Cache of LLM answers, by the fingerprint of the whole request (endpoint, model and every message)
+pub struct LlmCache {
+    connection: Connection,
+}
+
+impl LlmCache {
+    // Function to open the LLM cache stored in the local database, deleting the answers older than `ttl_secs`
+    pub fn open(ttl_secs: u64) -> Result<LlmCache, PilotError> {
+        let cache = LlmCache { connection: open_database()? };
+        let oldest = now().saturating_sub(ttl_secs) as i64;
+        cache
+            .connection
+            .execute("DELETE FROM llm_cache WHERE cached_at < ?1", params![oldest])
+            .map_err(|e| e.to_string())?;
+        Ok(cache)
+    }
+
+    // Function to get the cached answer to a request
+    pub fn get(&self, request: &str) -> Option<String> {
+        self.connection
+            .query_row("SELECT answer FROM llm_cache WHERE request = ?1", params![request], |row| row.get(0))
+            .optional()
+            .ok()
+            .flatten()
+    }
+
+    // Function to store the answer to a request
+    pub fn put(&self, request: &str, answer: &str) -> Result<(), PilotError> {
+        self.connection
+            .execute(
+                "INSERT OR REPLACE INTO llm_cache (request, answer, cached_at) VALUES (?1, ?2, ?3)",
+                params![request, answer, now() as i64],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+// Function to empty the search, embedding and LLM caches, returning how many entries each had
+pub fn clear_caches() -> Result<(usize, usize, usize), PilotError> {
+    let connection = open_database()?;
+    let searches = connection.execute("DELETE FROM search_cache", []).map_err(|e| e.to_string())?;
+    let embeddings = connection.execute("DELETE FROM embedding_cache", []).map_err(|e| e.to_string())?;
+    let answers = connection.execute("DELETE FROM llm_cache", []).map_err(|e| e.to_string())?;
+    Ok((searches, embeddings, answers))
+}
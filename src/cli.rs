@@ -0,0 +1,708 @@
+// Import necessary modules and crates
+use crate::commands::export::ExportFormat;
+use crate::config::PresetSettings;
+use crate::ordering::{Arc, SortOrder};
+use crate::service::Service;
+
+// Commands of the application, suggesting songs when none is given
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    // Ask the LLM for songs similar to the playlist and add them
+    Suggest,
+    // Remove the tracks added by the most recent run
+    Undo,
+    // Put a playlist back the way it was in a backup file
+    Restore(String),
+    // Remove tracks by URI or by name, or picked from a list when none is given
+    Remove(Vec<String>),
+    // Remove the extra copies of songs in a playlist, the configured playlist when None
+    Dedupe(Option<String>),
+    // Show what was added to and removed from a playlist between its archived snapshots, the configured playlist when None
+    History(Option<String>),
+    // Remove or swap the tracks of a playlist that can no longer be played, the configured playlist when None
+    Cleanup(Option<String>),
+    // Copy a playlist into a new playlist of the user
+    Clone(String),
+    // Add the tracks of several playlists to the --into playlist
+    Merge(Vec<String>),
+    // Sort the tracks of a playlist into new playlists by mood or genre, the configured playlist when None
+    Split(Option<String>),
+    // Compare two playlists or backups
+    Diff(String, String),
+    // Make the --into playlist mirror a source playlist
+    Sync(String),
+    // Suggest songs bridging two playlists into a shared blend playlist
+    Blend(String, String),
+    // Reorder a playlist along an arc, the configured playlist when None
+    Reorder(Option<String>),
+    // Shuffle the order of a playlist, the configured playlist when None
+    Shuffle(Option<String>),
+    // Sort a playlist by a key such as popularity:desc, the configured playlist when None
+    Sort(SortOrder, Option<String>),
+    // Print statistics about a playlist, the configured playlist when None
+    Stats(Option<String>),
+    // Chart the genres of a playlist, the configured playlist when None
+    Genres(Option<String>),
+    // Add the new releases of followed artists that fit a playlist, the configured playlist when None
+    Releases(Option<String>),
+    // Score the health of a playlist and suggest fixes, the configured playlist when None
+    Health(Option<String>),
+    // Chart the structure of a track or the energy of a playlist, the configured playlist when None
+    Analyze(Option<String>),
+    // Compare the configured model with those of [[ensemble]] on a playlist, the configured playlist when None
+    Bench(Option<String>),
+    // Build a new playlist from a description alone, without a seed playlist
+    Generate(String),
+    // Build a new playlist of an artist's lesser-known songs
+    DeepDive(String),
+    // Build a new playlist tracing a playlist's vibe across the decades, the configured playlist when None
+    Journey(Option<String>),
+    // Rate the tracks runs added to a playlist, the configured playlist when None
+    Feedback(Option<String>),
+    // Empty the local search and embedding caches
+    CacheClear,
+    // Write a playlist to a file for local players, the configured playlist when None
+    Export(Option<String>),
+    // Add the songs listed in a text or CSV file
+    Import(String),
+    // Write the Atom feed of the tracks runs added to a playlist, the configured playlist when None
+    Feed(Option<String>),
+    // Open a track or playlist in the browser or app, the configured playlist when None
+    Open(Option<String>),
+    // Run the schedules of the config file until stopped
+    Daemon,
+    // Archive and refill the weekly playlist, from these seed playlists or the configured ones when empty
+    Weekly(Vec<String>),
+    // Build a shared playlist from the top tracks of the accounts of these profiles, the group setting when empty
+    Group(Vec<String>),
+    // Start the web interface to preview and approve suggestions
+    Serve,
+    // Serve the tools over the Model Context Protocol on the standard input and output
+    Mcp,
+}
+
+// Source material for the prompt other than the playlist itself
+#[derive(Debug, Clone, PartialEq)]
+pub enum Seed {
+    // The tracks the user played recently
+    Recent,
+    // The user's top tracks and artists over the --range period
+    Top,
+    // A random sample of the user's Liked Songs, of --limit tracks
+    Liked,
+    // The catalogs and styles of the named artists, written to a new playlist
+    Artists(Vec<String>),
+    // The recent and top scrobbles of a Last.fm user, over the --range period
+    LastFm(String),
+    // The recent and top listens of a ListenBrainz user, over the --range period
+    ListenBrainz(String),
+}
+
+impl Seed {
+    // Function to check whether reading the seed needs the user's authorization
+    pub fn is_personal(&self) -> bool {
+        !matches!(self, Seed::Artists(_) | Seed::LastFm(_) | Seed::ListenBrainz(_))
+    }
+}
+
+impl std::str::FromStr for Seed {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "recent" => Ok(Seed::Recent),
+            "top" => Ok(Seed::Top),
+            "liked" => Ok(Seed::Liked),
+            _ => match value.split_once(':') {
+                Some(("lastfm", user)) if !user.is_empty() => Ok(Seed::LastFm(user.to_string())),
+                Some(("listenbrainz", user)) if !user.is_empty() => Ok(Seed::ListenBrainz(user.to_string())),
+                _ => Err(format!(
+                    "Unknown seed: {} (expected recent, top, liked, artist, lastfm:<user> or listenbrainz:<user>)",
+                    value
+                )),
+            },
+        }
+    }
+}
+
+// Period Spotify computes the user's top tracks and artists over
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeRange {
+    // About the last 4 weeks
+    Short,
+    // About the last 6 months
+    Medium,
+    // About the last year
+    Long,
+}
+
+impl TimeRange {
+    // Function to get the name Spotify uses for the period
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeRange::Short => "short_term",
+            TimeRange::Medium => "medium_term",
+            TimeRange::Long => "long_term",
+        }
+    }
+
+    // Function to get the closest period Last.fm computes top tracks over
+    pub fn lastfm_period(&self) -> &'static str {
+        match self {
+            TimeRange::Short => "1month",
+            TimeRange::Medium => "6month",
+            TimeRange::Long => "12month",
+        }
+    }
+
+    // Function to get the closest range ListenBrainz computes statistics over
+    pub fn listenbrainz_range(&self) -> &'static str {
+        match self {
+            TimeRange::Short => "month",
+            TimeRange::Medium => "half_yearly",
+            TimeRange::Long => "year",
+        }
+    }
+}
+
+impl std::str::FromStr for TimeRange {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "short_term" => Ok(TimeRange::Short),
+            "medium_term" => Ok(TimeRange::Medium),
+            "long_term" => Ok(TimeRange::Long),
+            _ => Err(format!("Unknown range: {} (expected short_term, medium_term or long_term)", value)),
+        }
+    }
+}
+
+// Command line options accepted by the application
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub command: Command,
+    // Authorize without opening a browser, for machines without one
+    pub headless: bool,
+    // Named profile from the config file to use instead of the default settings
+    pub profile: Option<String>,
+    // Named preset from the config file whose options the run starts from
+    pub preset: Option<String>,
+    // Suggest and match songs without modifying the playlist
+    pub dry_run: bool,
+    // Maximum number of playlist tracks read as the seed, all of them when None
+    pub max_seed_tracks: Option<usize>,
+    // Maximum number of Spotify searches running at the same time
+    pub parallelism: usize,
+    // Split seeds longer than this many tracks into chunks, each asked for suggestions in parallel
+    pub chunk_size: Option<usize>,
+    // Search every song on Spotify instead of reusing cached results
+    pub no_cache: bool,
+    // Only add Spotify's recommendations for the seed, without asking the LLM
+    pub no_llm: bool,
+    // Ask the LLM again instead of reusing its answer to the same request
+    pub no_llm_cache: bool,
+    // Confidence below which matches are flagged for review, overriding the config
+    pub min_confidence: Option<f64>,
+    // Also skip suggestions already saved in the user's Liked Songs
+    pub skip_saved: bool,
+    // Also skip suggestions already in any other playlist the user owns
+    pub skip_my_playlists: bool,
+    // Index new tracks are inserted at, 0 for the top, appended at the end when None
+    pub position: Option<usize>,
+    // Spread new tracks at random places through the playlist instead of adding them together
+    pub shuffle_in: bool,
+    // Replace the whole playlist with the suggestions instead of adding them
+    pub replace: bool,
+    // Name of a new playlist the suggestions are added to, leaving the seed playlist untouched
+    pub create: Option<String>,
+    // Name of the playlist created by a command, e.g. clone, blend or generate, in place of the one the LLM would give
+    pub name: Option<String>,
+    // Playlist a command writes to, e.g. merge or blend
+    pub into: Option<String>,
+    // Number of playlists a split creates
+    pub buckets: usize,
+    // Print results as JSON instead of text
+    pub json: bool,
+    // Tracks (URIs or links) a sync leaves out of the target, or tracks and artists never suggested
+    pub exclude: Vec<String>,
+    // Let a sync remove target tracks that aren't in the source
+    pub remove_extra: bool,
+    // Shape a reorder follows, build-up when None; with suggest, the order of the new songs and the moods they go through
+    pub arc: Option<Arc>,
+    // Largest tempo change between consecutive tracks a reorder allows, in BPM
+    pub bpm_delta: Option<f64>,
+    // Seed of the shuffle command's order, the same seed giving the same order; random when None
+    pub random_seed: Option<u64>,
+    // Make a reorder keep consecutive tracks in compatible keys
+    pub harmonic: bool,
+    // Mix Spotify's recommendations into the LLM suggestions
+    pub hybrid: bool,
+    // Generate a cover for the playlist the songs were added to and upload it
+    pub cover: bool,
+    // Add the picked songs to the queue of the active device instead of the playlist
+    pub to_queue: bool,
+    // Show the picked songs and let the user refine them with follow-ups until they accept them
+    pub refine: bool,
+    // Forget the conversation earlier runs had with the LLM about the playlist before suggesting
+    pub forget_conversation: bool,
+    // Also save the added songs to the user's Liked Songs
+    pub save_to_library: bool,
+    // Save the picked songs to the user's Liked Songs instead of the playlist
+    pub library_only: bool,
+    // Look for a playable edition of each track the cleanup command removes, and put it in its place
+    pub find_replacements: bool,
+    // Mix the songs Last.fm's listeners associate with the seed into the LLM suggestions
+    pub lastfm: bool,
+    // Record the songs a run added as a ListenBrainz playlist
+    pub listenbrainz: bool,
+    // Correct the LLM's song names against MusicBrainz before searching Spotify
+    pub musicbrainz: bool,
+    // Streaming service the songs are searched on and written to, overriding the configured one
+    pub service: Option<Service>,
+    // Seed the prompt with something else than the playlist, which still receives the songs
+    pub seed: Option<Seed>,
+    // Seed the prompt with only the tracks this member of a collaborative playlist added, by user ID or profile name
+    pub added_by: Option<String>,
+    // Playlists to suggest for one after the other with the same options, given by repeating --playlist
+    pub playlists: Vec<String>,
+    // Period of the top tracks and artists used by --seed top
+    pub range: TimeRange,
+    // Number of Liked Songs sampled by --seed liked
+    pub limit: usize,
+    // Number of songs to suggest, asked when None
+    pub count: Option<usize>,
+    // Number of days back a release counts as new for the releases command, 30 when None
+    pub days: Option<u64>,
+    // How far from the hits a deep dive goes, from 1 to 5
+    pub obscurity: u8,
+    // Mood the suggestions should also fit, added to the prompt
+    pub vibe: Option<String>,
+    // Most songs a run adds by the same artist, no limit when None
+    pub max_per_artist: Option<usize>,
+    // Only suggest tracks that aren't marked explicit
+    pub no_explicit: bool,
+    // First and last release years of the suggested tracks, both included
+    pub years: Option<(i32, i32)>,
+    // Number of months back the suggested tracks may have been released, for a playlist of new finds
+    pub released_within: Option<u32>,
+    // Spotify popularity bounds of the suggested tracks, from 0 to 100
+    pub min_popularity: Option<u32>,
+    pub max_popularity: Option<u32>,
+    // Genres the suggested tracks' artists may not be tagged with
+    pub exclude_genres: Vec<String>,
+    // Kinds of songs never to suggest, e.g. "christmas music", from --avoid
+    pub avoid: Vec<String>,
+    // Only suggest instrumental, or acoustic, tracks
+    pub instrumental: bool,
+    pub acoustic: bool,
+    // Tempo range of the suggested tracks in BPM, both included, for a running or lifting cadence
+    pub bpm: Option<(f64, f64)>,
+    // Rerank the suggestions by how close their embeddings are to the seed tracks'
+    pub rerank: bool,
+    // Cosine similarity under which reranked suggestions are left out, none are when None
+    pub min_similarity: Option<f64>,
+    // Ask the [[ensemble]] models the same prompt as the configured one and rank their songs by how many proposed them
+    pub ensemble: bool,
+    // Only keep the songs of an ensemble proposed by at least this many models
+    pub min_agreement: Option<usize>,
+    // Only keep the suggestions whose artists share a genre with the seed's
+    pub match_genres: bool,
+    // Ask for album tracks and B-sides by the seed's artists and those close to them, under a popularity ceiling
+    pub deep_cuts: bool,
+    // Number of years back whose songs of this month are blended into the suggestions
+    pub throwback: Option<u32>,
+    // Ask the LLM whether each matched song truly fits the seed, and leave out those it flags
+    pub verify: bool,
+    // Match more songs than asked for and have the LLM pick and order the best of them
+    pub judge: bool,
+    // File format of an export
+    pub format: ExportFormat,
+    // File an export or a feed is written to, named after the playlist when None
+    pub output: Option<String>,
+    // Add the songs listed in the clipboard instead of suggesting songs
+    pub from_clipboard: bool,
+    // HTML file the report of the run is written to, none is when None
+    pub report: Option<String>,
+    // Write the log messages as JSON lines
+    pub log_json: bool,
+    // File every HTTP request and response is recorded to, secrets hidden, none is when None
+    pub debug_http: Option<String>,
+    // Nobody is there to answer, set by the daemon: low confidence matches are left for review and --replace isn't confirmed
+    pub unattended: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            command: Command::Suggest,
+            headless: false,
+            profile: None,
+            preset: None,
+            dry_run: false,
+            max_seed_tracks: None,
+            parallelism: 5,
+            chunk_size: None,
+            no_cache: false,
+            no_llm: false,
+            no_llm_cache: false,
+            min_confidence: None,
+            skip_saved: false,
+            skip_my_playlists: false,
+            position: None,
+            shuffle_in: false,
+            replace: false,
+            create: None,
+            name: None,
+            into: None,
+            buckets: 4,
+            json: false,
+            exclude: Vec::new(),
+            remove_extra: false,
+            arc: None,
+            bpm_delta: None,
+            random_seed: None,
+            harmonic: false,
+            hybrid: false,
+            cover: false,
+            to_queue: false,
+            refine: false,
+            forget_conversation: false,
+            save_to_library: false,
+            library_only: false,
+            find_replacements: false,
+            lastfm: false,
+            listenbrainz: false,
+            musicbrainz: false,
+            service: None,
+            seed: None,
+            added_by: None,
+            playlists: Vec::new(),
+            range: TimeRange::Medium,
+            limit: 50,
+            count: None,
+            days: None,
+            obscurity: 3,
+            vibe: None,
+            max_per_artist: None,
+            no_explicit: false,
+            years: None,
+            released_within: None,
+            min_popularity: None,
+            max_popularity: None,
+            exclude_genres: Vec::new(),
+            avoid: Vec::new(),
+            instrumental: false,
+            acoustic: false,
+            bpm: None,
+            rerank: false,
+            min_similarity: None,
+            ensemble: false,
+            min_agreement: None,
+            match_genres: false,
+            deep_cuts: false,
+            throwback: None,
+            verify: false,
+            judge: false,
+            format: ExportFormat::M3u,
+            output: None,
+            from_clipboard: false,
+            report: None,
+            log_json: false,
+            debug_http: None,
+            unattended: false,
+        }
+    }
+}
+
+// Function to parse the command line arguments (without the program name) into Options
+pub fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+
+    let mut positionals = Vec::new();
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--headless" => options.headless = true,
+            "--dry-run" => options.dry_run = true,
+            "--max-seed-tracks" => options.max_seed_tracks = Some(parse_value(&arg, args.next())?),
+            "--parallelism" => options.parallelism = parse_value(&arg, args.next())?,
+            "--chunk-size" => options.chunk_size = Some(parse_value(&arg, args.next())?),
+            "--no-cache" => options.no_cache = true,
+            "--no-llm" => options.no_llm = true,
+            "--no-llm-cache" => options.no_llm_cache = true,
+            "--min-confidence" => options.min_confidence = Some(parse_value(&arg, args.next())?),
+            "--skip-saved" => options.skip_saved = true,
+            "--skip-my-playlists" => options.skip_my_playlists = true,
+            "--position" => {
+                options.position = match args.next() {
+                    Some(value) if value == "top" => Some(0),
+                    value => Some(parse_value(&arg, value)?),
+                }
+            },
+            "--shuffle-in" => options.shuffle_in = true,
+            "--replace" => options.replace = true,
+            "--create" => options.create = Some(args.next().ok_or("--create requires a playlist name")?),
+            "--name" => options.name = Some(args.next().ok_or("--name requires a playlist name")?),
+            "--into" => options.into = Some(args.next().ok_or("--into requires a playlist")?),
+            "--buckets" => options.buckets = parse_value(&arg, args.next())?,
+            "--json" => options.json = true,
+            "--exclude" => options.exclude.push(args.next().ok_or("--exclude requires a track or an artist")?),
+            "--remove-extra" => options.remove_extra = true,
+            "--arc" => options.arc = Some(parse_value(&arg, args.next())?),
+            "--bpm-delta" => options.bpm_delta = Some(parse_value(&arg, args.next())?),
+            "--random-seed" => options.random_seed = Some(parse_value(&arg, args.next())?),
+            "--harmonic" => options.harmonic = true,
+            "--hybrid" => options.hybrid = true,
+            "--cover" => options.cover = true,
+            "--to-queue" => options.to_queue = true,
+            "--refine" => options.refine = true,
+            "--forget-conversation" => options.forget_conversation = true,
+            "--save-to-library" => options.save_to_library = true,
+            "--library-only" => options.library_only = true,
+            "--find-replacements" => options.find_replacements = true,
+            "--lastfm" => options.lastfm = true,
+            "--listenbrainz" => options.listenbrainz = true,
+            "--musicbrainz" => options.musicbrainz = true,
+            "--format" => options.format = parse_value(&arg, args.next())?,
+            "--from-clipboard" => options.from_clipboard = true,
+            "--vibe" => options.vibe = Some(args.next().ok_or("--vibe requires a description")?),
+            "--log-json" => options.log_json = true,
+            "--debug-http" => options.debug_http = Some(args.next().ok_or("--debug-http requires a file")?),
+            "--report" => options.report = Some(args.next().ok_or("--report requires a file")?),
+            "--output" => options.output = Some(args.next().ok_or("--output requires a file")?),
+            "--service" => options.service = Some(parse_value(&arg, args.next())?),
+            "--playlist" => options.playlists.push(args.next().ok_or("--playlist requires a playlist")?),
+            "--added-by" => options.added_by = Some(args.next().ok_or("--added-by requires a member of the playlist")?),
+            "--seed" => {
+                options.seed = match args.next() {
+                    // The artists follow as one comma-separated value
+                    Some(value) if value == "artist" => {
+                        let names = args.next().ok_or("--seed artist requires artist names")?;
+                        let artists: Vec<String> = names.split(',').map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect();
+                        if artists.is_empty() {
+                            return Err("--seed artist requires artist names".into());
+                        }
+                        Some(Seed::Artists(artists))
+                    },
+                    value => Some(parse_value(&arg, value)?),
+                }
+            },
+            "--range" => options.range = parse_value(&arg, args.next())?,
+            "--limit" => options.limit = parse_value(&arg, args.next())?,
+            "--count" => options.count = Some(parse_value(&arg, args.next())?),
+            "--days" => options.days = Some(parse_value(&arg, args.next())?),
+            "--no-explicit" => options.no_explicit = true,
+            "--rerank" => options.rerank = true,
+            "--min-similarity" => {
+                options.rerank = true;
+                options.min_similarity = Some(parse_value(&arg, args.next())?);
+            },
+            "--ensemble" => options.ensemble = true,
+            "--min-agreement" => {
+                options.ensemble = true;
+                options.min_agreement = Some(parse_value(&arg, args.next())?);
+            },
+            "--match-genres" => options.match_genres = true,
+            "--deep-cuts" => options.deep_cuts = true,
+            "--throwback" => options.throwback = Some(parse_value(&arg, args.next())?),
+            "--verify" => options.verify = true,
+            "--judge" => options.judge = true,
+            "--instrumental" => options.instrumental = true,
+            "--bpm" => options.bpm = Some(parse_bpm(&args.next().ok_or("--bpm requires a range such as 165-180")?)?),
+            "--acoustic" => options.acoustic = true,
+            "--exclude-genres" => {
+                let genres = args.next().ok_or("--exclude-genres requires genres")?;
+                options.exclude_genres.extend(genres.split(',').map(|genre| genre.trim().to_string()).filter(|genre| !genre.is_empty()));
+            },
+            "--avoid" => {
+                let kinds = args.next().ok_or("--avoid requires the kinds of songs to avoid")?;
+                options.avoid.extend(kinds.split(',').map(|kind| kind.trim().to_string()).filter(|kind| !kind.is_empty()));
+            },
+            "--min-popularity" => options.min_popularity = Some(parse_value(&arg, args.next())?),
+            "--max-popularity" => options.max_popularity = Some(parse_value(&arg, args.next())?),
+            "--years" => options.years = Some(parse_years(&args.next().ok_or("--years requires a range such as 1990-1999")?)?),
+            "--released-within" => {
+                options.released_within = Some(parse_months(&args.next().ok_or("--released-within requires a duration such as 6m")?)?)
+            },
+            "--decade" => options.years = Some(parse_decade(&args.next().ok_or("--decade requires a decade such as 80s")?)?),
+            "--max-per-artist" => options.max_per_artist = Some(parse_value(&arg, args.next())?),
+            "--obscurity" => {
+                options.obscurity = parse_value(&arg, args.next())?;
+                if !(1..=5).contains(&options.obscurity) {
+                    return Err("--obscurity must be between 1 and 5".into());
+                }
+            },
+            "--profile" => options.profile = Some(args.next().ok_or("--profile requires a name")?),
+            "--preset" => options.preset = Some(args.next().ok_or("--preset requires a name")?),
+            _ if arg.starts_with("--") => return Err(format!("Unknown argument: {}", arg)),
+            _ => positionals.push(arg),
+        }
+    }
+
+    let placements = [options.shuffle_in, options.position.is_some(), options.replace, options.create.is_some()];
+    if placements.iter().filter(|set| **set).count() > 1 {
+        return Err("Only one of --position, --shuffle-in, --replace and --create can be used".into());
+    }
+
+    // The first positional argument names the command, the others are its arguments
+    let mut positionals = positionals.into_iter();
+    options.command = match positionals.next().as_deref() {
+        None | Some("suggest") => Command::Suggest,
+        Some("undo") => Command::Undo,
+        Some("restore") => Command::Restore(positionals.next().ok_or("restore requires a backup file")?),
+        Some("remove") => Command::Remove(positionals.by_ref().collect()),
+        Some("dedupe") => Command::Dedupe(positionals.next()),
+        Some("history") => Command::History(positionals.next()),
+        Some("cleanup") => Command::Cleanup(positionals.next()),
+        Some("clone") => Command::Clone(positionals.next().ok_or("clone requires a source playlist")?),
+        Some("merge") => Command::Merge(positionals.by_ref().collect()),
+        Some("split") => Command::Split(positionals.next()),
+        Some("diff") => match (positionals.next(), positionals.next()) {
+            (Some(a), Some(b)) => Command::Diff(a, b),
+            _ => return Err("diff requires two playlists".into()),
+        },
+        Some("blend") => match (positionals.next(), positionals.next()) {
+            (Some(a), Some(b)) => Command::Blend(a, b),
+            _ => return Err("blend requires two playlists".into()),
+        },
+        Some("reorder") => Command::Reorder(positionals.next()),
+        Some("shuffle") => Command::Shuffle(positionals.next()),
+        Some("sort") => {
+            let order = positionals.next().ok_or("sort requires a key, e.g. added or popularity:desc")?;
+            Command::Sort(order.parse()?, positionals.next())
+        },
+        Some("genres") => Command::Genres(positionals.next()),
+        Some("health") => Command::Health(positionals.next()),
+        Some("releases") => Command::Releases(positionals.next()),
+        Some("analyze") => Command::Analyze(positionals.next()),
+        Some("bench") => Command::Bench(positionals.next()),
+        Some("generate") => Command::Generate(positionals.next().ok_or("generate requires a description")?),
+        Some("cache") => match positionals.next().as_deref() {
+            Some("clear") => Command::CacheClear,
+            _ => return Err("cache requires a subcommand: clear".into()),
+        },
+        Some("feedback") => Command::Feedback(positionals.next()),
+        Some("deep-dive") => Command::DeepDive(positionals.next().ok_or("deep-dive requires an artist")?),
+        Some("journey") => Command::Journey(positionals.next()),
+        Some("stats") => Command::Stats(positionals.next()),
+        Some("export") => Command::Export(positionals.next()),
+        Some("feed") => Command::Feed(positionals.next()),
+        Some("open") => Command::Open(positionals.next()),
+        Some("daemon") => Command::Daemon,
+        Some("weekly") => Command::Weekly(positionals.by_ref().collect()),
+        Some("group") => Command::Group(positionals.by_ref().collect()),
+        Some("serve") => Command::Serve,
+        Some("mcp") => Command::Mcp,
+        Some("import") => Command::Import(positionals.next().ok_or("import requires a file")?),
+        Some("sync") => Command::Sync(positionals.next().ok_or("sync requires a source playlist")?),
+        Some(other) => return Err(format!("Unknown command: {}", other)),
+    };
+    if let Some(extra) = positionals.next() {
+        return Err(format!("Unexpected argument: {}", extra));
+    }
+
+    Ok(options)
+}
+
+// Function to parse the value following a flag
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} requires a value", flag))?;
+    value.parse().map_err(|_| format!("Invalid value for {}: {}", flag, value))
+}
+
+// Function to parse a range of years such as "1990-1999", or a single year
+fn parse_years(value: &str) -> Result<(i32, i32), String> {
+    let (first, last) = value.split_once('-').unwrap_or((value, value));
+    match (first.trim().parse(), last.trim().parse()) {
+        (Ok(first), Ok(last)) if first <= last => Ok((first, last)),
+        _ => Err(format!("Invalid value for --years: {}", value)),
+    }
+}
+
+// Function to parse a number of months such as "6m", "1y" or "6"
+fn parse_months(value: &str) -> Result<u32, String> {
+    let value = value.trim().to_lowercase();
+    let parsed = match value.strip_suffix('y') {
+        Some(years) => years.parse::<u32>().ok().and_then(|years| years.checked_mul(12)),
+        None => value.strip_suffix('m').unwrap_or(&value).parse().ok(),
+    };
+    match parsed {
+        Some(months) if months > 0 => Ok(months),
+        _ => Err(format!("Invalid value for --released-within: {}", value)),
+    }
+}
+
+// Function to parse a tempo range such as "165-180", or a single tempo
+fn parse_bpm(value: &str) -> Result<(f64, f64), String> {
+    let (low, high) = value.split_once('-').unwrap_or((value, value));
+    match (low.trim().parse::<f64>(), high.trim().parse::<f64>()) {
+        (Ok(low), Ok(high)) if low > 0.0 && low <= high => Ok((low, high)),
+        _ => Err(format!("Invalid value for --bpm: {}", value)),
+    }
+}
+
+// Function to parse a decade such as "80s", "1980s" or "2010s" into its first and last years
+// Two-digit decades up to 20s are taken as 2000s, the others as 1900s.
+fn parse_decade(value: &str) -> Result<(i32, i32), String> {
+    let digits = value.trim().trim_end_matches(['s', 'S']).trim_end_matches('\'');
+    let start: i32 = match (digits.len(), digits.parse::<i32>()) {
+        (2, Ok(decade)) if decade <= 20 => 2000 + decade,
+        (2, Ok(decade)) => 1900 + decade,
+        (4, Ok(year)) => year,
+        _ => return Err(format!("Invalid value for --decade: {}", value)),
+    };
+    if start % 10 != 0 {
+        return Err(format!("Invalid value for --decade: {}", value));
+    }
+    Ok((start, start + 9))
+}
+
+// Function to parse the command line arguments on top of a preset: the preset's options come first, so the command line's win
+pub fn apply_preset(args: &[String], preset: &PresetSettings) -> Result<Options, String> {
+    let mut options = parse_args(preset.args.iter().cloned().chain(args.iter().cloned()))?;
+    options.vibe = options.vibe.or_else(|| preset.vibe.clone());
+    options.count = options.count.or(preset.count);
+    Ok(options)
+}
+
+// Function to parse a selection of list entries such as "1 3, 5-7" into zero-based indexes
+// Entries are numbered from 1 as they are shown to the user, and must be at most `count`.
+pub fn parse_selection(input: &str, count: usize) -> Result<Vec<usize>, String> {
+    let mut indexes = Vec::new();
+    for part in input.split(|c: char| c == ',' || c.is_whitespace()).filter(|part| !part.is_empty()) {
+        let (start, end) = match part.split_once('-') {
+            Some((start, end)) => (start, end),
+            None => (part, part),
+        };
+        let start: usize = start.trim().parse().map_err(|_| format!("Invalid selection: {}", part))?;
+        let end: usize = end.trim().parse().map_err(|_| format!("Invalid selection: {}", part))?;
+        if start == 0 || end < start || end > count {
+            return Err(format!("Invalid selection: {}", part));
+        }
+        for index in start - 1..end {
+            if !indexes.contains(&index) {
+                indexes.push(index);
+            }
+        }
+    }
+    Ok(indexes)
+}
+
+// Function to ask the user a question and read the answer line
+pub fn ask(question: &str) -> String {
+    println!("{}", question);
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).ok();
+    answer.trim().to_string()
+}
+
+// Function to ask the user a yes/no question, defaulting to no
+pub fn confirm(question: &str) -> bool {
+    println!("{} [y/N]", question);
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
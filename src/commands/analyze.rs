@@ -0,0 +1,183 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use serde::Serialize;
+
+use crate::auth::get_read_only_token;
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::ordering::Camelot;
+use crate::spotify::*;
+
+// Width of a full bar, in characters
+const BAR_WIDTH: usize = 30;
+
+// Loudness drawn as an empty bar, in decibels; 0 dB is a full one
+const QUIETEST: f64 = -60.0;
+
+// Energy and loudness of a track of a playlist, at its position
+#[derive(Serialize)]
+struct TrackLevel {
+    position: usize,
+    name: String,
+    artists: Vec<String>,
+    // None for tracks Spotify has no features for
+    energy: Option<f64>,
+    loudness: Option<f64>,
+    tempo: Option<f64>,
+}
+
+// Function to draw a bar for a value between 0 and 1, clamped to that range
+pub fn bar(value: f64, width: usize) -> String {
+    let filled = (value.clamp(0.0, 1.0) * width as f64).round() as usize;
+    format!("{}{}", "█".repeat(filled), "·".repeat(width - filled))
+}
+
+// Function to scale a loudness in decibels between 0 (QUIETEST or below) and 1 (0 dB)
+pub fn loudness_level(loudness: f64) -> f64 {
+    ((loudness - QUIETEST) / -QUIETEST).clamp(0.0, 1.0)
+}
+
+// Function to format a number of seconds as m:ss
+fn clock(seconds: f64) -> String {
+    let seconds = seconds.max(0.0).round() as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+// Function to name a key on the Camelot wheel, "?" when Spotify couldn't tell it
+fn key_label(key: i32, mode: i32) -> String {
+    match Camelot::from_key(key, mode) {
+        Some(camelot) => format!("{}{}", camelot.number, if camelot.minor { "A" } else { "B" }),
+        None => "?".to_string(),
+    }
+}
+
+// Function to average a slice of values, 0 when it is empty
+fn average(values: &[f64]) -> f64 {
+    if values.is_empty() { 0.0 } else { values.iter().sum::<f64>() / values.len() as f64 }
+}
+
+// Function to print the sections of a track from Spotify's audio analysis, with their loudness, tempo and key
+fn analyze_track(ctx: &Context, options: &Options, track_id: &str) -> Result<(), PilotError> {
+    let analysis = get_audio_analysis(ctx, track_id)?;
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&analysis)?);
+        return Ok(());
+    }
+
+    let track = &analysis.track;
+    println!(
+        "Track {}: {}, {:.1} dB, {:.0} BPM, key {}",
+        track_id,
+        clock(track.duration),
+        track.loudness,
+        track.tempo,
+        key_label(track.key, track.mode)
+    );
+    for section in &analysis.sections {
+        println!(
+            "  {:>5} {:>5}  {} {:>6.1} dB {:>4.0} BPM {:>3}",
+            clock(section.start),
+            clock(section.duration),
+            bar(loudness_level(section.loudness), BAR_WIDTH),
+            section.loudness,
+            section.tempo,
+            key_label(section.key, section.mode)
+        );
+    }
+    Ok(())
+}
+
+// Function to print the energy and loudness of each track of a playlist in order, and how they evolve over its thirds
+fn analyze_playlist(ctx: &Context, options: &Options, playlist_id: &str) -> Result<(), PilotError> {
+    let playlist = get_playlist(ctx, playlist_id, None)?;
+    let (tracks, _) = spotify_tracks(playlist.tracks.items);
+    let uris: Vec<String> = tracks.iter().map(|track| track.uri.clone()).collect();
+    let features = get_audio_features(ctx, &uris)?;
+
+    let levels: Vec<TrackLevel> = tracks
+        .iter()
+        .enumerate()
+        .map(|(index, track)| {
+            let track_features = track.uri.strip_prefix("spotify:track:").and_then(|id| features.get(id));
+            TrackLevel {
+                position: index + 1,
+                name: track.name.clone(),
+                artists: track.artists.iter().map(|artist| artist.name.clone()).collect(),
+                energy: track_features.map(|f| f.energy),
+                loudness: track_features.map(|f| f.loudness),
+                tempo: track_features.map(|f| f.tempo),
+            }
+        })
+        .collect();
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&levels)?);
+        return Ok(());
+    }
+
+    println!("Energy and loudness of '{}' ({} tracks):", playlist.name, levels.len());
+    for level in &levels {
+        let name = format!("{} - {}", level.name, level.artists.join(", "));
+        match (level.energy, level.loudness, level.tempo) {
+            (Some(energy), Some(loudness), Some(tempo)) => println!(
+                "{:>4}. energy {} {:.2}  loudness {} {:>5.1} dB  {:>3.0} BPM  {}",
+                level.position,
+                bar(energy, BAR_WIDTH / 2),
+                energy,
+                bar(loudness_level(loudness), BAR_WIDTH / 2),
+                loudness,
+                tempo,
+                name
+            ),
+            _ => println!("{:>4}. (no audio features)  {}", level.position, name),
+        }
+    }
+
+    // The progression over the beginning, middle and end of the playlist
+    let measured: Vec<(f64, f64)> = levels.iter().filter_map(|level| Some((level.energy?, level.loudness?))).collect();
+    if measured.len() >= 3 {
+        let thirds: Vec<&[(f64, f64)]> = (0..3).map(|third| &measured[third * measured.len() / 3..(third + 1) * measured.len() / 3]).collect();
+        let energies: Vec<String> = thirds.iter().map(|part| format!("{:.2}", average(&part.iter().map(|(energy, _)| *energy).collect::<Vec<f64>>()))).collect();
+        let loudness: Vec<String> = thirds.iter().map(|part| format!("{:.1} dB", average(&part.iter().map(|(_, loudness)| *loudness).collect::<Vec<f64>>()))).collect();
+        println!("Energy over the beginning, middle and end: {}", energies.join(" → "));
+        println!("Loudness over the beginning, middle and end: {}", loudness.join(" → "));
+    }
+    Ok(())
+}
+
+// Function to chart a track's structure or a playlist's energy and loudness, from Spotify's audio analysis and features
+// Tracks need their URI or link, anything else is a playlist, the configured one when None.
+pub fn run(client: Client, config: Config, options: &Options, what: Option<&str>) -> Result<(), PilotError> {
+    let what = what.map(str::trim);
+    let bare = what.is_some_and(|what| !what.contains(':') && !what.contains('/'));
+    let track_id = what.and_then(|what| parse_spotify_id(what, "track")).filter(|_| !bare);
+    let playlist_id = match what {
+        Some(playlist) if track_id.is_none() => playlist_argument(playlist)?,
+        _ => config.default_playlist()?,
+    };
+    // Nothing is written, so the user token is only needed for private playlists
+    let access_token = get_read_only_token(&client, &config)?;
+    let ctx = Context { client, config, access_token };
+
+    match track_id {
+        Some(track_id) => analyze_track(&ctx, options, &track_id),
+        None => analyze_playlist(&ctx, options, &playlist_id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bars_scale_and_clamp_levels() {
+        assert_eq!(bar(0.5, 4), "██··");
+        assert_eq!(bar(1.7, 3), "███");
+        assert_eq!(bar(-0.2, 2), "··");
+        assert_eq!(loudness_level(0.0), 1.0);
+        assert_eq!(loudness_level(-30.0), 0.5);
+        assert_eq!(loudness_level(-80.0), 0.0);
+    }
+}
@@ -0,0 +1,196 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use serde::Serialize;
+use std::time::Instant;
+
+use crate::auth::get_read_only_token;
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::commands::suggest::{bench_seed, provider_context, BenchSeed};
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::llm::*;
+use crate::matching::{Duplicates, TrackFilter, TrackMatch};
+use crate::models::LlmSongsResponse;
+use crate::service::{music_service, search_songs, MusicService};
+
+// Number of songs each model is asked for when --count isn't given
+const BENCH_SONGS: usize = 20;
+
+// How one model did on the seed
+#[derive(Serialize)]
+struct ModelResult {
+    name: String,
+    model: String,
+    // Songs it proposed, those found with confidence on Spotify, and those of them already in the seed
+    suggested: usize,
+    found: usize,
+    in_seed: usize,
+    // Time it took to answer, in seconds
+    seconds: f64,
+    // Tokens the provider reported, 0 when it doesn't
+    tokens: u64,
+    // Estimated from the model's llm_price_per_million_tokens, None when it has no price
+    cost: Option<f64>,
+    // Why it gave no songs, None when it answered
+    error: Option<String>,
+}
+
+impl ModelResult {
+    // Function to count the songs that are usable: found, and not already in the seed
+    fn new_songs(&self) -> usize {
+        self.found - self.in_seed
+    }
+}
+
+// Function to format a share as a whole percentage, "-" when there is nothing to take it of
+fn percent(part: usize, total: usize) -> String {
+    if total == 0 { "-".to_string() } else { format!("{:.0}%", part as f64 * 100.0 / total as f64) }
+}
+
+// Function to ask one model for the songs of the seed and search them on Spotify, with how long it took and the tokens it used
+// Models are asked one after the other, so the tokens counted are this model's alone. Songs that have no confident match count as
+// not found, and they aren't replaced: the model is judged on its answer.
+fn bench_model(ctx: &Context, service: &dyn MusicService, options: &Options, name: &str, api_key: &str, seed: &BenchSeed) -> ModelResult {
+    // A provider that is down doesn't keep the next ones from being asked
+    reset_breaker();
+    let tokens_before = tokens_used();
+    let started = Instant::now();
+    let answer = ask_llm(ctx, api_key, &seed.prompt)
+        .and_then(|response| parse_llm_response(&response))
+        .and_then(|cleaned| read_llm_json::<LlmSongsResponse>(&cleaned));
+    let seconds = started.elapsed().as_secs_f64();
+    let tokens = tokens_used() - tokens_before;
+    let mut result = ModelResult {
+        name: name.to_string(),
+        model: ctx.config.llm_model.clone(),
+        suggested: 0,
+        found: 0,
+        in_seed: 0,
+        seconds,
+        tokens,
+        cost: ctx.config.llm_price_per_million_tokens.map(|price| tokens as f64 * price / 1_000_000.0),
+        error: None,
+    };
+    match answer {
+        Ok(answer) => {
+            let in_seed = Duplicates::new(&seed.tracks);
+            let found: Vec<TrackMatch> = search_songs(service, ctx, &answer.songs, options.parallelism, None, &TrackFilter::default())
+                .into_iter()
+                .filter_map(Result::ok)
+                .filter(|found| found.confidence >= ctx.config.min_confidence)
+                .collect();
+            result.suggested = answer.songs.len();
+            result.found = found.len();
+            result.in_seed = found.iter().filter(|found| in_seed.contains(found)).count();
+        },
+        Err(e) => result.error = Some(e.to_string()),
+    }
+    result
+}
+
+// Function to lay out the comparison of the models, one row per model in the order they were asked
+fn comparison_table(results: &[ModelResult]) -> Vec<String> {
+    let width = results.iter().map(|result| result.name.chars().count()).max().unwrap_or(0).max("Model".len());
+    let mut rows = vec![format!("{:<width$}  {:>5}  {:>7}  {:>7}  {:>6}  {:>8}", "Model", "Found", "In seed", "Latency", "Tokens", "Cost")];
+    for result in results {
+        rows.push(match &result.error {
+            Some(error) => format!("{:<width$}  failed: {}", result.name, error),
+            None => format!(
+                "{:<width$}  {:>5}  {:>7}  {:>6.1}s  {:>6}  {:>8}",
+                result.name,
+                percent(result.found, result.suggested),
+                percent(result.in_seed, result.found),
+                result.seconds,
+                result.tokens,
+                result.cost.map_or("-".to_string(), |cost| format!("{:.4}", cost))
+            ),
+        });
+    }
+    rows
+}
+
+// Function to pick the model that gave the most new songs found on Spotify, the fastest of those that tie
+fn best_model(results: &[ModelResult]) -> Option<&ModelResult> {
+    results
+        .iter()
+        .filter(|result| result.error.is_none() && result.new_songs() > 0)
+        .max_by(|a, b| a.new_songs().cmp(&b.new_songs()).then(b.seconds.total_cmp(&a.seconds)))
+}
+
+// Function to ask the configured model and those of [[ensemble]] for songs like a playlist, the configured one by default, and compare
+// how many of their songs are found on Spotify, how many are already in the playlist, how long they took and what they cost
+// Nothing is written, and the LLM cache isn't used so every model is really asked.
+pub fn run(client: Client, config: Config, options: &Options, playlist: Option<&str>) -> Result<(), PilotError> {
+    let playlist_id = match playlist {
+        Some(playlist) => playlist_argument(playlist)?,
+        None => config.default_playlist()?,
+    };
+    let api_key = get_llm_secret(&config)?;
+    // Nothing is written, so the user token is only needed for private playlists
+    let access_token = get_read_only_token(&client, &config)?;
+    let ctx = Context { client, config, access_token };
+
+    let number = options.count.unwrap_or(BENCH_SONGS);
+    let seed = bench_seed(&ctx, options, &playlist_id, number)?;
+    let others: Vec<(String, Context, String)> = ctx.config.ensemble.iter().map(|provider| provider_context(&ctx, provider, &api_key)).collect();
+    let mut models: Vec<(&str, &Context, &str)> = vec![(ctx.config.llm_model.as_str(), &ctx, api_key.as_str())];
+    models.extend(others.iter().map(|(name, ctx, key)| (name.as_str(), ctx, key.as_str())));
+    let service = music_service(ctx.config.service);
+    if !options.json {
+        println!("Asking {} models for {} songs like '{}'...", models.len(), number, seed.name);
+    }
+    let results: Vec<ModelResult> = models.iter().map(|(name, ctx, key)| bench_model(ctx, service.as_ref(), options, name, key, &seed)).collect();
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+        return Ok(());
+    }
+
+    for row in comparison_table(&results) {
+        println!("{}", row);
+    }
+    println!("Found: share of the songs found on Spotify. In seed: share of those already in '{}'.", seed.name);
+    match best_model(&results) {
+        Some(best) => println!("{} gave the most new songs ({} of {}).", best.name, best.new_songs(), best.suggested),
+        None => println!("No model gave new songs found on Spotify."),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(name: &str, suggested: usize, found: usize, in_seed: usize, seconds: f64, error: Option<&str>) -> ModelResult {
+        ModelResult {
+            name: name.to_string(),
+            model: name.to_string(),
+            suggested,
+            found,
+            in_seed,
+            seconds,
+            tokens: 1200,
+            cost: None,
+            error: error.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn models_are_compared_on_their_new_songs() {
+        let mut results = vec![
+            result("llama", 20, 17, 2, 4.2, None),
+            result("gpt", 20, 16, 1, 2.5, None),
+            result("mistral", 0, 0, 0, 0.3, Some("The LLM API answered 401 Unauthorized")),
+        ];
+        results[1].cost = Some(0.00042);
+        let rows = comparison_table(&results);
+        assert_eq!(rows.len(), 4);
+        assert!(rows[1].starts_with("llama      85%      12%     4.2s    1200         -"), "{}", rows[1]);
+        assert!(rows[2].ends_with("0.0004"));
+        assert_eq!(rows[3], "mistral  failed: The LLM API answered 401 Unauthorized");
+        // Both found 15 new songs, the faster one wins
+        assert_eq!(best_model(&results).map(|best| best.name.as_str()), Some("gpt"));
+        assert_eq!(percent(0, 0), "-");
+    }
+}
@@ -0,0 +1,10 @@
+// Import necessary modules and crates
+use crate::cache::clear_caches;
+use crate::error::PilotError;
+
+// Function to empty the local search, embedding and LLM caches
+pub fn run_clear() -> Result<(), PilotError> {
+    let (searches, embeddings, answers) = clear_caches()?;
+    println!("Cleared {} cached searches, {} cached embeddings and {} cached LLM answers.", searches, embeddings, answers);
+    Ok(())
+}
@@ -0,0 +1,152 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use std::collections::HashMap;
+
+use crate::auth::*;
+use crate::backup::backup_playlist;
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::matching::{TrackFilter, TrackMatch};
+use crate::models::{PlaylistItem, Song, Track};
+use crate::spotify::*;
+
+// Function to describe a track as "Title - Artist, Artist"
+fn describe(track: &Track) -> String {
+    let artists: Vec<&str> = track.artists.iter().map(|a| a.name.as_str()).collect();
+    format!("{} - {}", track.name, artists.join(", "))
+}
+
+// Function to find the playlist tracks, given with their positions, that can't be played in the market the versions were checked for
+// Tracks Spotify no longer returns at all were withdrawn and can't be played either.
+pub fn find_unplayable<'a>(tracks: &[(usize, &'a Track)], versions: &HashMap<String, Option<String>>) -> Vec<(usize, &'a Track)> {
+    tracks.iter().copied().filter(|(_, track)| versions.get(&track.uri).is_none_or(|version| version.is_none())).collect()
+}
+
+// Function to get the positions replacements are inserted at, in order, once all the unplayable tracks are removed
+// Takes the positions of the removed tracks in ascending order, with whether each one has a replacement; a replacement
+// goes back where its track was, shifted by the tracks before it that were removed without one.
+pub fn insert_positions(removed: &[(usize, bool)]) -> Vec<usize> {
+    let mut dropped = 0;
+    let mut positions = Vec::new();
+    for (position, replaced) in removed {
+        if *replaced {
+            positions.push(position - dropped);
+        } else {
+            dropped += 1;
+        }
+    }
+    positions
+}
+
+// Function to search for another edition of a track that can be played, None when nothing else was found
+fn find_replacement(ctx: &Context, track: &Track) -> Option<TrackMatch> {
+    let song = Song {
+        name: track.name.clone(),
+        artist: track.artists.first().map(|artist| artist.name.clone()).unwrap_or_default(),
+        isrc: track.external_ids.isrc.clone(),
+        mbid: None,
+    };
+    match search_song(ctx, &song, &TrackFilter::default()) {
+        Ok(found) if found.uri != track.uri && found.confidence >= ctx.config.min_confidence => Some(found),
+        Ok(_) => None,
+        Err(e) => {
+            eprintln!("Could not search for another edition of '{}': {}", describe(track), e);
+            None
+        },
+    }
+}
+
+// Function to remove the tracks of a playlist that can no longer be played, or swap them for a playable edition with --find-replacements
+pub fn run(client: Client, config: Config, options: &Options, playlist: Option<&str>) -> Result<(), PilotError> {
+    let playlist_id = match playlist {
+        Some(playlist) => playlist_argument(playlist)?,
+        None => config.default_playlist()?,
+    };
+    // Without a configured market, the user's own market is checked, which needs their token
+    let market = config.market.clone().unwrap_or_else(|| "from_token".to_string());
+    let access_token = if options.dry_run && config.market.is_some() {
+        get_read_only_token(&client, &config)?
+    } else {
+        get_access_token(&client, &config, options.headless)?
+    };
+    let mut ctx = Context { client, config, access_token };
+    // Searches for replacements only return editions that can be played there
+    ctx.config.market = Some(market.clone());
+
+    let playlist = get_playlist(&ctx, &playlist_id, None)?;
+    // Positions count every item, removed tracks, local files and episodes included
+    let tracks: Vec<(usize, &Track)> = playlist
+        .tracks
+        .items
+        .iter()
+        .enumerate()
+        .filter_map(|(position, item)| match &item.track {
+            Some(PlaylistItem::Track(track)) if !track.is_local => Some((position, track)),
+            _ => None,
+        })
+        .collect();
+    let missing = playlist.tracks.items.iter().filter(|item| item.track.is_none()).count();
+    if missing > 0 {
+        println!("{} items of {} are gone from Spotify and can only be removed from the Spotify app.", missing, playlist.name);
+    }
+    let uris: Vec<String> = tracks.iter().map(|(_, track)| track.uri.clone()).collect();
+    let versions = playable_versions(&ctx, &uris, &market)?;
+    let unplayable = find_unplayable(&tracks, &versions);
+    if unplayable.is_empty() {
+        println!("Every track of {} can be played.", playlist.name);
+        return Ok(());
+    }
+
+    let mut replacements: Vec<Option<TrackMatch>> = Vec::new();
+    println!("{} tracks of {} can no longer be played:", unplayable.len(), playlist.name);
+    for (position, track) in &unplayable {
+        let replacement = if options.find_replacements { find_replacement(&ctx, track) } else { None };
+        match &replacement {
+            Some(found) => println!("{:>4}. {} (swapped for {} - {})", position + 1, describe(track), found.name, found.artists.join(", ")),
+            None => println!("{:>4}. {}", position + 1, describe(track)),
+        }
+        replacements.push(replacement);
+    }
+    if options.dry_run {
+        println!("Dry run, nothing was changed.");
+        return Ok(());
+    }
+    if !confirm("Remove these tracks from the playlist, putting in the editions found?") {
+        return Ok(());
+    }
+
+    backup_playlist(&ctx, &playlist_id).map_err(|e| format!("Could not back up the playlist, nothing was changed: {}", e))?;
+    let occurrences: Vec<(String, usize)> = unplayable.iter().map(|(position, track)| (track.uri.clone(), *position)).collect();
+    remove_occurrences(&ctx, &playlist_id, &occurrences, playlist.snapshot_id.clone())?;
+    let removed: Vec<(usize, bool)> = unplayable.iter().zip(&replacements).map(|((position, _), found)| (*position, found.is_some())).collect();
+    let swapped: Vec<&TrackMatch> = replacements.iter().flatten().collect();
+    for (found, position) in swapped.iter().zip(insert_positions(&removed)) {
+        add_to_playlist(&ctx, &playlist_id, vec![found.uri.clone()], Some(position))?;
+    }
+    println!("Removed {} tracks from the playlist, {} of them swapped for a playable edition.", unplayable.len(), swapped.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::track_json;
+
+    #[test]
+    fn unplayable_tracks_and_where_replacements_go() {
+        let tracks: Vec<Track> = ["a", "b", "c", "d"].iter().map(|id| serde_json::from_value(track_json(id, "Song", "Artist")).unwrap()).collect();
+        let positioned: Vec<(usize, &Track)> = tracks.iter().enumerate().map(|(index, track)| (index * 2, track)).collect();
+        // b is relinked, c can't be played, d was withdrawn
+        let versions = HashMap::from([
+            ("spotify:track:a".to_string(), Some("spotify:track:a".to_string())),
+            ("spotify:track:b".to_string(), Some("spotify:track:b2".to_string())),
+            ("spotify:track:c".to_string(), None),
+        ]);
+        let found: Vec<usize> = find_unplayable(&positioned, &versions).iter().map(|(position, _)| *position).collect();
+        assert_eq!(found, vec![4, 6]);
+        assert_eq!(insert_positions(&[(1, false), (3, true), (4, false), (7, true)]), vec![2, 5]);
+    }
+}
@@ -0,0 +1,48 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+
+use crate::auth::*;
+use crate::backup::{backup_items, BackupItem};
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::spotify::*;
+
+// Function to copy any playlist the user can read into a new playlist of their own
+pub fn run(client: Client, config: Config, options: &Options, source: &str) -> Result<(), PilotError> {
+    let source_id = playlist_argument(source)?;
+
+    // A dry run only reads the source, an app token is enough for public playlists
+    let access_token = if options.dry_run {
+        get_read_only_token(&client, &config)?
+    } else {
+        get_access_token(&client, &config, options.headless)?
+    };
+    let ctx = Context { client, config, access_token };
+
+    let playlist = get_playlist(&ctx, &source_id, None)?;
+    let name = options.name.clone().unwrap_or_else(|| format!("{} (copy)", playlist.name));
+
+    // Local files only exist on the owner's devices and can't be copied
+    let (local, items): (Vec<BackupItem>, Vec<BackupItem>) = backup_items(playlist.tracks.items).into_iter().partition(|item| item.is_local);
+    if !local.is_empty() {
+        println!("Skipped {} local files, they can't be copied.", local.len());
+    }
+
+    if options.dry_run {
+        println!("Dry run, '{}' would be created with {} items from '{}'.", name, items.len(), playlist.name);
+        return Ok(());
+    }
+
+    let description = format!("Copy of {} made with PlaylistPilot", playlist.name);
+    let created = create_playlist(&ctx, &name, &description)?;
+    let uris = items.into_iter().map(|item| item.uri).collect::<Vec<String>>();
+    let count = uris.len();
+    if count > 0 {
+        add_to_playlist(&ctx, &created.id, uris, None)?;
+    }
+    println!("Copied {} items into '{}': {}", count, name, created.external_urls.spotify);
+    Ok(())
+}
@@ -0,0 +1,213 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use serde_json::json;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::cli::*;
+use crate::auth::get_read_only_token;
+use crate::commands::{history, playlist_argument, suggest};
+use crate::context::Context;
+use crate::config::*;
+use crate::error::PilotError;
+use crate::http::configure_requests;
+use crate::llm::tokens_used;
+use crate::metrics::{count_run, serve_metrics};
+use crate::schedule::{local_time, Cron};
+use crate::tokens::{data_dir, iso_date, now};
+
+// Number of songs a scheduled run adds when neither count nor --count is set
+const DEFAULT_SCHEDULE_COUNT: usize = 5;
+
+// Longest the daemon catches up on minutes it missed (e.g. while a run was going), in seconds
+// After a longer gap, such as the machine sleeping, the missed runs are skipped rather than all started at once.
+const MAX_CATCH_UP: u64 = 3600;
+
+// A schedule of the config file, checked when the daemon starts
+struct Job {
+    name: String,
+    cron: Cron,
+    settings: ScheduleSettings,
+    options: Options,
+}
+
+// A playlist archived on a schedule, checked when the daemon starts
+struct ArchiveJob {
+    cron: Cron,
+    settings: ArchiveSettings,
+}
+
+// Function to check a schedule and read its command line options
+fn job(mut settings: ScheduleSettings) -> Result<Job, PilotError> {
+    let name = settings.name.clone().unwrap_or_else(|| settings.cron.clone());
+    let cron: Cron = settings.cron.parse().map_err(|e| format!("Schedule {}: {}", name, e))?;
+    let mut options = parse_args(settings.args.iter().cloned()).map_err(|e| format!("Schedule {}: {}", name, e))?;
+    if let Some(preset) = &options.preset {
+        let preset = load_preset(preset).map_err(|e| format!("Schedule {}: {}", name, e))?;
+        options = apply_preset(&settings.args, &preset).map_err(|e| format!("Schedule {}: {}", name, e))?;
+        settings.playlist = settings.playlist.or(preset.playlist);
+    }
+    if !matches!(options.command, Command::Suggest | Command::Blend(..) | Command::Generate(_) | Command::DeepDive(_) | Command::Weekly(_)) {
+        return Err(format!("Schedule {}: only suggest, blend, generate, deep-dive and weekly can be scheduled", name).into());
+    }
+    if options.from_clipboard {
+        return Err(format!("Schedule {}: --from-clipboard can't be scheduled", name).into());
+    }
+    // Nobody is there to answer, the cached tokens are used and a new authorization only prints its link
+    options.count = settings.count.or(options.count).or(Some(DEFAULT_SCHEDULE_COUNT));
+    options.profile = settings.profile.clone().or(options.profile);
+    options.headless = true;
+    options.unattended = true;
+    // The settings are read now so a typo in a profile shows when the daemon starts, not at the first run
+    load_config(options.profile.as_deref()).map_err(|e| format!("Schedule {}: {}", name, e))?;
+    if let Some(playlist) = &settings.playlist {
+        playlist_argument(playlist).map_err(|e| format!("Schedule {}: {}", name, e))?;
+    }
+    Ok(Job { name, cron, settings, options })
+}
+
+// Function to check an archive entry, named by its playlist in errors
+fn archive_job(settings: ArchiveSettings) -> Result<ArchiveJob, PilotError> {
+    let name = settings.playlist.clone().unwrap_or_else(|| "of the profile".to_string());
+    let cron: Cron = settings.cron.parse().map_err(|e| format!("Archive {}: {}", name, e))?;
+    load_config(settings.profile.as_deref()).map_err(|e| format!("Archive {}: {}", name, e))?;
+    if let Some(playlist) = &settings.playlist {
+        playlist_argument(playlist).map_err(|e| format!("Archive {}: {}", name, e))?;
+    }
+    Ok(ArchiveJob { cron, settings })
+}
+
+// Function to take a snapshot of an archived playlist, returning its ID and whether it changed since the last one
+// Only the playlist is read, the user token is only needed for private playlists.
+fn run_archive(client: &Client, job: &ArchiveJob) -> Result<(String, bool), PilotError> {
+    let mut config = load_config(job.settings.profile.as_deref())?;
+    if let Some(playlist) = &job.settings.playlist {
+        config.playlist_id = Some(playlist_argument(playlist)?);
+    }
+    configure_requests(&config);
+    let access_token = get_read_only_token(client, &config)?;
+    let playlist_id = config.default_playlist()?;
+    let ctx = Context { client: client.clone(), config, access_token };
+    let changed = history::archive_playlist(&ctx, &playlist_id)?;
+    Ok((playlist_id, changed))
+}
+
+// Function to run one scheduled job, returning the number of songs added
+fn run_job(client: &Client, job: &Job) -> Result<usize, PilotError> {
+    let options = &job.options;
+    let mut config = load_config(options.profile.as_deref())?;
+    if let Some(playlist) = &job.settings.playlist {
+        config.playlist_id = Some(playlist_argument(playlist)?);
+    }
+    if let Some(min_confidence) = options.min_confidence {
+        config.min_confidence = min_confidence;
+    }
+    if let Some(service) = options.service {
+        config.service = service;
+    }
+    // Each profile may set its own limits
+    configure_requests(&config);
+    let client = client.clone();
+    match &options.command {
+        Command::Blend(a, b) => suggest::run_blend(client, config, options, a, b),
+        Command::Generate(description) => suggest::run_generate(client, config, options, description),
+        Command::DeepDive(artist) => suggest::run_deep_dive(client, config, options, artist),
+        Command::Weekly(seeds) => suggest::run_weekly(client, config, options, seeds),
+        _ => suggest::run(client, config, options),
+    }
+}
+
+// Function to write a line of the daemon's log, to the output and to daemon.log in the data directory
+fn log(entry: serde_json::Value) {
+    let line = entry.to_string();
+    println!("{}", line);
+    let written = data_dir().and_then(|dir| {
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join("daemon.log"))
+            .and_then(|mut file| writeln!(file, "{}", line))
+            .map_err(PilotError::from)
+    });
+    if let Err(e) = written {
+        warn!("Could not write the daemon log: {}", e);
+    }
+}
+
+// Function to run the schedules of the config file until stopped, each at the minutes its cron expression matches,
+// and to take the snapshots of the playlists in [[archives]] at theirs
+// Runs never ask anything: low confidence matches are left for review and the cached tokens are reused.
+// The metrics are served on metrics_address when it is set.
+pub fn run(client: Client, config: &Config) -> Result<(), PilotError> {
+    let file = load_config_file()?;
+    if file.schedules.is_empty() && file.archives.is_empty() {
+        return Err("No schedules in the config file, add [[schedules]] or [[archives]] entries".into());
+    }
+    let jobs = file.schedules.into_iter().map(job).collect::<Result<Vec<Job>, PilotError>>()?;
+    let archives = file.archives.into_iter().map(archive_job).collect::<Result<Vec<ArchiveJob>, PilotError>>()?;
+    if let Some(address) = &config.metrics_address {
+        serve_metrics(address)?;
+        println!("Metrics are served on http://{}/metrics.", address);
+    }
+    let names: Vec<&str> = jobs.iter().map(|job| job.name.as_str()).collect();
+    log(json!({ "time": iso_date(now()), "event": "started", "schedules": names, "archives": archives.len() }));
+
+    let mut next_minute = now() / 60 * 60;
+    loop {
+        let current = now();
+        if current >= next_minute + MAX_CATCH_UP {
+            log(json!({ "time": iso_date(current), "event": "skipped", "from": iso_date(next_minute) }));
+            next_minute = current / 60 * 60;
+        }
+        while next_minute <= current {
+            let time = local_time(next_minute);
+            for job in jobs.iter().filter(|job| job.cron.matches(&time)) {
+                let started_at = now();
+                let tokens_before = tokens_used();
+                let result = run_job(&client, job);
+                count_run(result.is_ok());
+                let mut entry = json!({
+                    "time": iso_date(started_at),
+                    "event": "run",
+                    "schedule": job.name,
+                    "profile": job.options.profile,
+                    "playlist": job.settings.playlist,
+                    "duration_secs": now() - started_at,
+                    "llm_tokens": tokens_used() - tokens_before,
+                });
+                match result {
+                    Ok(added) => {
+                        entry["status"] = json!("ok");
+                        entry["added"] = json!(added);
+                    },
+                    Err(e) => {
+                        entry["status"] = json!("error");
+                        entry["error"] = json!(e.to_string());
+                    },
+                }
+                log(entry);
+            }
+            for archive in archives.iter().filter(|archive| archive.cron.matches(&time)) {
+                let mut entry = json!({ "time": iso_date(now()), "event": "archive", "playlist": archive.settings.playlist });
+                match run_archive(&client, archive) {
+                    Ok((playlist_id, changed)) => {
+                        entry["status"] = json!("ok");
+                        entry["playlist"] = json!(playlist_id);
+                        entry["changed"] = json!(changed);
+                    },
+                    Err(e) => {
+                        entry["status"] = json!("error");
+                        entry["error"] = json!(e.to_string());
+                    },
+                }
+                log(entry);
+            }
+            next_minute += 60;
+        }
+        thread::sleep(Duration::from_secs(next_minute.saturating_sub(now()).max(1)));
+    }
+}
@@ -0,0 +1,138 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use std::collections::HashMap;
+
+use crate::auth::*;
+use crate::backup::backup_playlist;
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::matching::song_key;
+use crate::models::{PlaylistItem, Track};
+use crate::spotify::*;
+
+// An extra copy of a song in a playlist, with the position of the copy that is kept
+pub struct Duplicate<'a> {
+    pub position: usize,
+    pub track: &'a Track,
+    pub kept: usize,
+    // The very same track, rather than another release of the song
+    pub exact: bool,
+}
+
+// Function to describe a track as "Title - Artist, Artist"
+fn describe(track: &Track) -> String {
+    let artists: Vec<&str> = track.artists.iter().map(|a| a.name.as_str()).collect();
+    format!("{} - {}", track.name, artists.join(", "))
+}
+
+// Function to find the extra copies of songs among playlist tracks given with their positions, the first copy being kept
+// A copy is the same URI, the same ISRC, or the same cleaned title by the same main artist (a remaster, a single version...).
+pub fn find_duplicates<'a>(tracks: &[(usize, &'a Track)]) -> Vec<Duplicate<'a>> {
+    let mut first_by_uri: HashMap<&str, usize> = HashMap::new();
+    let mut first_by_song: HashMap<String, usize> = HashMap::new();
+    let mut duplicates = Vec::new();
+    for (position, track) in tracks {
+        let isrc = track.external_ids.isrc.as_ref().map(|isrc| format!("isrc:{}", isrc.to_uppercase()));
+        let song = track.artists.first().map(|artist| song_key(&track.name, &artist.name));
+        if let Some(kept) = first_by_uri.get(track.uri.as_str()) {
+            duplicates.push(Duplicate { position: *position, track, kept: *kept, exact: true });
+            continue;
+        }
+        if let Some(kept) = [&isrc, &song].into_iter().flatten().find_map(|key| first_by_song.get(key)) {
+            duplicates.push(Duplicate { position: *position, track, kept: *kept, exact: false });
+            continue;
+        }
+        first_by_uri.insert(&track.uri, *position);
+        for key in [isrc, song].into_iter().flatten() {
+            first_by_song.entry(key).or_insert(*position);
+        }
+    }
+    duplicates
+}
+
+// Function to remove the extra copies of songs from a playlist, the configured one by default
+pub fn run(client: Client, config: Config, options: &Options, playlist: Option<&str>) -> Result<(), PilotError> {
+    let playlist_id = match playlist {
+        Some(playlist) => playlist_argument(playlist)?,
+        None => config.default_playlist()?,
+    };
+    // A dry run only reads the playlist, an app token is enough
+    let access_token = if options.dry_run {
+        get_read_only_token(&client, &config)?
+    } else {
+        get_access_token(&client, &config, options.headless)?
+    };
+    let ctx = Context { client, config, access_token };
+
+    let playlist = get_playlist(&ctx, &playlist_id, None)?;
+    // Positions count every item, removed tracks, local files and episodes included
+    let tracks: Vec<(usize, &Track)> = playlist
+        .tracks
+        .items
+        .iter()
+        .enumerate()
+        .filter_map(|(position, item)| match &item.track {
+            Some(PlaylistItem::Track(track)) if !track.is_local => Some((position, track)),
+            _ => None,
+        })
+        .collect();
+    let duplicates = find_duplicates(&tracks);
+    if duplicates.is_empty() {
+        println!("No duplicates in {}.", playlist.name);
+        return Ok(());
+    }
+
+    let by_position: HashMap<usize, &Track> = tracks.iter().copied().collect();
+    println!("{} extra copies in {}:", duplicates.len(), playlist.name);
+    for duplicate in &duplicates {
+        let kind = if duplicate.exact { "same track as" } else { "another release of" };
+        println!("{:>4}. {} ({} {}. {})", duplicate.position + 1, describe(duplicate.track), kind, duplicate.kept + 1, describe(by_position[&duplicate.kept]));
+    }
+    if options.dry_run {
+        println!("Dry run, nothing was removed.");
+        return Ok(());
+    }
+    if !confirm("Remove the extra copies from the playlist?") {
+        return Ok(());
+    }
+
+    backup_playlist(&ctx, &playlist_id).map_err(|e| format!("Could not back up the playlist, nothing was removed: {}", e))?;
+    let occurrences: Vec<(String, usize)> = duplicates.iter().map(|duplicate| (duplicate.track.uri.clone(), duplicate.position)).collect();
+    remove_occurrences(&ctx, &playlist_id, &occurrences, playlist.snapshot_id.clone())?;
+    println!("Removed {} extra copies from the playlist.", duplicates.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::track_json;
+
+    // Function to build a track, optionally with an ISRC
+    fn track(id: &str, name: &str, artist: &str, isrc: Option<&str>) -> Track {
+        let mut json = track_json(id, name, artist);
+        if let Some(isrc) = isrc {
+            json["external_ids"] = serde_json::json!({ "isrc": isrc });
+        }
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn exact_and_near_duplicates_keep_the_first_copy() {
+        let tracks = [
+            track("a", "Karma Police", "Radiohead", None),
+            track("b", "Teardrop", "Massive Attack", Some("GBAAA9800001")),
+            track("a", "Karma Police", "Radiohead", None),
+            track("c", "Karma Police - Remastered 2017", "Radiohead", None),
+            track("d", "Teardrop (Single Edit)", "Massive Attack", Some("gbaaa9800001")),
+            track("e", "Karma", "Radiohead", None),
+        ];
+        let positioned: Vec<(usize, &Track)> = tracks.iter().enumerate().collect();
+        let found: Vec<(usize, usize, bool)> =
+            find_duplicates(&positioned).iter().map(|duplicate| (duplicate.position, duplicate.kept, duplicate.exact)).collect();
+        assert_eq!(found, vec![(2, 0, true), (3, 0, false), (4, 1, false)]);
+    }
+}
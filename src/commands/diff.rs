@@ -0,0 +1,122 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use serde::Serialize;
+
+use crate::auth::get_read_only_token;
+use crate::backup::*;
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::matching::song_key;
+use crate::spotify::get_playlist;
+
+// One side of a comparison: its name and entries
+struct Side {
+    name: String,
+    items: Vec<BackupItem>,
+}
+
+// A track in the comparison output
+#[derive(Debug, Serialize)]
+struct DiffTrack {
+    uri: String,
+    name: String,
+    artists: Vec<String>,
+}
+
+// A track found on both sides, with how it was matched
+#[derive(Debug, Serialize)]
+struct CommonTrack {
+    a: DiffTrack,
+    b: DiffTrack,
+    // True for the same Spotify track, false for the same title and artist in another release
+    same_uri: bool,
+}
+
+// Result of a comparison
+#[derive(Debug, Serialize)]
+struct Diff {
+    a: String,
+    b: String,
+    only_in_a: Vec<DiffTrack>,
+    only_in_b: Vec<DiffTrack>,
+    in_both: Vec<CommonTrack>,
+}
+
+// Function to describe an entry for the output
+fn diff_track(item: &BackupItem) -> DiffTrack {
+    DiffTrack { uri: item.uri.clone(), name: item.name.clone(), artists: item.artists.clone() }
+}
+
+// Function to build the keys identifying an entry by its title and each of its artists
+fn item_keys(item: &BackupItem) -> Vec<String> {
+    item.artists.iter().map(|artist| song_key(&item.name, artist)).collect()
+}
+
+// Function to read one side of the comparison: a backup file, or a playlist given as a link, URI or ID
+fn load_side(ctx: &Context, value: &str) -> Result<Side, PilotError> {
+    if let Ok(path) = find_backup(value) {
+        let backup = load_backup(&path)?;
+        return Ok(Side { name: path.display().to_string(), items: backup.items });
+    }
+    let playlist = get_playlist(ctx, &playlist_argument(value)?, None)?;
+    Ok(Side { name: playlist.name, items: backup_items(playlist.tracks.items) })
+}
+
+// Function to compare two sides, matching entries by URI first and by normalized title and artist second
+fn compare(a: Side, b: Side) -> Diff {
+    let mut b_left: Vec<Option<&BackupItem>> = b.items.iter().map(Some).collect();
+    let b_keys: Vec<Vec<String>> = b.items.iter().map(item_keys).collect();
+    let mut only_in_a = Vec::new();
+    let mut in_both = Vec::new();
+
+    for item in &a.items {
+        let by_uri = b_left.iter().position(|other| other.is_some_and(|other| other.uri == item.uri));
+        let keys = item_keys(item);
+        let by_key = || {
+            (0..b_left.len()).find(|index| b_left[*index].is_some() && b_keys[*index].iter().any(|key| keys.contains(key)))
+        };
+        match by_uri.or_else(by_key) {
+            Some(index) => {
+                let other = b_left[index].take().unwrap();
+                in_both.push(CommonTrack { a: diff_track(item), b: diff_track(other), same_uri: other.uri == item.uri });
+            },
+            None => only_in_a.push(diff_track(item)),
+        }
+    }
+
+    let only_in_b = b_left.into_iter().flatten().map(diff_track).collect();
+    Diff { a: a.name, b: b.name, only_in_a, only_in_b, in_both }
+}
+
+// Function to print a list of tracks under a title
+fn print_tracks(title: &str, tracks: &[DiffTrack]) {
+    println!("{} ({}):", title, tracks.len());
+    for track in tracks {
+        println!("  {} - {}", track.name, track.artists.join(", "));
+    }
+}
+
+// Function to show the tracks only in one of two playlists or backups, and those in both
+pub fn run(client: Client, config: Config, options: &Options, a: &str, b: &str) -> Result<(), PilotError> {
+    // Nothing is written, so the user token is only needed for private playlists
+    let access_token = get_read_only_token(&client, &config)?;
+    let ctx = Context { client, config, access_token };
+
+    let diff = compare(load_side(&ctx, a)?, load_side(&ctx, b)?);
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+        return Ok(());
+    }
+
+    print_tracks(&format!("Only in {}", diff.a), &diff.only_in_a);
+    print_tracks(&format!("Only in {}", diff.b), &diff.only_in_b);
+    println!("In both ({}):", diff.in_both.len());
+    for common in &diff.in_both {
+        let note = if common.same_uri { "" } else { " (another release)" };
+        println!("  {} - {}{}", common.a.name, common.a.artists.join(", "), note);
+    }
+    Ok(())
+}
@@ -0,0 +1,211 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use serde::Serialize;
+use std::fs;
+use std::str::FromStr;
+
+use crate::auth::get_read_only_token;
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::models::*;
+use crate::spotify::*;
+
+// Characters file systems don't allow in file names, replaced when a name is built from a title
+const UNSAFE_FILE_CHARACTERS: [char; 9] = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+// Columns of a CSV export, in order
+const CSV_HEADER: &str = "name,artists,album,uri,added_at,duration_ms,popularity";
+
+// File format a playlist is exported to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    // Extended M3U, with the .m3u extension
+    M3u,
+    // The same in UTF-8, with the .m3u8 extension
+    M3u8,
+    // One row per track, artists separated by semicolons
+    Csv,
+    // An array of track objects
+    Json,
+}
+
+impl ExportFormat {
+    // Function to get the extension of the exported files
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::M3u => "m3u",
+            ExportFormat::M3u8 => "m3u8",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "m3u" => Ok(ExportFormat::M3u),
+            "m3u8" => Ok(ExportFormat::M3u8),
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(format!("Unknown format: {} (expected m3u, m3u8, csv or json)", value)),
+        }
+    }
+}
+
+// One track of an exported playlist, in playlist order
+#[derive(Debug, Serialize)]
+struct ExportedTrack {
+    name: String,
+    artists: Vec<String>,
+    album: String,
+    uri: String,
+    added_at: Option<String>,
+    duration_ms: u64,
+    // Missing for local files
+    popularity: Option<u32>,
+}
+
+// Function to keep the tracks of playlist items with what is exported of them, local files included
+// Episodes and removed tracks are left out.
+fn exported_tracks(items: Vec<TrackItem>) -> Vec<ExportedTrack> {
+    items
+        .into_iter()
+        .filter_map(|item| match item.track {
+            Some(PlaylistItem::Track(track)) => Some(ExportedTrack {
+                name: track.name,
+                artists: track.artists.into_iter().map(|artist| artist.name).collect(),
+                album: track.album.name,
+                uri: track.uri,
+                added_at: item.added_at,
+                duration_ms: track.duration_ms,
+                popularity: track.popularity,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+// Function to turn a title into a file name, e.g. "AC/DC - T.N.T." becomes "AC_DC - T.N.T."
+fn file_name(title: &str) -> String {
+    title.chars().map(|c| if UNSAFE_FILE_CHARACTERS.contains(&c) { '_' } else { c }).collect::<String>().trim().to_string()
+}
+
+// Function to write the tracks of a playlist as an extended M3U playlist
+// Spotify tracks have no file, so each entry points at "<Artist> - <Title>.mp3" next to the playlist, the usual name of ripped
+// or downloaded files, and keeps the Spotify link as a comment.
+fn render_m3u(name: &str, tracks: &[ExportedTrack]) -> String {
+    let mut lines = vec!["#EXTM3U".to_string(), format!("#PLAYLIST:{}", name)];
+    for track in tracks {
+        let title = format!("{} - {}", track.artists.join(", "), track.name);
+        lines.push(format!("#EXTINF:{},{}", track.duration_ms / 1000, title));
+        if !track.album.is_empty() {
+            lines.push(format!("#EXTALB:{}", track.album));
+        }
+        if let Some(id) = parse_spotify_id(&track.uri, "track") {
+            lines.push(format!("# https://open.spotify.com/track/{}", id));
+        }
+        lines.push(format!("{}.mp3", file_name(&title)));
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+// Function to quote a CSV field when it holds a separator, a quote or a line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Function to write the tracks of a playlist as CSV, with a header row
+fn render_csv(tracks: &[ExportedTrack]) -> String {
+    let mut lines = vec![CSV_HEADER.to_string()];
+    for track in tracks {
+        let fields = [
+            track.name.clone(),
+            track.artists.join("; "),
+            track.album.clone(),
+            track.uri.clone(),
+            track.added_at.clone().unwrap_or_default(),
+            track.duration_ms.to_string(),
+            track.popularity.map(|popularity| popularity.to_string()).unwrap_or_default(),
+        ];
+        lines.push(fields.iter().map(|field| csv_field(field)).collect::<Vec<String>>().join(","));
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+// Function to export a playlist to a file for local players, spreadsheets or other tools, named after the playlist unless --output is given
+pub fn run(client: Client, config: Config, options: &Options, playlist: Option<&str>) -> Result<(), PilotError> {
+    let playlist_id = match playlist {
+        Some(playlist) => playlist_argument(playlist)?,
+        None => config.default_playlist()?,
+    };
+    // Nothing is written to Spotify, so the user token is only needed for private playlists
+    let access_token = get_read_only_token(&client, &config)?;
+    let ctx = Context { client, config, access_token };
+
+    let playlist = get_playlist(&ctx, &playlist_id, None)?;
+    let length = playlist.tracks.items.len();
+    let tracks = exported_tracks(playlist.tracks.items);
+    let content = match options.format {
+        ExportFormat::M3u | ExportFormat::M3u8 => render_m3u(&playlist.name, &tracks),
+        ExportFormat::Csv => render_csv(&tracks),
+        ExportFormat::Json => serde_json::to_string_pretty(&tracks)? + "\n",
+    };
+
+    let path = options.output.clone().unwrap_or_else(|| format!("{}.{}", file_name(&playlist.name), options.format.extension()));
+    fs::write(&path, content).map_err(|e| format!("{}: {}", path, e))?;
+    println!("Exported {} tracks of '{}' to {}.", tracks.len(), playlist.name, path);
+    if tracks.len() < length {
+        println!("{} items that aren't tracks (episodes, removed tracks) were left out.", length - tracks.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track() -> ExportedTrack {
+        ExportedTrack {
+            name: "T.N.T.".to_string(),
+            artists: vec!["AC/DC".to_string()],
+            album: "High Voltage".to_string(),
+            uri: "spotify:track:abc".to_string(),
+            added_at: Some("2024-05-21T18:03:12Z".to_string()),
+            duration_ms: 214_000,
+            popularity: Some(71),
+        }
+    }
+
+    #[test]
+    fn render_m3u_writes_extended_entries() {
+        assert_eq!(
+            render_m3u("Rock", &[track()]),
+            "#EXTM3U\n#PLAYLIST:Rock\n#EXTINF:214,AC/DC - T.N.T.\n#EXTALB:High Voltage\n# https://open.spotify.com/track/abc\nAC_DC - T.N.T..mp3\n"
+        );
+    }
+
+    #[test]
+    fn render_csv_quotes_fields_when_needed() {
+        let mut other = track();
+        other.name = "Love, \"Reign\" o'er Me".to_string();
+        other.artists = vec!["The Who".to_string(), "Pearl Jam".to_string()];
+        other.popularity = None;
+        let csv = render_csv(&[track(), other]);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines[1], "T.N.T.,AC/DC,High Voltage,spotify:track:abc,2024-05-21T18:03:12Z,214000,71");
+        assert_eq!(lines[2], "\"Love, \"\"Reign\"\" o'er Me\",The Who; Pearl Jam,High Voltage,spotify:track:abc,2024-05-21T18:03:12Z,214000,");
+    }
+}
@@ -0,0 +1,21 @@
+// Import necessary modules and crates
+use std::path::PathBuf;
+
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::error::PilotError;
+use crate::feed::write_feed;
+
+// Function to write the Atom feed of the tracks runs added to a playlist (the configured one by default)
+// Only the local history is used, so nothing is asked from Spotify; the file is named after the playlist ID unless --output is given.
+pub fn run(config: Config, options: &Options, playlist: Option<&str>) -> Result<(), PilotError> {
+    let playlist_id = match playlist {
+        Some(playlist) => playlist_argument(playlist)?,
+        None => config.default_playlist()?,
+    };
+    let path = PathBuf::from(options.output.clone().unwrap_or_else(|| format!("{}.xml", playlist_id)));
+    let entries = write_feed(&config, &playlist_id, &path)?;
+    println!("Wrote the feed of the {} latest tracks added to playlist {} to {}.", entries, playlist_id, path.display());
+    Ok(())
+}
@@ -0,0 +1,40 @@
+// Import necessary modules and crates
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::error::PilotError;
+use crate::history::*;
+
+// Function to rate the tracks runs added to a playlist (the configured one by default) as keepers or misses
+// Only the local history is used, so nothing is asked from Spotify.
+pub fn run(config: Config, playlist: Option<&str>) -> Result<(), PilotError> {
+    let playlist_id = match playlist {
+        Some(playlist) => playlist_argument(playlist)?,
+        None => config.default_playlist()?,
+    };
+    let history = History::open()?;
+    let tracks = history.unrated_tracks(&playlist_id)?;
+    if tracks.is_empty() {
+        println!("No added tracks left to rate for playlist {}.", playlist_id);
+        return Ok(());
+    }
+
+    println!("Tracks added to playlist {} by earlier runs:", playlist_id);
+    for (index, track) in tracks.iter().enumerate() {
+        println!("  {}. {} - {}", index + 1, track.name, track.artists);
+    }
+    let keepers = parse_selection(&ask("Keepers (e.g. 1 3 5-7, empty for none):"), tracks.len())?;
+    let misses = parse_selection(&ask("Misses (e.g. 2 4, empty for none):"), tracks.len())?;
+    if let Some(index) = keepers.iter().find(|index| misses.contains(index)) {
+        return Err(format!("Track {} can't be both a keeper and a miss.", index + 1).into());
+    }
+
+    for index in &keepers {
+        history.rate(&playlist_id, &tracks[*index], Rating::Keeper)?;
+    }
+    for index in &misses {
+        history.rate(&playlist_id, &tracks[*index], Rating::Miss)?;
+    }
+    println!("Saved {} keepers and {} misses, the next suggestions for this playlist will take them into account.", keepers.len(), misses.len());
+    Ok(())
+}
@@ -0,0 +1,52 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+
+use crate::auth::get_read_only_token;
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::spotify::*;
+
+// Number of genres shown in the chart
+const CHART_GENRES: usize = 15;
+
+// Width of the longest bar, in characters
+const CHART_WIDTH: usize = 40;
+
+// Function to print a bar chart of the genres of a playlist, from its artists' genre tags
+pub fn run(client: Client, config: Config, options: &Options, playlist: Option<&str>) -> Result<(), PilotError> {
+    let playlist_id = match playlist {
+        Some(playlist) => playlist_argument(playlist)?,
+        None => config.default_playlist()?,
+    };
+    // Nothing is written, so the user token is only needed for private playlists
+    let access_token = get_read_only_token(&client, &config)?;
+    let ctx = Context { client, config, access_token };
+
+    let playlist = get_playlist(&ctx, &playlist_id, None)?;
+    let (tracks, _) = spotify_tracks(playlist.tracks.items);
+    let counts = genre_counts(&ctx, &tracks)?;
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&counts)?);
+        return Ok(());
+    }
+    if counts.is_empty() {
+        println!("Spotify has no genres for the artists of '{}'.", playlist.name);
+        return Ok(());
+    }
+
+    println!("Genres of '{}' ({} tracks):", playlist.name, tracks.len());
+    let widest = counts.iter().take(CHART_GENRES).map(|(genre, _)| genre.chars().count()).max().unwrap_or(0);
+    let largest = counts[0].1;
+    for (genre, count) in counts.iter().take(CHART_GENRES) {
+        let bar = "█".repeat((count * CHART_WIDTH).div_ceil(largest));
+        let share = *count as f64 * 100.0 / tracks.len() as f64;
+        println!("  {:<widest$}  {} {:.0}%", genre, bar, share);
+    }
+    if counts.len() > CHART_GENRES {
+        println!("  ... and {} more genres", counts.len() - CHART_GENRES);
+    }
+    Ok(())
+}
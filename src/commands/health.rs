@@ -0,0 +1,199 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::auth::*;
+use crate::cli::*;
+use crate::commands::cleanup::find_unplayable;
+use crate::commands::dedupe::find_duplicates;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::models::{PlaylistItem, Track};
+use crate::spotify::*;
+use crate::tokens::{iso_date, now};
+
+// Tracks added longer ago than this count as stale, in days
+const STALE_DAYS: u64 = 365;
+
+// Share of the tracks a single artist can have before a fix is suggested
+const TOP_ARTIST_SHARE: f64 = 0.2;
+
+// Share of stale tracks above which fresh songs are suggested
+const STALE_SHARE: f64 = 0.5;
+
+// Score of one aspect of a playlist, from 0 to 100
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub score: u32,
+    pub detail: String,
+}
+
+// Scores of a playlist, with the fixes that would improve them
+#[derive(Serialize)]
+struct Health {
+    name: String,
+    tracks: usize,
+    // Average of the checks
+    score: u32,
+    checks: Vec<Check>,
+    fixes: Vec<String>,
+}
+
+// Function to turn a share of good tracks into a score
+fn percent(good: usize, total: usize) -> u32 {
+    if total == 0 { 100 } else { (good as f64 * 100.0 / total as f64).round() as u32 }
+}
+
+// Function to score the tracks of a playlist on diversity, freshness, duplicates and playability, and suggest fixes
+// `added` holds when each item was added, `since` is the date before which a track is stale, and `unplayable` is None when
+// playability couldn't be checked.
+pub fn assess(playlist_id: &str, tracks: &[&Track], added: &[Option<&str>], since: &str, duplicates: usize, unplayable: Option<usize>) -> (Vec<Check>, Vec<String>) {
+    let mut checks = Vec::new();
+    let mut fixes = Vec::new();
+
+    // Diversity: one point per distinct main artist per track
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for track in tracks {
+        if let Some(artist) = track.artists.first() {
+            *counts.entry(artist.name.as_str()).or_default() += 1;
+        }
+    }
+    let top = counts.iter().max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0))).map(|(artist, count)| (*artist, *count));
+    let detail = match top {
+        Some((artist, count)) => format!("{} artists for {} tracks, {} of them by {}", counts.len(), tracks.len(), count, artist),
+        None => "no tracks".to_string(),
+    };
+    checks.push(Check { name: "Diversity", score: percent(counts.len(), tracks.len()), detail });
+    if let Some((artist, count)) = top.filter(|(_, count)| *count > 1 && *count as f64 > tracks.len() as f64 * TOP_ARTIST_SHARE) {
+        fixes.push(format!(
+            "{} has {} of the {} tracks: `playlistpilot --playlist {} --exclude \"{}\"` adds songs by other artists.",
+            artist,
+            count,
+            tracks.len(),
+            playlist_id,
+            artist
+        ));
+    }
+
+    // Freshness: tracks added within STALE_DAYS, those without a date being the oldest
+    let stale = added.iter().filter(|date| date.is_none_or(|date| date < since)).count();
+    let oldest = added.iter().flatten().min();
+    let detail = match oldest {
+        Some(oldest) => format!("{} of {} added over a year ago, the oldest on {}", stale, added.len(), &oldest[..oldest.len().min(10)]),
+        None => format!("{} of {} added over a year ago", stale, added.len()),
+    };
+    checks.push(Check { name: "Freshness", score: percent(added.len() - stale, added.len()), detail });
+    if !added.is_empty() && stale as f64 > added.len() as f64 * STALE_SHARE {
+        fixes.push(format!("Most tracks were added over a year ago: `playlistpilot --playlist {}` adds fresh songs.", playlist_id));
+    }
+
+    let detail = if duplicates == 0 { "no extra copies".to_string() } else { format!("{} extra copies", duplicates) };
+    checks.push(Check { name: "Duplicates", score: percent(tracks.len() - duplicates.min(tracks.len()), tracks.len()), detail });
+    if duplicates > 0 {
+        fixes.push(format!("`playlistpilot dedupe {}` removes the {} extra copies.", playlist_id, duplicates));
+    }
+
+    if let Some(unplayable) = unplayable {
+        let detail = if unplayable == 0 { "every track can be played".to_string() } else { format!("{} tracks can't be played", unplayable) };
+        checks.push(Check { name: "Playability", score: percent(tracks.len() - unplayable.min(tracks.len()), tracks.len()), detail });
+        if unplayable > 0 {
+            fixes.push(format!("`playlistpilot cleanup {} --find-replacements` swaps the {} unplayable tracks for playable editions.", playlist_id, unplayable));
+        }
+    }
+    (checks, fixes)
+}
+
+// Function to score the health of a playlist and suggest how to improve it, the configured playlist by default
+pub fn run(client: Client, config: Config, options: &Options, playlist: Option<&str>) -> Result<(), PilotError> {
+    let playlist_id = match playlist {
+        Some(playlist) => playlist_argument(playlist)?,
+        None => config.default_playlist()?,
+    };
+    // As with cleanup, without a configured market the user's own market is checked, which needs their token
+    let market = config.market.clone().unwrap_or_else(|| "from_token".to_string());
+    let access_token = if config.market.is_some() {
+        get_read_only_token(&client, &config)?
+    } else {
+        get_access_token(&client, &config, options.headless)?
+    };
+    let ctx = Context { client, config, access_token };
+
+    let playlist = get_playlist(&ctx, &playlist_id, None)?;
+    // Positions count every item, removed tracks, local files and episodes included
+    let positioned: Vec<(usize, &Track)> = playlist
+        .tracks
+        .items
+        .iter()
+        .enumerate()
+        .filter_map(|(position, item)| match &item.track {
+            Some(PlaylistItem::Track(track)) if !track.is_local => Some((position, track)),
+            _ => None,
+        })
+        .collect();
+    let tracks: Vec<&Track> = positioned.iter().map(|(_, track)| *track).collect();
+    let added: Vec<Option<&str>> = playlist.tracks.items.iter().filter(|item| item.track.is_some()).map(|item| item.added_at.as_deref()).collect();
+    if added.is_empty() {
+        println!("'{}' is empty.", playlist.name);
+        return Ok(());
+    }
+
+    let duplicates = find_duplicates(&positioned).len();
+    let uris: Vec<String> = tracks.iter().map(|track| track.uri.clone()).collect();
+    let unplayable = match playable_versions(&ctx, &uris, &market) {
+        Ok(versions) => Some(find_unplayable(&positioned, &versions).len()),
+        Err(e) => {
+            // On stderr, so the JSON output stays parseable
+            eprintln!("Could not check which tracks can be played: {}", e);
+            None
+        },
+    };
+    let since = iso_date(now().saturating_sub(STALE_DAYS * 86400));
+    let (checks, fixes) = assess(&playlist_id, &tracks, &added, &since, duplicates, unplayable);
+    let score = (checks.iter().map(|check| check.score).sum::<u32>() as f64 / checks.len() as f64).round() as u32;
+    let health = Health { name: playlist.name, tracks: added.len(), score, checks, fixes };
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&health)?);
+        return Ok(());
+    }
+
+    println!("Health of '{}' ({} tracks): {}/100", health.name, health.tracks, health.score);
+    for check in &health.checks {
+        println!("  {:<12} {:>3}  {}", check.name, check.score, check.detail);
+    }
+    if health.fixes.is_empty() {
+        println!("Nothing to fix.");
+    } else {
+        println!("To improve it:");
+        for fix in &health.fixes {
+            println!("  - {}", fix);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::track_json;
+
+    #[test]
+    fn checks_score_the_playlist_and_suggest_fixes() {
+        let tracks: Vec<Track> = [("a", "Radiohead"), ("b", "Radiohead"), ("c", "Radiohead"), ("d", "Portishead")]
+            .iter()
+            .map(|(id, artist)| serde_json::from_value(track_json(id, "Song", artist)).unwrap())
+            .collect();
+        let tracks: Vec<&Track> = tracks.iter().collect();
+        let added = [Some("2020-01-02T10:00:00Z"), None, Some("2026-01-01T10:00:00Z"), Some("2026-02-01T10:00:00Z")];
+        let (checks, fixes) = assess("xyz", &tracks, &added, "2025-10-14T00:00:00Z", 1, Some(0));
+        let scores: Vec<(&str, u32)> = checks.iter().map(|check| (check.name, check.score)).collect();
+        assert_eq!(scores, vec![("Diversity", 50), ("Freshness", 50), ("Duplicates", 75), ("Playability", 100)]);
+        assert!(checks[1].detail.ends_with("the oldest on 2020-01-02"));
+        assert_eq!(fixes.len(), 2);
+        assert!(fixes[0].contains("--exclude \"Radiohead\""));
+        assert!(fixes[1].contains("dedupe xyz"));
+    }
+}
@@ -0,0 +1,104 @@
+// Import necessary modules and crates
+use std::collections::HashMap;
+
+use crate::backup::{backup_items, BackupItem};
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::history::History;
+use crate::spotify::get_playlist;
+use crate::tokens::iso_date;
+
+// What changed in a playlist between two snapshots
+pub struct Changes<'a> {
+    pub added: Vec<&'a BackupItem>,
+    pub removed: Vec<&'a BackupItem>,
+}
+
+// Function to find the tracks added and removed between two snapshots of a playlist, by URI
+// A track in twice before and once after counts as one copy removed; moving a track changes nothing.
+pub fn changes<'a>(before: &'a [BackupItem], after: &'a [BackupItem]) -> Changes<'a> {
+    let mut copies: HashMap<&str, usize> = HashMap::new();
+    for item in before {
+        *copies.entry(&item.uri).or_default() += 1;
+    }
+    let mut added = Vec::new();
+    for item in after {
+        match copies.get_mut(item.uri.as_str()) {
+            Some(left) if *left > 0 => *left -= 1,
+            _ => added.push(item),
+        }
+    }
+    // The copies left over are the last ones of each track in the old snapshot
+    let mut removed = Vec::new();
+    for item in before.iter().rev() {
+        if let Some(left) = copies.get_mut(item.uri.as_str()).filter(|left| **left > 0) {
+            *left -= 1;
+            removed.push(item);
+        }
+    }
+    removed.reverse();
+    Changes { added, removed }
+}
+
+// Function to describe a snapshot entry as "Title - Artist, Artist"
+fn describe(item: &BackupItem) -> String {
+    format!("{} - {}", item.name, item.artists.join(", "))
+}
+
+// Function to archive the current content of a playlist in the local database, returning false when it didn't change
+pub fn archive_playlist(ctx: &Context, playlist_id: &str) -> Result<bool, PilotError> {
+    let playlist = get_playlist(ctx, playlist_id, None)?;
+    let items = backup_items(playlist.tracks.items);
+    History::open()?.save_snapshot(playlist_id, &playlist.snapshot_id, &playlist.name, &items)
+}
+
+// Function to show what was added to and removed from a playlist between its archived snapshots, the configured playlist by default
+// Only the local database is read; the daemon takes the snapshots of the playlists in [[archives]].
+pub fn run(config: &Config, playlist: Option<&str>) -> Result<(), PilotError> {
+    let playlist_id = match playlist {
+        Some(playlist) => playlist_argument(playlist)?,
+        None => config.default_playlist()?,
+    };
+    let snapshots = History::open()?.snapshots(&playlist_id)?;
+    let Some(first) = snapshots.first() else {
+        println!("No snapshots of {} yet, add it to [[archives]] and run the daemon.", playlist_id);
+        return Ok(());
+    };
+
+    println!("{}: {} tracks", iso_date(first.taken_at), first.items.len());
+    for pair in snapshots.windows(2) {
+        let (before, after) = (&pair[0], &pair[1]);
+        let changes = changes(&before.items, &after.items);
+        let renamed = if after.name != before.name { format!(", renamed to {}", after.name) } else { String::new() };
+        println!("{}: {} tracks, {} added, {} removed{}", iso_date(after.taken_at), after.items.len(), changes.added.len(), changes.removed.len(), renamed);
+        for item in changes.added {
+            println!("  + {}", describe(item));
+        }
+        for item in changes.removed {
+            println!("  - {}", describe(item));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Function to build a snapshot entry
+    fn item(uri: &str) -> BackupItem {
+        BackupItem { uri: uri.to_string(), name: uri.to_uppercase(), artists: vec!["Artist".to_string()], is_local: false }
+    }
+
+    #[test]
+    fn changes_count_copies_and_ignore_moves() {
+        let before = [item("a"), item("b"), item("a"), item("c")];
+        let after = [item("c"), item("a"), item("d"), item("b"), item("d")];
+        let changes = changes(&before, &after);
+        let uris = |items: &[&BackupItem]| items.iter().map(|item| item.uri.clone()).collect::<Vec<String>>();
+        assert_eq!(uris(&changes.added), vec!["d", "d"]);
+        assert_eq!(uris(&changes.removed), vec!["a"]);
+    }
+}
@@ -0,0 +1,214 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use std::fs;
+use std::process::Command;
+
+use crate::cli::*;
+use crate::commands::suggest::run_list;
+use crate::config::Config;
+use crate::error::PilotError;
+use crate::models::Song;
+
+// Names a CSV header may give the columns of the artist, the title and the ISRC
+const ARTIST_COLUMNS: [&str; 2] = ["artist", "artists"];
+const TITLE_COLUMNS: [&str; 4] = ["title", "name", "track", "song"];
+const ISRC_COLUMNS: [&str; 1] = ["isrc"];
+
+// Longest pasted line read as a song, longer ones are sentences of the surrounding text
+const MAX_PASTED_LINE: usize = 120;
+
+// Separators between the artist and the title of a pasted song, hyphen and dashes
+const PASTED_SEPARATORS: [&str; 3] = [" - ", " – ", " — "];
+
+// Quotes pasted titles are often written between
+const QUOTES: [char; 5] = ['"', '\'', '“', '”', '‘'];
+
+// Function to split a CSV line into its fields, quoted fields may hold commas and doubled quotes
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            },
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields.into_iter().map(|field| field.trim().to_string()).collect()
+}
+
+// Function to find a column of a CSV header by its possible names
+fn column(header: &[String], names: &[&str]) -> Option<usize> {
+    header.iter().position(|field| names.contains(&field.to_lowercase().as_str()))
+}
+
+// Function to make a song from an artist and a title, None when either is missing
+// Several artists (e.g. "The Who; Pearl Jam" in an export) are searched by the first one.
+fn song(artist: &str, title: &str, isrc: Option<&str>) -> Option<Song> {
+    let artist = artist.split(';').next().unwrap_or_default().trim();
+    let title = title.trim();
+    if artist.is_empty() || title.is_empty() {
+        return None;
+    }
+    Some(Song {
+        name: title.to_string(),
+        artist: artist.to_string(),
+        isrc: isrc.map(str::trim).filter(|isrc| !isrc.is_empty()).map(str::to_string),
+        mbid: None,
+    })
+}
+
+// Function to read the songs of a list: "artist,title" rows, CSV with a header naming its columns, or "Title - Artist" lines
+// Blank lines and lines starting with # (e.g. M3U comments) are skipped, as are lines that fit none of these shapes.
+pub fn parse_songs(text: &str) -> Vec<Song> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')).peekable();
+
+    // A header is recognized by naming both an artist and a title column
+    let columns = lines.peek().and_then(|first| {
+        let header = csv_fields(first);
+        Some((column(&header, &ARTIST_COLUMNS)?, column(&header, &TITLE_COLUMNS)?, column(&header, &ISRC_COLUMNS)))
+    });
+    if let Some((artist, title, isrc)) = columns {
+        lines.next();
+        return lines
+            .filter_map(|line| {
+                let fields = csv_fields(line);
+                song(fields.get(artist)?, fields.get(title)?, isrc.and_then(|isrc| fields.get(isrc)).map(String::as_str))
+            })
+            .collect();
+    }
+
+    lines
+        .filter_map(|line| {
+            if line.contains(',') {
+                let fields = csv_fields(line);
+                song(fields.first()?, fields.get(1)?, None)
+            } else {
+                let (title, artist) = line.split_once(" - ").or_else(|| line.split_once(" – "))?;
+                song(artist, title, None)
+            }
+        })
+        .collect()
+}
+
+// Function to remove the list marker of a pasted line, e.g. "1. ", "2) " or "• "
+fn strip_list_marker(line: &str) -> &str {
+    let unnumbered = line.trim_start_matches(|c: char| c.is_ascii_digit());
+    let line = match unnumbered.strip_prefix(['.', ')']) {
+        Some(rest) if unnumbered.len() < line.len() => rest,
+        _ => line,
+    };
+    line.trim_start_matches(['-', '*', '•', '–']).trim()
+}
+
+// Function to clean a pasted title or artist: surrounding quotes and a trailing year such as "(1994)" are removed
+fn clean_title(title: &str) -> &str {
+    let title = title.trim();
+    let title = match title.rsplit_once(" (") {
+        Some((rest, year)) if year.len() == 5 && year.ends_with(')') && year[..4].chars().all(|c| c.is_ascii_digit()) => rest,
+        _ => title,
+    };
+    title.trim_matches(QUOTES).trim()
+}
+
+// Function to read the songs of text pasted from a blog post or a chat, one song per line
+// Lines are "Artist - Title" or "Title by Artist", possibly numbered or bulleted; a quoted part is always the title.
+// Lines that don't look like a song are skipped.
+pub fn parse_pasted(text: &str) -> Vec<Song> {
+    text.lines()
+        .map(|line| strip_list_marker(line.trim()))
+        .filter(|line| !line.is_empty() && line.chars().count() <= MAX_PASTED_LINE)
+        .filter_map(|line| {
+            let quoted = |part: &str| part.trim().starts_with(QUOTES);
+            if let Some((left, right)) = PASTED_SEPARATORS.iter().find_map(|separator| line.split_once(separator)) {
+                let (artist, title) = if quoted(left) && !quoted(right) { (right, left) } else { (left, right) };
+                return song(clean_title(artist), clean_title(title), None);
+            }
+            let (title, artist) = line.rsplit_once(" by ")?;
+            song(clean_title(artist), clean_title(title), None)
+        })
+        .collect()
+}
+
+// Function to read the text of the clipboard with the tool of the platform
+fn read_clipboard() -> Result<String, PilotError> {
+    let tools: &[(&str, &[&str])] = if cfg!(target_os = "macos") {
+        &[("pbpaste", &[])]
+    } else if cfg!(target_os = "windows") {
+        &[("powershell", &["-NoProfile", "-Command", "Get-Clipboard"])]
+    } else {
+        // Wayland first, then the two usual X11 tools
+        &[("wl-paste", &["--no-newline"]), ("xclip", &["-selection", "clipboard", "-o"]), ("xsel", &["--clipboard", "--output"])]
+    };
+    for (program, args) in tools {
+        if let Ok(output) = Command::new(program).args(*args).output() {
+            if output.status.success() {
+                return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+            }
+        }
+    }
+    let names: Vec<&str> = tools.iter().map(|(program, _)| *program).collect();
+    Err(format!("Could not read the clipboard, install one of: {}", names.join(", ")).into())
+}
+
+// Function to add the songs listed in the clipboard to a playlist, matched like suggestions but without the LLM
+pub fn run_clipboard(client: Client, config: Config, options: &Options) -> Result<(), PilotError> {
+    let songs = parse_pasted(&read_clipboard()?);
+    println!("Read {} songs from the clipboard.", songs.len());
+    for song in &songs {
+        println!("  {} - {}", song.artist, song.name);
+    }
+    run_list(client, config, options, songs, "the clipboard")
+}
+
+// Function to add the songs listed in a text or CSV file to a playlist, matched like suggestions but without the LLM
+pub fn run(client: Client, config: Config, options: &Options, path: &str) -> Result<(), PilotError> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let songs = parse_songs(&text);
+    println!("Read {} songs from {}.", songs.len(), path);
+    run_list(client, config, options, songs, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(songs: &[Song]) -> Vec<(&str, &str)> {
+        songs.iter().map(|song| (song.artist.as_str(), song.name.as_str())).collect()
+    }
+
+    #[test]
+    fn parse_songs_reads_rows_lines_and_headers() {
+        let plain = "Portishead,Glory Box\n\n# a comment\nTeardrop - Massive Attack\n\"Simon & Garfunkel\",\"Cecilia, Live\"\nnot a song\n";
+        assert_eq!(
+            pairs(&parse_songs(plain)),
+            vec![("Portishead", "Glory Box"), ("Massive Attack", "Teardrop"), ("Simon & Garfunkel", "Cecilia, Live")]
+        );
+
+        let exported = "name,artists,album,uri,isrc\nT.N.T.,AC/DC,High Voltage,spotify:track:abc,AUAP07600012\nLove,The Who; Pearl Jam,,,\n";
+        let songs = parse_songs(exported);
+        assert_eq!(pairs(&songs), vec![("AC/DC", "T.N.T."), ("The Who", "Love")]);
+        assert_eq!(songs[0].isrc.as_deref(), Some("AUAP07600012"));
+        assert_eq!(songs[1].isrc, None);
+    }
+
+    #[test]
+    fn parse_pasted_reads_numbered_and_bulleted_lines() {
+        let pasted = "My favourite songs of the summer:\n1. Portishead - Glory Box\n2) \"Stand by Me\" by Ben E. King\n\
+            • “Teardrop” – Massive Attack\n- Roads by Portishead (1994)\n\nThanks for reading!";
+        assert_eq!(
+            pairs(&parse_pasted(pasted)),
+            vec![
+                ("Portishead", "Glory Box"),
+                ("Ben E. King", "Stand by Me"),
+                ("Massive Attack", "Teardrop"),
+                ("Portishead", "Roads"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,337 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, Write};
+
+use crate::auth::{get_cached_access_token, has_cached_token};
+use crate::cli::*;
+use crate::commands::serve::{confident, suggestions_json};
+use crate::commands::suggest::Preview;
+use crate::commands::{playlist_argument, undo};
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::llm::has_llm_secret;
+use crate::matching::TrackFilter;
+use crate::models::Song;
+use crate::report::track_link;
+use crate::spotify::{add_to_playlist, get_playlist, search_song, spotify_tracks};
+
+// Version of the Model Context Protocol spoken
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+// Number of songs the suggest tool asks for when the client doesn't say, and at most
+const DEFAULT_MCP_COUNT: usize = 10;
+const MAX_MCP_COUNT: usize = 50;
+
+// Tracks of a playlist given to the client at most, a long playlist would fill its context
+const MAX_PLAYLIST_TRACKS: usize = 200;
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+// Function to describe the tools the server offers, with the JSON schema of their arguments
+fn tool_definitions() -> Value {
+    let playlist = json!({ "type": "string", "description": "Spotify playlist link, URI or ID, the configured playlist when left out" });
+    json!([
+        {
+            "name": "get_playlist",
+            "description": "Read a Spotify playlist: its name and tracks, with their URIs.",
+            "inputSchema": { "type": "object", "properties": { "playlist": playlist } },
+        },
+        {
+            "name": "suggest",
+            "description": "Suggest songs that fit a playlist, matched on Spotify. Nothing is added: the answer is a preview \
+                            whose suggestions are added with apply_suggestions.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "playlist": playlist,
+                    "count": { "type": "integer", "minimum": 1, "maximum": MAX_MCP_COUNT, "description": "Number of songs" },
+                    "vibe": { "type": "string", "description": "Mood the songs should also fit" },
+                },
+            },
+        },
+        {
+            "name": "apply_suggestions",
+            "description": "Add suggestions of a preview to its playlist. The others are recorded as rejected and not suggested again.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "preview": { "type": "integer", "description": "Preview ID returned by suggest" },
+                    "approve": {
+                        "type": "array",
+                        "items": { "type": "integer" },
+                        "description": "Indexes of the suggestions to add, the confident matches when left out",
+                    },
+                },
+                "required": ["preview"],
+            },
+        },
+        {
+            "name": "search",
+            "description": "Search Spotify for a song and return the best match with its URI and how confident the match is.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "artist": { "type": "string" }, "name": { "type": "string", "description": "Title of the song" } },
+                "required": ["artist", "name"],
+            },
+        },
+        {
+            "name": "add_tracks",
+            "description": "Append tracks to a playlist by their Spotify URIs. They aren't part of a run, so undo doesn't remove them.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "playlist": playlist, "uris": { "type": "array", "items": { "type": "string" } } },
+                "required": ["uris"],
+            },
+        },
+        {
+            "name": "undo",
+            "description": "Remove the tracks the most recent run added, after backing up the playlist.",
+            "inputSchema": { "type": "object", "properties": {} },
+        },
+    ])
+}
+
+// Function to answer the methods of the protocol that don't touch the services, None for the others
+fn protocol_method(method: &str) -> Option<Result<Value, (i64, String)>> {
+    match method {
+        "initialize" => Some(Ok(json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "playlistpilot", "version": env!("CARGO_PKG_VERSION") },
+        }))),
+        "ping" => Some(Ok(json!({}))),
+        "tools/list" => Some(Ok(json!({ "tools": tool_definitions() }))),
+        "tools/call" => None,
+        _ => Some(Err((METHOD_NOT_FOUND, format!("Method not found: {}", method)))),
+    }
+}
+
+// Function to write the JSON-RPC response to a request
+fn response(id: Value, result: Result<Value, (i64, String)>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+    }
+}
+
+// Function to read an optional text argument of a tool
+fn text_argument(arguments: &Value, name: &str) -> Option<String> {
+    arguments.get(name).and_then(Value::as_str).map(str::trim).filter(|value| !value.is_empty()).map(str::to_string)
+}
+
+// What the tools share across calls
+struct McpServer {
+    client: Client,
+    config: Config,
+    options: Options,
+    // Previews waiting to be applied, by their ID
+    previews: HashMap<u64, Preview>,
+    next_id: u64,
+}
+
+impl McpServer {
+    // Function to get the settings with the playlist a tool was given, the configured one when it wasn't
+    fn config_for(&self, arguments: &Value) -> Result<Config, PilotError> {
+        let mut config = self.config.clone();
+        if let Some(playlist) = text_argument(arguments, "playlist") {
+            config.playlist_id = Some(playlist_argument(&playlist)?);
+        }
+        Ok(config)
+    }
+
+    // Function to build the context of a call, the cached token being refreshed when needed
+    // The user is never asked to authorize: the prompt would go through the protocol's input.
+    fn context(&self, config: Config) -> Result<Context, PilotError> {
+        let access_token = get_cached_access_token(&self.client, &config)?;
+        Ok(Context { client: self.client.clone(), config, access_token })
+    }
+
+    // Function to run a tool, returning what it gives back to the client
+    fn call_tool(&mut self, name: &str, arguments: &Value) -> Result<Value, PilotError> {
+        match name {
+            "get_playlist" => {
+                let ctx = self.context(self.config_for(arguments)?)?;
+                let playlist_id = ctx.config.default_playlist()?;
+                let playlist = get_playlist(&ctx, &playlist_id, None)?;
+                let (tracks, _) = spotify_tracks(playlist.tracks.items);
+                let listed: Vec<Value> = tracks
+                    .iter()
+                    .take(MAX_PLAYLIST_TRACKS)
+                    .map(|track| {
+                        let artists: Vec<&str> = track.artists.iter().map(|artist| artist.name.as_str()).collect();
+                        json!({ "name": track.name, "artists": artists, "uri": track.uri })
+                    })
+                    .collect();
+                Ok(json!({ "id": playlist_id, "name": playlist.name, "total_tracks": tracks.len(), "tracks": listed }))
+            },
+            "suggest" => {
+                let config = self.config_for(arguments)?;
+                let count = arguments.get("count").and_then(Value::as_u64).map_or(DEFAULT_MCP_COUNT, |count| count as usize);
+                if !(1..=MAX_MCP_COUNT).contains(&count) {
+                    return Err(format!("count goes from 1 to {}", MAX_MCP_COUNT).into());
+                }
+                let options = Options { count: Some(count), vibe: text_argument(arguments, "vibe"), ..self.options.clone() };
+                let ctx = self.context(config)?;
+                let preview = Preview::new(ctx.client, ctx.config, &options, Some(ctx.access_token)).map_err(|e| e.to_string())?;
+                let id = self.next_id;
+                self.next_id += 1;
+                let answer = json!({ "preview": id, "playlist": preview.playlist_name(), "suggestions": suggestions_json(preview.suggestions()) });
+                self.previews.insert(id, preview);
+                Ok(answer)
+            },
+            "apply_suggestions" => {
+                let id = arguments.get("preview").and_then(Value::as_u64).ok_or("preview is required")?;
+                let preview = self.previews.remove(&id).ok_or("No such preview, it was already applied")?;
+                let approved = match arguments.get("approve").and_then(Value::as_array) {
+                    Some(indexes) => indexes.iter().filter_map(Value::as_u64).map(|index| index as usize).collect(),
+                    None => confident(preview.suggestions()),
+                };
+                let playlist = preview.playlist_name().to_string();
+                let added = preview.apply(&approved).map_err(|e| e.to_string())?;
+                Ok(json!({ "playlist": playlist, "added": added }))
+            },
+            "search" => {
+                let (Some(artist), Some(name)) = (text_argument(arguments, "artist"), text_argument(arguments, "name")) else {
+                    return Err("artist and name are required".into());
+                };
+                let ctx = self.context(self.config.clone())?;
+                let found = search_song(&ctx, &Song { name, artist, isrc: None, mbid: None }, &TrackFilter::default())?;
+                Ok(json!({
+                    "uri": found.uri,
+                    "name": found.name,
+                    "artists": found.artists,
+                    "confidence": found.confidence,
+                    "link": track_link(&found.uri),
+                }))
+            },
+            "add_tracks" => {
+                let uris: Vec<String> = arguments
+                    .get("uris")
+                    .and_then(Value::as_array)
+                    .map(|uris| uris.iter().filter_map(Value::as_str).map(str::to_string).collect())
+                    .unwrap_or_default();
+                if uris.is_empty() || uris.iter().any(|uri| !uri.starts_with("spotify:track:")) {
+                    return Err("uris must be a list of Spotify track URIs (spotify:track:...)".into());
+                }
+                let ctx = self.context(self.config_for(arguments)?)?;
+                let added = uris.len();
+                let playlist_id = ctx.config.default_playlist()?;
+                add_to_playlist(&ctx, &playlist_id, uris, None)?;
+                Ok(json!({ "playlist": playlist_id, "added": added }))
+            },
+            "undo" => {
+                let ctx = self.context(self.config.clone())?;
+                match undo::undo_last_run(ctx.client, ctx.config, &self.options, Some(ctx.access_token)).map_err(|e| e.to_string())? {
+                    Some((run, removed)) => Ok(json!({ "run": run, "removed": removed })),
+                    None => Ok(json!({ "removed": 0, "message": "Nothing to undo" })),
+                }
+            },
+            _ => Err(format!("Unknown tool: {}", name).into()),
+        }
+    }
+
+    // Function to answer one message, None for notifications which get no answer
+    fn handle(&mut self, message: &Value) -> Option<Value> {
+        let id = message.get("id")?.clone();
+        let method = message.get("method").and_then(Value::as_str).unwrap_or("");
+        if let Some(result) = protocol_method(method) {
+            return Some(response(id, result));
+        }
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let Some(name) = params.get("name").and_then(Value::as_str) else {
+            return Some(response(id, Err((INVALID_PARAMS, "tools/call requires a tool name".to_string()))));
+        };
+        let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+        // A failing tool is a result the model can read, not a protocol error
+        let result = match self.call_tool(name, &arguments) {
+            Ok(value) => json!({ "content": [{ "type": "text", "text": value.to_string() }], "isError": false }),
+            Err(e) => json!({ "content": [{ "type": "text", "text": e.to_string() }], "isError": true }),
+        };
+        Some(response(id, Ok(result)))
+    }
+}
+
+// Function to get where the protocol is written, the output, and send everything else printed to the error output
+// The pipeline reports its progress with println!, which would corrupt the messages.
+#[cfg(unix)]
+fn protocol_output() -> Result<File, PilotError> {
+    use std::os::unix::io::FromRawFd;
+
+    std::io::stdout().flush().map_err(|e| e.to_string())?;
+    // SAFETY: dup and dup2 only duplicate the process' own standard descriptors, the copy is owned by the returned File
+    unsafe {
+        let output = libc::dup(libc::STDOUT_FILENO);
+        if output < 0 || libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            return Err("Could not redirect the output".into());
+        }
+        Ok(File::from_raw_fd(output))
+    }
+}
+
+// Function to refuse serving where the standard descriptors can't be redirected, the messages would be corrupted
+#[cfg(not(unix))]
+fn protocol_output() -> Result<File, PilotError> {
+    Err("The MCP server needs a Unix system to keep its output clean".to_string())
+}
+
+// Function to serve PlaylistPilot's tools over the Model Context Protocol on the standard input and output, until the client leaves
+// Messages are JSON-RPC, one per line. Nothing is asked in the terminal: Spotify must have been authorized before.
+pub fn run(client: Client, config: Config, options: &Options) -> Result<(), PilotError> {
+    // The input carries the protocol, an authorization or a question for the API key there would swallow the client's messages
+    if !has_cached_token(&config) {
+        return Err("Authorize Spotify first by running a command in a terminal, e.g. `playlistpilot stats`".into());
+    }
+    if !has_llm_secret(&config) {
+        return Err("Set the LLM API key first (llm_client_secret, or run a suggestion in a terminal to store it)".into());
+    }
+    let mut output = protocol_output()?;
+    let options = Options { headless: true, unattended: true, ..options.clone() };
+    let mut server = McpServer { client, config, options, previews: HashMap::new(), next_id: 1 };
+    eprintln!("PlaylistPilot MCP server ready.");
+
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let answer = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => server.handle(&message),
+            Err(e) => Some(response(Value::Null, Err((PARSE_ERROR, format!("Invalid JSON: {}", e))))),
+        };
+        if let Some(answer) = answer {
+            writeln!(output, "{}", answer)?;
+            output.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protocol_methods_answer_without_the_services() {
+        let initialized = response(json!(1), protocol_method("initialize").unwrap());
+        assert_eq!(initialized["jsonrpc"], "2.0");
+        assert_eq!(initialized["id"], 1);
+        assert_eq!(initialized["result"]["protocolVersion"], PROTOCOL_VERSION);
+        assert!(initialized["result"]["capabilities"]["tools"].is_object());
+
+        let tools = protocol_method("tools/list").unwrap().unwrap();
+        let names: Vec<&str> = tools["tools"].as_array().unwrap().iter().map(|tool| tool["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["get_playlist", "suggest", "apply_suggestions", "search", "add_tracks", "undo"]);
+        assert!(tools["tools"].as_array().unwrap().iter().all(|tool| tool["inputSchema"]["type"] == "object"));
+
+        assert!(protocol_method("tools/call").is_none());
+        let unknown = response(json!("a"), protocol_method("resources/list").unwrap());
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+    }
+}
@@ -0,0 +1,62 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use std::collections::HashSet;
+
+use crate::auth::*;
+use crate::backup::{backup_items, backup_playlist};
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::spotify::*;
+
+// Function to add the tracks of several playlists to a target playlist, skipping those it already has
+pub fn run(client: Client, config: Config, options: &Options, sources: &[String]) -> Result<(), PilotError> {
+    if sources.is_empty() {
+        return Err("merge requires at least one source playlist".into());
+    }
+    let target_id = playlist_argument(options.into.as_deref().ok_or("merge requires --into <playlist>")?)?;
+    let source_ids = sources.iter().map(|source| playlist_argument(source)).collect::<Result<Vec<String>, PilotError>>()?;
+
+    let access_token = if options.dry_run {
+        get_read_only_token(&client, &config)?
+    } else {
+        get_access_token(&client, &config, options.headless)?
+    };
+    let ctx = Context { client, config, access_token };
+
+    // Tracks already in the target are never added twice
+    let target = get_playlist(&ctx, &target_id, None)?;
+    let mut seen: HashSet<String> = backup_items(target.tracks.items).into_iter().map(|item| item.uri).collect();
+
+    let mut uris = Vec::new();
+    for source_id in &source_ids {
+        let playlist = get_playlist(&ctx, source_id, None)?;
+        let items = backup_items(playlist.tracks.items);
+        let total = items.len();
+        let mut added = 0;
+        for item in items.into_iter().filter(|item| !item.is_local) {
+            if seen.insert(item.uri.clone()) {
+                uris.push(item.uri);
+                added += 1;
+            }
+        }
+        println!("'{}': {} new of {} items.", playlist.name, added, total);
+    }
+
+    if uris.is_empty() {
+        println!("'{}' already has every track.", target.name);
+        return Ok(());
+    }
+    if options.dry_run {
+        println!("Dry run, {} tracks would be added to '{}'.", uris.len(), target.name);
+        return Ok(());
+    }
+
+    backup_playlist(&ctx, &target_id).map_err(|e| format!("Could not back up the playlist, nothing was added: {}", e))?;
+    let count = uris.len();
+    add_to_playlist(&ctx, &target_id, uris, None)?;
+    println!("Added {} tracks to '{}'.", count, target.name);
+    Ok(())
+}
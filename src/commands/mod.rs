@@ -0,0 +1,38 @@
+// Commands of the application, each taking the resolved settings and the command line options
+
+use crate::error::PilotError;
+pub mod analyze;
+pub mod bench;
+pub mod cache;
+pub mod cleanup;
+pub mod clone;
+pub mod daemon;
+pub mod dedupe;
+pub mod diff;
+pub mod export;
+pub mod feed;
+pub mod feedback;
+pub mod genres;
+pub mod health;
+pub mod history;
+pub mod import;
+pub mod mcp;
+pub mod merge;
+pub mod open;
+pub mod releases;
+pub mod remove;
+pub mod reorder;
+pub mod restore;
+pub mod serve;
+pub mod shuffle;
+pub mod sort;
+pub mod split;
+pub mod stats;
+pub mod suggest;
+pub mod sync;
+pub mod undo;
+
+// Function to read a playlist given on the command line as a link, URI or ID
+pub fn playlist_argument(value: &str) -> Result<String, PilotError> {
+    crate::spotify::parse_spotify_id(value, "playlist").ok_or_else(|| format!("Not a Spotify playlist: {}", value).into())
+}
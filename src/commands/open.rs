@@ -0,0 +1,68 @@
+// Import necessary modules and crates
+use crate::auth::open_in_browser;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::error::PilotError;
+use crate::feed::playlist_link;
+use crate::report::track_link;
+use crate::service::{music_service, Service};
+use crate::spotify::parse_spotify_id;
+
+// Function to get the web page of what was given: a track URI or link, a playlist, or the configured playlist when None
+// Bare IDs are playlists, tracks need their URI or link.
+pub fn link(config: &Config, what: Option<&str>) -> Result<String, PilotError> {
+    let Some(what) = what.map(str::trim) else {
+        return playlist_link(config.service, &config.default_playlist()?)
+            .ok_or_else(|| PilotError::Usage(format!("{} playlists have no web page", music_service(config.service).name())));
+    };
+    if let Some(link) = track_link(what) {
+        return Ok(link);
+    }
+    let bare = !what.contains(':') && !what.contains('/');
+    if let Some(id) = parse_spotify_id(what, "track").filter(|_| !bare) {
+        return Ok(format!("https://open.spotify.com/track/{}", id));
+    }
+    let playlist_id = playlist_argument(what).map_err(|e| PilotError::Usage(e.to_string()))?;
+    Ok(playlist_link(Service::Spotify, &playlist_id).unwrap_or_default())
+}
+
+// Function to open a track or playlist in the browser, or the Spotify app when it handles its links
+// Nothing is asked from the API; the link is printed too, for when no browser can be opened.
+pub fn run(config: &Config, what: Option<&str>) -> Result<(), PilotError> {
+    let link = link(config, what)?;
+    println!("{}", link);
+    if let Err(e) = open_in_browser(&link) {
+        eprintln!("Could not open the browser ({}), open the link above instead.", e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProfileSettings;
+    use crate::testing::test_config;
+
+    #[test]
+    fn links_of_tracks_and_playlists() {
+        let mut config = test_config(ProfileSettings { playlist_id: Some("configured".to_string()), ..Default::default() });
+        config.service = Service::Spotify;
+        assert_eq!(link(&config, None).unwrap(), "https://open.spotify.com/playlist/configured");
+        assert_eq!(link(&config, Some("spotify:track:abc")).unwrap(), "https://open.spotify.com/track/abc");
+        assert_eq!(link(&config, Some("https://open.spotify.com/intl-fr/track/abc?si=x")).unwrap(), "https://open.spotify.com/track/abc");
+        assert_eq!(link(&config, Some("deezer:track:42")).unwrap(), "https://www.deezer.com/track/42");
+        assert_eq!(link(&config, Some("pl2")).unwrap(), "https://open.spotify.com/playlist/pl2");
+        assert_eq!(link(&config, Some("spotify:playlist:pl3")).unwrap(), "https://open.spotify.com/playlist/pl3");
+        assert!(matches!(link(&config, Some("not a playlist")), Err(PilotError::Usage(_))));
+    }
+
+    #[test]
+    fn a_config_without_a_playlist_loads() {
+        let mut config = test_config(ProfileSettings::default());
+        config.service = Service::Spotify;
+        assert_eq!(config.playlist_id, None);
+        // Only falling back to the default playlist needs it
+        assert_eq!(link(&config, Some("pl2")).unwrap(), "https://open.spotify.com/playlist/pl2");
+        assert_eq!(link(&config, None).unwrap_err().to_string(), "playlist id not set");
+    }
+}
@@ -0,0 +1,174 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use std::collections::{HashMap, HashSet};
+use tracing::{info, warn};
+
+use crate::auth::get_access_token;
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::commands::suggest::run_list;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::llm::{find_misfits, get_llm_secret};
+use crate::matching::{Duplicates, TrackMatch};
+use crate::models::{Release, Song, Track};
+use crate::spotify::*;
+use crate::tokens::{iso_date, now};
+
+// Releases out for longer than this don't count as new, in days, unless --days is given
+const RELEASE_DAYS: u64 = 30;
+
+// Maximum number of new tracks shown to the LLM, the newest first
+const MAX_CANDIDATES: usize = 50;
+
+// Number of artists named when describing the playlist to the LLM
+const SUMMARY_ARTISTS: usize = 10;
+
+// Function to keep the releases out on `since` (a YYYY-MM-DD date) or later
+// Dates only known to the year or month count from the first day of it, so they are usually too old.
+pub fn recent_releases(releases: Vec<Release>, since: &str) -> Vec<Release> {
+    releases.into_iter().filter(|release| release.release_date.as_deref().is_some_and(|date| date >= since)).collect()
+}
+
+// Function to describe a playlist for the LLM: its name, its most present artists and the vibe asked for
+fn playlist_summary(name: &str, tracks: &[Track], vibe: Option<&str>) -> String {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for artist in tracks.iter().flat_map(|track| &track.artists) {
+        *counts.entry(artist.name.as_str()).or_default() += 1;
+    }
+    let mut artists: Vec<(&str, usize)> = counts.into_iter().collect();
+    artists.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    let artists: Vec<&str> = artists.into_iter().take(SUMMARY_ARTISTS).map(|(name, _)| name).collect();
+
+    let mut summary = format!("Songs are being added to the playlist '{}'.", name);
+    if !artists.is_empty() {
+        summary.push_str(&format!(" It is mostly made of songs by {}.", artists.join(", ")));
+    }
+    if let Some(vibe) = vibe {
+        summary.push_str(&format!(" The songs should fit this vibe: {}.", vibe));
+    }
+    summary
+}
+
+// Function to add the new releases of the artists the user follows that fit a playlist, the configured playlist by default
+// The releases of the last --days are read, the tracks already in the playlist skipped, and the LLM leaves out those that don't
+// fit its genre and mood (unless --no-llm). The rest are added like an imported list, with the same filters, dry run and backups.
+pub fn run(client: Client, config: Config, options: &Options, playlist: Option<&str>) -> Result<(), PilotError> {
+    let mut config = config;
+    if let Some(playlist) = playlist {
+        config.playlist_id = Some(playlist_argument(playlist)?);
+    }
+    // Followed artists are private to the user, even for a dry run
+    let access_token = get_access_token(&client, &config, options.headless)?;
+    let ctx = Context { client, config, access_token };
+
+    let playlist = get_playlist(&ctx, &ctx.config.default_playlist()?, None)?;
+    let (tracks, _) = spotify_tracks(playlist.tracks.items);
+    let artists = get_followed_artists(&ctx)?;
+    if artists.is_empty() {
+        println!("You don't follow any artists on Spotify.");
+        return Ok(());
+    }
+
+    let days = options.days.unwrap_or(RELEASE_DAYS);
+    let since = iso_date(now().saturating_sub(days * 86400));
+    let since = &since[..10];
+    println!("Looking for releases since {} by the {} artists you follow...", since, artists.len());
+    let mut releases = Vec::new();
+    for artist in &artists {
+        match get_artist_releases(&ctx, &artist.id) {
+            Ok(found) => releases.extend(recent_releases(found, since)),
+            Err(e) => warn!("Could not read the releases of {}: {}", artist.name, e),
+        }
+    }
+    // An album listed for two of its artists is read once
+    let mut seen = HashSet::new();
+    releases.retain(|release| seen.insert(release.id.clone()));
+    if releases.is_empty() {
+        println!("No new releases in the last {} days.", days);
+        return Ok(());
+    }
+    for release in &releases {
+        info!("New release: '{}', out {}.", release.name, release.release_date.as_deref().unwrap_or("recently"));
+    }
+
+    // Only the tracks of followed artists, as albums also hold their guests' songs
+    let followed: HashSet<&str> = artists.iter().map(|artist| artist.id.as_str()).collect();
+    let album_ids: Vec<String> = releases.iter().map(|release| release.id.clone()).collect();
+    let mut new_tracks: Vec<Track> = get_album_tracks(&ctx, &album_ids)?
+        .into_iter()
+        .filter(|track| track.artists.iter().any(|artist| artist.id.as_deref().is_some_and(|id| followed.contains(id))))
+        .collect();
+    new_tracks.sort_by(|a, b| b.album.release_date.cmp(&a.album.release_date));
+    // Singles come out again on the album, and some songs are already in the playlist
+    let mut duplicates = Duplicates::new(&tracks);
+    new_tracks.retain(|track| duplicates.insert(&TrackMatch::from_track(track, 1.0)));
+    if new_tracks.len() > MAX_CANDIDATES {
+        info!("Keeping the {} newest of the {} new tracks.", MAX_CANDIDATES, new_tracks.len());
+        new_tracks.truncate(MAX_CANDIDATES);
+    }
+    println!("Found {} new tracks on {} releases.", new_tracks.len(), releases.len());
+    if new_tracks.is_empty() {
+        return Ok(());
+    }
+
+    if !options.no_llm {
+        let api_key = get_llm_secret(&ctx.config)?;
+        let summary = playlist_summary(&playlist.name, &tracks, options.vibe.as_deref());
+        let listed: Vec<String> = new_tracks
+            .iter()
+            .map(|track| {
+                let artists: Vec<&str> = track.artists.iter().map(|artist| artist.name.as_str()).collect();
+                format!("{} by {} (from '{}', released {})", track.name, artists.join(", "), track.album.name, track.album.release_date.as_deref().unwrap_or("recently"))
+            })
+            .collect();
+        match find_misfits(&ctx, &api_key, &summary, &listed) {
+            Ok(misfits) => {
+                let mut left_out = HashSet::new();
+                for (index, reason) in misfits {
+                    info!("Skipped '{}': the LLM found it doesn't fit ({}).", listed[index], reason);
+                    left_out.insert(index);
+                }
+                let mut index = 0;
+                new_tracks.retain(|_| {
+                    index += 1;
+                    !left_out.contains(&(index - 1))
+                });
+            },
+            Err(e) => warn!("The new tracks could not be checked against the playlist, they are all kept: {}", e),
+        }
+        if new_tracks.is_empty() {
+            println!("None of the new tracks fit '{}'.", playlist.name);
+            return Ok(());
+        }
+    }
+
+    // Album tracks come without their ISRC, so they are searched by title and artist like any listed song
+    let songs: Vec<Song> = new_tracks
+        .iter()
+        .map(|track| Song {
+            name: track.name.clone(),
+            artist: track.artists.first().map(|artist| artist.name.clone()).unwrap_or_default(),
+            isrc: None,
+            mbid: None,
+        })
+        .collect();
+    let Context { client, config, .. } = ctx;
+    run_list(client, config, options, songs, "the new releases")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_releases_since_the_date_are_new() {
+        let releases: Vec<Release> = [("a", Some("2026-10-01")), ("b", Some("2026-09-13")), ("c", Some("2026")), ("d", None), ("e", Some("2026-09-14"))]
+            .iter()
+            .map(|(id, date)| serde_json::from_value(serde_json::json!({ "id": id, "release_date": date })).unwrap())
+            .collect();
+        let ids: Vec<String> = recent_releases(releases, "2026-09-14").into_iter().map(|release| release.id).collect();
+        assert_eq!(ids, vec!["a", "e"]);
+    }
+}
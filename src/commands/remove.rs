@@ -0,0 +1,90 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+
+use crate::auth::*;
+use crate::backup::backup_playlist;
+use crate::cli::*;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::matching::matches_query;
+use crate::models::Track;
+use crate::spotify::*;
+
+// Function to describe a track as "Title - Artist, Artist"
+fn describe(track: &Track) -> String {
+    let artists: Vec<&str> = track.artists.iter().map(|a| a.name.as_str()).collect();
+    format!("{} - {}", track.name, artists.join(", "))
+}
+
+// Function to pick the playlist tracks selected by the command line arguments
+// Spotify URIs and links select that exact track, any other argument selects the tracks whose title and artists contain it.
+fn select_tracks<'a>(tracks: &'a [Track], selectors: &[String]) -> Result<Vec<&'a Track>, PilotError> {
+    let mut selected: Vec<&Track> = Vec::new();
+    for selector in selectors {
+        let matching: Vec<&Track> = if selector.starts_with("spotify:") || selector.starts_with("http") {
+            let id = parse_spotify_id(selector, "track").ok_or_else(|| format!("Not a Spotify track: {}", selector))?;
+            let uri = format!("spotify:track:{}", id);
+            tracks.iter().filter(|track| track.uri == uri).collect()
+        } else {
+            tracks.iter().filter(|track| matches_query(track, selector)).collect()
+        };
+
+        if matching.is_empty() {
+            println!("No track of the playlist matches '{}'.", selector);
+        }
+        for track in matching {
+            if !selected.iter().any(|s| s.uri == track.uri) {
+                selected.push(track);
+            }
+        }
+    }
+    Ok(selected)
+}
+
+// Function to let the user pick tracks from the numbered list of the playlist
+fn pick_tracks(tracks: &[Track]) -> Result<Vec<&Track>, PilotError> {
+    for (index, track) in tracks.iter().enumerate() {
+        println!("{:>4}. {}", index + 1, describe(track));
+    }
+    let answer = ask("Enter the numbers of the tracks to remove (e.g. 1 3 5-7):");
+    Ok(parse_selection(&answer, tracks.len())?.into_iter().map(|index| &tracks[index]).collect())
+}
+
+// Function to remove tracks from the playlist
+pub fn run(client: Client, config: Config, options: &Options, selectors: &[String]) -> Result<(), PilotError> {
+    // A dry run only reads the playlist, an app token is enough
+    let access_token = if options.dry_run {
+        get_read_only_token(&client, &config)?
+    } else {
+        get_access_token(&client, &config, options.headless)?
+    };
+    let playlist_id = config.default_playlist()?;
+    let ctx = Context { client, config, access_token };
+
+    let playlist = get_playlist(&ctx, &playlist_id, None)?;
+    let (tracks, _) = spotify_tracks(playlist.tracks.items);
+    let selected = if selectors.is_empty() { pick_tracks(&tracks)? } else { select_tracks(&tracks, selectors)? };
+    if selected.is_empty() {
+        println!("Nothing to remove.");
+        return Ok(());
+    }
+
+    println!("{} tracks selected:", selected.len());
+    for track in &selected {
+        println!("  {}", describe(track));
+    }
+    if options.dry_run {
+        println!("Dry run, nothing was removed.");
+        return Ok(());
+    }
+    if !confirm("Remove them from the playlist?") {
+        return Ok(());
+    }
+
+    backup_playlist(&ctx, &playlist_id).map_err(|e| format!("Could not back up the playlist, nothing was removed: {}", e))?;
+    let uris = selected.iter().map(|track| track.uri.clone()).collect();
+    remove_from_playlist(&ctx, &playlist_id, uris)?;
+    println!("Removed {} tracks from the playlist.", selected.len());
+    Ok(())
+}
@@ -0,0 +1,113 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+
+use crate::auth::*;
+use crate::backup::{backup_items, backup_playlist};
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::ordering::*;
+use crate::spotify::*;
+
+// Function to reorder a playlist so its intensity follows an arc, using each track's audio features
+// With --bpm-delta, consecutive tempos are kept within that many BPM, and with --harmonic consecutive keys are
+// neighbours on the Camelot wheel, for DJ-style sets.
+// Items without audio features (local files, episodes) keep their relative order at the end.
+pub fn run(client: Client, config: Config, options: &Options, playlist: Option<&str>) -> Result<(), PilotError> {
+    let playlist_id = match playlist {
+        Some(playlist) => playlist_argument(playlist)?,
+        None => config.default_playlist()?,
+    };
+    let arc = options.arc.unwrap_or(Arc::BuildUp);
+
+    let access_token = if options.dry_run {
+        get_read_only_token(&client, &config)?
+    } else {
+        get_access_token(&client, &config, options.headless)?
+    };
+    let ctx = Context { client, config, access_token };
+
+    let playlist = get_playlist(&ctx, &playlist_id, None)?;
+    let snapshot_id = playlist.snapshot_id.clone();
+    let items = backup_items(playlist.tracks.items);
+    let uris: Vec<String> = items.iter().map(|item| item.uri.clone()).collect();
+    let features = get_audio_features(&ctx, &uris)?;
+
+    // Split the items between those with features, which are arranged, and the others
+    let mut scored = Vec::new();
+    let mut intensities = Vec::new();
+    let mut tempos = Vec::new();
+    let mut keys = Vec::new();
+    let mut unscored = Vec::new();
+    for (index, uri) in uris.iter().enumerate() {
+        match uri.strip_prefix("spotify:track:").and_then(|id| features.get(id)) {
+            Some(track_features) => {
+                scored.push(index);
+                intensities.push(intensity(track_features));
+                tempos.push(track_features.tempo);
+                keys.push(Camelot::from_key(track_features.key, track_features.mode));
+            },
+            None => unscored.push(index),
+        }
+    }
+    if !unscored.is_empty() {
+        println!("{} items have no audio features and are moved to the end.", unscored.len());
+    }
+    let mut arranged = arrange(&intensities, arc);
+    if options.harmonic {
+        // Neighbours on the Camelot wheel mix without clashing, tracks of unknown key fit anywhere
+        let compatible = |a: usize, b: usize| match (keys[a], keys[b]) {
+            (Some(a), Some(b)) => a.is_compatible(&b),
+            _ => true,
+        };
+        let steps = |a: usize, b: usize| match (keys[a], keys[b]) {
+            (Some(a), Some(b)) => a.distance(&b) as f64,
+            _ => 0.0,
+        };
+        // With --bpm-delta too, both have to fit; otherwise the closest key wins, then the closest tempo
+        let max_delta = options.bpm_delta.unwrap_or(f64::MAX);
+        let (chained, jumps) = chain(
+            &arranged,
+            |a, b| compatible(a, b) && tempo_distance(tempos[a], tempos[b]) <= max_delta,
+            |a, b| steps(a, b) * 1000.0 + tempo_distance(tempos[a], tempos[b]),
+        );
+        if jumps > 0 {
+            println!("{} transitions are not harmonically compatible, no fitting track was left.", jumps);
+        }
+        arranged = chained;
+    } else if let Some(max_delta) = options.bpm_delta {
+        // Keep the tempo mixable from one track to the next, bending the arc where needed
+        let (limited, jumps) = limit_tempo_jumps(&arranged, &tempos, max_delta);
+        if jumps > 0 {
+            println!("{} transitions change the tempo by more than {} BPM, no closer track was left.", jumps, max_delta);
+        }
+        arranged = limited;
+    }
+    let mut order: Vec<usize> = arranged.into_iter().map(|index| scored[index]).collect();
+    order.extend(unscored);
+
+    let moves = plan_moves(&order);
+    if moves.is_empty() {
+        println!("'{}' already follows this arc.", playlist.name);
+        return Ok(());
+    }
+    if options.dry_run {
+        println!("Dry run, '{}' would be reordered ({} moves):", playlist.name, moves.len());
+        for index in &order {
+            println!("  {} - {}", items[*index].name, items[*index].artists.join(", "));
+        }
+        return Ok(());
+    }
+
+    backup_playlist(&ctx, &playlist_id).map_err(|e| format!("Could not back up the playlist, nothing was changed: {}", e))?;
+    println!("Reordering '{}' ({} moves)...", playlist.name, moves.len());
+    // Every move is made against the snapshot the previous one returned, so a concurrent edit makes the next one fail
+    let mut snapshot = Some(snapshot_id);
+    for (range_start, insert_before) in moves {
+        snapshot = Some(move_in_playlist(&ctx, &playlist_id, range_start, insert_before, snapshot)?);
+    }
+    println!("Reordered '{}'.", playlist.name);
+    Ok(())
+}
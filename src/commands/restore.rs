@@ -0,0 +1,44 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+
+use crate::auth::get_access_token;
+use crate::backup::*;
+use crate::cli::*;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::spotify::replace_playlist;
+
+// Function to make a playlist match one of its backups again
+pub fn run(client: Client, config: Config, options: &Options, name: &str) -> Result<(), PilotError> {
+    let path = find_backup(name)?;
+    let backup = load_backup(&path)?;
+
+    // Local files can't be added through the API, they have to be added back from the Spotify app
+    let (local, items): (Vec<BackupItem>, Vec<BackupItem>) = backup.items.into_iter().partition(|item| item.is_local);
+    for item in &local {
+        println!("Skipped local file '{}', it can't be restored automatically.", item.name);
+    }
+
+    println!("Backup {} has {} items for playlist {}.", path.display(), items.len(), backup.playlist_id);
+    if options.dry_run {
+        println!("Dry run, the playlist was not changed.");
+        return Ok(());
+    }
+    if !confirm("Replace the current content of the playlist with it?") {
+        return Ok(());
+    }
+
+    let access_token = get_access_token(&client, &config, options.headless)?;
+    let ctx = Context { client, config, access_token };
+
+    // The current content is backed up too, so a restore can itself be reverted
+    let current = backup_playlist(&ctx, &backup.playlist_id)
+        .map_err(|e| format!("Could not back up the playlist, nothing was changed: {}", e))?;
+    println!("Current content saved to {}.", current.display());
+
+    let uris = items.into_iter().map(|item| item.uri).collect();
+    replace_playlist(&ctx, &backup.playlist_id, uris)?;
+    println!("Restored the playlist from {}.", path.display());
+    Ok(())
+}
@@ -0,0 +1,544 @@
+// Import necessary modules and crates
+use axum::extract::{Form, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::Html;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use rand::Rng;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use crate::cli::*;
+use crate::commands::suggest::Preview;
+use crate::context::Context;
+use crate::config::Config;
+use crate::error::PilotError;
+use crate::history::{History, Status, Suggestion};
+use crate::metrics::{render_metrics, METRICS_CONTENT_TYPE};
+use crate::models::UserPlaylist;
+use crate::report::{escape, track_link, STYLE};
+use crate::service::{music_service, Service};
+use crate::spotify::get_user_playlists;
+use crate::tokens::iso_date;
+
+// Number of songs the page suggests by default, and at most
+const DEFAULT_SERVE_COUNT: usize = 10;
+const MAX_SERVE_COUNT: usize = 50;
+
+// Previews kept waiting for approval, the oldest is dropped beyond this
+const MAX_PREVIEWS: usize = 20;
+
+// Number of runs the history endpoint lists by default
+const DEFAULT_HISTORY_LIMIT: usize = 20;
+
+// Style of the forms, on top of the report's
+const FORM_STYLE: &str = "label { display: block; margin: 0.8em 0 0.2em; }
+input[type=text], select { width: 100%; max-width: 30em; padding: 0.3em; }
+button { margin-top: 1em; padding: 0.5em 1.2em; font-size: 1em; }
+.review { color: #9a6700; }";
+
+// What every request of the web interface shares
+struct Server {
+    client: Client,
+    config: Config,
+    options: Options,
+    // Previews waiting to be applied and their IDs, the oldest first
+    previews: Mutex<Vec<(u64, Preview)>>,
+}
+
+// Function to pick the ID of a new preview, at random so that another page can't guess it and apply a preview it didn't make
+// It stays below 2^53, the integers JavaScript clients of the API read exactly.
+fn preview_id() -> u64 {
+    rand::thread_rng().gen_range(1..1 << 53)
+}
+
+impl Server {
+    // Function to keep a preview until it is applied, under an ID from preview_id
+    // Previews nobody applied are forgotten, the oldest first.
+    fn store(&self, id: u64, preview: Preview) {
+        let mut previews = self.previews.lock().unwrap();
+        let forgotten = (previews.len() + 1).saturating_sub(MAX_PREVIEWS);
+        previews.drain(..forgotten);
+        previews.push((id, preview));
+    }
+
+    // Function to take a preview out to apply it, None when it was applied or forgotten
+    fn take(&self, id: u64) -> Option<Preview> {
+        let mut previews = self.previews.lock().unwrap();
+        let index = previews.iter().position(|(kept, _)| *kept == id)?;
+        Some(previews.remove(index).1)
+    }
+}
+
+// A page and its status, errors are pages too
+type Page = (StatusCode, Html<String>);
+
+// An answer of the JSON API and its status, errors are {"error": "..."}
+type Answer = (StatusCode, Json<Value>);
+
+#[derive(Deserialize)]
+struct SuggestRequest {
+    // Playlist link, URI or ID, the configured playlist when None
+    playlist: Option<String>,
+    count: Option<usize>,
+    vibe: Option<String>,
+    // Add the confident matches right away instead of returning a preview to apply
+    #[serde(default)]
+    apply: bool,
+}
+
+#[derive(Deserialize)]
+struct ApplyRequest {
+    preview: u64,
+    // Indexes of the suggestions to add, the confident matches when None
+    approve: Option<Vec<usize>>,
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct PreviewForm {
+    playlist: String,
+    count: usize,
+    #[serde(default)]
+    vibe: String,
+}
+
+// Function to write a whole page around its body
+fn page(title: &str, body: &str) -> Html<String> {
+    Html(format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n<style>\n{}\n{}\n</style>\n</head><body>\n<h1>{}</h1>\n{}\n</body></html>\n",
+        escape(title),
+        STYLE,
+        FORM_STYLE,
+        escape(title),
+        body
+    ))
+}
+
+// Function to show an error with a way back to the start
+fn error_page(status: StatusCode, message: &str) -> Page {
+    (status, page("PlaylistPilot", &format!("<p class=\"failed\">{}</p>\n<p><a href=\"/\">Back</a></p>", escape(message))))
+}
+
+// Function to write the form picking a playlist, the number of songs and a vibe
+fn render_index(playlists: &[UserPlaylist], selected: &str) -> String {
+    let options: Vec<String> = playlists
+        .iter()
+        .map(|playlist| {
+            let attribute = if playlist.id == selected { " selected" } else { "" };
+            format!("<option value=\"{}\"{}>{} ({} tracks)</option>", escape(&playlist.id), attribute, escape(&playlist.name), playlist.tracks.total)
+        })
+        .collect();
+    format!(
+        "<form method=\"post\" action=\"/preview\">\n\
+         <label for=\"playlist\">Playlist</label>\n<select id=\"playlist\" name=\"playlist\">{}</select>\n\
+         <label for=\"count\">Number of songs</label>\n<input id=\"count\" name=\"count\" type=\"number\" min=\"1\" max=\"{}\" value=\"{}\">\n\
+         <label for=\"vibe\">Vibe (optional)</label>\n<input id=\"vibe\" name=\"vibe\" type=\"text\" placeholder=\"e.g. upbeat, for a sunny morning\">\n\
+         <button type=\"submit\">Preview suggestions</button>\n</form>\n\
+         <p class=\"muted\">Getting the suggestions takes a little while, nothing is added before you approve it.</p>",
+        options.join(""),
+        MAX_SERVE_COUNT,
+        DEFAULT_SERVE_COUNT
+    )
+}
+
+// Function to write the suggestions of a preview, each one that can be added with a box to approve it
+// Matches the LLM was sure of are approved to begin with, low confidence matches are left for the user to check.
+fn render_preview(id: u64, suggestions: &[Suggestion]) -> String {
+    let rows: Vec<String> = suggestions
+        .iter()
+        .enumerate()
+        .filter(|(_, suggestion)| Preview::approvable(suggestion))
+        .filter_map(|(index, suggestion)| {
+            let found = suggestion.found.as_ref()?;
+            let art = match &found.image {
+                Some(image) => format!("<img src=\"{}\" alt=\"\">", escape(image)),
+                None => String::new(),
+            };
+            let title = format!("{} – {}", escape(&found.artists.join(", ")), escape(&found.name));
+            let title = match track_link(&found.uri) {
+                Some(link) => format!("<a href=\"{}\" target=\"_blank\">{}</a>", escape(&link), title),
+                None => title,
+            };
+            let (checked, note) = match suggestion.status {
+                Status::Review => ("", format!(" <span class=\"review\">(low confidence match, {:.0}%)</span>", found.confidence * 100.0)),
+                _ => (" checked", String::new()),
+            };
+            Some(format!(
+                "<tr><td><input type=\"checkbox\" name=\"s{}\" value=\"on\"{}></td><td class=\"art\">{}</td><td>{}{}</td><td>{}</td></tr>",
+                index,
+                checked,
+                art,
+                title,
+                note,
+                suggestion.source.label()
+            ))
+        })
+        .collect();
+    if rows.is_empty() {
+        return "<p>No new songs could be found, try another vibe.</p>\n<p><a href=\"/\">Back</a></p>".to_string();
+    }
+    format!(
+        "<p>Untick the songs you don't want, the others are added to the playlist.</p>\n\
+         <form method=\"post\" action=\"/apply\">\n<input type=\"hidden\" name=\"preview\" value=\"{}\">\n\
+         <table><tr><th>Add</th><th></th><th>Song</th><th>From</th></tr>\n{}\n</table>\n\
+         <button type=\"submit\">Add the approved songs</button>\n</form>\n<p><a href=\"/\">Start over</a></p>",
+        id,
+        rows.join("\n")
+    )
+}
+
+// Function to tell whether a form was sent from the server's own page, so that other sites the user visits can't post to it
+// The Host must be the address served on (or localhost for a loopback address, any host on the port when every interface is served),
+// which also keeps out DNS rebinding, and the Origin browsers send must be that same host.
+fn same_origin(address: &str, headers: &HeaderMap) -> bool {
+    let value = |name: header::HeaderName| headers.get(name).and_then(|value| value.to_str().ok());
+    let Some(host) = value(header::HOST) else { return false };
+    let allowed = match address.parse::<SocketAddr>() {
+        Ok(served) if served.ip().is_unspecified() => host.rsplit_once(':').is_some_and(|(_, port)| port == served.port().to_string()),
+        Ok(served) if served.ip().is_loopback() => host == address || host == format!("localhost:{}", served.port()),
+        _ => host == address,
+    };
+    allowed && value(header::ORIGIN).is_none_or(|origin| origin == format!("http://{}", host))
+}
+
+// Function to refuse a form sent from another site
+fn check_origin(server: &Server, headers: &HeaderMap) -> Result<(), Page> {
+    if !same_origin(&server.config.serve_address, headers) {
+        return Err(error_page(StatusCode::FORBIDDEN, "This form can only be sent from the PlaylistPilot page"));
+    }
+    Ok(())
+}
+
+// Function to get the HTTP status a failed call is answered with
+// Most failures come from the services behind the server, so they are a bad gateway unless they say otherwise.
+fn error_status(error: &PilotError) -> StatusCode {
+    match error {
+        PilotError::Usage(_) => StatusCode::BAD_REQUEST,
+        PilotError::NotFound(_) => StatusCode::NOT_FOUND,
+        PilotError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+        PilotError::LlmUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::BAD_GATEWAY,
+    }
+}
+
+// Function to run a blocking call (the API clients are blocking) off the server's threads
+async fn blocking<T: Send + 'static, E>(
+    call: impl FnOnce() -> Result<T, PilotError> + Send + 'static,
+    error: impl Fn(StatusCode, &str) -> E,
+) -> Result<T, E> {
+    match tokio::task::spawn_blocking(call).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(e)) => Err(error(error_status(&e), &e.to_string())),
+        Err(e) => Err(error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string())),
+    }
+}
+
+// Function to read the user's playlists, authorizing with the cached token
+fn user_playlists(client: Client, config: Config, headless: bool) -> Result<Vec<UserPlaylist>, PilotError> {
+    let access_token = music_service(Service::Spotify).access_token(&client, &config, headless, false).map_err(|e| e.to_string())?;
+    get_user_playlists(&Context { client, config, access_token })
+}
+
+// Function to show the form, with the user's playlists to pick from
+async fn index(State(server): State<Arc<Server>>) -> Result<Page, Page> {
+    let (client, config, headless) = (server.client.clone(), server.config.clone(), server.options.headless);
+    let selected = config.playlist_id.clone().unwrap_or_default();
+    let playlists = blocking(move || user_playlists(client, config, headless), error_page).await?;
+    Ok((StatusCode::OK, page("PlaylistPilot", &render_index(&playlists, &selected))))
+}
+
+// Function to suggest songs for the chosen playlist and show them for approval
+async fn preview(State(server): State<Arc<Server>>, headers: HeaderMap, Form(form): Form<PreviewForm>) -> Result<Page, Page> {
+    check_origin(&server, &headers)?;
+    if !(1..=MAX_SERVE_COUNT).contains(&form.count) {
+        return Err(error_page(StatusCode::BAD_REQUEST, &format!("The number of songs goes from 1 to {}", MAX_SERVE_COUNT)));
+    }
+    let mut config = server.config.clone();
+    config.playlist_id = Some(crate::commands::playlist_argument(&form.playlist).map_err(|e| error_page(StatusCode::BAD_REQUEST, &e.to_string()))?);
+    let vibe = form.vibe.trim();
+    let options = Options { count: Some(form.count), vibe: (!vibe.is_empty()).then(|| vibe.to_string()), ..server.options.clone() };
+    let client = server.client.clone();
+    let preview = blocking(move || Preview::new(client, config, &options, None), error_page).await?;
+
+    let title = format!("Suggestions for {}", preview.playlist_name());
+    let id = preview_id();
+    let body = render_preview(id, preview.suggestions());
+    server.store(id, preview);
+    Ok((StatusCode::OK, page(&title, &body)))
+}
+
+// Function to add the approved songs of a preview to its playlist
+async fn apply(State(server): State<Arc<Server>>, headers: HeaderMap, Form(form): Form<HashMap<String, String>>) -> Result<Page, Page> {
+    check_origin(&server, &headers)?;
+    let id: Option<u64> = form.get("preview").and_then(|id| id.parse().ok());
+    let Some(preview) = id.and_then(|id| server.take(id)) else {
+        return Err(error_page(StatusCode::NOT_FOUND, "These suggestions were already applied or have expired, preview them again"));
+    };
+    let approved: Vec<usize> = form.keys().filter_map(|key| key.strip_prefix('s')?.parse().ok()).collect();
+    let title = format!("Suggestions for {}", preview.playlist_name());
+    let added = blocking(move || preview.apply(&approved), error_page).await?;
+    let plural = if added == 1 { "" } else { "s" };
+    let body = format!("<p class=\"added\">Added {} song{}.</p>\n<p><a href=\"/\">Suggest more</a></p>", added, plural);
+    Ok((StatusCode::OK, page(&title, &body)))
+}
+
+// Function to answer an API request with an error
+fn api_error(status: StatusCode, message: &str) -> Answer {
+    (status, Json(json!({ "error": message })))
+}
+
+// Function to check the Authorization header of an API request against the token, compared in constant time
+fn token_matches(expected: &str, authorization: Option<&str>) -> bool {
+    let given = authorization.and_then(|value| value.strip_prefix("Bearer ")).unwrap_or("");
+    given.len() == expected.len() && given.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+// Function to let an API request through only with the configured token
+fn authorize(server: &Server, headers: &HeaderMap) -> Result<(), Answer> {
+    // An empty token would let every request through
+    let Some(expected) = server.config.api_token.as_deref().filter(|token| !token.is_empty()) else {
+        return Err(api_error(StatusCode::NOT_FOUND, "The API is off, set api_token to turn it on"));
+    };
+    if !token_matches(expected, headers.get(header::AUTHORIZATION).and_then(|value| value.to_str().ok())) {
+        return Err(api_error(StatusCode::UNAUTHORIZED, "Missing or wrong API token"));
+    }
+    Ok(())
+}
+
+// Function to describe the suggestions of a preview that can be approved, by their index
+pub fn suggestions_json(suggestions: &[Suggestion]) -> Vec<Value> {
+    suggestions
+        .iter()
+        .enumerate()
+        .filter(|(_, suggestion)| Preview::approvable(suggestion))
+        .filter_map(|(index, suggestion)| {
+            let found = suggestion.found.as_ref()?;
+            Some(json!({
+                "index": index,
+                "suggested": { "name": suggestion.song.name, "artist": suggestion.song.artist },
+                "uri": found.uri,
+                "name": found.name,
+                "artists": found.artists,
+                "confidence": found.confidence,
+                "needs_review": suggestion.status == Status::Review,
+                "source": suggestion.source.as_str(),
+                "image": found.image,
+                "link": track_link(&found.uri),
+            }))
+        })
+        .collect()
+}
+
+// Function to list the suggestions of a preview added when none are chosen: the confident matches
+pub fn confident(suggestions: &[Suggestion]) -> Vec<usize> {
+    suggestions
+        .iter()
+        .enumerate()
+        .filter(|(_, suggestion)| Preview::approvable(suggestion) && suggestion.status == Status::Accepted)
+        .map(|(index, _)| index)
+        .collect()
+}
+
+// Function to list the user's playlists: GET /api/playlists
+async fn api_playlists(State(server): State<Arc<Server>>, headers: HeaderMap) -> Result<Answer, Answer> {
+    authorize(&server, &headers)?;
+    let (client, config, headless) = (server.client.clone(), server.config.clone(), server.options.headless);
+    let playlists = blocking(move || user_playlists(client, config, headless), api_error).await?;
+    let playlists: Vec<Value> =
+        playlists.iter().map(|playlist| json!({ "id": playlist.id, "name": playlist.name, "tracks": playlist.tracks.total })).collect();
+    Ok((StatusCode::OK, Json(json!({ "playlists": playlists }))))
+}
+
+// Function to suggest songs for a playlist: POST /api/suggest
+// The suggestions are kept as a preview to apply, or the confident matches are added right away with "apply": true.
+async fn api_suggest(State(server): State<Arc<Server>>, headers: HeaderMap, Json(request): Json<SuggestRequest>) -> Result<Answer, Answer> {
+    authorize(&server, &headers)?;
+    let count = request.count.unwrap_or(DEFAULT_SERVE_COUNT);
+    if !(1..=MAX_SERVE_COUNT).contains(&count) {
+        return Err(api_error(StatusCode::BAD_REQUEST, &format!("count goes from 1 to {}", MAX_SERVE_COUNT)));
+    }
+    let mut config = server.config.clone();
+    if let Some(playlist) = &request.playlist {
+        config.playlist_id = Some(crate::commands::playlist_argument(playlist).map_err(|e| api_error(StatusCode::BAD_REQUEST, &e.to_string()))?);
+    }
+    let vibe = request.vibe.map(|vibe| vibe.trim().to_string()).filter(|vibe| !vibe.is_empty());
+    let options = Options { count: Some(count), vibe, ..server.options.clone() };
+    let client = server.client.clone();
+    let preview = blocking(move || Preview::new(client, config, &options, None), api_error).await?;
+
+    if request.apply {
+        let playlist = preview.playlist_name().to_string();
+        let approved = confident(preview.suggestions());
+        let added = blocking(move || preview.apply(&approved), api_error).await?;
+        return Ok((StatusCode::OK, Json(json!({ "playlist": playlist, "added": added }))));
+    }
+    let id = preview_id();
+    let answer = json!({ "preview": id, "playlist": preview.playlist_name(), "suggestions": suggestions_json(preview.suggestions()) });
+    server.store(id, preview);
+    Ok((StatusCode::OK, Json(answer)))
+}
+
+// Function to add the approved suggestions of a preview: POST /api/apply
+async fn api_apply(State(server): State<Arc<Server>>, headers: HeaderMap, Json(request): Json<ApplyRequest>) -> Result<Answer, Answer> {
+    authorize(&server, &headers)?;
+    let Some(preview) = server.take(request.preview) else {
+        return Err(api_error(StatusCode::NOT_FOUND, "No such preview, it was already applied or has expired"));
+    };
+    let playlist = preview.playlist_name().to_string();
+    let approved = request.approve.unwrap_or_else(|| confident(preview.suggestions()));
+    let added = blocking(move || preview.apply(&approved), api_error).await?;
+    Ok((StatusCode::OK, Json(json!({ "playlist": playlist, "added": added }))))
+}
+
+// Function to list the latest runs of the profile: GET /api/history?limit=N
+async fn api_history(State(server): State<Arc<Server>>, headers: HeaderMap, Query(query): Query<HistoryQuery>) -> Result<Answer, Answer> {
+    authorize(&server, &headers)?;
+    let profile = server.config.profile.clone();
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    let runs = blocking(move || History::open()?.recent_runs(profile.as_deref(), limit), api_error).await?;
+    let runs: Vec<Value> = runs
+        .iter()
+        .map(|run| {
+            json!({
+                "id": run.id,
+                "started_at": iso_date(run.started_at),
+                "playlist_id": run.playlist_id,
+                "playlist_name": run.playlist_name,
+                "dry_run": run.dry_run,
+                "suggested": run.suggested,
+                "added": run.added,
+            })
+        })
+        .collect();
+    Ok((StatusCode::OK, Json(json!({ "runs": runs }))))
+}
+
+// Function to expose the metrics of the server to Prometheus: GET /metrics
+async fn metrics() -> ([(header::HeaderName, &'static str); 1], String) {
+    ([(header::CONTENT_TYPE, METRICS_CONTENT_TYPE)], render_metrics())
+}
+
+// Function to serve the web interface until stopped: pick a playlist, preview suggestions, approve them and add them
+// The user authorizes Spotify once in the terminal, the requests then reuse the cached token.
+pub fn run(client: Client, config: Config, options: &Options) -> Result<(), PilotError> {
+    music_service(Service::Spotify).access_token(&client, &config, options.headless, false)?;
+    let address = config.serve_address.clone();
+    // Requests never ask anything in the terminal, low confidence matches are shown for review instead
+    let options = Options { headless: true, unattended: true, ..options.clone() };
+    let server = Arc::new(Server { client, config, options, previews: Mutex::new(Vec::new()) });
+    if server.config.api_token.as_deref().is_none_or(str::is_empty) {
+        println!("The JSON API is off, set api_token to turn it on.");
+    }
+    let app = Router::new()
+        .route("/", get(index))
+        .route("/preview", post(preview))
+        .route("/apply", post(apply))
+        .route("/api/playlists", get(api_playlists))
+        .route("/api/suggest", post(api_suggest))
+        .route("/api/apply", post(api_apply))
+        .route("/api/history", get(api_history))
+        .route("/metrics", get(metrics))
+        .with_state(server);
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind(&address).await.map_err(|e| format!("{}: {}", address, e))?;
+        println!("PlaylistPilot is served on http://{}, stop it with Ctrl+C.", address);
+        axum::serve(listener, app).await.map_err(|e| e.to_string())
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Source;
+    use crate::matching::TrackMatch;
+    use crate::models::Song;
+
+    fn suggestion(name: &str, status: Status, confidence: f64) -> Suggestion {
+        Suggestion {
+            song: Song { name: name.to_string(), artist: "The Who".to_string(), isrc: None, mbid: None },
+            found: Some(TrackMatch {
+                uri: format!("spotify:track:{}", name.len()),
+                name: name.to_string(),
+                artists: vec!["The Who".to_string()],
+                artist_ids: Vec::new(),
+                confidence,
+                image: Some("https://i.scdn.co/image/small".to_string()),
+                popularity: None,
+                tempo: None,
+            }),
+            status,
+            source: Source::Llm,
+        }
+    }
+
+    #[test]
+    fn render_preview_approves_confident_matches_only() {
+        let suggestions = [
+            suggestion("Love <Reign>", Status::Accepted, 0.95),
+            suggestion("My Generation", Status::Duplicate, 1.0),
+            suggestion("Pinball", Status::Review, 0.6),
+        ];
+        let html = render_preview(3, &suggestions);
+        assert!(html.contains("<input type=\"hidden\" name=\"preview\" value=\"3\">"));
+        assert!(html.contains("<input type=\"checkbox\" name=\"s0\" value=\"on\" checked>"));
+        assert!(html.contains("The Who – Love &lt;Reign&gt;"));
+        assert!(!html.contains("My Generation"));
+        assert!(html.contains("<input type=\"checkbox\" name=\"s2\" value=\"on\">"));
+        assert!(html.contains("(low confidence match, 60%)"));
+        assert!(html.contains("<img src=\"https://i.scdn.co/image/small\" alt=\"\">"));
+    }
+
+    #[test]
+    fn api_lists_approvable_suggestions_and_checks_the_token() {
+        let suggestions = [
+            suggestion("Baba O'Riley", Status::Accepted, 0.95),
+            suggestion("My Generation", Status::Duplicate, 1.0),
+            suggestion("Pinball", Status::Review, 0.6),
+        ];
+        let listed = suggestions_json(&suggestions);
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0]["index"], 0);
+        assert_eq!(listed[0]["link"], "https://open.spotify.com/track/12");
+        assert_eq!(listed[1]["index"], 2);
+        assert_eq!(listed[1]["needs_review"], true);
+        assert_eq!(confident(&suggestions), vec![0]);
+
+        assert!(token_matches("secret", Some("Bearer secret")));
+        assert!(!token_matches("secret", Some("Bearer secreT")));
+        assert!(!token_matches("secret", Some("secret")));
+        assert!(!token_matches("secret", None));
+    }
+
+    #[test]
+    fn forms_are_only_taken_from_the_served_page() {
+        let headers = |host: &str, origin: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::HOST, host.parse().unwrap());
+            if let Some(origin) = origin {
+                headers.insert(header::ORIGIN, origin.parse().unwrap());
+            }
+            headers
+        };
+        assert!(same_origin("127.0.0.1:8080", &headers("127.0.0.1:8080", Some("http://127.0.0.1:8080"))));
+        assert!(same_origin("127.0.0.1:8080", &headers("localhost:8080", None)));
+        assert!(!same_origin("127.0.0.1:8080", &headers("127.0.0.1:8080", Some("https://evil.example"))));
+        // A rebound domain name still sends its own Host
+        assert!(!same_origin("127.0.0.1:8080", &headers("evil.example:8080", Some("http://evil.example:8080"))));
+        assert!(!same_origin("127.0.0.1:8080", &HeaderMap::new()));
+        assert!(same_origin("0.0.0.0:8080", &headers("192.168.1.20:8080", Some("http://192.168.1.20:8080"))));
+        assert!(!same_origin("0.0.0.0:8080", &headers("192.168.1.20:9090", None)));
+    }
+}
@@ -0,0 +1,68 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+
+use crate::auth::*;
+use crate::backup::backup_playlist;
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::models::PlaylistItem;
+use crate::ordering::*;
+use crate::spotify::*;
+
+// Function to shuffle the order of a playlist on Spotify, the configured playlist by default
+// The tracks are moved rather than written again, so they keep the date they were added. The order comes from --random-seed,
+// or from a random seed that is printed, so the same shuffle can be made again.
+pub fn run(client: Client, config: Config, options: &Options, playlist: Option<&str>) -> Result<(), PilotError> {
+    let playlist_id = match playlist {
+        Some(playlist) => playlist_argument(playlist)?,
+        None => config.default_playlist()?,
+    };
+    let seed = options.random_seed.unwrap_or_else(rand::random);
+
+    // Only a user token can read a private playlist, even for a dry run
+    let access_token = get_access_token(&client, &config, options.headless)?;
+    let ctx = Context { client, config, access_token };
+
+    let playlist = get_playlist(&ctx, &playlist_id, None)?;
+    // Every item is moved, removed tracks and local files included, so the positions match Spotify's
+    let names: Vec<String> = playlist
+        .tracks
+        .items
+        .iter()
+        .map(|item| match &item.track {
+            Some(PlaylistItem::Track(track)) => {
+                let artists: Vec<&str> = track.artists.iter().map(|a| a.name.as_str()).collect();
+                format!("{} - {}", track.name, artists.join(", "))
+            },
+            Some(PlaylistItem::Episode(episode)) => episode.name.clone(),
+            _ => "(removed track)".to_string(),
+        })
+        .collect();
+    let order = shuffled_order(names.len(), seed);
+    let moves = plan_moves(&order);
+    println!("Shuffling with --random-seed {}.", seed);
+    if moves.is_empty() {
+        println!("'{}' has nothing to shuffle.", playlist.name);
+        return Ok(());
+    }
+    if options.dry_run {
+        println!("Dry run, '{}' would be shuffled ({} moves):", playlist.name, moves.len());
+        for index in &order {
+            println!("  {}", names[*index]);
+        }
+        return Ok(());
+    }
+
+    backup_playlist(&ctx, &playlist_id).map_err(|e| format!("Could not back up the playlist, nothing was changed: {}", e))?;
+    println!("Shuffling '{}' ({} moves)...", playlist.name, moves.len());
+    // Every move is made against the snapshot the previous one returned, so a concurrent edit makes the next one fail
+    let mut snapshot = Some(playlist.snapshot_id.clone());
+    for (range_start, insert_before) in moves {
+        snapshot = Some(move_in_playlist(&ctx, &playlist_id, range_start, insert_before, snapshot)?);
+    }
+    println!("Shuffled '{}'.", playlist.name);
+    Ok(())
+}
@@ -0,0 +1,59 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+
+use crate::auth::*;
+use crate::backup::backup_playlist;
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::models::PlaylistItem;
+use crate::ordering::*;
+use crate::spotify::*;
+
+// Function to sort a playlist on Spotify by a key, the configured playlist by default
+// The tracks are moved rather than written again, so they keep the date they were added and the order stays in every app.
+pub fn run(client: Client, config: Config, options: &Options, order: SortOrder, playlist: Option<&str>) -> Result<(), PilotError> {
+    let playlist_id = match playlist {
+        Some(playlist) => playlist_argument(playlist)?,
+        None => config.default_playlist()?,
+    };
+
+    // Only a user token can read a private playlist, even for a dry run
+    let access_token = get_access_token(&client, &config, options.headless)?;
+    let ctx = Context { client, config, access_token };
+
+    let playlist = get_playlist(&ctx, &playlist_id, None)?;
+    // Every item is moved, removed tracks and local files included, so the positions match Spotify's
+    let sorted = sorted_order(&playlist.tracks.items, order);
+    let moves = plan_moves(&sorted);
+    if moves.is_empty() {
+        println!("'{}' is already in this order.", playlist.name);
+        return Ok(());
+    }
+    if options.dry_run {
+        println!("Dry run, '{}' would be sorted ({} moves):", playlist.name, moves.len());
+        for index in &sorted {
+            match &playlist.tracks.items[*index].track {
+                Some(PlaylistItem::Track(track)) => {
+                    let artists: Vec<&str> = track.artists.iter().map(|a| a.name.as_str()).collect();
+                    println!("  {} - {}", track.name, artists.join(", "));
+                },
+                Some(PlaylistItem::Episode(episode)) => println!("  {}", episode.name),
+                _ => println!("  (removed track)"),
+            }
+        }
+        return Ok(());
+    }
+
+    backup_playlist(&ctx, &playlist_id).map_err(|e| format!("Could not back up the playlist, nothing was changed: {}", e))?;
+    println!("Sorting '{}' ({} moves)...", playlist.name, moves.len());
+    // Every move is made against the snapshot the previous one returned, so a concurrent edit makes the next one fail
+    let mut snapshot = Some(playlist.snapshot_id.clone());
+    for (range_start, insert_before) in moves {
+        snapshot = Some(move_in_playlist(&ctx, &playlist_id, range_start, insert_before, snapshot)?);
+    }
+    println!("Sorted '{}'.", playlist.name);
+    Ok(())
+}
@@ -0,0 +1,145 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use tracing::warn;
+
+use crate::auth::*;
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::llm::*;
+use crate::models::*;
+use crate::spotify::*;
+
+// Number of tracks classified per LLM request, long playlists are sent in several parts
+const SPLIT_CHUNK_SIZE: usize = 200;
+
+// A group of tracks of the playlist, as indexes into its track list
+struct Bucket {
+    name: String,
+    tracks: Vec<usize>,
+}
+
+// Function to build the prompt classifying a numbered list of tracks
+// The first part lets the LLM choose the buckets, the next ones must reuse the same names.
+fn split_prompt(tracks: &[Track], buckets: usize, names: &[String]) -> String {
+    let mut list = String::new();
+    for (index, track) in tracks.iter().enumerate() {
+        let artists: Vec<&str> = track.artists.iter().map(|a| a.name.as_str()).collect();
+        list.push_str(&format!("{}. {} by {}\n", index + 1, track.name, artists.join(", ")));
+    }
+    let groups = if names.is_empty() {
+        format!("Sort them into {buckets} coherent groups by mood and genre, and give each group a short name.")
+    } else {
+        format!("Sort them into these groups, using exactly these names: {}.", names.join(", "))
+    };
+    format!(
+        "Here is a numbered list of songs. {groups} Every song must be in exactly one group. \
+        You are only allowed to give me the groups nothing more. The format of your answer will be a JSON object \
+        with the key 'buckets' and the value being a list of objects with the keys 'name' (the group name) and \
+        'tracks' (the list of song numbers in the group). Here are the songs:\n{list}"
+    )
+}
+
+// Function to classify tracks into buckets with the LLM
+fn classify(ctx: &Context, api_key: &str, tracks: &[Track], buckets: usize) -> Result<Vec<Bucket>, PilotError> {
+    let mut result: Vec<Bucket> = Vec::new();
+    for (chunk_index, chunk) in tracks.chunks(SPLIT_CHUNK_SIZE).enumerate() {
+        let names: Vec<String> = result.iter().map(|bucket| bucket.name.clone()).collect();
+        let response = ask_llm(ctx, api_key, &split_prompt(chunk, buckets, &names))?;
+        let parsed: LlmBucketsResponse = read_llm_json(&parse_llm_response(&response)?)?;
+
+        let offset = chunk_index * SPLIT_CHUNK_SIZE;
+        for bucket in parsed.buckets {
+            let indexes = bucket
+                .tracks
+                .iter()
+                .filter(|number| (1..=chunk.len()).contains(*number))
+                .map(|number| offset + number - 1);
+            match result.iter_mut().find(|existing| existing.name.eq_ignore_ascii_case(&bucket.name)) {
+                Some(existing) => existing.tracks.extend(indexes),
+                None => result.push(Bucket { name: bucket.name.clone(), tracks: indexes.collect() }),
+            }
+        }
+    }
+
+    // A track the LLM put in two groups stays in the first one
+    let mut assigned = vec![false; tracks.len()];
+    for bucket in result.iter_mut() {
+        bucket.tracks.retain(|index| !std::mem::replace(&mut assigned[*index], true));
+    }
+    result.retain(|bucket| !bucket.tracks.is_empty());
+    Ok(result)
+}
+
+// Function to get the name and description of a bucket's playlist
+// The LLM names it from its tracks unless --name is given, the name then being "<name> - <group>".
+fn bucket_details(ctx: &Context, api_key: &str, options: &Options, source_name: &str, bucket: &Bucket, tracks: &[Track]) -> (String, String) {
+    let fallback = |prefix: &str| (format!("{} - {}", prefix, bucket.name), format!("{} tracks of {}, sorted by PlaylistPilot", bucket.name, source_name));
+    if let Some(name) = &options.name {
+        return fallback(name);
+    }
+    let songs: Vec<String> = bucket
+        .tracks
+        .iter()
+        .map(|index| &tracks[*index])
+        .map(|track| format!("{} by {}", track.name, track.artists.iter().map(|a| a.name.as_str()).collect::<Vec<&str>>().join(", ")))
+        .collect();
+    match name_playlist(ctx, api_key, &format!("the '{}' part of the playlist '{}'", bucket.name, source_name), &songs) {
+        Ok(details) => (details.name, details.description),
+        Err(e) => {
+            warn!("Could not get a name for the '{}' playlist from the LLM: {}", bucket.name, e);
+            fallback(source_name)
+        },
+    }
+}
+
+// Function to split a playlist into one new playlist per mood or genre
+pub fn run(client: Client, config: Config, options: &Options, source: Option<&str>) -> Result<(), PilotError> {
+    if options.buckets < 2 {
+        return Err("--buckets must be at least 2".into());
+    }
+    let source_id = match source {
+        Some(source) => playlist_argument(source)?,
+        None => config.default_playlist()?,
+    };
+    let llm_client_secret = get_llm_secret(&config)?;
+
+    let access_token = if options.dry_run {
+        get_read_only_token(&client, &config)?
+    } else {
+        get_access_token(&client, &config, options.headless)?
+    };
+    let ctx = Context { client, config, access_token };
+
+    let playlist = get_playlist(&ctx, &source_id, None)?;
+    let (tracks, _) = spotify_tracks(playlist.tracks.items);
+    if tracks.is_empty() {
+        println!("'{}' has no tracks to split.", playlist.name);
+        return Ok(());
+    }
+
+    let buckets = classify(&ctx, &llm_client_secret, &tracks, options.buckets)?;
+    let sorted: usize = buckets.iter().map(|bucket| bucket.tracks.len()).sum();
+    if sorted < tracks.len() {
+        println!("{} tracks were not put in any group and are left out.", tracks.len() - sorted);
+    }
+    for bucket in &buckets {
+        println!("{}: {} tracks", bucket.name, bucket.tracks.len());
+    }
+    if options.dry_run {
+        println!("Dry run, no playlist was created.");
+        return Ok(());
+    }
+
+    // The source playlist is only read, every bucket becomes a new playlist
+    for bucket in buckets {
+        let (playlist_name, description) = bucket_details(&ctx, &llm_client_secret, options, &playlist.name, &bucket, &tracks);
+        let created = create_playlist(&ctx, &playlist_name, &description)?;
+        let uris = bucket.tracks.iter().map(|index| tracks[*index].uri.clone()).collect();
+        add_to_playlist(&ctx, &created.id, uris, None)?;
+        println!("Created '{}': {}", playlist_name, created.external_urls.spotify);
+    }
+    Ok(())
+}
@@ -0,0 +1,170 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::auth::get_read_only_token;
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::models::*;
+use crate::spotify::*;
+
+// Number of artists listed in the report
+const TOP_ARTISTS: usize = 10;
+
+// Average audio features of a playlist
+#[derive(Debug, Serialize)]
+struct FeatureAverages {
+    energy: f64,
+    valence: f64,
+    danceability: f64,
+    acousticness: f64,
+    tempo: f64,
+}
+
+// Statistics about a playlist
+#[derive(Debug, Serialize)]
+struct Stats {
+    name: String,
+    tracks: usize,
+    duration_ms: u64,
+    // Artists with the most tracks, with their number of tracks
+    top_artists: Vec<(String, usize)>,
+    average_popularity: Option<f64>,
+    // Number of tracks released in each decade, e.g. 1990
+    decades: BTreeMap<i32, usize>,
+    // None when Spotify doesn't give audio features to this app
+    audio_features: Option<FeatureAverages>,
+    // Members who added tracks, with their number of tracks, for collaborative playlists
+    members: Vec<(String, usize)>,
+}
+
+// Function to compute the average of some values, None when there are none
+fn average(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+// Function to format a duration as hours and minutes, e.g. "2 h 05 min"
+fn format_duration(duration_ms: u64) -> String {
+    let minutes = duration_ms / 60_000;
+    format!("{} h {:02} min", minutes / 60, minutes % 60)
+}
+
+// Function to count the tracks each member of a collaborative playlist added, named by their profile, most first
+fn member_additions(ctx: &Context, items: &[TrackItem]) -> Vec<(String, usize)> {
+    let members = playlist_members(items);
+    let names = member_names(ctx, &members);
+    let mut additions: Vec<(String, usize)> = members
+        .iter()
+        .map(|id| {
+            let count = items.iter().filter(|item| item.added_by.as_ref().is_some_and(|added_by| &added_by.id == id)).count();
+            (names[id].clone(), count)
+        })
+        .collect();
+    additions.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    additions
+}
+
+// Function to compute the statistics of a playlist's tracks
+fn compute_stats(name: String, tracks: &[Track], features: Option<Vec<AudioFeatures>>, members: Vec<(String, usize)>) -> Stats {
+    let mut artists: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut decades = BTreeMap::new();
+    for track in tracks {
+        for artist in &track.artists {
+            *artists.entry(artist.name.as_str()).or_default() += 1;
+        }
+        if let Some(year) = track.album.release_year() {
+            *decades.entry(year / 10 * 10).or_default() += 1;
+        }
+    }
+    let mut top_artists: Vec<(String, usize)> = artists.into_iter().map(|(name, count)| (name.to_string(), count)).collect();
+    top_artists.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    top_artists.truncate(TOP_ARTISTS);
+
+    let audio_features = features.filter(|features| !features.is_empty()).map(|features| FeatureAverages {
+        energy: average(features.iter().map(|f| f.energy)).unwrap_or_default(),
+        valence: average(features.iter().map(|f| f.valence)).unwrap_or_default(),
+        danceability: average(features.iter().map(|f| f.danceability)).unwrap_or_default(),
+        acousticness: average(features.iter().map(|f| f.acousticness)).unwrap_or_default(),
+        tempo: average(features.iter().map(|f| f.tempo)).unwrap_or_default(),
+    });
+
+    Stats {
+        name,
+        tracks: tracks.len(),
+        duration_ms: tracks.iter().map(|track| track.duration_ms).sum(),
+        top_artists,
+        average_popularity: average(tracks.iter().filter_map(|track| track.popularity).map(f64::from)),
+        decades,
+        audio_features,
+        members,
+    }
+}
+
+// Function to print the statistics as a table
+fn print_stats(stats: &Stats) {
+    println!("{}", stats.name);
+    println!("  {:<20} {}", "Tracks", stats.tracks);
+    println!("  {:<20} {}", "Duration", format_duration(stats.duration_ms));
+    if let Some(popularity) = stats.average_popularity {
+        println!("  {:<20} {:.0}/100", "Average popularity", popularity);
+    }
+    if let Some(features) = &stats.audio_features {
+        println!("  {:<20} {:.2}", "Energy", features.energy);
+        println!("  {:<20} {:.2}", "Valence", features.valence);
+        println!("  {:<20} {:.2}", "Danceability", features.danceability);
+        println!("  {:<20} {:.2}", "Acousticness", features.acousticness);
+        println!("  {:<20} {:.0} BPM", "Tempo", features.tempo);
+    }
+    println!("Top artists");
+    for (artist, count) in &stats.top_artists {
+        println!("  {:<30} {:>4}", artist, count);
+    }
+    println!("Decades");
+    for (decade, count) in &stats.decades {
+        println!("  {:<30} {:>4}", format!("{}s", decade), count);
+    }
+    if !stats.members.is_empty() {
+        println!("Added by");
+        for (member, count) in &stats.members {
+            println!("  {:<30} {:>4}", member, count);
+        }
+    }
+}
+
+// Function to print statistics about a playlist: size, duration, artists, popularity, decades and audio features
+pub fn run(client: Client, config: Config, options: &Options, playlist: Option<&str>) -> Result<(), PilotError> {
+    let playlist_id = match playlist {
+        Some(playlist) => playlist_argument(playlist)?,
+        None => config.default_playlist()?,
+    };
+    // Nothing is written, so the user token is only needed for private playlists
+    let access_token = get_read_only_token(&client, &config)?;
+    let ctx = Context { client, config, access_token };
+
+    let playlist = get_playlist(&ctx, &playlist_id, None)?;
+    // Only the owner adds to other playlists, there is nothing to tell apart
+    let members = if playlist.collaborative { member_additions(&ctx, &playlist.tracks.items) } else { Vec::new() };
+    let (tracks, _) = spotify_tracks(playlist.tracks.items);
+    let uris: Vec<String> = tracks.iter().map(|track| track.uri.clone()).collect();
+    let features = match get_audio_features(&ctx, &uris) {
+        Ok(features) => Some(features.into_values().collect()),
+        Err(e) => {
+            // On stderr, so the JSON output stays parseable
+            eprintln!("Audio features unavailable: {}", e);
+            None
+        },
+    };
+
+    let stats = compute_stats(playlist.name, &tracks, features, members);
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+    } else {
+        print_stats(&stats);
+    }
+    Ok(())
+}
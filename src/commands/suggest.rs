@@ -0,0 +1,3354 @@
+// Import necessary modules and crates
+use rand::seq::SliceRandom;
+use reqwest::blocking::Client;
+use std::collections::{HashMap, HashSet};
+use std::thread;
+use tracing::{error, info, info_span, warn};
+
+use crate::auth::get_access_token;
+use crate::audit::*;
+use crate::cache::{fingerprint, EmbeddingCache, LlmCache, SearchCache};
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::config::{load_config_file, resolve_config, Config, ProviderSettings};
+use crate::cover::{cover_prompt, make_cover};
+use crate::context::Context;
+use crate::email::{run_summary, send_email};
+use crate::embeddings::*;
+use crate::error::PilotError;
+use crate::feed::update_feed;
+use crate::history::*;
+use crate::lastfm::*;
+use crate::listenbrainz;
+use crate::llm::*;
+use crate::matching::{clean_name, song_key, Blacklist, Duplicates, TrackFilter, TrackMatch};
+use crate::metrics::count_suggestions;
+use crate::models::*;
+use crate::musicbrainz::find_recording;
+use crate::notify::notify_webhook;
+use crate::ordering::{arrange, intensity, sparkline, sparkline_step, Arc, Mood};
+use crate::preview::confirm_with_preview;
+use crate::report::{write_report, Report};
+use crate::service::*;
+use crate::spotify::*;
+use crate::tokens::{civil_date, days_from_civil, iso_date, months_before, now};
+
+// A playlist read at the start of a run, as a seed for the LLM or as the playlist the songs are written to
+#[derive(Clone)]
+struct PlaylistState {
+    id: String,
+    name: String,
+    snapshot_id: Option<String>,
+    // Number of items, including those that aren't Spotify tracks
+    length: usize,
+    tracks: Vec<Track>,
+    // Artists the seed is about besides those of its tracks, for seeds that aren't playlists
+    artists: Vec<String>,
+    // Songs the seed is about that aren't read from Spotify, e.g. Last.fm scrobbles
+    songs: Vec<Song>,
+    // What the playlist should be, for playlists generated without seed tracks
+    brief: Option<Brief>,
+    // Genre tags of the artists of the tracks, with the number of tracks tagged each, the most common first
+    genres: Vec<(String, usize)>,
+}
+
+// What a playlist generated without seed tracks should be
+#[derive(Clone)]
+enum Brief {
+    // A description of the playlist wanted
+    Description(String),
+    // The lesser-known side of an artist, obscurity going from 1 (well-loved album tracks) to 5 (rarities)
+    DeepDive { artist: String, obscurity: u8 },
+}
+
+// Where the suggested songs are written
+enum Target {
+    // An existing playlist, appended to or replaced
+    Existing(PlaylistState),
+    // A new playlist created with this name
+    New(String),
+}
+
+impl Target {
+    // Function to get the name of the playlist the songs are written to
+    fn name(&self) -> &str {
+        match self {
+            Target::Existing(playlist) => &playlist.name,
+            Target::New(name) => name,
+        }
+    }
+}
+
+// Function to read a playlist, an unreadable one is reported and treated as empty so the run can go on
+fn read_playlist(ctx: &Context, service: &dyn MusicService, playlist_id: &str) -> PlaylistState {
+    let mut state = PlaylistState {
+        id: playlist_id.to_string(),
+        name: playlist_id.to_string(),
+        snapshot_id: None,
+        length: 0,
+        tracks: Vec::new(),
+        artists: Vec::new(),
+        songs: Vec::new(),
+        brief: None,
+        genres: Vec::new(),
+    };
+
+    // Fetch the whole playlist: every track is needed to avoid duplicates, even when the seed is capped
+    match service.get_playlist(ctx, playlist_id) {
+        Ok(playlist) => {
+            if playlist.skipped > 0 {
+                info!(
+                    "Skipped {} playlist items that are not {} tracks (local files, podcast episodes or removed tracks).",
+                    playlist.skipped,
+                    service.name()
+                );
+            }
+            state.snapshot_id = playlist.snapshot_id;
+            state.length = playlist.length;
+            state.name = playlist.name;
+            state.tracks = playlist.tracks;
+        },
+        Err(e) => {
+            warn!("{}", e);
+        }
+    }
+    state
+}
+
+// Function to read the tracks one member of a collaborative playlist added, for --added-by
+// The member is given by their Spotify user ID or the name on their profile.
+fn read_member_seed(ctx: &Context, playlist_id: &str, member: &str) -> Result<PlaylistState, PilotError> {
+    let playlist = get_playlist(ctx, playlist_id, None)?;
+    let members = playlist_members(&playlist.tracks.items);
+    let user_id = match members.iter().find(|id| id.eq_ignore_ascii_case(member)) {
+        Some(id) => id.clone(),
+        None => {
+            let names = member_names(ctx, &members);
+            let Some(id) = members.iter().find(|id| names[*id].eq_ignore_ascii_case(member)) else {
+                let known: Vec<&str> = members.iter().map(|id| names[id].as_str()).collect();
+                return Err(PilotError::Usage(format!("Nobody named {} added tracks to {}, its members are: {}", member, playlist.name, known.join(", "))));
+            };
+            id.clone()
+        },
+    };
+    let items = playlist.tracks.items.into_iter().filter(|item| item.added_by.as_ref().is_some_and(|added_by| added_by.id == user_id)).collect();
+    let (tracks, _) = spotify_tracks(items);
+    info!("Seeding with the {} tracks {} added to '{}'.", tracks.len(), member, playlist.name);
+    Ok(PlaylistState {
+        id: playlist_id.to_string(),
+        name: format!("{} (added by {})", playlist.name, member),
+        snapshot_id: None,
+        length: tracks.len(),
+        tracks,
+        artists: Vec::new(),
+        songs: Vec::new(),
+        brief: None,
+        genres: Vec::new(),
+    })
+}
+
+// Function to read the seed given by --seed as a playlist-like state
+fn read_seed(ctx: &Context, seed: &Seed, options: &Options) -> Result<PlaylistState, PilotError> {
+    let (id, name, tracks, artists) = match seed {
+        Seed::Recent => ("recently-played", "your recently played tracks", get_recently_played(ctx)?, Vec::new()),
+        Seed::Top => {
+            let range = options.range.as_str();
+            let artists = get_top_artists(ctx, range)?.into_iter().map(|artist| artist.name).collect();
+            ("top", "your top tracks", get_top_tracks(ctx, range)?, artists)
+        },
+        Seed::Liked => {
+            // A whole library is too long for a prompt, and a random sample gives different songs each run
+            let saved = get_saved_tracks(ctx)?;
+            let sample = saved.choose_multiple(&mut rand::thread_rng(), options.limit).cloned().collect();
+            ("liked", "your Liked Songs", sample, Vec::new())
+        },
+        Seed::Artists(names) => ("artists", "the artists you named", Vec::new(), names.clone()),
+        Seed::LastFm(user) => return read_lastfm_seed(ctx, user, options),
+        Seed::ListenBrainz(user) => return read_listenbrainz_seed(ctx, user, options),
+    };
+    Ok(PlaylistState {
+        id: id.to_string(),
+        name: name.to_string(),
+        snapshot_id: None,
+        length: tracks.len(),
+        tracks,
+        artists,
+        songs: Vec::new(),
+        brief: None,
+        genres: Vec::new(),
+    })
+}
+
+// Number of recent and of top tracks read from a listening history (Last.fm or ListenBrainz), and of top artists
+const HISTORY_SEED_TRACKS: usize = 50;
+const HISTORY_SEED_ARTISTS: usize = 10;
+
+// Function to turn the recent and top tracks and the top artists of a listening history into a seed
+fn history_seed(id: String, name: String, recent: Vec<Song>, top: Vec<Song>, artists: Vec<String>) -> Result<PlaylistState, PilotError> {
+    let mut songs = recent;
+    for song in top {
+        if !songs.iter().any(|known| known.name == song.name && known.artist == song.artist) {
+            songs.push(song);
+        }
+    }
+    if songs.is_empty() && artists.is_empty() {
+        return Err(format!("Nothing found in {}", name).into());
+    }
+    Ok(PlaylistState { id, name, snapshot_id: None, length: songs.len(), tracks: Vec::new(), artists, songs, brief: None, genres: Vec::new() })
+}
+
+// Function to read the recent and top scrobbles of a Last.fm user as a seed, for listening that happens outside Spotify
+fn read_lastfm_seed(ctx: &Context, user: &str, options: &Options) -> Result<PlaylistState, PilotError> {
+    let api_key = ctx.config.lastfm_api_key.as_deref().ok_or("lastfm api key not set")?;
+    let period = options.range.lastfm_period();
+    history_seed(
+        format!("lastfm:{}", user),
+        format!("the scrobbles of {}", user),
+        get_user_recent_tracks(ctx, api_key, user, HISTORY_SEED_TRACKS)?,
+        get_user_top_tracks(ctx, api_key, user, period, HISTORY_SEED_TRACKS)?,
+        get_user_top_artists(ctx, api_key, user, period, HISTORY_SEED_ARTISTS)?,
+    )
+}
+
+// Function to read the recent and top listens of a ListenBrainz user as a seed
+// Statistics are computed daily by ListenBrainz, a new account may only have its recent listens.
+fn read_listenbrainz_seed(ctx: &Context, user: &str, options: &Options) -> Result<PlaylistState, PilotError> {
+    let range = options.range.listenbrainz_range();
+    history_seed(
+        format!("listenbrainz:{}", user),
+        format!("the listens of {}", user),
+        listenbrainz::get_listens(ctx, user, HISTORY_SEED_TRACKS)?,
+        listenbrainz::get_top_recordings(ctx, user, range, HISTORY_SEED_TRACKS)?,
+        listenbrainz::get_top_artists(ctx, user, range, HISTORY_SEED_ARTISTS)?,
+    )
+}
+
+// Function to record the songs a run added as a private ListenBrainz playlist, named after the playlist they went to
+// Songs ListenBrainz can't find a recording for are left out of it.
+fn record_on_listenbrainz(ctx: &Context, token: &str, playlist_name: &str, suggestions: &[Suggestion]) {
+    let mut recording_ids = Vec::new();
+    let mut unknown = 0;
+    for suggestion in suggestions.iter().filter(|s| s.status == Status::Added) {
+        let Some(found) = &suggestion.found else { continue };
+        // Songs corrected against MusicBrainz already know their recording
+        let lookup = match &suggestion.song.mbid {
+            Some(id) => Ok(Some(id.clone())),
+            None => listenbrainz::lookup_recording(ctx, &found.name, &found.artists.join(", ")),
+        };
+        match lookup {
+            Ok(Some(id)) => recording_ids.push(id),
+            Ok(None) => unknown += 1,
+            Err(e) => {
+                warn!("Could not record the songs on ListenBrainz: {}", e);
+                return;
+            },
+        }
+    }
+    if recording_ids.is_empty() {
+        info!("None of the added songs is known to ListenBrainz, nothing was recorded there.");
+        return;
+    }
+    let title = format!("Added to {} by PlaylistPilot", playlist_name);
+    match listenbrainz::create_playlist(ctx, token, &title, &recording_ids) {
+        Ok(url) if unknown > 0 => println!("Recorded {} songs on ListenBrainz ({} unknown to it): {}", recording_ids.len(), unknown, url),
+        Ok(url) => println!("Recorded the songs on ListenBrainz: {}", url),
+        Err(e) => warn!("{}", e),
+    }
+}
+
+// Function to build the blacklist from the config and --exclude, which takes track URIs or links and artist names
+fn blacklist(config: &Config, options: &Options) -> Blacklist {
+    let mut artists = config.exclude_artists.clone();
+    let mut tracks = config.exclude_tracks.clone();
+    for value in &options.exclude {
+        if parse_spotify_id(value, "track").is_some() && (value.contains(':') || value.contains('/')) {
+            tracks.push(value.clone());
+        } else {
+            artists.push(value.clone());
+        }
+    }
+    Blacklist::new(&artists, &tracks)
+}
+
+// Function to describe the average sound of tracks from their audio features, so the LLM matches more than artist names
+// None when Spotify has no features for them or doesn't give features to this app.
+fn sonic_profile(ctx: &Context, tracks: &[Track]) -> Option<String> {
+    let uris: Vec<String> = tracks.iter().map(|track| track.uri.clone()).filter(|uri| uri.starts_with("spotify:track:")).collect();
+    if uris.is_empty() {
+        return None;
+    }
+    let features = match get_audio_features(ctx, &uris) {
+        Ok(features) if !features.is_empty() => features,
+        Ok(_) => return None,
+        Err(e) => {
+            warn!("The prompt won't include the sonic profile: {}", e);
+            return None;
+        },
+    };
+    let count = features.len() as f64;
+    let average = |value: fn(&AudioFeatures) -> f64| features.values().map(value).sum::<f64>() / count;
+    Some(format!(
+        "average tempo {:.0} BPM, energy {:.2}, valence {:.2}, acousticness {:.2} (from 0 to 1)",
+        average(|f| f.tempo),
+        average(|f| f.energy),
+        average(|f| f.valence),
+        average(|f| f.acousticness)
+    ))
+}
+
+// Function to get the artists with the most tracks among tracks left out of the prompt, most present first
+fn left_out_artists(tracks: &[Track]) -> Vec<String> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for artist in tracks.iter().flat_map(|track| &track.artists) {
+        *counts.entry(artist.name.as_str()).or_default() += 1;
+    }
+    let mut artists: Vec<(&str, usize)> = counts.into_iter().collect();
+    artists.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    artists.into_iter().take(LEFT_OUT_ARTISTS).map(|(name, _)| name.to_string()).collect()
+}
+
+// Function to format the seed tracks of a playlist for the LLM prompt, followed by their sonic profile
+fn format_seed(ctx: &Context, playlist: &PlaylistState, max_seed_tracks: Option<usize>) -> String {
+    let seed_count = max_seed_tracks.unwrap_or(usize::MAX).min(playlist.tracks.len());
+    if seed_count < playlist.tracks.len() {
+        info!("Using {} of the {} tracks of '{}' as the seed.", seed_count, playlist.tracks.len(), playlist.name);
+    }
+    let seed = &playlist.tracks[..seed_count];
+    let mut output = String::new();
+    for track in seed {
+        let artist_names: Vec<String> = track.artists.iter().map(|a| a.name.clone()).collect();
+        output.push_str(&format!("{} by {}, ", track.name, artist_names.join(", ")));
+    }
+    // The tracks left out are summed up by their most present artists, so the LLM still knows about them
+    let left_out = left_out_artists(&playlist.tracks[seed_count..]);
+    if !left_out.is_empty() {
+        output.push_str(&format!("and more songs by {}, ", left_out.join(", ")));
+    }
+    for song in playlist.songs.iter().take(max_seed_tracks.unwrap_or(usize::MAX)) {
+        output.push_str(&format!("{} by {}, ", song.name, song.artist));
+    }
+    if !playlist.artists.is_empty() {
+        output.push_str(&format!("and songs by {}, ", playlist.artists.join(", ")));
+    }
+    if let Some(profile) = sonic_profile(ctx, seed) {
+        output.push_str(&format!("with a sonic profile of {}. ", profile));
+    }
+    if !playlist.genres.is_empty() {
+        let genres: Vec<&str> = playlist.genres.iter().take(GENRES_IN_PROMPT).map(|(genre, _)| genre.as_str()).collect();
+        output.push_str(&format!("The playlist skews {}. ", genres.join("/")));
+    }
+    output
+}
+
+// Most common genres of a seed named in the prompt
+const GENRES_IN_PROMPT: usize = 3;
+
+// Function to tag the seeds with the genres of their artists, resolving every artist with the batch artists endpoint
+// Only Spotify knows the genres of its artists; a failure only leaves the seed untagged.
+fn tag_genres(ctx: &Context, seeds: &mut [PlaylistState]) {
+    if ctx.config.service != Service::Spotify {
+        return;
+    }
+    for seed in seeds.iter_mut().filter(|seed| !seed.tracks.is_empty()) {
+        match genre_counts(ctx, &seed.tracks) {
+            Ok(genres) => seed.genres = genres,
+            Err(e) => warn!("Could not read the genres of '{}': {}", seed.name, e),
+        }
+    }
+}
+
+// Format every prompt asks the LLM to answer in
+const ANSWER_FORMAT: &str = "The format of your answer will be a JSON object with the key 'songs' and the value being a list \
+    of song objects. Each song object should have the keys 'name' and 'artist', and the key 'isrc' only if you know the exact ISRC of the recording.";
+
+// Function to prepare the prompt for the LLM to generate songs similar to the seed playlists
+// With two seeds, the songs have to bridge both vibes.
+fn build_prompt(ctx: &Context, number: i32, seeds: &[PlaylistState], max_seed_tracks: Option<usize>) -> String {
+    let answer_format = ANSWER_FORMAT;
+    match seeds {
+        [PlaylistState { brief: Some(Brief::Description(description)), .. }] => format!(
+            "Give me {number} songs for a playlist matching this description: {description}. Your goal is to give me songs that fit \
+            the description as a whole, in genre, era and mood. {answer_format}"
+        ),
+        [PlaylistState { brief: Some(Brief::DeepDive { artist, obscurity }), .. }] => {
+            let depth = match obscurity {
+                1 => "well-loved album tracks that were never singles",
+                2 => "album cuts and a few collaborations",
+                3 => "album cuts, B-sides, collaborations and side projects",
+                4 => "B-sides, early recordings, collaborations and side projects that only fans know",
+                _ => "the rarest recordings: B-sides, demos, early and one-off collaborations, side projects and guest appearances",
+            };
+            format!(
+                "Give me {number} songs for a deep dive into {artist}, going beyond the singles and hits everyone knows. \
+                Focus on {depth}. Side projects and collaborations may be credited to other artists. No song should be one of \
+                the artist's famous singles. {answer_format}"
+            )
+        },
+        // Only artists, nothing to match the sound of
+        [seed] if seed.tracks.is_empty() && seed.songs.is_empty() && !seed.artists.is_empty() => format!(
+            "Give me {number} songs in the orbit of these artists: {}. Draw on their catalogs and their styles: mix some of their own \
+            songs with songs by artists who share their sound and influences, so the result works as a playlist for their fans. \
+            {answer_format}",
+            seed.artists.join(", ")
+        ),
+        [seed] => {
+            let output = format_seed(ctx, seed, max_seed_tracks);
+            format!(
+                "I will give you a playlist, give me {number} songs that are similar to the songs in the playlist. Your goal is to give me songs that fit the vibe \
+                and the sound of the playlist. \
+                {answer_format} Here is the playlist: {output}"
+            )
+        },
+        _ => {
+            let playlists: Vec<String> = seeds
+                .iter()
+                .enumerate()
+                .map(|(index, seed)| format!("Playlist {}: {}", index + 1, format_seed(ctx, seed, max_seed_tracks)))
+                .collect();
+            format!(
+                "I will give you {} playlists, give me {number} songs that would fit in all of them. Your goal is to give me songs that bridge the vibes \
+                of the playlists, so that people who like any of them enjoy the result. {answer_format} Here are the playlists: {}",
+                seeds.len(),
+                playlists.join(" ")
+            )
+        },
+    }
+}
+
+// Function to ask the user how many songs they want to add, asking again until the answer is a number
+fn ask_number() -> Result<i32, PilotError> {
+    loop {
+        println!("Enter the number of songs you want to add to the playlist:");
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input)? == 0 {
+            return Err(PilotError::Usage("No number of songs was given, use --count".to_string()));
+        }
+        match parse_number(&input) {
+            Ok(number) => return Ok(number),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+// Function to read the number of songs the user typed
+fn parse_number(input: &str) -> Result<i32, PilotError> {
+    input.trim().parse().map_err(|_| PilotError::Usage(format!("Please enter a valid number, not '{}'", input.trim())))
+}
+
+// Function to turn the search result of a suggested song into a suggestion
+// Low confidence matches are often covers or karaoke versions, the user decides (or they are flagged for review in a dry run).
+fn match_suggestion(ctx: &Context, options: &Options, song: Song, result: Result<TrackMatch, PilotError>, source: Source) -> Suggestion {
+    let (found, status) = match result {
+        Ok(found) if found.confidence >= ctx.config.min_confidence => (Some(found), Status::Accepted),
+        Ok(found) => {
+            let question = format!(
+                "Low confidence match for '{} - {}': found '{} - {}' ({:.0}%). Add it?",
+                song.name, song.artist, found.name, found.artists.join(", "), found.confidence * 100.0
+            );
+            let status = if options.dry_run || options.unattended {
+                info!("{} (needs review)", question);
+                Status::Review
+            } else if confirm_with_preview(ctx, &question, &found.uri) {
+                Status::Accepted
+            } else {
+                Status::Rejected
+            };
+            (Some(found), status)
+        },
+        Err(e) => {
+            warn!("Error finding song '{} - {}': {}", song.name, song.artist, e);
+            (None, Status::NotFound)
+        },
+    };
+    Suggestion { song, found, status, source }
+}
+
+// Function to replace the names of suggested songs with their MusicBrainz spelling and primary artist, before searching Spotify
+// The LLM often gets a title or an artist slightly wrong; songs MusicBrainz doesn't know well enough are kept as written.
+fn canonicalize(ctx: &Context, songs: &mut [Song]) {
+    for song in songs.iter_mut() {
+        match find_recording(ctx, &song.name, &song.artist) {
+            Ok(Some(recording)) => {
+                let artist = recording.artist_credit.first().map_or(song.artist.clone(), |credit| credit.name.clone());
+                if recording.title != song.name || artist != song.artist {
+                    info!("Corrected '{} - {}' to '{} - {}'.", song.name, song.artist, recording.title, artist);
+                }
+                song.name = recording.title;
+                song.artist = artist;
+                song.mbid = Some(recording.id);
+            },
+            Ok(None) => {},
+            Err(e) => {
+                warn!("Could not check the song names on MusicBrainz: {}", e);
+                return;
+            },
+        }
+    }
+}
+
+// Function to ask the LLM for one song similar to a suggestion that isn't on Spotify, and search for it
+// Replacements aren't replaced in turn; None when the LLM gives nothing usable.
+fn find_replacement(
+    ctx: &Context,
+    service: &dyn MusicService,
+    options: &Options,
+    api_key: &str,
+    song: &Song,
+    filter: &TrackFilter,
+    search_cache: Option<&SearchCache>,
+) -> Option<Suggestion> {
+    let prompt = format!(
+        "The song '{}' by {} isn't on {}. Suggest one similar song that is, and that fits the same playlist. {}",
+        song.name, song.artist, service.name(), ANSWER_FORMAT
+    );
+    let mut replacement = ask_llm(ctx, api_key, &prompt)
+        .and_then(|response| parse_llm_response(&response))
+        .and_then(|cleaned| read_llm_json::<LlmSongsResponse>(&cleaned))
+        .map_err(|e| warn!("Could not get a replacement: {}", e))
+        .ok()?
+        .songs
+        .into_iter()
+        .next()?;
+    if options.musicbrainz {
+        canonicalize(ctx, std::slice::from_mut(&mut replacement));
+    }
+    info!("Trying '{} - {}' instead.", replacement.name, replacement.artist);
+    let result = search_songs(service, ctx, std::slice::from_ref(&replacement), 1, search_cache, filter).pop()?;
+    Some(match_suggestion(ctx, options, replacement, result, Source::Llm))
+}
+
+// Function to open the search cache unless disabled, running without it if the database can't be opened
+// Cached matches are only Spotify URIs, they can't be checked against the filters.
+fn open_search_cache(ctx: &Context, options: &Options, filter: &TrackFilter) -> Option<SearchCache> {
+    if options.no_cache || filter.is_active() || ctx.config.service != Service::Spotify {
+        None
+    } else {
+        SearchCache::open().map_err(|e| warn!("Search cache unavailable: {}", e)).ok()
+    }
+}
+
+// Function to open the cache of LLM answers unless disabled, running without it if the database can't be opened
+fn open_llm_cache(ctx: &Context, options: &Options) -> Option<LlmCache> {
+    if options.no_llm_cache || ctx.config.llm_cache_ttl_secs == 0 {
+        None
+    } else {
+        LlmCache::open(ctx.config.llm_cache_ttl_secs).map_err(|e| warn!("LLM cache unavailable: {}", e)).ok()
+    }
+}
+
+// Function to get the key of a request to the LLM in its cache, from everything that makes up the request
+// The prompt holds the seed tracks, the number of songs and the filters, so any change to them is another request.
+fn llm_request_key(ctx: &Context, conversation: &[Message]) -> String {
+    let messages = serde_json::to_string(conversation).unwrap_or_default();
+    fingerprint(&format!("{}\u{1f}{}\u{1f}{}\u{1f}{}", ctx.config.llm_api_url, ctx.config.llm_model, ctx.config.system_prompt, messages))
+}
+
+// Function to ask the LLM for song suggestions and search for them on Spotify
+// Low confidence matches are confirmed by the user, or flagged for review in a dry run.
+pub fn find_suggestions(
+    ctx: &Context,
+    service: &dyn MusicService,
+    options: &Options,
+    api_key: &str,
+    prompt: &str,
+    filter: &TrackFilter,
+) -> Result<Vec<Suggestion>, PilotError> {
+    let conversation = vec![Message { role: "user".to_string(), content: prompt.to_string() }];
+    find_conversation_suggestions(ctx, service, options, api_key, conversation, filter)
+}
+
+// Function to continue a conversation with the LLM, its last turn asking for songs, and search for the songs it answers
+fn find_conversation_suggestions(
+    ctx: &Context,
+    service: &dyn MusicService,
+    options: &Options,
+    api_key: &str,
+    conversation: Vec<Message>,
+    filter: &TrackFilter,
+) -> Result<Vec<Suggestion>, PilotError> {
+    let search_cache = open_search_cache(ctx, options, filter);
+    // Running again right after a failure reuses the answer instead of paying for the same request
+    let llm_cache = open_llm_cache(ctx, options);
+    let request_key = llm_request_key(ctx, &conversation);
+    let cached = llm_cache.as_ref().and_then(|cache| cache.get(&request_key));
+    let answer = match &cached {
+        Some(answer) => {
+            info!("Reusing the LLM's answer to the same request (--no-llm-cache to ask again).");
+            Ok(answer.clone())
+        },
+        None => ask_llm_conversation(ctx, api_key, conversation),
+    };
+
+    let mut suggestions = Vec::new();
+    match answer {
+        Ok(response) => {
+            match parse_llm_response(&response) {
+                Ok(cleaned_response) => {
+                    let mut llm_songs: LlmSongsResponse = read_llm_json(&cleaned_response)?;
+                    // Only answers in the format asked for are kept
+                    if let (Some(cache), None) = (&llm_cache, &cached) {
+                        if let Err(e) = cache.put(&request_key, &response) {
+                            warn!("Could not cache the LLM's answer: {}", e);
+                        }
+                    }
+                    if options.musicbrainz {
+                        canonicalize(ctx, &mut llm_songs.songs);
+                    }
+                    let results = search_songs(service, ctx, &llm_songs.songs, options.parallelism, search_cache.as_ref(), filter);
+                    for (song, result) in llm_songs.songs.into_iter().zip(results) {
+                        let unfound = result.is_err();
+                        suggestions.push(match_suggestion(ctx, options, song, result, Source::Llm));
+                        // Ask for a similar song right away instead of leaving a gap
+                        if unfound {
+                            let song = &suggestions[suggestions.len() - 1].song;
+                            if let Some(replacement) = find_replacement(ctx, service, options, api_key, song, filter, search_cache.as_ref()) {
+                                suggestions.push(replacement);
+                            }
+                        }
+                    }
+                    check_suggestions(ctx, filter, &mut suggestions);
+                },
+                Err(e) => warn!("{}", e),
+            }
+        },
+        Err(e) => warn!("{}", e),
+    }
+    Ok(suggestions)
+}
+
+// Highest Spotify popularity of a track with --deep-cuts, unless --max-popularity is given
+const DEEP_CUTS_MAX_POPULARITY: u32 = 35;
+
+// Function to write the --deep-cuts rule for the LLM prompt, naming the artists of the seeds to dig into
+fn deep_cuts_rule(seeds: &[PlaylistState]) -> String {
+    let tracks: Vec<Track> = seeds.iter().flat_map(|seed| seed.tracks.iter().cloned()).collect();
+    let mut artists = left_out_artists(&tracks);
+    for artist in seeds.iter().flat_map(|seed| &seed.artists) {
+        if !artists.contains(artist) {
+            artists.push(artist.clone());
+        }
+    }
+    let by = if artists.is_empty() {
+        "by the artists of the playlist and artists close to them".to_string()
+    } else {
+        format!("by these artists and artists close to them in sound and scene: {}", artists.join(", "))
+    };
+    format!(
+        "Only give deep cuts {}: album tracks that were never released as singles, B-sides and non-album tracks. \
+        Never give their hits, singles or songs that are on their best-of compilations.",
+        by
+    )
+}
+
+// Function to get the conditions the matched tracks must meet from the options
+fn track_filter(options: &Options) -> TrackFilter {
+    TrackFilter {
+        no_explicit: options.no_explicit,
+        years: options.years,
+        released_since: options.released_within.map(|months| months_before(now(), months)),
+        min_popularity: options.min_popularity,
+        max_popularity: options.max_popularity.or(options.deep_cuts.then_some(DEEP_CUTS_MAX_POPULARITY)),
+        exclude_genres: options.exclude_genres.clone(),
+        instrumental: options.instrumental,
+        acoustic: options.acoustic,
+        avoid: options.avoid.clone(),
+        seed_genres: Vec::new(),
+        bpm: options.bpm,
+        intensity: match options.arc {
+            Some(Arc::Transition(from, to)) => {
+                let (low, high) = if from.intensity() <= to.intensity() { (from, to) } else { (to, from) };
+                Some((low.intensity() - TRANSITION_MARGIN, high.intensity() + TRANSITION_MARGIN))
+            },
+            _ => None,
+        },
+    }
+}
+
+// How far from the intensities of the moods of an --arc transition a song may be
+const TRANSITION_MARGIN: f64 = 0.15;
+
+// Function to ask the LLM for the songs of a transition between two moods, in three groups going from one to the other
+fn transition_rule(from: Mood, to: Mood, number: usize) -> String {
+    let (first, last) = (number / 3, number / 3);
+    format!(
+        "The playlist should move smoothly from {} to {}: give {} {} songs first, then {} songs halfway between {} and {}, then {} {} songs, in this order.",
+        from.name(),
+        to.name(),
+        first,
+        from.name(),
+        number - first - last,
+        from.name(),
+        to.name(),
+        last,
+        to.name()
+    )
+}
+
+// Function to order the picked songs along the --arc, the most intense last for a build-up, from their audio features
+// Songs Spotify has no features for go last; nothing is moved when the features can't be read.
+fn order_along_arc(ctx: &Context, arc: Arc, uris: &mut Vec<String>) {
+    let features = match get_audio_features(ctx, uris) {
+        Ok(features) => features,
+        Err(e) => {
+            warn!("The songs are left in the order the LLM gave them: {}", e);
+            return;
+        },
+    };
+    let (scored, unscored): (Vec<&String>, Vec<&String>) =
+        uris.iter().partition(|uri| uri.strip_prefix("spotify:track:").is_some_and(|id| features.contains_key(id)));
+    let intensities: Vec<f64> =
+        scored.iter().filter_map(|uri| uri.strip_prefix("spotify:track:").and_then(|id| features.get(id))).map(intensity).collect();
+    let ordered: Vec<String> = arrange(&intensities, arc).into_iter().map(|index| scored[index].clone()).chain(unscored.into_iter().cloned()).collect();
+    *uris = ordered;
+}
+
+// Function to get the tracks of the seeds, the most popular first
+// Seed playlists are read without the popularity of their tracks, it is only fetched for the runs that need it. When it can't be,
+// the tracks keep the order of the seeds.
+fn most_popular(ctx: &Context, seeds: &[PlaylistState]) -> Vec<Track> {
+    let mut tracks: Vec<Track> = seeds.iter().flat_map(|seed| seed.tracks.iter().cloned()).collect();
+    let unknown: Vec<String> = tracks.iter().filter(|track| track.popularity.is_none()).map(|track| track.uri.clone()).collect();
+    if unknown.iter().any(|uri| uri.starts_with("spotify:track:")) {
+        match get_tracks(ctx, &unknown) {
+            Ok(full) => {
+                let popularity: HashMap<String, u32> = full.into_iter().filter_map(|track| Some((track.uri, track.popularity?))).collect();
+                for track in tracks.iter_mut().filter(|track| track.popularity.is_none()) {
+                    track.popularity = popularity.get(&track.uri).copied();
+                }
+            },
+            Err(e) => warn!("Could not read the popularity of the seed tracks: {}", e),
+        }
+    }
+    tracks.sort_by_key(|track| std::cmp::Reverse(track.popularity.unwrap_or(0)));
+    tracks
+}
+
+// Function to pick the seeds of Spotify's recommendations: the most popular tracks and the most present artists
+// Spotify accepts five seeds at most, three tracks and two artists are used.
+fn recommendation_seeds(ctx: &Context, seeds: &[PlaylistState]) -> (Vec<String>, Vec<String>) {
+    let tracks = most_popular(ctx, seeds);
+    let track_ids: Vec<String> = tracks
+        .iter()
+        .filter_map(|track| parse_spotify_id(&track.uri, "track"))
+        .take(3)
+        .collect();
+
+    let mut artist_counts: HashMap<&str, usize> = HashMap::new();
+    for artist in tracks.iter().flat_map(|track| &track.artists) {
+        if let Some(id) = &artist.id {
+            *artist_counts.entry(id).or_insert(0) += 1;
+        }
+    }
+    let mut artists: Vec<(&str, usize)> = artist_counts.into_iter().collect();
+    artists.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    let artist_ids = artists
+        .into_iter()
+        .take(RECOMMENDATION_SEEDS - track_ids.len())
+        .map(|(id, _)| id.to_string())
+        .collect();
+    (track_ids, artist_ids)
+}
+
+// Function to get Spotify's recommendations for the seed playlists as suggestions
+// They are real tracks, so they are taken as exact matches; an error only leaves the LLM suggestions.
+fn recommended_suggestions(ctx: &Context, seeds: &[PlaylistState], count: usize, filter: &TrackFilter) -> Vec<Suggestion> {
+    let (track_ids, artist_ids) = recommendation_seeds(ctx, seeds);
+    if track_ids.is_empty() && artist_ids.is_empty() {
+        return Vec::new();
+    }
+    match get_recommendations(ctx, &track_ids, &artist_ids, count) {
+        Ok(tracks) => {
+            let mut suggestions: Vec<Suggestion> = tracks
+                .iter()
+                .filter(|track| filter.allows(track))
+                .map(|track| Suggestion {
+                    song: Song {
+                        name: track.name.clone(),
+                        artist: track.artists.iter().map(|a| a.name.clone()).collect::<Vec<String>>().join(", "),
+                        isrc: track.external_ids.isrc.clone(),
+                        mbid: None,
+                    },
+                    found: Some(TrackMatch::from_track(track, 1.0)),
+                    status: Status::Accepted,
+                    source: Source::Spotify,
+                })
+                .collect();
+            check_suggestions(ctx, filter, &mut suggestions);
+            suggestions
+        },
+        Err(e) => {
+            warn!("Could not get Spotify's recommendations: {}", e);
+            Vec::new()
+        },
+    }
+}
+
+// Number of seed tracks, and of seed artists, Last.fm is asked about
+const LASTFM_SEEDS: usize = 3;
+
+// Number of top tracks taken from each artist Last.fm finds similar
+const LASTFM_TRACKS_PER_ARTIST: usize = 2;
+
+// Function to list the artists of the seeds by name, those a seed is about first, then the most present in its tracks
+fn seed_artists(seeds: &[PlaylistState], count: usize) -> Vec<String> {
+    let mut artists: Vec<String> = seeds.iter().flat_map(|seed| seed.artists.iter().cloned()).collect();
+    let mut artist_counts: HashMap<&str, usize> = HashMap::new();
+    for artist in seeds.iter().flat_map(|seed| &seed.tracks).flat_map(|track| &track.artists) {
+        *artist_counts.entry(&artist.name).or_insert(0) += 1;
+    }
+    let mut counted: Vec<(&str, usize)> = artist_counts.into_iter().collect();
+    counted.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    for (name, _) in counted {
+        if !artists.iter().any(|artist| artist == name) {
+            artists.push(name.to_string());
+        }
+    }
+    artists.truncate(count);
+    artists
+}
+
+// Function to get the songs Last.fm's listeners associate with the seeds, as suggestions searched on Spotify
+// Songs similar to the most popular seed tracks are mixed with the top tracks of artists similar to the seed's top artists.
+// Failed lookups (e.g. a track Last.fm doesn't know) are skipped, the others are still used.
+fn lastfm_suggestions(
+    ctx: &Context,
+    service: &dyn MusicService,
+    options: &Options,
+    api_key: &str,
+    seeds: &[PlaylistState],
+    count: usize,
+    filter: &TrackFilter,
+) -> Vec<Suggestion> {
+    let mut lists: Vec<Vec<Song>> = Vec::new();
+    let mut errors: Vec<PilotError> = Vec::new();
+    let tracks = most_popular(ctx, seeds);
+    for track in tracks.iter().take(LASTFM_SEEDS) {
+        let Some(artist) = track.artists.first() else { continue };
+        match get_similar_tracks(ctx, api_key, &track.name, &artist.name, count) {
+            Ok(songs) => lists.push(songs),
+            Err(e) => errors.push(e),
+        }
+    }
+    for artist in seed_artists(seeds, LASTFM_SEEDS) {
+        let similar = match get_similar_artists(ctx, api_key, &artist, LASTFM_SEEDS) {
+            Ok(similar) => similar,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            },
+        };
+        let mut songs = Vec::new();
+        for similar_artist in similar {
+            match get_artist_top_tracks(ctx, api_key, &similar_artist, LASTFM_TRACKS_PER_ARTIST) {
+                Ok(top) => songs.extend(top),
+                Err(e) => errors.push(e),
+            }
+        }
+        lists.push(songs);
+    }
+    if let Some(first) = errors.first() {
+        warn!("{} Last.fm lookups failed, e.g.: {}", errors.len(), first);
+    }
+
+    // The same song is often similar to several seeds
+    let mut seen = std::collections::HashSet::new();
+    let songs: Vec<Song> = interleave(lists)
+        .into_iter()
+        .filter(|song| seen.insert((clean_name(&song.name), clean_name(&song.artist))))
+        .take(count)
+        .collect();
+    if songs.is_empty() {
+        return Vec::new();
+    }
+    let search_cache = open_search_cache(ctx, options, filter);
+    let results = search_songs(service, ctx, &songs, options.parallelism, search_cache.as_ref(), filter);
+    let mut suggestions: Vec<Suggestion> = songs
+        .into_iter()
+        .zip(results)
+        .map(|(song, result)| match_suggestion(ctx, options, song, result, Source::LastFm))
+        .collect();
+    check_suggestions(ctx, filter, &mut suggestions);
+    suggestions
+}
+
+// Number of songs --throwback blends into a run
+const THROWBACK_SONGS: usize = 3;
+
+// Function to get the first and last Unix times of this month `years` years ago, the last one excluded
+fn throwback_month(timestamp: u64, years: u32) -> (u64, u64) {
+    let (year, month, _) = civil_date((timestamp / 86400) as i64);
+    let year = year - years as i64;
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    let day = |year: i64, month: u32| (days_from_civil(year, month, 1).max(0) * 86400) as u64;
+    (day(year, month), day(next_year, next_month))
+}
+
+// Function to resurface a few songs the user was into this month `years` years ago, for --throwback
+// They are the songs runs added that month and, with `lastfm_user` set, those scrobbled the most that month. Songs already in the
+// target or suggested to it before are passed over, so the few that are kept can all be added.
+// Lookups that fail are skipped, the run still goes on without throwbacks.
+fn throwback_suggestions(
+    ctx: &Context,
+    service: &dyn MusicService,
+    options: &Options,
+    years: u32,
+    target: &Target,
+    past_songs: &[PastSong],
+    filter: &TrackFilter,
+) -> Vec<Suggestion> {
+    let (from, to) = throwback_month(now(), years);
+    let mut lists: Vec<Vec<Song>> = Vec::new();
+    if let (Some(api_key), Some(user)) = (&ctx.config.lastfm_api_key, &ctx.config.lastfm_user) {
+        match get_user_tracks_between(ctx, api_key, user, from, to) {
+            Ok(songs) => lists.push(songs),
+            Err(e) => warn!("Could not read the scrobbles of {} years ago: {}", years, e),
+        }
+    }
+    match History::open().and_then(|history| history.added_between(ctx.config.profile.as_deref(), from, to)) {
+        Ok(tracks) => lists.push(tracks.into_iter().map(|track| Song { name: track.name, artist: track.artists, isrc: None, mbid: None }).collect()),
+        Err(e) => warn!("Could not read the runs of {} years ago: {}", years, e),
+    }
+    let mut seen = HashSet::new();
+    let songs: Vec<Song> = interleave(lists).into_iter().filter(|song| seen.insert((clean_name(&song.name), clean_name(&song.artist)))).collect();
+    let (year, month, _) = civil_date((from / 86400) as i64);
+    if songs.is_empty() {
+        info!("No songs from {}-{:02} to bring back.", year, month);
+        return Vec::new();
+    }
+
+    let mut known = Duplicates::new(&[]);
+    if let Target::Existing(playlist) = target {
+        known.extend(&playlist.tracks);
+    }
+    for song in past_songs {
+        known.add(song.uri.as_deref(), &song.name, &song.artists);
+    }
+    let search_cache = open_search_cache(ctx, options, filter);
+    let results = search_songs(service, ctx, &songs, options.parallelism, search_cache.as_ref(), filter);
+    let mut suggestions: Vec<Suggestion> = songs
+        .into_iter()
+        .zip(results)
+        .map(|(song, result)| match_suggestion(ctx, options, song, result, Source::Throwback))
+        .collect();
+    check_suggestions(ctx, filter, &mut suggestions);
+    suggestions.retain(|s| s.status == Status::Accepted && s.found.as_ref().is_some_and(|found| !known.contains(found)));
+    suggestions.truncate(THROWBACK_SONGS);
+    info!("Bringing back {} songs from {}-{:02}.", suggestions.len(), year, month);
+    suggestions
+}
+
+// Function to interleave lists, one item of each in turn, the rest of the longer ones at the end
+fn interleave<T>(lists: Vec<Vec<T>>) -> Vec<T> {
+    let mut result = Vec::with_capacity(lists.iter().map(Vec::len).sum());
+    let mut iterators: Vec<std::vec::IntoIter<T>> = lists.into_iter().map(Vec::into_iter).collect();
+    loop {
+        let round: Vec<T> = iterators.iter_mut().filter_map(Iterator::next).collect();
+        if round.is_empty() {
+            return result;
+        }
+        result.extend(round);
+    }
+}
+
+// Function to sum up where the picked songs come from, e.g. "6 from the LLM, 4 from Last.fm"
+fn source_summary(suggestions: &[Suggestion]) -> String {
+    let parts: Vec<String> = [Source::Llm, Source::Spotify, Source::LastFm, Source::Throwback]
+        .into_iter()
+        .filter_map(|source| {
+            let count = suggestions.iter().filter(|s| s.status == Status::Accepted && s.source == source).count();
+            (count > 0).then(|| format!("{} from {}", count, source.label()))
+        })
+        .collect();
+    parts.join(", ")
+}
+
+// Most songs of earlier runs listed in the prompt, the most recent ones
+const PAST_SONGS_IN_PROMPT: usize = 100;
+
+// Artists listed in the prompt for the tracks of a seed that are left out of it
+const LEFT_OUT_ARTISTS: usize = 20;
+
+// Tokens of the context window kept for the LLM's answer, per song asked for and in all
+const ANSWER_TOKENS_PER_SONG: usize = 30;
+const ANSWER_TOKENS_MARGIN: usize = 500;
+
+// Function to split a playlist seed into seeds of at most `chunk_size` tracks each, for --chunk-size
+// Blends, descriptions and seeds that aren't too long are left whole.
+fn seed_chunks(seeds: &[PlaylistState], chunk_size: Option<usize>) -> Vec<Vec<PlaylistState>> {
+    match (seeds, chunk_size) {
+        ([seed], Some(size)) if size > 0 && seed.brief.is_none() && seed.tracks.len() > size => seed
+            .tracks
+            .chunks(size)
+            .map(|tracks| vec![PlaylistState { tracks: tracks.to_vec(), length: tracks.len(), ..seed.clone() }])
+            .collect(),
+        _ => vec![seeds.to_vec()],
+    }
+}
+
+// Function to ask the LLM for suggestions for every chunk of a seed at the same time, given the conversation of each,
+// returning one list per chunk that answered
+// A chunk that fails is only reported, the others still make up the pool.
+fn chunk_suggestions(
+    ctx: &Context,
+    service: &dyn MusicService,
+    options: &Options,
+    api_key: &str,
+    conversations: Vec<Vec<Message>>,
+    filter: &TrackFilter,
+) -> Vec<Vec<Suggestion>> {
+    info!("Asking for suggestions for the {} chunks of the seed.", conversations.len());
+    thread::scope(|scope| {
+        let requests: Vec<_> = conversations
+            .into_iter()
+            .map(|conversation| scope.spawn(move || find_conversation_suggestions(ctx, service, options, api_key, conversation, filter)))
+            .collect();
+        requests
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, request)| match request.join() {
+                Ok(Ok(suggestions)) => Some(suggestions),
+                Ok(Err(e)) => {
+                    warn!("Chunk {} of the seed gave no suggestions: {}", index + 1, e);
+                    None
+                },
+                Err(_) => {
+                    warn!("Chunk {} of the seed gave no suggestions", index + 1);
+                    None
+                },
+            })
+            .collect()
+    })
+}
+
+// Function to tell which song a suggestion is for --ensemble, from the title and main artist the model gave
+fn agreement_key(suggestion: &Suggestion) -> String {
+    song_key(&suggestion.song.name, &suggestion.song.artist)
+}
+
+// Function to rank the songs several models proposed by how many of them proposed each, for --ensemble
+// Each list is the answer of one model, where a song counts once. The most proposed songs come first, ties in their best place
+// in the answers; those proposed by fewer than `min_agreement` models are left out.
+fn merge_by_agreement(answers: Vec<Vec<Suggestion>>, min_agreement: usize) -> Vec<Suggestion> {
+    // Each song with the number of models that proposed it and its best place
+    let mut merged: Vec<(Suggestion, usize, usize)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for answer in answers {
+        let mut seen = HashSet::new();
+        for (place, suggestion) in answer.into_iter().enumerate() {
+            let key = agreement_key(&suggestion);
+            if !seen.insert(key.clone()) {
+                continue;
+            }
+            match positions.get(&key) {
+                Some(position) => {
+                    merged[*position].1 += 1;
+                    merged[*position].2 = merged[*position].2.min(place);
+                },
+                None => {
+                    positions.insert(key, merged.len());
+                    merged.push((suggestion, 1, place));
+                },
+            }
+        }
+    }
+    merged.retain(|(_, models, _)| *models >= min_agreement);
+    merged.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(&b.2)));
+    merged.into_iter().map(|(suggestion, _, _)| suggestion).collect()
+}
+
+// Function to build the context and API key of an [[ensemble]] model, returning them with the name it is shown with
+// What the entry doesn't set is taken from the configured LLM, except its price.
+pub fn provider_context(ctx: &Context, provider: &ProviderSettings, api_key: &str) -> (String, Context, String) {
+    let config = Config {
+        llm_api_url: provider.llm_api_url.clone().unwrap_or_else(|| ctx.config.llm_api_url.clone()),
+        llm_model: provider.llm_model.clone(),
+        llm_price_per_million_tokens: provider.llm_price_per_million_tokens,
+        ..ctx.config.clone()
+    };
+    let name = provider.name.clone().unwrap_or_else(|| provider.llm_model.clone());
+    let key = provider.llm_client_secret.clone().unwrap_or_else(|| api_key.to_string());
+    (name, Context { client: ctx.client.clone(), config, access_token: ctx.access_token.clone() }, key)
+}
+
+// Function to ask the configured model and those of [[ensemble]] for suggestions at the same time, for --ensemble
+// How many songs of each model another one proposed too is printed, then the answers are merged by agreement.
+// Models that fail are only reported, the run fails when none answered.
+fn ensemble_suggestions(
+    ctx: &Context,
+    service: &dyn MusicService,
+    options: &Options,
+    api_key: &str,
+    conversation: Vec<Message>,
+    filter: &TrackFilter,
+) -> Result<Vec<Suggestion>, PilotError> {
+    let others: Vec<(String, Context, String)> = ctx.config.ensemble.iter().map(|provider| provider_context(ctx, provider, api_key)).collect();
+    let mut models: Vec<(&str, &Context, &str)> = vec![(ctx.config.llm_model.as_str(), ctx, api_key)];
+    models.extend(others.iter().map(|(name, ctx, key)| (name.as_str(), ctx, key.as_str())));
+    info!("Asking {} models for suggestions.", models.len());
+    let answers: Vec<(&str, Result<Vec<Suggestion>, PilotError>)> = thread::scope(|scope| {
+        let requests: Vec<_> = models
+            .iter()
+            .map(|(name, ctx, key)| {
+                let conversation = conversation.clone();
+                (*name, scope.spawn(move || find_conversation_suggestions(ctx, service, options, key, conversation, filter)))
+            })
+            .collect();
+        requests.into_iter().map(|(name, request)| (name, request.join().unwrap_or_else(|_| Err("The request failed".into())))).collect()
+    });
+
+    let mut lists: Vec<(&str, Vec<Suggestion>)> = Vec::new();
+    let mut last_error = None;
+    for (name, answer) in answers {
+        match answer {
+            Ok(suggestions) => lists.push((name, suggestions)),
+            Err(e) => {
+                warn!("{} gave no suggestions: {}", name, e);
+                last_error = Some(e);
+            },
+        }
+    }
+    if lists.is_empty() {
+        return Err(last_error.unwrap_or_else(|| "No model gave suggestions".into()));
+    }
+    let keys: Vec<HashSet<String>> = lists.iter().map(|(_, suggestions)| suggestions.iter().map(agreement_key).collect()).collect();
+    for (index, (name, _)) in lists.iter().enumerate() {
+        let shared = keys[index].iter().filter(|key| keys.iter().enumerate().any(|(other, keys)| other != index && keys.contains(*key))).count();
+        println!("{}: {} songs, {} of them also proposed by another model.", name, keys[index].len(), shared);
+    }
+    Ok(merge_by_agreement(lists.into_iter().map(|(_, suggestions)| suggestions).collect(), options.min_agreement.unwrap_or(1)))
+}
+
+// Function to fit the prompt in the model's context window, leaving out seed tracks until it does
+// `build` writes the prompt with at most that many tracks per seed; `earlier` is the conversation sent before it.
+// A warning tells how many tracks had to be left out.
+fn fit_prompt(
+    config: &Config,
+    number: usize,
+    seeds: &[PlaylistState],
+    max_seed_tracks: Option<usize>,
+    earlier: &[Message],
+    build: impl Fn(Option<usize>) -> String,
+) -> Result<String, PilotError> {
+    let reserved = estimate_tokens(&config.system_prompt) + estimate_message_tokens(earlier) + number * ANSWER_TOKENS_PER_SONG + ANSWER_TOKENS_MARGIN;
+    let budget = config.llm_context_tokens.saturating_sub(reserved);
+    let longest = seeds.iter().map(|seed| seed.tracks.len().max(seed.songs.len())).max().unwrap_or(0);
+    let wanted = max_seed_tracks.unwrap_or(longest).min(longest);
+    let mut limit = wanted;
+    let mut prompt = build(max_seed_tracks);
+    let mut tokens = estimate_tokens(&prompt);
+    while tokens > budget && limit > 0 {
+        // Tracks cost about the same, the next try scales the limit down with some room to spare
+        limit = ((limit as f64 * budget as f64 / tokens as f64 * 0.9) as usize).min(limit - 1);
+        prompt = build(Some(limit));
+        tokens = estimate_tokens(&prompt);
+    }
+    if tokens > budget {
+        return Err(format!(
+            "The prompt takes about {} tokens even without seed tracks, more than the {} left in the context window of {} (set llm_context_tokens if it is larger)",
+            tokens, budget, config.llm_model
+        )
+        .into());
+    }
+    if limit < wanted {
+        warn!(
+            "The seed is too long for the context window of {} ({} tokens): only {} tracks of each seed are sent, the others are summed up by their artists.",
+            config.llm_model, config.llm_context_tokens, limit
+        );
+    }
+    Ok(prompt)
+}
+
+// The seed playlist of a comparison of the models, with the prompt every model gets
+pub struct BenchSeed {
+    pub name: String,
+    pub tracks: Vec<Track>,
+    pub prompt: String,
+}
+
+// Function to read a seed playlist and write the prompt asking for `number` songs like it, for bench
+// The prompt is the plain one of a run on the playlist, without the rules of the filters or of earlier runs, so it is the same
+// whatever was added since.
+pub fn bench_seed(ctx: &Context, options: &Options, playlist_id: &str, number: usize) -> Result<BenchSeed, PilotError> {
+    let service = music_service(ctx.config.service);
+    let seeds = [read_playlist(ctx, service.as_ref(), playlist_id)];
+    if seeds[0].tracks.is_empty() {
+        return Err(format!("'{}' has no tracks to compare the models on", seeds[0].name).into());
+    }
+    let prompt = fit_prompt(&ctx.config, number, &seeds, options.max_seed_tracks, &[], |max_seed_tracks| {
+        build_prompt(ctx, number as i32, &seeds, max_seed_tracks)
+    })?;
+    let [PlaylistState { name, tracks, .. }] = seeds;
+    Ok(BenchSeed { name, tracks, prompt })
+}
+
+// Most rated songs of each kind given to the LLM as examples, the most recently rated ones
+const RATED_SONGS_IN_PROMPT: usize = 30;
+
+// Number of Spotify recommendations asked for per song with --no-llm
+const NO_LLM_RECOMMENDATIONS: usize = 2;
+
+// Number of times the LLM is asked for replacements of the songs that couldn't be used
+const REFILL_ROUNDS: usize = 3;
+
+// Choice of the songs to add among the matched suggestions
+#[derive(Clone)]
+struct Picker<'a> {
+    // Songs already in the playlists, and those picked so far
+    duplicates: Duplicates,
+    // Songs added to or rejected for the target by earlier runs
+    past: Duplicates,
+    blacklist: &'a Blacklist,
+    max_per_artist: Option<usize>,
+    // Songs picked so far by each main artist, by cleaned name
+    per_artist: HashMap<String, usize>,
+    // Number of songs after which the others are surplus
+    limit: usize,
+    uris: Vec<String>,
+}
+
+impl Picker<'_> {
+    // Function to pick the accepted suggestions that can be added, and mark the others with the reason they are skipped
+    fn pick(&mut self, suggestions: &mut [Suggestion]) {
+        for suggestion in suggestions.iter_mut().filter(|s| s.status == Status::Accepted) {
+            let Some(found) = &suggestion.found else { continue };
+            let artist = found.artists.first().map(|artist| clean_name(artist)).unwrap_or_default();
+            let artist_count = self.per_artist.get(&artist).copied().unwrap_or(0);
+            if self.blacklist.blocks(found) {
+                info!("Skipped '{} - {}': it is blacklisted.", found.name, found.artists.join(", "));
+                suggestion.status = Status::Excluded;
+            } else if self.past.contains(found) {
+                info!("Skipped '{} - {}': an earlier run already suggested it.", found.name, found.artists.join(", "));
+                suggestion.status = Status::Previous;
+            } else if self.uris.len() >= self.limit {
+                suggestion.status = Status::Surplus;
+            } else if self.max_per_artist.is_some_and(|max| artist_count >= max) {
+                info!("Skipped '{} - {}': enough songs by this artist.", found.name, found.artists.join(", "));
+                suggestion.status = Status::ArtistLimit;
+            } else if self.duplicates.insert(found) {
+                self.uris.push(found.uri.clone());
+                self.per_artist.insert(artist, artist_count + 1);
+            } else {
+                info!("Skipped '{} - {}': you already have it.", found.name, found.artists.join(", "));
+                suggestion.status = Status::Duplicate;
+            }
+        }
+    }
+
+    // Function to list the artists that reached the per-artist cap
+    fn full_artists(&self, suggestions: &[Suggestion]) -> Vec<String> {
+        let Some(max) = self.max_per_artist else { return Vec::new() };
+        let mut artists: Vec<String> = Vec::new();
+        for found in suggestions.iter().filter(|s| s.status == Status::ArtistLimit).filter_map(|s| s.found.as_ref()) {
+            let Some(artist) = found.artists.first() else { continue };
+            if self.per_artist.get(&clean_name(artist)).is_some_and(|count| *count >= max) && !artists.contains(artist) {
+                artists.push(artist.clone());
+            }
+        }
+        artists
+    }
+
+    // Function to get replacements from `ask` for the songs that couldn't be used, until `wanted` songs are picked
+    // `ask` is given the prompt asking for them; it is called at most REFILL_ROUNDS times.
+    fn refill(
+        &mut self,
+        prompt: &str,
+        wanted: usize,
+        filter: &TrackFilter,
+        suggestions: &mut Vec<Suggestion>,
+        ask: &dyn Fn(&str) -> Result<Vec<Suggestion>, PilotError>,
+    ) -> Result<(), PilotError> {
+        let mut round = 0;
+        while round < REFILL_ROUNDS && self.uris.len() < wanted && llm_available() {
+            round += 1;
+            let _refill = info_span!("refill", round).entered();
+            let missing = wanted - self.uris.len();
+            info!("Asking for {} more songs to replace those that were left out ({}/{}).", missing, round, REFILL_ROUNDS);
+            let refill_prompt = refill_prompt(prompt, missing, suggestions, &self.full_artists(suggestions), filter);
+            let start = suggestions.len();
+            suggestions.extend(ask(&refill_prompt)?);
+            self.pick(&mut suggestions[start..]);
+        }
+        Ok(())
+    }
+}
+
+// Function to ask again for `missing` songs, none of which was suggested already or is by an artist of `full_artists`
+// The LLM is told why each unused song was left out, so it doesn't make the same mistake again.
+// Tracks left out for their tempo are given with it, so the replacements fit the --bpm cadence.
+fn refill_prompt(prompt: &str, missing: usize, suggestions: &[Suggestion], full_artists: &[String], filter: &TrackFilter) -> String {
+    let unused: Vec<String> = suggestions
+        .iter()
+        .filter_map(|s| {
+            let tempo = s.found.as_ref().and_then(|found| found.tempo).filter(|tempo| s.status == Status::Filtered && !filter.allows_tempo(*tempo));
+            let reason = match tempo {
+                Some(tempo) => format!("its tempo is {:.0} BPM", tempo),
+                None => rejection_reason(s.status)?.to_string(),
+            };
+            Some(format!("{} by {} ({})", s.song.name, s.song.artist, reason))
+        })
+        .collect();
+    let suggested: Vec<String> = suggestions.iter().map(|s| format!("{} by {}", s.song.name, s.song.artist)).collect();
+    let mut refill = format!(
+        "{prompt} These songs you gave me can't be used: {}. Give me {missing} other songs instead, none of which is one of these: {}.",
+        unused.join(", "),
+        suggested.join(", ")
+    );
+    if !full_artists.is_empty() {
+        refill.push_str(&format!(" Don't give me songs by {}, there are enough of them.", full_artists.join(", ")));
+    }
+    refill
+}
+
+// Function to write the picked (or added) songs the way the LLM answers, as its turn in the conversation
+fn picked_answer(suggestions: &[Suggestion]) -> String {
+    let songs = suggestions
+        .iter()
+        .filter(|s| matches!(s.status, Status::Accepted | Status::Added) && s.found.is_some())
+        .map(|s| s.song.clone())
+        .collect();
+    serde_json::to_string(&LlmSongsResponse { songs }).unwrap_or_default()
+}
+
+// Function to make a turn of the conversation with the LLM
+fn turn(role: &str, content: String) -> Message {
+    Message { role: role.to_string(), content }
+}
+
+// Function to turn each list of picked songs and the follow-up it got into turns of the conversation
+fn refinement_turns(turns: &[(String, String)], wanted: usize) -> Vec<Message> {
+    let mut conversation = Vec::new();
+    for (answer, follow_up) in turns {
+        conversation.push(turn("assistant", answer.clone()));
+        conversation.push(turn(
+            "user",
+            format!(
+                "Change the songs: {}. Give me a new list of {} songs with this change, in the same JSON format.",
+                follow_up.trim_end_matches('.'),
+                wanted
+            ),
+        ));
+    }
+    conversation
+}
+
+// Function to build the conversation refining the suggestions: the earlier runs, the prompt, then the refinement turns
+fn refinement_conversation(past: &[Message], prompt: &str, turns: &[(String, String)], wanted: usize) -> Vec<Message> {
+    let mut conversation = past.to_vec();
+    conversation.push(turn("user", prompt.to_string()));
+    conversation.extend(refinement_turns(turns, wanted));
+    conversation
+}
+
+// Function to read the conversation earlier runs had with the LLM about the target, forgetting it first with --forget-conversation
+// New playlists have no earlier runs, and a history failure only means this run starts cold.
+fn past_conversation(ctx: &Context, options: &Options, target: &Target) -> Vec<Message> {
+    let Target::Existing(playlist) = target else { return Vec::new() };
+    if !options.forget_conversation && ctx.config.conversation_runs == 0 {
+        return Vec::new();
+    }
+    let history = match History::open() {
+        Ok(history) => history,
+        Err(e) => {
+            warn!("Could not read the earlier conversation with the LLM, this run starts cold: {}", e);
+            return Vec::new();
+        },
+    };
+    if options.forget_conversation {
+        if let Err(e) = history.forget_conversation(&playlist.id) {
+            warn!("Could not forget the earlier conversation with the LLM: {}", e);
+        }
+        return Vec::new();
+    }
+    history.conversation(&playlist.id, ctx.config.conversation_runs).unwrap_or_else(|e| {
+        warn!("Could not read the earlier conversation with the LLM, this run starts cold: {}", e);
+        Vec::new()
+    })
+}
+
+// Function to tell the LLM what became of its suggestions, the last turn of the conversation a run saves
+fn run_outcome(suggestions: &[Suggestion]) -> String {
+    let added = suggestions.iter().filter(|s| s.status == Status::Added).count();
+    let rejected: Vec<String> =
+        suggestions.iter().filter(|s| s.status == Status::Rejected).map(|s| format!("{} by {}", s.song.name, s.song.artist)).collect();
+    let mut outcome = format!("I added {} of these songs to the playlist.", added);
+    if !rejected.is_empty() {
+        outcome.push_str(&format!(" I turned down {}.", rejected.join(", ")));
+    }
+    outcome
+}
+
+// Function to save the conversation of a run with the LLM, ending with the songs it added and what became of the others
+fn save_conversation(playlist_id: &str, mut conversation: Vec<Message>, suggestions: &[Suggestion]) {
+    conversation.push(turn("assistant", picked_answer(suggestions)));
+    conversation.push(turn("user", run_outcome(suggestions)));
+    if let Err(e) = History::open().and_then(|history| history.save_conversation(playlist_id, &conversation)) {
+        warn!("Could not save the conversation with the LLM: {}", e);
+    }
+}
+
+// Function to show the picked songs and read the user's follow-up, None when they accept the songs as they are
+fn ask_follow_up(suggestions: &[Suggestion]) -> Option<String> {
+    println!("Picked songs:");
+    for (index, found) in suggestions.iter().filter(|s| s.status == Status::Accepted).filter_map(|s| s.found.as_ref()).enumerate() {
+        println!("  {}. {} - {}", index + 1, found.name, found.artists.join(", "));
+    }
+    println!("Type a follow-up to change them (e.g. \"more upbeat, fewer ballads, nothing before 2015\"), or press Enter to accept them:");
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).ok()?;
+    let answer = answer.trim();
+    (!answer.is_empty()).then(|| answer.to_string())
+}
+
+// Function to explain to the LLM why a suggestion wasn't used, None when it was
+fn rejection_reason(status: Status) -> Option<&'static str> {
+    match status {
+        Status::NotFound => Some("not on the streaming service"),
+        Status::Duplicate => Some("already in the playlist"),
+        Status::Rejected => Some("rejected by the user"),
+        Status::Excluded => Some("blacklisted"),
+        Status::ArtistLimit => Some("too many songs by this artist"),
+        Status::Filtered => Some("doesn't meet the filters"),
+        Status::Previous => Some("suggested in an earlier run"),
+        Status::Added | Status::Accepted | Status::Review | Status::Failed | Status::Undone | Status::Surplus => None,
+    }
+}
+
+// Function to apply the filters that need more than the matched track to the accepted suggestions
+fn check_suggestions(ctx: &Context, filter: &TrackFilter, suggestions: &mut [Suggestion]) {
+    check_availability(ctx, filter, suggestions);
+    check_titles(filter, suggestions);
+    check_genres(ctx, filter, suggestions);
+    check_features(ctx, filter, suggestions);
+}
+
+// Function to leave out the accepted suggestions whose audio features don't meet the filters
+// Tracks Spotify has no features for are left out too, but nothing is when the features can't be read at all.
+fn check_features(ctx: &Context, filter: &TrackFilter, suggestions: &mut [Suggestion]) {
+    if !filter.needs_features() {
+        return;
+    }
+    let uris: Vec<String> = suggestions
+        .iter()
+        .filter(|s| s.status == Status::Accepted)
+        .filter_map(|s| s.found.as_ref().map(|found| found.uri.clone()))
+        .collect();
+    let features = match get_audio_features(ctx, &uris) {
+        Ok(features) => features,
+        Err(e) => {
+            warn!("Could not check the audio features of the suggestions: {}", e);
+            return;
+        },
+    };
+
+    for suggestion in suggestions.iter_mut().filter(|s| s.status == Status::Accepted) {
+        let Some(found) = &mut suggestion.found else { continue };
+        let id = found.uri.strip_prefix("spotify:track:").unwrap_or(&found.uri);
+        let track_features = features.get(id);
+        if let Some(features) = track_features.filter(|_| filter.bpm.is_some()) {
+            // A track at half or double time fits the cadence, it is counted at the tempo it is felt at
+            let cadence = filter.cadence(features.tempo);
+            if let Some(cadence) = cadence.filter(|cadence| *cadence != features.tempo) {
+                info!("'{} - {}' is at {:.0} BPM, which fits as {:.0} BPM.", found.name, found.artists.join(", "), features.tempo, cadence);
+            }
+            found.tempo = Some(cadence.unwrap_or(features.tempo));
+        }
+        if !track_features.is_some_and(|features| filter.allows_features(features)) {
+            info!("Skipped '{} - {}': it doesn't sound right for the filters.", found.name, found.artists.join(", "));
+            suggestion.status = Status::Filtered;
+        }
+    }
+}
+
+// Function to make sure the accepted suggestions can be played in the configured market, right before they are picked
+// Relinked tracks are swapped for the version of the market, and unplayable ones for another version found by searching
+// again; those without one are left out. Matches can come from the search cache, and availability changes over time.
+fn check_availability(ctx: &Context, filter: &TrackFilter, suggestions: &mut [Suggestion]) {
+    let Some(market) = ctx.config.market.as_deref().filter(|_| ctx.config.service == Service::Spotify) else { return };
+    let uris: Vec<String> = suggestions
+        .iter()
+        .filter(|s| s.status == Status::Accepted)
+        .filter_map(|s| s.found.as_ref().map(|found| found.uri.clone()))
+        .collect();
+    if uris.is_empty() {
+        return;
+    }
+    let versions = match playable_versions(ctx, &uris, market) {
+        Ok(versions) => versions,
+        Err(e) => {
+            warn!("Could not check that the suggestions can be played in your market: {}", e);
+            return;
+        },
+    };
+
+    for suggestion in suggestions.iter_mut().filter(|s| s.status == Status::Accepted) {
+        let Some(found) = &mut suggestion.found else { continue };
+        match versions.get(&found.uri) {
+            Some(Some(uri)) if *uri != found.uri => {
+                info!("'{} - {}' is relinked to {} in your market.", found.name, found.artists.join(", "), uri);
+                found.uri = uri.clone();
+            },
+            Some(None) => match search_song(ctx, &suggestion.song, filter) {
+                Ok(other) if other.uri != found.uri && other.confidence >= ctx.config.min_confidence => {
+                    info!("'{} - {}' can't be played in your market, {} is used instead.", found.name, found.artists.join(", "), other.uri);
+                    *found = other;
+                },
+                _ => {
+                    info!("Skipped '{} - {}': it can't be played in your market.", found.name, found.artists.join(", "));
+                    suggestion.status = Status::Filtered;
+                },
+            },
+            _ => {},
+        }
+    }
+}
+
+// Function to leave out the accepted suggestions whose title gives away a kind of song to avoid
+fn check_titles(filter: &TrackFilter, suggestions: &mut [Suggestion]) {
+    for suggestion in suggestions.iter_mut().filter(|s| s.status == Status::Accepted) {
+        let Some(found) = &suggestion.found else { continue };
+        if let Some(kind) = filter.avoided_title(&found.name) {
+            info!("Skipped '{} - {}': it looks like {}.", found.name, found.artists.join(", "), kind);
+            suggestion.status = Status::Filtered;
+        }
+    }
+}
+
+// Function to leave out the accepted suggestions whose artists are tagged with an excluded genre, or one of a kind to avoid
+// Genre tags belong to artists on Spotify, so every artist of a track is checked; other services only check titles.
+fn check_genres(ctx: &Context, filter: &TrackFilter, suggestions: &mut [Suggestion]) {
+    let avoids = !filter.avoid.is_empty() && ctx.config.service == Service::Spotify;
+    if filter.exclude_genres.is_empty() && filter.seed_genres.is_empty() && !avoids {
+        return;
+    }
+    let mut artist_ids: Vec<String> = suggestions
+        .iter()
+        .filter(|s| s.status == Status::Accepted)
+        .filter_map(|s| s.found.as_ref())
+        .flat_map(|found| found.artist_ids.iter().cloned())
+        .collect();
+    artist_ids.sort();
+    artist_ids.dedup();
+    let genres = match get_artist_genres(ctx, &artist_ids) {
+        Ok(genres) => genres,
+        Err(e) => {
+            warn!("Could not check the genres of the suggestions: {}", e);
+            return;
+        },
+    };
+
+    for suggestion in suggestions.iter_mut().filter(|s| s.status == Status::Accepted) {
+        let Some(found) = &suggestion.found else { continue };
+        let tags: Vec<String> = found.artist_ids.iter().filter_map(|id| genres.get(id)).flatten().cloned().collect();
+        if let Some(genre) = filter.excluded_genre(&tags) {
+            info!("Skipped '{} - {}': tagged {}.", found.name, found.artists.join(", "), genre);
+            suggestion.status = Status::Filtered;
+        } else if let Some(kind) = filter.avoided_genre(&tags).filter(|_| avoids) {
+            info!("Skipped '{} - {}': its artist's genres look like {}.", found.name, found.artists.join(", "), kind);
+            suggestion.status = Status::Filtered;
+        } else if !filter.shares_seed_genre(&tags) {
+            info!("Skipped '{} - {}': none of its genres ({}) are the playlist's.", found.name, found.artists.join(", "), tags.join(", "));
+            suggestion.status = Status::Filtered;
+        }
+    }
+}
+
+// Function to describe the user's ratings of earlier suggestions for a playlist, to steer the LLM
+// None when nothing was rated, or when the ratings can't be read.
+fn feedback_prompt(playlist_id: &str) -> Option<String> {
+    let history = History::open().map_err(|e| warn!("Could not read your feedback: {}", e)).ok()?;
+    let describe = |rating: Rating| -> Vec<String> {
+        history
+            .rated_tracks(playlist_id, rating)
+            .unwrap_or_default()
+            .iter()
+            .take(RATED_SONGS_IN_PROMPT)
+            .map(|track| format!("{} by {}", track.name, track.artists))
+            .collect()
+    };
+    let (keepers, misses) = (describe(Rating::Keeper), describe(Rating::Miss));
+    let mut rules = Vec::new();
+    if !keepers.is_empty() {
+        rules.push(format!("Of your earlier suggestions, the user liked: {}; give more songs like these.", keepers.join(", ")));
+    }
+    if !misses.is_empty() {
+        rules.push(format!("The user removed or disliked: {}; avoid songs like these.", misses.join(", ")));
+    }
+    (!rules.is_empty()).then(|| rules.join(" "))
+}
+
+// Reranking of suggestions by how close they sound to the seed tracks, from text embeddings of "title by artist"
+struct Reranker {
+    // Average embedding of the seed tracks
+    centroid: Vec<f64>,
+    min_similarity: Option<f64>,
+    // Embeddings of tracks seen in earlier runs, so the same seed playlist isn't embedded every time
+    cache: Option<EmbeddingCache>,
+}
+
+// Function to describe a song for the embeddings API
+fn embedding_text(name: &str, artists: &str) -> String {
+    format!("{} by {}", name, artists)
+}
+
+impl Reranker {
+    // Function to embed the seed tracks, None (reported) when there are none or the API can't be used
+    fn new(ctx: &Context, api_key: &str, seeds: &[PlaylistState], options: &Options) -> Option<Reranker> {
+        let tracks: Vec<(String, String)> = seeds
+            .iter()
+            .flat_map(|seed| seed.tracks.iter().take(options.max_seed_tracks.unwrap_or(usize::MAX)))
+            .map(|track| {
+                let artists: Vec<&str> = track.artists.iter().map(|a| a.name.as_str()).collect();
+                (track.uri.clone(), embedding_text(&track.name, &artists.join(", ")))
+            })
+            .collect();
+        if tracks.is_empty() {
+            info!("No seed tracks to compare the suggestions with, they are not reranked.");
+            return None;
+        }
+        let cache = if options.no_cache {
+            None
+        } else {
+            EmbeddingCache::open().map_err(|e| warn!("Embedding cache unavailable: {}", e)).ok()
+        };
+        match embed_tracks(ctx, api_key, cache.as_ref(), &tracks) {
+            Ok(vectors) => centroid(&vectors).map(|centroid| Reranker { centroid, min_similarity: options.min_similarity, cache }),
+            Err(e) => {
+                warn!("The suggestions are not reranked: {}", e);
+                None
+            },
+        }
+    }
+
+    // Function to order the accepted suggestions from the closest to the seed to the farthest, leaving out those under the threshold
+    // The other suggestions keep their place after them.
+    fn rerank(&self, ctx: &Context, api_key: &str, suggestions: &mut [Suggestion]) {
+        let tracks: Vec<(String, String)> = suggestions
+            .iter()
+            .filter(|s| s.status == Status::Accepted)
+            .filter_map(|s| s.found.as_ref())
+            .map(|found| (found.uri.clone(), embedding_text(&found.name, &found.artists.join(", "))))
+            .collect();
+        if tracks.is_empty() {
+            return;
+        }
+        let mut similarities = match embed_tracks(ctx, api_key, self.cache.as_ref(), &tracks) {
+            Ok(vectors) => vectors.into_iter().map(|vector| cosine_similarity(&self.centroid, &vector)),
+            Err(e) => {
+                warn!("The suggestions are not reranked: {}", e);
+                return;
+            },
+        };
+
+        let mut scored: Vec<(f64, usize)> = Vec::new();
+        for (index, suggestion) in suggestions.iter_mut().enumerate() {
+            if suggestion.status != Status::Accepted || suggestion.found.is_none() {
+                continue;
+            }
+            let Some(similarity) = similarities.next() else { break };
+            if self.min_similarity.is_some_and(|min| similarity < min) {
+                if let Some(found) = &suggestion.found {
+                    info!("Skipped '{} - {}': too far from the playlist ({:.2}).", found.name, found.artists.join(", "), similarity);
+                }
+                suggestion.status = Status::Filtered;
+            } else {
+                scored.push((similarity, index));
+            }
+        }
+
+        // Closest first, then everything else in its original order
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let ranked: Vec<usize> = scored.iter().map(|(_, index)| *index).collect();
+        let others = (0..suggestions.len()).filter(|index| !ranked.contains(index));
+        let order: Vec<usize> = ranked.iter().copied().chain(others).collect();
+        let reordered: Vec<Suggestion> = order.iter().map(|index| suggestions[*index].clone()).collect();
+        suggestions.clone_from_slice(&reordered);
+    }
+}
+
+// Function to ask the LLM whether each accepted suggestion truly fits the seed, for --verify, leaving out those it flags
+// `summary` is the seed_summary of the seeds. When it can't be asked, every suggestion is kept.
+fn verify_suggestions(ctx: &Context, api_key: &str, summary: &str, suggestions: &mut [Suggestion]) {
+    let checked: Vec<usize> = (0..suggestions.len()).filter(|index| suggestions[*index].status == Status::Accepted && suggestions[*index].found.is_some()).collect();
+    if checked.is_empty() {
+        return;
+    }
+    let listed: Vec<String> = checked
+        .iter()
+        .filter_map(|index| suggestions[*index].found.as_ref())
+        .map(|found| format!("{} by {}", found.name, found.artists.join(", ")))
+        .collect();
+    let misfits = match find_misfits(ctx, api_key, summary, &listed) {
+        Ok(misfits) => misfits,
+        Err(e) => {
+            warn!("The suggestions could not be verified, they are all kept: {}", e);
+            return;
+        },
+    };
+    for (index, reason) in misfits {
+        let suggestion = &mut suggestions[checked[index]];
+        if let Some(found) = &suggestion.found {
+            info!("Skipped '{} - {}': the LLM found it doesn't fit ({}).", found.name, found.artists.join(", "), reason);
+        }
+        suggestion.status = Status::Filtered;
+    }
+}
+
+// Number of songs matched for each song asked for with --judge, for the LLM to choose from
+const JUDGE_CANDIDATES: usize = 2;
+
+// Function to describe the seeds for the --verify and --judge passes: their names, their most present artists and the vibe asked for
+fn seed_summary(seeds: &[PlaylistState], vibe: Option<&str>) -> String {
+    let names: Vec<String> = seeds.iter().map(|seed| format!("'{}'", seed.name)).collect();
+    let tracks: Vec<Track> = seeds.iter().flat_map(|seed| seed.tracks.iter().cloned()).collect();
+    let mut artists = left_out_artists(&tracks);
+    for artist in seeds.iter().flat_map(|seed| &seed.artists) {
+        if !artists.contains(artist) {
+            artists.push(artist.clone());
+        }
+    }
+    let mut summary = format!("Songs are being added to the playlist {}.", names.join(" and "));
+    if !artists.is_empty() {
+        summary.push_str(&format!(" It is mostly made of songs by {}.", artists.join(", ")));
+    }
+    for seed in seeds {
+        match &seed.brief {
+            Some(Brief::Description(description)) => summary.push_str(&format!(" It should be: {}.", description)),
+            Some(Brief::DeepDive { artist, .. }) => summary.push_str(&format!(" It is about the lesser-known songs of {}.", artist)),
+            None => {},
+        }
+    }
+    if let Some(vibe) = vibe {
+        summary.push_str(&format!(" The songs should fit this vibe: {}.", vibe));
+    }
+    summary
+}
+
+// Function to have the LLM pick the best `wanted` of the picked suggestions and order them, for --judge
+// The candidates are shown with their popularity and, on Spotify, the genres of their artists. Those it leaves out are surplus;
+// when it can't be asked, the first ones picked are kept.
+// `summary` is the seed_summary of the seeds.
+fn judge_suggestions(ctx: &Context, api_key: &str, summary: &str, suggestions: &mut [Suggestion], uris: &mut Vec<String>, wanted: usize) {
+    let candidates: Vec<usize> = (0..suggestions.len())
+        .filter(|index| suggestions[*index].status == Status::Accepted)
+        .filter(|index| suggestions[*index].found.as_ref().is_some_and(|found| uris.contains(&found.uri)))
+        .collect();
+    if candidates.len() < 2 {
+        return;
+    }
+    let genres = if ctx.config.service == Service::Spotify {
+        let mut artist_ids: Vec<String> =
+            candidates.iter().filter_map(|index| suggestions[*index].found.as_ref()).flat_map(|found| found.artist_ids.iter().cloned()).collect();
+        artist_ids.sort();
+        artist_ids.dedup();
+        get_artist_genres(ctx, &artist_ids).map_err(|e| warn!("The genres of the suggestions are not shown to the judge: {}", e)).unwrap_or_default()
+    } else {
+        HashMap::new()
+    };
+    let listed: Vec<String> = candidates
+        .iter()
+        .filter_map(|index| suggestions[*index].found.as_ref())
+        .map(|found| {
+            let mut details = Vec::new();
+            if let Some(popularity) = found.popularity {
+                details.push(format!("popularity {}", popularity));
+            }
+            let mut tags: Vec<&str> = found.artist_ids.iter().filter_map(|id| genres.get(id)).flatten().map(|genre| genre.as_str()).collect();
+            tags.sort();
+            tags.dedup();
+            if !tags.is_empty() {
+                details.push(format!("genres: {}", tags.join(", ")));
+            }
+            let details = if details.is_empty() { String::new() } else { format!(" ({})", details.join("; ")) };
+            format!("{} by {}{}", found.name, found.artists.join(", "), details)
+        })
+        .collect();
+
+    // The judge can be a cheaper model than the one that suggested the songs
+    let judge_ctx;
+    let judge = match &ctx.config.judge_model {
+        Some(model) => {
+            judge_ctx = Context {
+                client: ctx.client.clone(),
+                config: Config { llm_model: model.clone(), ..ctx.config.clone() },
+                access_token: ctx.access_token.clone(),
+            };
+            &judge_ctx
+        },
+        None => ctx,
+    };
+    info!("Asking the LLM to pick the best {} of {} songs.", wanted.min(candidates.len()), candidates.len());
+    let picks = match pick_candidates(judge, api_key, summary, &listed, wanted) {
+        Ok(picks) if !picks.is_empty() => picks,
+        Ok(_) => {
+            warn!("The LLM picked none of the songs, the first ones found are kept.");
+            (0..wanted.min(candidates.len())).collect()
+        },
+        Err(e) => {
+            warn!("The songs could not be judged, the first ones found are kept: {}", e);
+            (0..wanted.min(candidates.len())).collect()
+        },
+    };
+
+    let chosen: Vec<usize> = picks.iter().map(|pick| candidates[*pick]).collect();
+    for index in candidates.iter().filter(|index| !chosen.contains(index)) {
+        if let Some(found) = &suggestions[*index].found {
+            info!("Left out '{} - {}': the LLM picked better songs.", found.name, found.artists.join(", "));
+        }
+        suggestions[*index].status = Status::Surplus;
+    }
+    *uris = chosen.iter().filter_map(|index| suggestions[*index].found.as_ref().map(|found| found.uri.clone())).collect();
+    // The picks first, in the judge's order, then everything else in its original order
+    let others = (0..suggestions.len()).filter(|index| !chosen.contains(index));
+    let order: Vec<usize> = chosen.iter().copied().chain(others).collect();
+    let reordered: Vec<Suggestion> = order.iter().map(|index| suggestions[*index].clone()).collect();
+    suggestions.clone_from_slice(&reordered);
+}
+
+// Function to print the result of a write and turn it into the status of the written songs
+fn write_status(result: Result<(), PilotError>) -> Status {
+    match result {
+        Ok(_) => {
+            println!("Successfully added songs to the playlist.");
+            Status::Added
+        },
+        Err(e) => {
+            error!("{}", e);
+            Status::Failed
+        },
+    }
+}
+
+// Function to check that the options of a run work with its service
+// Recommendations, the library, audio features and genres only exist on Spotify. Other services know some of the release dates,
+// explicit ratings and popularity of their tracks, and some can only append to playlists.
+fn check_service_options(options: &Options, service: Service) -> Result<(), PilotError> {
+    if service == Service::Spotify {
+        return Ok(());
+    }
+    let explicit = matches!(service, Service::AppleMusic | Service::Tidal | Service::Deezer);
+    let dated = matches!(service, Service::AppleMusic | Service::Tidal);
+    let popularity = service == Service::Tidal;
+    let position = matches!(service, Service::YtMusic | Service::Tidal);
+    let unsupported = [
+        ("--hybrid", options.hybrid),
+        ("--cover", options.cover),
+        ("--to-queue", options.to_queue),
+        ("--save-to-library", options.save_to_library),
+        ("--library-only", options.library_only),
+        ("--seed recent, top and liked", options.seed.as_ref().is_some_and(|seed| matches!(seed, Seed::Recent | Seed::Top | Seed::Liked))),
+        ("--skip-saved", options.skip_saved),
+        ("--skip-my-playlists", options.skip_my_playlists),
+        ("--replace", options.replace),
+        ("--shuffle-in", options.shuffle_in),
+        ("--no-explicit", options.no_explicit && !explicit),
+        ("--years and --decade", options.years.is_some() && !dated),
+        ("--released-within", options.released_within.is_some() && !dated),
+        ("--min-popularity and --max-popularity", (options.min_popularity.is_some() || options.max_popularity.is_some()) && !popularity),
+        ("--deep-cuts", options.deep_cuts && !popularity),
+        ("--exclude-genres", !options.exclude_genres.is_empty()),
+        ("--match-genres", options.match_genres),
+        ("--instrumental and --acoustic", options.instrumental || options.acoustic),
+        ("--bpm", options.bpm.is_some()),
+        ("--position", options.position.is_some() && !position),
+        ("--added-by", options.added_by.is_some()),
+        ("--no-llm", options.no_llm),
+        ("--arc", options.arc.is_some()),
+    ];
+    let used: Vec<&str> = unsupported.iter().filter(|(_, used)| *used).map(|(name, _)| *name).collect();
+    if used.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Not available with {}: {}", music_service(service).name(), used.join(", ")).into())
+    }
+}
+
+// Function to write the songs to the target, returning their status and the ID of the playlist they went to
+// None means nothing was attempted.
+fn write_songs(
+    ctx: &Context,
+    service: &dyn MusicService,
+    options: &Options,
+    target: &Target,
+    uris: Vec<String>,
+    description: &str,
+) -> (Option<Status>, Option<String>) {
+    match target {
+        // Write to a new playlist, the seed playlists are left untouched
+        Target::New(name) => match service.create_playlist(ctx, name, description) {
+            Ok(created) => {
+                println!("Created playlist '{}': {}", name, created.url);
+                let status = write_status(service.add_to_playlist(ctx, &created.id, uris, None));
+                (Some(status), Some(created.id))
+            },
+            Err(e) => {
+                error!("{}", e);
+                (Some(Status::Accepted), None)
+            },
+        },
+        Target::Existing(playlist) => {
+            if options.replace && !options.unattended && !confirm(&format!("Replace the {} items of the playlist with {} new songs?", playlist.length, uris.len())) {
+                return (None, Some(playlist.id.clone()));
+            }
+
+            // Warn when the playlist was edited elsewhere while the suggestions were being prepared
+            if let Some(expected) = &playlist.snapshot_id {
+                match get_snapshot_id(ctx, &playlist.id) {
+                    Ok(current) if &current != expected => {
+                        warn!("The playlist changed since it was read, the suggestions may not account for those changes.");
+                    },
+                    Ok(_) => {},
+                    Err(e) => warn!("{}", e),
+                }
+            }
+
+            // Back up the playlist first, and leave it untouched if that isn't possible
+            if let Err(e) = service.backup_playlist(ctx, &playlist.id) {
+                error!("Could not back up the playlist, nothing was added: {}", e);
+                return (Some(Status::Accepted), Some(playlist.id.clone()));
+            }
+
+            // If songs are found, add them to the playlist, at the requested place
+            let result = if options.replace {
+                replace_playlist(ctx, &playlist.id, uris)
+            } else if options.shuffle_in {
+                shuffle_into_playlist(ctx, &playlist.id, uris, playlist.length)
+            } else {
+                service.add_to_playlist(ctx, &playlist.id, uris, options.position)
+            };
+            (Some(write_status(result)), Some(playlist.id.clone()))
+        },
+    }
+}
+
+// Function to post the tracks a run added to the configured webhook, a webhook failure doesn't fail the run
+fn post_to_webhook(ctx: &Context, target: &Target, suggestions: &[Suggestion]) {
+    let Some(url) = &ctx.config.webhook_url else { return };
+    if let Err(e) = notify_webhook(&ctx.client, url, target.name(), suggestions) {
+        warn!("{}", e);
+    }
+}
+
+// Function to email the summary of a run when an SMTP server and recipients are set, an email failure doesn't fail the run
+fn email_summary(ctx: &Context, target: &Target, suggestions: &[Suggestion], tokens: u64) {
+    if ctx.config.smtp_host.is_none() || ctx.config.email_to.is_empty() {
+        return;
+    }
+    let (subject, body) = run_summary(target.name(), suggestions, tokens, ctx.config.llm_price_per_million_tokens);
+    match send_email(&ctx.config, &subject, &body) {
+        Ok(()) => println!("Emailed the summary of the run to {}.", ctx.config.email_to.join(", ")),
+        Err(e) => warn!("Could not email the summary of the run: {}", e),
+    }
+}
+
+// Function to write the HTML report of a run when --report is given, a report failure doesn't fail the run
+fn save_report(service: &dyn MusicService, options: &Options, seeds: Vec<(String, usize)>, target: &Target, suggestions: &[Suggestion]) {
+    let Some(path) = &options.report else { return };
+    let report = Report {
+        service: service.name(),
+        seeds,
+        target: target.name().to_string(),
+        dry_run: options.dry_run,
+        suggestions,
+    };
+    match write_report(path, &report) {
+        Ok(()) => println!("Wrote the report of the run to {}.", path),
+        Err(e) => warn!("{}", e),
+    }
+}
+
+// Suggestions of a run, picked from the seeds but not written yet
+struct Picked {
+    ctx: Context,
+    seeds: Vec<PlaylistState>,
+    target: Target,
+    prompt: String,
+    suggestions: Vec<Suggestion>,
+    // Songs to write, in order
+    uris: Vec<String>,
+    listenbrainz_token: Option<String>,
+    tokens_before: u64,
+    // Key of the LLM, to name a new playlist once its songs are known
+    llm_api_key: String,
+    // Turns of the conversation with the LLM this run saves for the next ones, once its songs are written
+    conversation: Vec<Message>,
+    // Unix time the run started, for the audit log
+    started_at: u64,
+}
+
+// Function to tell whether a run only needs an app token: a dry run never writes, and an app token can read public playlists,
+// unless the seed is the user's own listening or the user's playlists are read
+fn read_only(options: &Options) -> bool {
+    options.dry_run && !options.seed.as_ref().is_some_and(Seed::is_personal) && !options.skip_my_playlists
+}
+
+// Function to read the tracks of the playlists the user owns, but the target, for --skip-my-playlists
+fn my_playlists_tracks(ctx: &Context, target_id: Option<&str>) -> Result<Vec<Track>, PilotError> {
+    let user_id = get_current_user_id(ctx)?;
+    let owned: Vec<UserPlaylist> = get_user_playlists(ctx)?
+        .into_iter()
+        .filter(|playlist| playlist.owner.as_ref().is_some_and(|owner| owner.id == user_id) && Some(playlist.id.as_str()) != target_id)
+        .collect();
+    let mut tracks = Vec::new();
+    for playlist in &owned {
+        tracks.extend(spotify_tracks(get_lean_playlist(ctx, &playlist.id)?.tracks.items).0);
+    }
+    info!("Skipping the {} tracks of your {} other playlists.", tracks.len(), owned.len());
+    Ok(tracks)
+}
+
+// Function to add the songs the user already has to the duplicates, as asked with --skip-saved and --skip-my-playlists
+fn skip_known(ctx: &Context, options: &Options, target: &Target, duplicates: &mut Duplicates) {
+    if options.skip_saved {
+        // Saved tracks are private, an app token from a dry run can't read them
+        match get_saved_tracks(ctx) {
+            Ok(saved) => duplicates.extend(&saved),
+            Err(e) => warn!("Could not read your saved tracks, they aren't checked for duplicates: {}", e),
+        }
+    }
+    if options.skip_my_playlists {
+        let target_id = match target {
+            Target::Existing(playlist) => Some(playlist.id.as_str()),
+            Target::New(_) => None,
+        };
+        match my_playlists_tracks(ctx, target_id) {
+            Ok(tracks) => duplicates.extend(&tracks),
+            Err(e) => warn!("Could not read your playlists, they aren't checked for duplicates: {}", e),
+        }
+    }
+}
+
+// Function to fill in the parts of an audit record every run has: when it ran, for which profile, with which arguments
+fn audit_record(service: &dyn MusicService, profile: Option<&str>, started_at: u64) -> AuditRecord {
+    AuditRecord {
+        started_at: iso_date(started_at),
+        finished_at: iso_date(now()),
+        profile: profile.map(str::to_string),
+        args: std::env::args().skip(1).collect(),
+        service: service.name().to_string(),
+        errors: take_problems(),
+        ..AuditRecord::default()
+    }
+}
+
+// Function to append the record of a finished run to the audit log, when there is one, with the snapshot the run left its playlist at
+fn audit_run(ctx: &Context, record: AuditRecord) {
+    let Some(path) = &ctx.config.audit_log else { return };
+    let snapshot_id = match (&record.playlist_id, !record.dry_run && ctx.config.service == Service::Spotify) {
+        (Some(playlist_id), true) => get_snapshot_id(ctx, playlist_id).map_err(|e| warn!("Could not read the snapshot of the playlist: {}", e)).ok(),
+        _ => None,
+    };
+    // The warning of a failed snapshot belongs to the run too
+    let mut errors = record.errors;
+    errors.extend(take_problems());
+    if let Err(e) = append_record(path, &AuditRecord { snapshot_id, errors, ..record }) {
+        warn!("{}", e);
+    }
+}
+
+// Function to suggest songs from the seed playlists, picking those that can be written to the target
+// The target is chosen once the seeds are read, so it can be one of them or be named after them. A run that fails is recorded in
+// the audit log, when there is one.
+fn pick_suggestions(
+    client: Client,
+    config: Config,
+    service: &dyn MusicService,
+    options: &Options,
+    // Token of an authorization shared by several runs, the service is authorized when None
+    access_token: Option<String>,
+    read_seeds: &dyn Fn(&Context) -> Result<Vec<PlaylistState>, PilotError>,
+    target: &dyn Fn(&Context, &[PlaylistState]) -> Target,
+) -> Result<Picked, PilotError> {
+    let (audit_log, profile) = (config.audit_log.clone(), config.profile.clone());
+    let started_at = now();
+    clear_problems();
+    let picked = pick_new_suggestions(client, config, service, options, access_token, read_seeds, target);
+    if let (Err(e), Some(path)) = (&picked, &audit_log) {
+        let record = AuditRecord { failure: Some(e.to_string()), ..audit_record(service, profile.as_deref(), started_at) };
+        if let Err(e) = append_record(path, &record) {
+            warn!("{}", e);
+        }
+    }
+    picked.map(|picked| Picked { started_at, ..picked })
+}
+
+// Function to do the work of pick_suggestions
+fn pick_new_suggestions(
+    client: Client,
+    config: Config,
+    service: &dyn MusicService,
+    options: &Options,
+    access_token: Option<String>,
+    read_seeds: &dyn Fn(&Context) -> Result<Vec<PlaylistState>, PilotError>,
+    target: &dyn Fn(&Context, &[PlaylistState]) -> Target,
+) -> Result<Picked, PilotError> {
+    check_service_options(options, config.service)?;
+    if options.no_llm && (options.ensemble || options.verify || options.judge || options.rerank || options.refine) {
+        return Err(PilotError::Usage("--no-llm doesn't ask the LLM, it can't be used with --ensemble, --verify, --judge, --rerank or --refine".to_string()));
+    }
+    // The daemon runs several times in the same process, only the tokens of this run are counted
+    let tokens_before = tokens_used();
+    // A run that doesn't ask the LLM doesn't need its key, e.g. when it is missing or out of quota
+    let llm_client_secret = if options.no_llm { String::new() } else { get_llm_secret(&config)? };
+    let listenbrainz_token = if options.listenbrainz {
+        Some(config.listenbrainz_token.clone().ok_or("listenbrainz token not set")?)
+    } else {
+        None
+    };
+    let lastfm_api_key = if options.lastfm {
+        Some(config.lastfm_api_key.clone().ok_or("lastfm api key not set")?)
+    } else {
+        None
+    };
+    if options.ensemble && config.ensemble.is_empty() {
+        return Err(PilotError::Usage("--ensemble needs other models, add [[ensemble]] entries to the config file".to_string()));
+    }
+    if options.ensemble && options.chunk_size.is_some() {
+        return Err(PilotError::Usage("--ensemble asks every model for the whole seed, it can't be used with --chunk-size".to_string()));
+    }
+    let number = match options.count {
+        Some(count) => count as i32,
+        None => ask_number()?,
+    };
+
+    // Authorize with the service and obtain an access token
+    let access_token = match access_token {
+        Some(access_token) => access_token,
+        None => service.access_token(&client, &config, options.headless, read_only(options))?,
+    };
+    let ctx = Context { client, config, access_token };
+
+    let mut seeds = info_span!("read_seeds").in_scope(|| read_seeds(&ctx))?;
+    info_span!("tag_genres").in_scope(|| tag_genres(&ctx, &mut seeds));
+    if options.no_llm && seeds.iter().all(|seed| seed.tracks.is_empty()) {
+        return Err(PilotError::Usage("--no-llm needs seed tracks for Spotify's recommendations, there are none".to_string()));
+    }
+    let target = target(&ctx, &seeds);
+    // The blacklist is both a rule for the LLM and a filter on its answer, it doesn't always follow the rules
+    // The same goes for the filters
+    let blacklist = blacklist(&ctx.config, options);
+    let mut filter = track_filter(options);
+    if options.match_genres {
+        filter.seed_genres = seeds.iter().flat_map(|seed| seed.genres.iter().map(|(genre, _)| genre.clone())).collect();
+    }
+    // Songs earlier runs added to the playlist or that were rejected aren't suggested again, so weekly runs don't repeat themselves
+    let past_songs = match &target {
+        Target::Existing(playlist) => History::open().and_then(|history| history.past_songs(&playlist.id)).unwrap_or_else(|e| {
+            warn!("Could not read the songs of earlier runs: {}", e);
+            Vec::new()
+        }),
+        Target::New(_) => Vec::new(),
+    };
+    let past_rule = (!past_songs.is_empty()).then(|| {
+        let listed: Vec<String> =
+            past_songs.iter().take(PAST_SONGS_IN_PROMPT).map(|song| format!("{} by {}", song.name, song.artists)).collect();
+        format!("Don't suggest any of these songs, they were suggested before: {}.", listed.join(", "))
+    });
+    let feedback_rule = match &target {
+        Target::Existing(playlist) => feedback_prompt(&playlist.id),
+        Target::New(_) => None,
+    };
+    let vibe_rule = options.vibe.as_ref().map(|vibe| format!("The songs should also fit this vibe: {}.", vibe));
+    let arc_rule = match options.arc {
+        Some(Arc::Transition(from, to)) => Some(transition_rule(from, to, number.max(0) as usize)),
+        _ => None,
+    };
+    let deep_cuts_rule = options.deep_cuts.then(|| deep_cuts_rule(&seeds));
+    let rules: Vec<String> =
+        [blacklist.prompt(), filter.prompt(), past_rule, feedback_rule, vibe_rule, arc_rule, deep_cuts_rule].into_iter().flatten().collect();
+    // Earlier runs on the playlist are continued rather than starting cold, when configured
+    let earlier = past_conversation(&ctx, options, &target);
+    // Large playlists are cut down to fit the model rather than failing at the provider, or split in chunks asked separately
+    let chunks = seed_chunks(&seeds, options.chunk_size);
+    let prompts = if options.no_llm {
+        // Nothing is sent, the run records where its songs come from
+        let names: Vec<&str> = seeds.iter().map(|seed| seed.name.as_str()).collect();
+        vec![format!("Spotify's recommendations for {}, without the LLM.", names.join(" and "))]
+    } else {
+        chunks
+            .iter()
+            .map(|chunk| {
+                fit_prompt(&ctx.config, number.max(0) as usize, chunk, options.max_seed_tracks, &earlier, |max_seed_tracks| {
+                    let mut prompt = build_prompt(&ctx, number, chunk, max_seed_tracks);
+                    for rule in &rules {
+                        prompt.push(' ');
+                        prompt.push_str(rule);
+                    }
+                    prompt
+                })
+            })
+            .collect::<Result<Vec<String>, PilotError>>()?
+    };
+    // The first chunk stands for the others when the prompt is recorded, refilled or refined
+    let prompt = &prompts[0];
+    let _suggest = info_span!("suggest", playlist = target.name()).entered();
+    let mut pool = if options.no_llm {
+        Vec::new()
+    } else if prompts.len() > 1 {
+        let conversations = prompts.iter().map(|prompt| refinement_conversation(&earlier, prompt, &[], number.max(0) as usize)).collect();
+        info_span!("ask_llm").in_scope(|| chunk_suggestions(&ctx, service, options, &llm_client_secret, conversations, &filter))
+    } else if options.ensemble {
+        let conversation = refinement_conversation(&earlier, prompt, &[], number.max(0) as usize);
+        vec![info_span!("ask_llm").in_scope(|| ensemble_suggestions(&ctx, service, options, &llm_client_secret, conversation, &filter))?]
+    } else {
+        let conversation = refinement_conversation(&earlier, prompt, &[], number.max(0) as usize);
+        vec![info_span!("ask_llm")
+            .in_scope(|| find_conversation_suggestions(&ctx, service, options, &llm_client_secret, conversation, &filter))?]
+    };
+    if options.no_llm {
+        // Some recommendations are already in the playlist, more are asked for than needed
+        pool.push(recommended_suggestions(&ctx, &seeds, number.max(0) as usize * NO_LLM_RECOMMENDATIONS, &filter));
+    } else if options.hybrid {
+        // Spotify's recommendations always exist, they make up for the songs the LLM made up
+        pool.push(recommended_suggestions(&ctx, &seeds, number.max(0) as usize, &filter));
+    } else if !llm_available() && ctx.config.service == Service::Spotify {
+        // The user already authorized, the run goes on with what Spotify recommends rather than failing
+        warn!("The LLM keeps failing, Spotify's recommendations are used instead.");
+        pool.push(recommended_suggestions(&ctx, &seeds, number.max(0) as usize, &filter));
+    }
+    if let Some(api_key) = &lastfm_api_key {
+        // What people who listen to the seed also listen to, grounded in real listening rather than the LLM's memory
+        pool.push(lastfm_suggestions(&ctx, service, options, api_key, &seeds, number.max(0) as usize, &filter));
+    }
+    if let Some(years) = options.throwback {
+        pool.push(throwback_suggestions(&ctx, service, options, years, &target, &past_songs, &filter));
+    }
+    let mut suggestions = interleave(pool);
+    // Suggestions that sound plausible but are stylistically off end up last, or are left out
+    let summary = seed_summary(&seeds, options.vibe.as_deref());
+    if options.verify {
+        info_span!("verify").in_scope(|| verify_suggestions(&ctx, &llm_client_secret, &summary, &mut suggestions));
+    }
+    let reranker = if options.rerank { Reranker::new(&ctx, &llm_client_secret, &seeds, options) } else { None };
+    if let Some(reranker) = &reranker {
+        info_span!("rerank").in_scope(|| reranker.rerank(&ctx, &llm_client_secret, &mut suggestions));
+    }
+
+    // Drop suggestions that are already in the playlists (or in the library, or suggested twice),
+    // the LLM doesn't always follow the instructions
+    let mut duplicates = Duplicates::new(&[]);
+    for seed in &seeds {
+        duplicates.extend(&seed.tracks);
+        for song in &seed.songs {
+            duplicates.add(None, &song.name, &song.artist);
+        }
+    }
+    if let Target::Existing(playlist) = &target {
+        duplicates.extend(&playlist.tracks);
+    }
+    skip_known(&ctx, options, &target, &mut duplicates);
+    let mut past = Duplicates::new(&[]);
+    for song in &past_songs {
+        past.add(song.uri.as_deref(), &song.name, &song.artists);
+    }
+    let wanted = number.max(0) as usize;
+    let mut picker = Picker {
+        duplicates,
+        past,
+        blacklist: &blacklist,
+        max_per_artist: options.max_per_artist,
+        per_artist: HashMap::new(),
+        // The LLM (or the recommendations of a hybrid run) may give more songs than asked for, --judge keeps more to choose from
+        limit: if options.judge { wanted * JUDGE_CANDIDATES } else { wanted },
+        uris: Vec::new(),
+    };
+    // Refined lists are picked from the same start, not after the songs they replace
+    let fresh_picker = picker.clone();
+    info_span!("pick").in_scope(|| picker.pick(&mut suggestions));
+
+    // Songs that couldn't be used are replaced by asking the LLM for other songs, until the requested number is reached
+    if !options.no_llm {
+        picker.refill(prompt, wanted, &filter, &mut suggestions, &|refill_prompt| {
+            let mut replacements = find_suggestions(&ctx, service, options, &llm_client_secret, refill_prompt, &filter)?;
+            if options.verify {
+                verify_suggestions(&ctx, &llm_client_secret, &summary, &mut replacements);
+            }
+            if let Some(reranker) = &reranker {
+                reranker.rerank(&ctx, &llm_client_secret, &mut replacements);
+            }
+            Ok(replacements)
+        })?;
+    }
+    if options.judge {
+        info_span!("judge").in_scope(|| judge_suggestions(&ctx, &llm_client_secret, &summary, &mut suggestions, &mut picker.uris, wanted));
+    }
+
+    // Each follow-up is a new turn of the conversation, its answer replaces the picked songs until the user accepts them
+    let mut follow_ups = Vec::new();
+    let mut turns = Vec::new();
+    if options.refine && !options.unattended {
+        while let Some(follow_up) = ask_follow_up(&suggestions) {
+            let _refine = info_span!("refine", round = turns.len() + 1).entered();
+            turns.push((picked_answer(&suggestions), follow_up.clone()));
+            let conversation = refinement_conversation(&earlier, prompt, &turns, wanted);
+            let mut refined = match find_conversation_suggestions(&ctx, service, options, &llm_client_secret, conversation, &filter) {
+                Ok(refined) => refined,
+                Err(e) => {
+                    warn!("Could not refine the songs, the previous ones are kept: {}", e);
+                    turns.pop();
+                    continue;
+                },
+            };
+            if options.verify {
+                verify_suggestions(&ctx, &llm_client_secret, &summary, &mut refined);
+            }
+            if let Some(reranker) = &reranker {
+                reranker.rerank(&ctx, &llm_client_secret, &mut refined);
+            }
+            let mut refined_picker = fresh_picker.clone();
+            refined_picker.pick(&mut refined);
+            if options.judge {
+                judge_suggestions(&ctx, &llm_client_secret, &summary, &mut refined, &mut refined_picker.uris, wanted);
+            }
+            if refined_picker.uris.is_empty() {
+                warn!("None of the new songs could be used, the previous ones are kept.");
+                turns.pop();
+                continue;
+            }
+            suggestions = refined;
+            picker = refined_picker;
+            follow_ups.push(follow_up);
+        }
+    }
+    // What this run saves of its conversation: what it was asked, without the whole seed, and how it was refined
+    let mut conversation = vec![turn("user", format!("Suggest {} songs for the playlist '{}'.", wanted, target.name()))];
+    if let Some(vibe) = &options.vibe {
+        conversation[0].content.push_str(&format!(" They should fit this vibe: {}.", vibe));
+    }
+    conversation.extend(refinement_turns(&turns, wanted));
+    let prompt = if follow_ups.is_empty() { prompt.clone() } else { format!("{} Refined with: {}.", prompt, follow_ups.join("; ")) };
+
+    if picker.uris.is_empty() && wanted > 0 && !llm_available() {
+        // Stopping here leaves the playlist and the history untouched
+        return Err(PilotError::LlmUnavailable("The LLM keeps failing and nothing else could be suggested, nothing was changed".to_string()));
+    }
+    if picker.uris.len() < wanted {
+        info!("Only {} of the {} songs asked for could be found.", picker.uris.len(), wanted);
+    }
+    if let Some(arc) = options.arc {
+        order_along_arc(&ctx, arc, &mut picker.uris);
+    }
+    Ok(Picked {
+        ctx,
+        seeds,
+        target,
+        prompt,
+        suggestions,
+        uris: picker.uris,
+        listenbrainz_token,
+        tokens_before,
+        llm_api_key: llm_client_secret,
+        conversation,
+        started_at: now(),
+    })
+}
+
+// Function to tell what a new playlist is about when the LLM names it: blends and generated playlists not named by --create or --name
+fn llm_naming(options: &Options, seeds: &[PlaylistState]) -> Option<String> {
+    if options.create.is_some() || options.name.is_some() || options.no_llm {
+        return None;
+    }
+    match &options.command {
+        Command::Blend(..) => Some(format!("a blend of the playlists '{}' and '{}'", seeds[0].name, seeds[1].name)),
+        Command::Generate(description) => Some(format!("made from this description: {}", description)),
+        Command::Group(_) => {
+            let names: Vec<&str> = seeds.iter().map(|seed| seed.id.trim_start_matches("group:")).collect();
+            Some(format!("shared by {} listening together", names.join(", ")))
+        },
+        _ => None,
+    }
+}
+
+// Function to list the picked songs as "title by artists", for the LLM to name their playlist
+fn picked_songs(suggestions: &[Suggestion]) -> Vec<String> {
+    suggestions
+        .iter()
+        .filter(|s| s.status == Status::Accepted)
+        .filter_map(|s| s.found.as_ref())
+        .map(|found| format!("{} by {}", found.name, found.artists.join(", ")))
+        .collect()
+}
+
+// Function to write a playlist description from the template of the config, {date} being today's date
+fn run_description(template: &str, date: &str, added: usize) -> String {
+    template.replace("{date}", date).replace("{added}", &added.to_string())
+}
+
+// Function to give a Spotify playlist a run added to the description of the config's template, if there is one
+// A description that couldn't be changed doesn't fail the run, the songs are already added.
+fn describe_playlist(ctx: &Context, playlist_id: &str, added: usize) {
+    let Some(template) = &ctx.config.description_template else { return };
+    if added == 0 || ctx.config.service != Service::Spotify {
+        return;
+    }
+    let description = run_description(template, &iso_date(now())[..10], added);
+    if let Err(e) = update_playlist_description(ctx, playlist_id, &description) {
+        warn!("Could not update the playlist description: {}", e);
+    }
+}
+
+// Function to add the picked songs to the queue of the active device, in order
+// The queue stops at the first failure, which is the same for the next songs (no device, no Premium).
+fn queue_songs(ctx: &Context, uris: &[String]) {
+    let mut queued = 0;
+    for uri in uris {
+        if let Err(e) = add_to_queue(ctx, uri) {
+            warn!("Could not add to the queue: {}", e);
+            break;
+        }
+        queued += 1;
+    }
+    println!("Queued {} of {} songs on your active device, nothing was added to the playlist.", queued, uris.len());
+}
+
+// Function to save songs to the user's Liked Songs, a failure only warns
+fn save_songs(ctx: &Context, uris: &[String]) {
+    match save_tracks(ctx, uris) {
+        Ok(()) => println!("Saved {} songs to your Liked Songs.", uris.len()),
+        Err(e) => warn!("Could not save the songs to your Liked Songs: {}", e),
+    }
+}
+
+// Function to get the URIs of the suggestions added to the playlist
+fn added_uris(suggestions: &[Suggestion]) -> Vec<String> {
+    suggestions
+        .iter()
+        .filter(|s| s.status == Status::Added)
+        .filter_map(|s| s.found.as_ref().map(|found| found.uri.clone()))
+        .collect()
+}
+
+// Function to find where the added songs are in a Spotify playlist, and the snapshot of the playlist those positions are in
+// Undoing the run removes these copies only. The songs weren't in the playlist before, so the first copy of each is the added one.
+fn added_positions(ctx: &Context, playlist_id: Option<&str>, suggestions: &[Suggestion]) -> (Option<String>, HashMap<String, usize>) {
+    let uris: HashSet<String> = added_uris(suggestions).into_iter().collect();
+    let Some(playlist_id) = playlist_id.filter(|_| ctx.config.service == Service::Spotify && !uris.is_empty()) else {
+        return (None, HashMap::new());
+    };
+    let playlist = match get_lean_playlist(ctx, playlist_id) {
+        Ok(playlist) => playlist,
+        Err(e) => {
+            warn!("Could not read where the songs were added, undoing the run will remove every copy of them: {}", e);
+            return (None, HashMap::new());
+        },
+    };
+    let mut positions = HashMap::new();
+    // Positions count every item, removed tracks, local files and episodes included
+    for (position, item) in playlist.tracks.items.iter().enumerate() {
+        if let Some(PlaylistItem::Track(track)) = &item.track {
+            if uris.contains(&track.uri) {
+                positions.entry(track.uri.clone()).or_insert(position);
+            }
+        }
+    }
+    (Some(playlist.snapshot_id), positions)
+}
+
+// Number of artists of the added songs the cover prompt names
+const COVER_ARTISTS: usize = 5;
+
+// Function to generate a cover for a playlist the run added to and upload it, a failed cover doesn't fail the run
+fn cover_playlist(ctx: &Context, options: &Options, llm_api_key: &str, playlist_id: &str, name: &str, suggestions: &[Suggestion]) {
+    let mut artists: Vec<String> = Vec::new();
+    for suggestion in suggestions.iter().filter(|s| s.status == Status::Added) {
+        let artist = suggestion.found.as_ref().and_then(|found| found.artists.first()).unwrap_or(&suggestion.song.artist);
+        if artists.len() < COVER_ARTISTS && !artists.contains(artist) {
+            artists.push(artist.clone());
+        }
+    }
+    if artists.is_empty() {
+        return;
+    }
+    info!("Generating a cover for '{}'.", name);
+    let api_key = ctx.config.image_api_key.as_deref().unwrap_or(llm_api_key);
+    match make_cover(ctx, api_key, playlist_id, &cover_prompt(name, &artists, options.vibe.as_deref())) {
+        Ok(()) => println!("Uploaded a new cover for '{}'.", name),
+        Err(e) => warn!("Could not give the playlist a cover: {}", e),
+    }
+}
+
+// Widest the energy curve of a run is drawn, in characters
+const CURVE_WIDTH: usize = 72;
+
+// Function to draw the energy and tempo of the playlist as it will be once the songs are written, marking the new ones
+// Spotify only; nothing is drawn when the new songs go to random places or its audio features can't be read.
+fn show_energy_curve(ctx: &Context, options: &Options, target: &Target, uris: &[String]) {
+    if ctx.config.service != Service::Spotify || uris.is_empty() || options.shuffle_in {
+        return;
+    }
+    let existing: Vec<&str> = match target {
+        Target::Existing(playlist) if !options.replace => playlist.tracks.iter().map(|track| track.uri.as_str()).collect(),
+        _ => Vec::new(),
+    };
+    let at = options.position.unwrap_or(existing.len()).min(existing.len());
+    let mut order: Vec<(&str, bool)> = existing[..at].iter().map(|uri| (*uri, false)).collect();
+    order.extend(uris.iter().map(|uri| (uri.as_str(), true)));
+    order.extend(existing[at..].iter().map(|uri| (*uri, false)));
+
+    let all: Vec<String> = order.iter().map(|(uri, _)| uri.to_string()).collect();
+    let features = match get_audio_features(ctx, &all) {
+        Ok(features) => features,
+        Err(e) => {
+            info!("No energy curve for this run: {}", e);
+            return;
+        },
+    };
+    let track_features: Vec<Option<&AudioFeatures>> =
+        order.iter().map(|(uri, _)| uri.strip_prefix("spotify:track:").and_then(|id| features.get(id))).collect();
+    let energies: Vec<Option<f64>> = track_features.iter().map(|f| f.map(|f| f.energy)).collect();
+    // Tempos are drawn between the slowest and the fastest track of the playlist
+    let known: Vec<f64> = track_features.iter().flatten().map(|f| f.tempo).collect();
+    let slowest = known.iter().copied().fold(f64::MAX, f64::min);
+    let fastest = known.iter().copied().fold(f64::MIN, f64::max);
+    let tempos: Vec<Option<f64>> =
+        track_features.iter().map(|f| f.map(|f| if fastest - slowest < 1.0 { 0.5 } else { (f.tempo - slowest) / (fastest - slowest) })).collect();
+    let marks: String = order
+        .chunks(sparkline_step(order.len(), CURVE_WIDTH))
+        .map(|chunk| if chunk.iter().any(|(_, new)| *new) { '^' } else { ' ' })
+        .collect();
+    println!("Energy {}", sparkline(&energies, CURVE_WIDTH));
+    println!("Tempo  {}", sparkline(&tempos, CURVE_WIDTH));
+    println!("New    {}", marks.trim_end());
+}
+
+// Function to write picked suggestions to their target (or show them in a dry run) and record the run,
+// returning the number of songs added
+fn write_picked(service: &dyn MusicService, options: &Options, picked: Picked) -> Result<usize, PilotError> {
+    let Picked {
+        ctx,
+        seeds,
+        mut target,
+        prompt,
+        mut suggestions,
+        uris: uris_to_add,
+        listenbrainz_token,
+        tokens_before,
+        llm_api_key,
+        conversation,
+        started_at,
+    } = picked;
+    let _write = info_span!("write", playlist = target.name()).entered();
+    let mixed = options.hybrid || options.lastfm || options.throwback.is_some();
+    // A dry run or a run to the queue or to the library only leaves the playlist as it is, it is recorded as a dry run
+    let writes = !options.dry_run && !options.to_queue && !options.library_only;
+    if mixed && !uris_to_add.is_empty() {
+        info!("Picked {}.", source_summary(&suggestions));
+    }
+
+    // Playlist the songs end up in, known once a new one is created
+    let mut target_playlist = match &target {
+        Target::Existing(playlist) => Some(playlist.id.clone()),
+        Target::New(_) => None,
+    };
+    if !options.to_queue && !options.library_only {
+        show_energy_curve(&ctx, options, &target, &uris_to_add);
+    }
+    if options.dry_run {
+        // In a dry run, only show what would have been added
+        let action = if options.replace { "replace the playlist" } else { "be added" };
+        println!("Dry run, {} songs would {}:", uris_to_add.len(), action);
+        let sources: HashMap<&str, Source> = suggestions
+            .iter()
+            .filter(|s| s.status == Status::Accepted)
+            .filter_map(|s| s.found.as_ref().map(|found| (found.uri.as_str(), s.source)))
+            .collect();
+        for uri in &uris_to_add {
+            match sources.get(uri.as_str()) {
+                Some(source) if mixed => println!("  {} (from {})", uri, source.label()),
+                _ => println!("  {}", uri),
+            }
+        }
+    } else if options.to_queue {
+        // Auditioned live, nothing is written: the songs can be suggested again
+        queue_songs(&ctx, &uris_to_add);
+    } else if options.library_only {
+        save_songs(&ctx, &uris_to_add);
+    } else if uris_to_add.is_empty() {
+        println!("No new songs to add.");
+    } else {
+        let seed_names: Vec<&str> = seeds.iter().map(|seed| seed.name.as_str()).collect();
+        let mut description = format!("Songs suggested by PlaylistPilot for {}", seed_names.join(" and "));
+        if let (Target::New(name), Some(about)) = (&mut target, llm_naming(options, &seeds)) {
+            match name_playlist(&ctx, &llm_api_key, &about, &picked_songs(&suggestions)) {
+                Ok(details) => {
+                    *name = details.name;
+                    description = details.description;
+                },
+                Err(e) => warn!("Could not get a name for the playlist from the LLM, it is named '{}': {}", name, e),
+            }
+        }
+        let (outcome, written_to) = write_songs(&ctx, service, options, &target, uris_to_add, &description);
+        target_playlist = written_to;
+        if let Some(status) = outcome {
+            for suggestion in suggestions.iter_mut().filter(|s| s.status == Status::Accepted) {
+                suggestion.status = status;
+            }
+        }
+        if options.save_to_library {
+            save_songs(&ctx, &added_uris(&suggestions));
+        }
+        if let Some(playlist_id) = &target_playlist {
+            describe_playlist(&ctx, playlist_id, suggestions.iter().filter(|s| s.status == Status::Added).count());
+            if options.cover {
+                cover_playlist(&ctx, options, &llm_api_key, playlist_id, target.name(), &suggestions);
+            }
+        }
+        if let Some(token) = &listenbrainz_token {
+            record_on_listenbrainz(&ctx, token, target.name(), &suggestions);
+        }
+        post_to_webhook(&ctx, &target, &suggestions);
+        email_summary(&ctx, &target, &suggestions, tokens_used() - tokens_before);
+    }
+
+    // Keep a record of the run, a history failure shouldn't fail a run that already happened
+    let (snapshot_id, positions) = added_positions(&ctx, target_playlist.as_deref(), &suggestions);
+    let run = Run {
+        profile: ctx.config.profile.as_deref(),
+        playlist_id: target_playlist.as_deref().unwrap_or(&seeds[0].id),
+        playlist_name: target_playlist.is_some().then(|| target.name()),
+        prompt: &prompt,
+        dry_run: !writes,
+        suggestions: &suggestions,
+        service: ctx.config.service,
+        snapshot_id: snapshot_id.as_deref(),
+        positions: &positions,
+    };
+    if let Err(e) = History::open().and_then(|history| history.record(&run)) {
+        warn!("Could not save the run history: {}", e);
+    }
+    audit_run(&ctx, AuditRecord {
+        seeds: seeds.iter().map(|seed| seed.name.clone()).collect(),
+        target: Some(target.name().to_string()),
+        playlist_id: target_playlist.clone(),
+        dry_run: !writes,
+        prompt_hash: Some(fingerprint(&prompt)),
+        suggestions: suggestions.iter().map(|s| AuditSuggestion::new(s, rejection_reason(s.status))).collect(),
+        ..audit_record(service, ctx.config.profile.as_deref(), started_at)
+    });
+    if let (Some(playlist_id), true) = (&target_playlist, writes) {
+        update_feed(&ctx.config, playlist_id);
+        if ctx.config.conversation_runs > 0 {
+            save_conversation(playlist_id, conversation, &suggestions);
+        }
+    }
+    if writes {
+        count_suggestions(&suggestions);
+    }
+    let mut seed_summary: Vec<(String, usize)> = seeds.iter().map(|seed| (seed.name.clone(), seed.tracks.len().max(seed.songs.len()))).collect();
+    // The songs brought back are listed with the seeds, so the report tells where they come from
+    if let Some(years) = options.throwback {
+        let (year, month, _) = civil_date((throwback_month(now(), years).0 / 86400) as i64);
+        let count = suggestions.iter().filter(|s| s.source == Source::Throwback).count();
+        seed_summary.push((format!("Throwbacks from {}-{:02}, {} years ago", year, month, years), count));
+    }
+    save_report(service, options, seed_summary, &target, &suggestions);
+    Ok(suggestions.iter().filter(|s| s.status == Status::Added).count())
+}
+
+// Function to suggest songs from the seed playlists and write them to the target, returning the number of songs added
+fn suggest(
+    client: Client,
+    config: Config,
+    service: &dyn MusicService,
+    options: &Options,
+    read_seeds: &dyn Fn(&Context) -> Result<Vec<PlaylistState>, PilotError>,
+    target: &dyn Fn(&Context, &[PlaylistState]) -> Target,
+) -> Result<usize, PilotError> {
+    let picked = pick_suggestions(client, config, service, options, None, read_seeds, target)?;
+    write_picked(service, options, picked)
+}
+
+// Suggestions for a playlist shown before anything is written, for the web interface to approve or reject
+pub struct Preview {
+    picked: Picked,
+    options: Options,
+}
+
+impl Preview {
+    // Function to suggest songs for the configured playlist without writing them, low confidence matches are left for review
+    // The token of an earlier authorization is used when given.
+    pub fn new(client: Client, config: Config, options: &Options, access_token: Option<String>) -> Result<Preview, PilotError> {
+        let options = Options { unattended: true, ..options.clone() };
+        let service = music_service(config.service);
+        let service = service.as_ref();
+        let playlist_id = config.default_playlist()?;
+        let read_seeds = |ctx: &Context| Ok(vec![read_playlist(ctx, service, &playlist_id)]);
+        let picked = pick_suggestions(client, config, service, &options, access_token, &read_seeds, &|_, seeds| Target::Existing(seeds[0].clone()))?;
+        Ok(Preview { picked, options })
+    }
+
+    pub fn playlist_name(&self) -> &str {
+        self.picked.target.name()
+    }
+
+    pub fn suggestions(&self) -> &[Suggestion] {
+        &self.picked.suggestions
+    }
+
+    // Function to tell whether a suggestion can be approved: it was found and picked, or left for review
+    pub fn approvable(suggestion: &Suggestion) -> bool {
+        suggestion.found.is_some() && matches!(suggestion.status, Status::Accepted | Status::Review)
+    }
+
+    // Function to write the approved suggestions, given by their index, to the playlist, returning the number of songs added
+    // The other suggestions that could have been approved are recorded as rejected, so they aren't suggested again.
+    pub fn apply(mut self, approved: &[usize]) -> Result<usize, PilotError> {
+        let mut uris = Vec::new();
+        for (index, suggestion) in self.picked.suggestions.iter_mut().enumerate() {
+            if !Preview::approvable(suggestion) {
+                continue;
+            }
+            if approved.contains(&index) {
+                suggestion.status = Status::Accepted;
+                uris.extend(suggestion.found.as_ref().map(|found| found.uri.clone()));
+            } else {
+                suggestion.status = Status::Rejected;
+            }
+        }
+        self.picked.uris = uris;
+        let service = music_service(self.picked.ctx.config.service);
+        write_picked(service.as_ref(), &self.options, self.picked)
+    }
+}
+
+// Function to suggest songs similar to the playlist and add them, the default command
+pub fn run(client: Client, config: Config, options: &Options) -> Result<usize, PilotError> {
+    run_playlist(client, config, options, None)
+}
+
+// Function to suggest songs for several playlists one after the other with the same options, then sum up what each run did
+// The service is authorized once for all of them and the HTTP client is shared; a playlist that fails doesn't stop the others.
+pub fn run_playlists(client: Client, config: Config, options: &Options, playlists: &[String]) -> Result<usize, PilotError> {
+    let playlist_ids = playlists.iter().map(|playlist| playlist_argument(playlist)).collect::<Result<Vec<String>, PilotError>>()?;
+    check_service_options(options, config.service)?;
+    let service = music_service(config.service);
+    // The number of songs is asked once, for every playlist
+    let count = match options.count {
+        Some(count) => count,
+        None => ask_number()?.max(0) as usize,
+    };
+    let options = Options { count: Some(count), ..options.clone() };
+    let access_token = service.access_token(&client, &config, options.headless, read_only(&options))?;
+
+    let mut results = Vec::new();
+    for (playlist, playlist_id) in playlists.iter().zip(playlist_ids) {
+        let config = Config { playlist_id: Some(playlist_id), ..config.clone() };
+        let result = info_span!("playlist", playlist = playlist.as_str())
+            .in_scope(|| run_playlist(client.clone(), config, &options, Some(access_token.clone())));
+        if let Err(e) = &result {
+            error!("Could not suggest songs for {}: {}", playlist, e);
+        }
+        results.push((playlist, result));
+    }
+
+    println!("Summary of {} playlists:", results.len());
+    for (playlist, result) in &results {
+        match result {
+            Ok(count) => println!("  {}: {} songs added", playlist, count),
+            Err(e) => println!("  {}: failed, {}", playlist, e),
+        }
+    }
+    let added: usize = results.iter().filter_map(|(_, result)| result.as_ref().ok()).sum();
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    println!("  {} songs added in all.", added);
+    if failed > 0 {
+        return Err(format!("{} of the {} playlists failed", failed, results.len()).into());
+    }
+    Ok(added)
+}
+
+// Function to suggest songs for the configured playlist and add them, with the token of a shared authorization when given
+fn run_playlist(client: Client, config: Config, options: &Options, access_token: Option<String>) -> Result<usize, PilotError> {
+    let service = music_service(config.service);
+    let service = service.as_ref();
+    // A seed going to a new playlist never reads the configured one
+    let standalone = options.seed.is_some() && (options.create.is_some() || matches!(options.seed, Some(Seed::Artists(_))));
+    let playlist_id = if standalone { config.playlist_id.clone().unwrap_or_default() } else { config.default_playlist()? };
+    if options.added_by.is_some() && options.seed.is_some() {
+        return Err(PilotError::Usage("--added-by picks tracks of the playlist, it can't be used with --seed".to_string()));
+    }
+    let read_seeds = |ctx: &Context| match &options.seed {
+        Some(seed) => Ok(vec![read_seed(ctx, seed, options)?]),
+        None => match &options.added_by {
+            Some(member) => Ok(vec![read_member_seed(ctx, &playlist_id, member)?]),
+            None => Ok(vec![read_playlist(ctx, service, &playlist_id)]),
+        },
+    };
+    let target = |ctx: &Context, seeds: &[PlaylistState]| match (&options.create, &options.seed) {
+        // Audition the suggestions in a new playlist
+        (Some(name), _) => Target::New(name.clone()),
+        // Artists start a playlist from nothing
+        (None, Some(Seed::Artists(names))) => Target::New(format!("{} and similar", names.join(", "))),
+        // The seed isn't a playlist, the songs go to the configured one
+        (None, Some(_)) => Target::Existing(read_playlist(ctx, service, &playlist_id)),
+        // The seed is only part of the playlist, the whole of it is checked for duplicates
+        (None, None) if options.added_by.is_some() => Target::Existing(read_playlist(ctx, service, &playlist_id)),
+        (None, None) => Target::Existing(seeds[0].clone()),
+    };
+    let picked = pick_suggestions(client, config, service, options, access_token, &read_seeds, &target)?;
+    write_picked(service, options, picked)
+}
+
+// Function to suggest songs bridging two playlists, written to a shared blend playlist
+// The blend is the --into playlist when given, otherwise a new playlist named by --create or --name,
+// or by the LLM once its songs are known (after both seeds until then).
+pub fn run_blend(client: Client, config: Config, options: &Options, a: &str, b: &str) -> Result<usize, PilotError> {
+    let seed_ids = [playlist_argument(a)?, playlist_argument(b)?];
+    let into = options.into.as_deref().map(playlist_argument).transpose()?;
+    let service = music_service(config.service);
+    let service = service.as_ref();
+    let read_seeds = |ctx: &Context| Ok(seed_ids.iter().map(|id| read_playlist(ctx, service, id)).collect());
+    suggest(client, config, service, options, &read_seeds, &|ctx, seeds| match (&into, options.create.as_ref().or(options.name.as_ref())) {
+        // Fill an existing playlist, read it unless it is one of the seeds
+        (Some(id), _) => Target::Existing(match seeds.iter().find(|seed| &seed.id == id) {
+            Some(seed) => seed.clone(),
+            None => read_playlist(ctx, service, id),
+        }),
+        (None, Some(name)) => Target::New(name.clone()),
+        (None, None) => Target::New(format!("Blend: {} + {}", seeds[0].name, seeds[1].name)),
+    })
+}
+
+// Function to build a new playlist from a description alone, named by --create or --name, or by the LLM (after the description until then)
+pub fn run_generate(client: Client, config: Config, options: &Options, description: &str) -> Result<usize, PilotError> {
+    let read_seeds = |_: &Context| {
+        Ok(vec![PlaylistState {
+            id: "generated".to_string(),
+            name: description.to_string(),
+            snapshot_id: None,
+            length: 0,
+            tracks: Vec::new(),
+            artists: Vec::new(),
+            songs: Vec::new(),
+            brief: Some(Brief::Description(description.to_string())),
+            genres: Vec::new(),
+        }])
+    };
+    let service = music_service(config.service);
+    suggest(client, config, service.as_ref(), options, &read_seeds, &|_, _| {
+        Target::New(options.create.clone().or_else(|| options.name.clone()).unwrap_or_else(|| description.to_string()))
+    })
+}
+
+// Function to build a new playlist exploring the lesser-known side of an artist, named by --create or "<artist>: Deep Dive"
+pub fn run_deep_dive(client: Client, config: Config, options: &Options, artist: &str) -> Result<usize, PilotError> {
+    let read_seeds = |_: &Context| {
+        Ok(vec![PlaylistState {
+            id: "deep-dive".to_string(),
+            name: artist.to_string(),
+            snapshot_id: None,
+            length: 0,
+            tracks: Vec::new(),
+            artists: Vec::new(),
+            songs: Vec::new(),
+            brief: Some(Brief::DeepDive { artist: artist.to_string(), obscurity: options.obscurity }),
+            genres: Vec::new(),
+        }])
+    };
+    let service = music_service(config.service);
+    suggest(client, config, service.as_ref(), options, &read_seeds, &|_, _| {
+        Target::New(options.create.clone().unwrap_or_else(|| format!("{}: Deep Dive", artist)))
+    })
+}
+
+// First year of a decade journey when --years isn't given
+const JOURNEY_FIRST_YEAR: i32 = 1960;
+
+// Number of songs per decade of a journey when --count isn't given
+const JOURNEY_PER_DECADE: usize = 5;
+
+// Function to split a range of years into the decades it spans, the first and last ones cut to the range
+fn journey_decades(first: i32, last: i32) -> Vec<(i32, i32)> {
+    let mut decades = Vec::new();
+    let mut start = first;
+    while start <= last {
+        let end = (start - start.rem_euclid(10) + 9).min(last);
+        decades.push((start, end));
+        start = end + 1;
+    }
+    decades
+}
+
+// Function to build a playlist tracing the vibe of a seed playlist (the configured one by default) across the decades, returning
+// the number of songs added
+// The LLM is asked separately for each decade of --years (1960 to today by default), --count songs each, and the decades are
+// written in order to the --into playlist or to a new one named by --create or --name, or after the seed.
+pub fn run_journey(client: Client, config: Config, options: &Options, playlist: Option<&str>) -> Result<usize, PilotError> {
+    if options.shuffle_in {
+        return Err(PilotError::Usage("A journey goes through the decades in order, it can't be used with --shuffle-in".to_string()));
+    }
+    let seed_id = match playlist {
+        Some(playlist) => playlist_argument(playlist)?,
+        None => config.default_playlist()?,
+    };
+    let into = options.into.as_deref().map(playlist_argument).transpose()?;
+    let this_year = civil_date((now() / 86400) as i64).0 as i32;
+    let (first, last) = options.years.unwrap_or((JOURNEY_FIRST_YEAR, this_year));
+    let per_decade = options.count.unwrap_or(JOURNEY_PER_DECADE);
+    check_service_options(options, config.service)?;
+    let service = music_service(config.service);
+    let service = service.as_ref();
+    let access_token = service.access_token(&client, &config, options.headless, read_only(options))?;
+
+    let read_seeds = |ctx: &Context| Ok(vec![read_playlist(ctx, service, &seed_id)]);
+    let name = options.create.clone().or_else(|| options.name.clone());
+    let target = |ctx: &Context, seeds: &[PlaylistState]| match (&into, &name) {
+        (Some(id), _) => Target::Existing(read_playlist(ctx, service, id)),
+        (None, Some(name)) => Target::New(name.clone()),
+        (None, None) => Target::New(format!("{}: Through the Decades", seeds[0].name)),
+    };
+    // Each decade is picked on its own, then they are all written at once, and recorded as one run
+    let mut journey: Option<Picked> = None;
+    let mut problems = Vec::new();
+    for (start, end) in journey_decades(first, last) {
+        let decade_options = Options { years: Some((start, end)), count: Some(per_decade), ..options.clone() };
+        info!("Asking for {} songs from {} to {}.", per_decade, start, end);
+        let picked = match pick_suggestions(client.clone(), config.clone(), service, &decade_options, Some(access_token.clone()), &read_seeds, &target) {
+            Ok(picked) => picked,
+            Err(e @ PilotError::Usage(_)) => return Err(e),
+            Err(e) => {
+                warn!("No songs from {} to {}: {}", start, end, e);
+                continue;
+            },
+        };
+        problems.extend(take_problems());
+        journey = Some(match journey {
+            None => picked,
+            Some(mut journey) => {
+                journey.suggestions.extend(picked.suggestions);
+                journey.uris.extend(picked.uris);
+                journey
+            },
+        });
+    }
+    let mut journey = journey.ok_or("None of the decades of the journey could be suggested, nothing was changed")?;
+    journey.prompt = format!("A journey from {} to {}, {} songs per decade. {}", first, last, per_decade, journey.prompt);
+    restore_problems(problems);
+    write_picked(service, options, journey)
+}
+
+// Function to build a playlist a group listens to together, from the top tracks of each member's account, returning the number of
+// songs added
+// Members are profiles, each authorized with its own Spotify account, and each gets as many seed tracks as the others so no taste
+// outweighs the rest. The songs go to the --into playlist, or to a new one of the account running the command.
+pub fn run_group(client: Client, config: Config, options: &Options, members: &[String]) -> Result<usize, PilotError> {
+    let members = if members.is_empty() { config.group.clone() } else { members.to_vec() };
+    if members.len() < 2 {
+        return Err(PilotError::Usage("group needs at least two profiles, give them or set the group setting".to_string()));
+    }
+    if options.seed.is_some() {
+        return Err(PilotError::Usage("group seeds from the top tracks of its members, it can't be used with --seed".to_string()));
+    }
+    let into = options.into.as_deref().map(playlist_argument).transpose()?;
+    let file = load_config_file()?;
+
+    let mut seeds = Vec::new();
+    for member in &members {
+        let member_config = resolve_config(&file, Some(member))?;
+        // Every member authorizes once, their tokens are kept with their profile
+        let access_token = get_access_token(&client, &member_config, options.headless)
+            .map_err(|e| format!("Could not authorize the account of {}: {}", member, e))?;
+        let ctx = Context { client: client.clone(), config: member_config, access_token };
+        let range = options.range.as_str();
+        let tracks = get_top_tracks(&ctx, range)?;
+        let artists = get_top_artists(&ctx, range)?.into_iter().map(|artist| artist.name).collect();
+        info!("Read {} top tracks of {}.", tracks.len(), member);
+        seeds.push(PlaylistState {
+            id: format!("group:{}", member),
+            name: format!("the top tracks of {}", member),
+            snapshot_id: None,
+            length: tracks.len(),
+            tracks,
+            artists,
+            songs: Vec::new(),
+            brief: None,
+            genres: Vec::new(),
+        });
+    }
+    // The member with the fewest top tracks sets how many everyone brings
+    let shared = seeds.iter().map(|seed| seed.tracks.len()).min().unwrap_or(0);
+    if shared == 0 {
+        return Err("A member of the group has no top tracks on Spotify yet, nothing was changed".into());
+    }
+    for seed in &mut seeds {
+        seed.tracks.truncate(shared);
+        seed.length = shared;
+    }
+
+    let service = music_service(config.service);
+    let service = service.as_ref();
+    let read_seeds = |_: &Context| Ok(seeds.clone());
+    suggest(client, config, service, options, &read_seeds, &|ctx, _| match (&into, options.create.as_ref().or(options.name.as_ref())) {
+        (Some(id), _) => Target::Existing(read_playlist(ctx, service, id)),
+        (None, Some(name)) => Target::New(name.clone()),
+        (None, None) => Target::New(format!("Group: {}", members.join(" + "))),
+    })
+}
+
+// Name of the weekly playlist, its archives add the date of their week
+const WEEKLY_NAME: &str = "PlaylistPilot Weekly";
+
+// Number of songs of a weekly playlist when --count isn't given
+const WEEKLY_COUNT: usize = 30;
+
+// Function to refill the profile's weekly playlist from the seeds (the weekly_seeds setting or the configured playlist), returning the
+// number of songs added
+// The weekly playlist is created by the first run. Each later run copies what it holds into a dated archive playlist, then replaces it
+// with new suggestions, which never repeat earlier weeks'.
+pub fn run_weekly(client: Client, config: Config, options: &Options, seeds: &[String]) -> Result<usize, PilotError> {
+    if options.position.is_some() || options.shuffle_in || options.create.is_some() || options.into.is_some() || options.seed.is_some() {
+        return Err("weekly always replaces its own playlist, --position, --shuffle-in, --create, --into and --seed don't apply".into());
+    }
+    let seeds = if seeds.is_empty() { &config.weekly_seeds } else { seeds };
+    let seed_ids = if seeds.is_empty() {
+        vec![config.default_playlist()?]
+    } else {
+        seeds.iter().map(|seed| playlist_argument(seed)).collect::<Result<Vec<String>, PilotError>>()?
+    };
+    let mut options = options.clone();
+    options.replace = true;
+    options.count = options.count.or(Some(WEEKLY_COUNT));
+
+    let history = History::open()?;
+    let profile = config.profile.clone();
+    let mut weekly = history.weekly_playlist(profile.as_deref())?;
+    if !options.dry_run {
+        let access_token = get_access_token(&client, &config, options.headless)?;
+        let ctx = Context { client: client.clone(), config: config.clone(), access_token };
+        match &mut weekly {
+            None => {
+                let created = create_playlist(&ctx, WEEKLY_NAME, "Songs suggested by PlaylistPilot, renewed every week")?;
+                println!("Created playlist '{}': {}", WEEKLY_NAME, created.external_urls.spotify);
+                weekly = Some(WeeklyPlaylist { playlist_id: created.id, filled_at: None, archived: false });
+            },
+            // Tracks a run that failed to refill the playlist already archived aren't archived again
+            Some(current) if !current.archived => {
+                let playlist = get_lean_playlist(&ctx, &current.playlist_id)?;
+                let (tracks, _) = spotify_tracks(playlist.tracks.items);
+                if !tracks.is_empty() {
+                    let week = iso_date(current.filled_at.unwrap_or_else(now))[..10].to_string();
+                    let name = format!("{} {}", WEEKLY_NAME, week);
+                    let description = format!("Songs PlaylistPilot suggested for the week of {}", week);
+                    let archive = create_playlist(&ctx, &name, &description)?;
+                    let count = tracks.len();
+                    add_to_playlist(&ctx, &archive.id, tracks.into_iter().map(|track| track.uri).collect(), None)?;
+                    println!("Archived the {} songs of last week in '{}': {}", count, name, archive.external_urls.spotify);
+                }
+                current.archived = true;
+            },
+            Some(_) => {},
+        }
+        if let Some(weekly) = &weekly {
+            history.save_weekly_playlist(profile.as_deref(), weekly)?;
+        }
+    }
+
+    let service = music_service(config.service);
+    let service = service.as_ref();
+    let read_seeds = |ctx: &Context| Ok(seed_ids.iter().map(|id| read_playlist(ctx, service, id)).collect());
+    let weekly_id = weekly.as_ref().map(|weekly| weekly.playlist_id.clone());
+    let added = suggest(client, config, service, &options, &read_seeds, &|ctx, _| match &weekly_id {
+        Some(id) => Target::Existing(read_playlist(ctx, service, id)),
+        // A dry run before the first week
+        None => Target::New(WEEKLY_NAME.to_string()),
+    })?;
+    if let Some(mut weekly) = weekly.filter(|_| !options.dry_run && added > 0) {
+        weekly.filled_at = Some(now());
+        weekly.archived = false;
+        history.save_weekly_playlist(profile.as_deref(), &weekly)?;
+    }
+    Ok(added)
+}
+
+// Function to match a list of songs given by the user and add them, without asking the LLM
+// The songs go through the same search, filters, duplicate checks and backups as suggestions. They are written to the --into playlist
+// (the configured one by default) or to a new --create playlist, and `origin` names the list in the history.
+pub fn run_list(client: Client, config: Config, options: &Options, songs: Vec<Song>, origin: &str) -> Result<(), PilotError> {
+    check_service_options(options, config.service)?;
+    if options.hybrid || options.lastfm || options.rerank || options.ensemble || options.verify || options.judge || options.seed.is_some() {
+        return Err("--hybrid, --lastfm, --rerank, --ensemble, --verify, --judge and --seed only work with suggestions".into());
+    }
+    if songs.is_empty() {
+        return Err(format!("No songs found in {}", origin).into());
+    }
+    let started_at = now();
+    clear_problems();
+    let service = music_service(config.service);
+    let service = service.as_ref();
+    let access_token = service.access_token(&client, &config, options.headless, options.dry_run)?;
+    let ctx = Context { client, config, access_token };
+
+    let target = match (&options.create, &options.into) {
+        (Some(name), _) => Target::New(name.clone()),
+        (None, Some(into)) => Target::Existing(read_playlist(&ctx, service, &playlist_argument(into)?)),
+        (None, None) => Target::Existing(read_playlist(&ctx, service, &ctx.config.default_playlist()?)),
+    };
+    let blacklist = blacklist(&ctx.config, options);
+    let filter = track_filter(options);
+
+    let mut songs = songs;
+    if options.musicbrainz {
+        canonicalize(&ctx, &mut songs);
+    }
+    let search_cache = open_search_cache(&ctx, options, &filter);
+    let results = search_songs(service, &ctx, &songs, options.parallelism, search_cache.as_ref(), &filter);
+    let mut suggestions: Vec<Suggestion> = songs
+        .into_iter()
+        .zip(results)
+        .map(|(song, result)| match_suggestion(&ctx, options, song, result, Source::List))
+        .collect();
+    check_suggestions(&ctx, &filter, &mut suggestions);
+
+    let mut duplicates = Duplicates::new(&[]);
+    if let Target::Existing(playlist) = &target {
+        duplicates.extend(&playlist.tracks);
+    }
+    skip_known(&ctx, options, &target, &mut duplicates);
+    // Songs of earlier runs aren't skipped, the user asked for these ones
+    let mut picker = Picker {
+        duplicates,
+        past: Duplicates::new(&[]),
+        blacklist: &blacklist,
+        max_per_artist: options.max_per_artist,
+        per_artist: HashMap::new(),
+        limit: options.count.unwrap_or(usize::MAX),
+        uris: Vec::new(),
+    };
+    picker.pick(&mut suggestions);
+    let uris_to_add = picker.uris;
+    let unmatched = suggestions.iter().filter(|s| s.status == Status::NotFound).count();
+    println!("Matched {} of the {} songs of {}.", suggestions.len() - unmatched, suggestions.len(), origin);
+
+    let mut target_playlist = match &target {
+        Target::Existing(playlist) => Some(playlist.id.clone()),
+        Target::New(_) => None,
+    };
+    let writes = !options.dry_run && !options.to_queue && !options.library_only;
+    if options.dry_run {
+        println!("Dry run, {} songs would be added:", uris_to_add.len());
+        for uri in &uris_to_add {
+            println!("  {}", uri);
+        }
+    } else if options.to_queue {
+        queue_songs(&ctx, &uris_to_add);
+    } else if options.library_only {
+        save_songs(&ctx, &uris_to_add);
+    } else if uris_to_add.is_empty() {
+        println!("No new songs to add.");
+    } else {
+        let description = format!("Songs imported by PlaylistPilot from {}", origin);
+        let (outcome, written_to) = write_songs(&ctx, service, options, &target, uris_to_add, &description);
+        target_playlist = written_to;
+        if let Some(status) = outcome {
+            for suggestion in suggestions.iter_mut().filter(|s| s.status == Status::Accepted) {
+                suggestion.status = status;
+            }
+        }
+        if options.save_to_library {
+            save_songs(&ctx, &added_uris(&suggestions));
+        }
+        post_to_webhook(&ctx, &target, &suggestions);
+        email_summary(&ctx, &target, &suggestions, 0);
+    }
+
+    let (snapshot_id, positions) = added_positions(&ctx, target_playlist.as_deref(), &suggestions);
+    let run = Run {
+        profile: ctx.config.profile.as_deref(),
+        playlist_id: target_playlist.as_deref().unwrap_or("import"),
+        playlist_name: target_playlist.is_some().then(|| target.name()),
+        prompt: origin,
+        dry_run: !writes,
+        suggestions: &suggestions,
+        service: ctx.config.service,
+        snapshot_id: snapshot_id.as_deref(),
+        positions: &positions,
+    };
+    if let Err(e) = History::open().and_then(|history| history.record(&run)) {
+        warn!("Could not save the run history: {}", e);
+    }
+    // Nothing is asked of the LLM, the list stands in for the prompt
+    audit_run(&ctx, AuditRecord {
+        seeds: vec![origin.to_string()],
+        target: Some(target.name().to_string()),
+        playlist_id: target_playlist.clone(),
+        dry_run: !writes,
+        prompt_hash: Some(fingerprint(origin)),
+        suggestions: suggestions.iter().map(|s| AuditSuggestion::new(s, rejection_reason(s.status))).collect(),
+        ..audit_record(service, ctx.config.profile.as_deref(), started_at)
+    });
+    if let (Some(playlist_id), true) = (&target_playlist, writes) {
+        update_feed(&ctx.config, playlist_id);
+    }
+    if writes {
+        count_suggestions(&suggestions);
+    }
+    save_report(service, options, vec![(origin.to_string(), suggestions.len())], &target, &suggestions);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::Spotify;
+    use crate::testing::*;
+    use std::cell::Cell;
+
+    // Function to make an accepted suggestion found as the track of the given ID
+    fn suggestion(id: &str, name: &str, artist: &str) -> Suggestion {
+        let found = TrackMatch {
+            uri: format!("spotify:track:{}", id),
+            name: name.to_string(),
+            artists: vec![artist.to_string()],
+            artist_ids: Vec::new(),
+            confidence: 1.0,
+            image: None,
+            popularity: None,
+            tempo: None,
+        };
+        let song = Song { name: name.to_string(), artist: artist.to_string(), isrc: None, mbid: None };
+        Suggestion { song, found: Some(found), status: Status::Accepted, source: Source::Llm }
+    }
+
+    // Function to make a picker of `limit` songs, none of the playlist's songs being known yet
+    fn picker(blacklist: &Blacklist, limit: usize, max_per_artist: Option<usize>) -> Picker<'_> {
+        Picker {
+            duplicates: Duplicates::new(&[]),
+            past: Duplicates::new(&[]),
+            blacklist,
+            max_per_artist,
+            per_artist: HashMap::new(),
+            limit,
+            uris: Vec::new(),
+        }
+    }
+
+    fn statuses(suggestions: &[Suggestion]) -> Vec<Status> {
+        suggestions.iter().map(|s| s.status).collect()
+    }
+
+    #[test]
+    fn numbers_of_songs_are_parsed() {
+        assert_eq!(parse_number(" 12\n").unwrap(), 12);
+        assert!(matches!(parse_number("twelve\n"), Err(PilotError::Usage(_))));
+        assert!(matches!(parse_number("\n"), Err(PilotError::Usage(_))));
+    }
+
+    #[test]
+    fn picking_skips_what_cant_be_added() {
+        let blacklist = Blacklist::new(&["Banned".to_string()], &[]);
+        let mut picker = picker(&blacklist, 10, Some(1));
+        picker.duplicates.add(None, "Owned", "Someone");
+        picker.past.add(Some("spotify:track:p"), "Earlier", "Someone Else");
+        let mut suggestions = vec![
+            suggestion("a", "First", "Radiohead"),
+            suggestion("b", "Second", "Radiohead"),
+            suggestion("o", "Owned", "Someone"),
+            suggestion("p", "Earlier", "Someone Else"),
+            suggestion("x", "Anything", "Banned"),
+            suggestion("c", "Third", "Portishead"),
+        ];
+        picker.pick(&mut suggestions);
+        assert_eq!(
+            statuses(&suggestions),
+            [Status::Accepted, Status::ArtistLimit, Status::Duplicate, Status::Previous, Status::Excluded, Status::Accepted]
+        );
+        assert_eq!(picker.uris, ["spotify:track:a", "spotify:track:c"]);
+        assert_eq!(picker.full_artists(&suggestions), ["Radiohead"]);
+    }
+
+    #[test]
+    fn songs_past_the_limit_are_surplus_before_the_artist_cap() {
+        let blacklist = Blacklist::new(&[], &[]);
+        let mut picker = picker(&blacklist, 1, Some(1));
+        let mut suggestions = vec![suggestion("a", "First", "Radiohead"), suggestion("b", "Second", "Radiohead")];
+        picker.pick(&mut suggestions);
+        assert_eq!(statuses(&suggestions), [Status::Accepted, Status::Surplus]);
+        // Surplus songs aren't a reason to turn the artist down
+        assert!(picker.full_artists(&suggestions).is_empty());
+    }
+
+    #[test]
+    fn refill_prompts_say_why_songs_were_left_out() {
+        let mut suggestions = vec![suggestion("a", "First", "Radiohead"), suggestion("b", "Second", "Radiohead"), suggestion("c", "Third", "Portishead")];
+        suggestions[1].status = Status::ArtistLimit;
+        suggestions[2].status = Status::Duplicate;
+        let prompt = refill_prompt("Suggest songs.", 2, &suggestions, &["Radiohead".to_string()], &TrackFilter::default());
+        assert_eq!(
+            prompt,
+            "Suggest songs. These songs you gave me can't be used: Second by Radiohead (too many songs by this artist), \
+             Third by Portishead (already in the playlist). Give me 2 other songs instead, none of which is one of these: \
+             First by Radiohead, Second by Radiohead, Third by Portishead. Don't give me songs by Radiohead, there are enough of them."
+        );
+        let prompt = refill_prompt("Suggest songs.", 2, &suggestions, &[], &TrackFilter::default());
+        assert!(!prompt.contains("Don't give me songs by"));
+    }
+
+    #[test]
+    fn refilling_stops_after_its_rounds() {
+        let blacklist = Blacklist::new(&[], &[]);
+        let mut picker = picker(&blacklist, 2, None);
+        picker.duplicates.add(None, "Owned", "Someone");
+        let mut suggestions = vec![suggestion("o", "Owned", "Someone")];
+        picker.pick(&mut suggestions);
+        let asked = Cell::new(0);
+        let ask = |_: &str| {
+            asked.set(asked.get() + 1);
+            Ok(vec![suggestion("o", "Owned", "Someone")])
+        };
+        picker.refill("Suggest songs.", 2, &TrackFilter::default(), &mut suggestions, &ask).unwrap();
+        assert_eq!(asked.get(), REFILL_ROUNDS);
+        assert!(picker.uris.is_empty());
+        assert_eq!(suggestions.len(), REFILL_ROUNDS + 1);
+    }
+
+    #[test]
+    fn added_songs_are_found_at_their_positions() {
+        let playlist = serde_json::json!({
+            "name": "Road trip",
+            "snapshot_id": "snap2",
+            "tracks": { "items": [{ "track": track_json("a", "Song A", "Artist A") }, { "track": null }, { "track": track_json("b", "Song B", "Artist B") }], "next": null },
+        });
+        let server = CannedServer::start(vec![(200, playlist.to_string())]);
+        let ctx = test_context(&server);
+        let mut suggestions = vec![suggestion("b", "Song B", "Artist B"), suggestion("c", "Song C", "Artist C")];
+        suggestions[0].status = Status::Added;
+        let (snapshot_id, positions) = added_positions(&ctx, Some("pl1"), &suggestions);
+        assert_eq!(snapshot_id.as_deref(), Some("snap2"));
+        assert_eq!(positions, HashMap::from([("spotify:track:b".to_string(), 2)]));
+        // Nothing is read when nothing was added
+        assert_eq!(added_positions(&ctx, Some("pl1"), &suggestions[1..]), (None, HashMap::new()));
+        assert_eq!(server.received().len(), 1);
+    }
+
+    #[test]
+    fn duplicates_are_replaced_until_the_count_is_reached() {
+        let first = r#"{"songs": [{"name": "Karma Police", "artist": "Radiohead"}]}"#;
+        let replacement = r#"{"songs": [{"name": "Teardrop", "artist": "Massive Attack"}]}"#;
+        let server = CannedServer::start(vec![
+            (200, llm_answer(first)),
+            (200, search_answer(vec![track_json("k", "Karma Police", "Radiohead")])),
+            (200, llm_answer(replacement)),
+            (200, search_answer(vec![track_json("t", "Teardrop", "Massive Attack")])),
+        ]);
+        let ctx = test_context(&server);
+        let options = Options { dry_run: true, no_cache: true, no_llm_cache: true, parallelism: 1, ..Default::default() };
+        let filter = TrackFilter::default();
+        let ask = |prompt: &str| find_suggestions(&ctx, &Spotify, &options, "key", prompt, &filter);
+
+        // The only song of the first answer is already in the playlist
+        let blacklist = Blacklist::new(&[], &[]);
+        let mut picker = picker(&blacklist, 1, None);
+        picker.duplicates.add(Some("spotify:track:k"), "Karma Police", "Radiohead");
+        let mut suggestions = ask("Suggest songs.").unwrap();
+        picker.pick(&mut suggestions);
+        assert!(picker.uris.is_empty());
+
+        picker.refill("Suggest songs.", 1, &filter, &mut suggestions, &ask).unwrap();
+        assert_eq!(picker.uris, ["spotify:track:t"]);
+        assert_eq!(statuses(&suggestions), [Status::Duplicate, Status::Accepted]);
+        let received = server.received();
+        assert_eq!(received.len(), 4);
+        let refill: serde_json::Value = serde_json::from_str(&received[2].body).unwrap();
+        let refill = refill["messages"].as_array().unwrap().last().unwrap()["content"].as_str().unwrap().to_string();
+        assert!(refill.contains("Karma Police by Radiohead (already in the playlist)"));
+    }
+}
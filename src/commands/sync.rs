@@ -0,0 +1,75 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use std::collections::HashSet;
+
+use crate::auth::*;
+use crate::backup::{backup_items, backup_playlist};
+use crate::cli::*;
+use crate::commands::playlist_argument;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::spotify::*;
+
+// Function to make the --into playlist mirror a source playlist, minus the excluded tracks
+// Missing tracks are appended in source order; with --remove-extra, target tracks the source doesn't have are removed.
+// Nothing is asked so it can run on a schedule, the target is backed up before any change.
+pub fn run(client: Client, config: Config, options: &Options, source: &str) -> Result<(), PilotError> {
+    let source_id = playlist_argument(source)?;
+    let target_id = playlist_argument(options.into.as_deref().ok_or("sync requires --into <playlist>")?)?;
+    let excluded = options
+        .exclude
+        .iter()
+        .map(|value| {
+            parse_spotify_id(value, "track")
+                .map(|id| format!("spotify:track:{}", id))
+                .ok_or_else(|| format!("Not a Spotify track: {}", value))
+        })
+        .collect::<Result<HashSet<String>, String>>()?;
+
+    let access_token = if options.dry_run {
+        get_read_only_token(&client, &config)?
+    } else {
+        get_access_token(&client, &config, options.headless)?
+    };
+    let ctx = Context { client, config, access_token };
+
+    let source = get_playlist(&ctx, &source_id, None)?;
+    let target = get_playlist(&ctx, &target_id, None)?;
+    let mut wanted: Vec<String> = Vec::new();
+    for item in backup_items(source.tracks.items) {
+        if !item.is_local && !excluded.contains(&item.uri) && !wanted.contains(&item.uri) {
+            wanted.push(item.uri);
+        }
+    }
+    let current: Vec<String> = backup_items(target.tracks.items).into_iter().map(|item| item.uri).collect();
+
+    let missing: Vec<String> = wanted.iter().filter(|uri| !current.contains(uri)).cloned().collect();
+    let mut extra: Vec<String> = Vec::new();
+    if options.remove_extra {
+        for uri in current.iter().filter(|uri| !wanted.contains(uri)) {
+            if !extra.contains(uri) {
+                extra.push(uri.clone());
+            }
+        }
+    }
+
+    println!("'{}' -> '{}': {} to add, {} to remove.", source.name, target.name, missing.len(), extra.len());
+    if missing.is_empty() && extra.is_empty() {
+        return Ok(());
+    }
+    if options.dry_run {
+        println!("Dry run, the playlist was not changed.");
+        return Ok(());
+    }
+
+    backup_playlist(&ctx, &target_id).map_err(|e| format!("Could not back up the playlist, nothing was changed: {}", e))?;
+    if !extra.is_empty() {
+        remove_from_playlist(&ctx, &target_id, extra)?;
+    }
+    if !missing.is_empty() {
+        add_to_playlist(&ctx, &target_id, missing, None)?;
+    }
+    println!("'{}' is in sync.", target.name);
+    Ok(())
+}
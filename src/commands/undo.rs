@@ -0,0 +1,72 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+
+use crate::auth::get_access_token;
+use crate::backup::backup_playlist;
+use crate::cli::*;
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::history::*;
+use crate::service::{music_service, Service};
+use crate::spotify::{remove_from_playlist, remove_occurrences};
+
+// Function to remove the tracks added by the most recent run of the profile
+pub fn run(client: Client, config: Config, options: &Options) -> Result<(), PilotError> {
+    undo_last_run(client, config, options, None).map(|_| ())
+}
+
+// Function to remove the tracks added by the most recent run of the profile, returning that run's ID and number of tracks
+// None when nothing was removed: there was nothing to undo, it was a dry run or the user declined.
+// The token of an earlier authorization is used when given.
+pub fn undo_last_run(client: Client, config: Config, options: &Options, access_token: Option<String>) -> Result<Option<(i64, usize)>, PilotError> {
+    let history = History::open()?;
+    let Some(last_run) = history.last_added_run(config.profile.as_deref())? else {
+        println!("Nothing to undo.");
+        return Ok(None);
+    };
+
+    println!("Run #{} added {} tracks to playlist {}:", last_run.id, last_run.tracks.len(), last_run.playlist_id);
+    for (track, _) in &last_run.tracks {
+        println!("  {} - {}", track.name, track.artists);
+    }
+    // Runs recorded before the service was stored are taken as Spotify's, the only service undo can remove tracks from
+    let service = last_run.service.unwrap_or(Service::Spotify);
+    if service != Service::Spotify {
+        return Err(PilotError::Usage(format!("Run #{} added to {}, only runs on Spotify can be undone", last_run.id, music_service(service).name())));
+    }
+
+    if options.dry_run {
+        println!("Dry run, nothing was removed.");
+        return Ok(None);
+    }
+    if !options.unattended && !confirm("Remove them from the playlist?") {
+        return Ok(None);
+    }
+
+    let access_token = match access_token {
+        Some(access_token) => access_token,
+        None => get_access_token(&client, &config, options.headless)?,
+    };
+    let ctx = Context { client, config, access_token };
+    backup_playlist(&ctx, &last_run.playlist_id).map_err(|e| format!("Could not back up the playlist, nothing was removed: {}", e))?;
+    let removed = last_run.tracks.len();
+    let occurrences: Option<Vec<(String, usize)>> =
+        last_run.tracks.iter().map(|(track, position)| position.map(|position| (track.uri.clone(), position))).collect();
+    match (last_run.snapshot_id, occurrences) {
+        // Only the copies the run added are removed, Spotify finds them from their positions in that snapshot even after later changes
+        (Some(snapshot_id), Some(occurrences)) => {
+            remove_occurrences(&ctx, &last_run.playlist_id, &occurrences, snapshot_id)?;
+        },
+        // Where the tracks were added wasn't recorded, every copy of them is removed
+        _ => {
+            let uris: Vec<String> = last_run.tracks.into_iter().map(|(track, _)| track.uri).collect();
+            remove_from_playlist(&ctx, &last_run.playlist_id, uris)?;
+        },
+    }
+
+    // Mark the tracks so the next undo goes back to the run before
+    history.update_status(last_run.id, Status::Added, Status::Undone)?;
+    println!("Removed the tracks of run #{}.", last_run.id);
+    Ok(Some((last_run.id, removed)))
+}
@@ -0,0 +1,622 @@
+// Import necessary modules and crates
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::error::PilotError;
+use crate::http::{Endpoint, RetryPolicies, RetryPolicy};
+use crate::llm::context_window;
+use crate::service::Service;
+
+// Default base URL of the Spotify Web API
+pub const DEFAULT_SPOTIFY_API_URL: &str = "https://api.spotify.com/v1";
+
+// Default base URL of the Spotify accounts service, which authorizes users and gives the tokens
+pub const DEFAULT_SPOTIFY_ACCOUNTS_URL: &str = "https://accounts.spotify.com";
+
+// Default LLM endpoint and model, used when a profile doesn't set its own
+pub const DEFAULT_LLM_API_URL: &str = "https://integrate.api.nvidia.com/v1/chat/completions";
+pub const DEFAULT_LLM_MODEL: &str = "nvidia/llama-3.1-nemotron-70b-instruct";
+
+// Default system message of the LLM requests: who the LLM is and the rules it follows, the prompts only give it the data and the answer format
+pub const DEFAULT_SYSTEM_PROMPT: &str = "You are a music curator with a deep knowledge of every genre and era. Only suggest songs that really exist \
+    and are released, credited to the artists who recorded them, and never a song that is already in the playlists you are given. \
+    Answer exactly in the format you are asked for, with nothing else.";
+
+// Default image generation endpoint and model, used to draw playlist covers
+pub const DEFAULT_IMAGE_API_URL: &str = "https://api.openai.com/v1/images/generations";
+pub const DEFAULT_IMAGE_MODEL: &str = "dall-e-3";
+
+// Default embeddings endpoint and model, used to rerank suggestions
+pub const DEFAULT_EMBEDDINGS_API_URL: &str = "https://integrate.api.nvidia.com/v1/embeddings";
+pub const DEFAULT_EMBEDDINGS_MODEL: &str = "nvidia/nv-embed-v1";
+
+// Default redirect URI of the Google OAuth client, a loopback address as Google expects for desktop apps
+pub const DEFAULT_GOOGLE_REDIRECT_URI: &str = "http://localhost:3000";
+
+// Default address of the local page MusicKit JS authorizes Apple Music from
+pub const DEFAULT_APPLE_REDIRECT_URI: &str = "http://localhost:3000";
+
+// Default redirect URI of the Deezer application
+pub const DEFAULT_DEEZER_REDIRECT_URI: &str = "http://localhost:3000";
+
+// Default SMTP port, submission with STARTTLS
+pub const DEFAULT_SMTP_PORT: u16 = 587;
+
+// Default address the web interface listens on, only reachable from this machine
+pub const DEFAULT_SERVE_ADDRESS: &str = "127.0.0.1:8080";
+
+// Default confidence (0 to 1) above which a Spotify match is added without asking
+pub const DEFAULT_MIN_CONFIDENCE: f64 = 0.8;
+
+// Default number of requests per second sent to Spotify, sustained; short bursts of as many are allowed on top
+pub const DEFAULT_SPOTIFY_REQUESTS_PER_SECOND: f64 = 10.0;
+
+// Default timeouts, in seconds: to connect to any server, and for a whole Spotify or LLM request
+// An LLM can take a minute or more to write its answer, Spotify answers in well under a second.
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_SPOTIFY_TIMEOUT_SECS: u64 = 30;
+pub const DEFAULT_LLM_TIMEOUT_SECS: u64 = 180;
+
+// Default retry policy of every outbound call: attempts in all, the wait before the first retry in milliseconds (doubled for every
+// next one), the share of it taken at random, and the statuses tried again; by default only rate limits are retried
+pub const DEFAULT_RETRY_MAX_ATTEMPTS: u32 = 5;
+pub const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 1000;
+pub const DEFAULT_RETRY_JITTER: f64 = 0.1;
+pub const DEFAULT_RETRY_STATUSES: [u16; 1] = [429];
+
+// Default number of earlier runs on a playlist whose conversation with the LLM the next run continues, none
+pub const DEFAULT_CONVERSATION_RUNS: usize = 0;
+
+// Default number of seconds an LLM answer is reused for the very same request, enough to run again right after a failure
+pub const DEFAULT_LLM_CACHE_TTL_SECS: u64 = 60 * 60;
+
+// Settings of one profile, as written in the config file
+// Every field is optional so a profile only has to set what differs from the environment.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct ProfileSettings {
+    pub spotify_client_id: Option<String>,
+    pub spotify_client_secret: Option<String>,
+    pub spotify_redirect_uri: Option<String>,
+    pub spotify_api_url: Option<String>,
+    pub spotify_accounts_url: Option<String>,
+    pub spotify_requests_per_second: Option<f64>,
+    pub connect_timeout_secs: Option<u64>,
+    pub spotify_timeout_secs: Option<u64>,
+    pub retry_max_attempts: Option<u32>,
+    pub retry_base_delay_ms: Option<u64>,
+    pub retry_jitter: Option<f64>,
+    pub retry_statuses: Option<Vec<u16>>,
+    // Retry policies of single endpoints, as written in [retry.<endpoint>] tables
+    pub retry: Option<HashMap<String, RetrySettings>>,
+    pub llm_timeout_secs: Option<u64>,
+    pub llm_context_tokens: Option<usize>,
+    pub llm_cache_ttl_secs: Option<u64>,
+    pub conversation_runs: Option<usize>,
+    pub proxy: Option<String>,
+    pub llm_proxy: Option<String>,
+    pub llm_client_secret: Option<String>,
+    pub llm_api_url: Option<String>,
+    pub llm_model: Option<String>,
+    pub judge_model: Option<String>,
+    pub system_prompt: Option<String>,
+    pub playlist_id: Option<String>,
+    pub playlists: Option<Vec<String>>,
+    pub min_confidence: Option<f64>,
+    pub market: Option<String>,
+    pub exclude_artists: Option<Vec<String>>,
+    pub exclude_tracks: Option<Vec<String>>,
+    pub embeddings_api_url: Option<String>,
+    pub embeddings_model: Option<String>,
+    pub image_api_url: Option<String>,
+    pub image_model: Option<String>,
+    pub image_api_key: Option<String>,
+    pub lastfm_api_key: Option<String>,
+    pub lastfm_user: Option<String>,
+    pub listenbrainz_token: Option<String>,
+    pub google_client_id: Option<String>,
+    pub google_client_secret: Option<String>,
+    pub google_redirect_uri: Option<String>,
+    pub apple_team_id: Option<String>,
+    pub apple_key_id: Option<String>,
+    pub apple_private_key_path: Option<String>,
+    pub apple_music_user_token: Option<String>,
+    pub apple_storefront: Option<String>,
+    pub apple_redirect_uri: Option<String>,
+    pub tidal_client_id: Option<String>,
+    pub tidal_client_secret: Option<String>,
+    pub deezer_app_id: Option<String>,
+    pub deezer_secret: Option<String>,
+    pub deezer_redirect_uri: Option<String>,
+    pub service: Option<String>,
+    pub webhook_url: Option<String>,
+    pub smtp_host: Option<String>,
+    pub smtp_port: Option<u16>,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub email_from: Option<String>,
+    pub email_to: Option<Vec<String>>,
+    pub llm_price_per_million_tokens: Option<f64>,
+    pub feed_dir: Option<String>,
+    pub audit_log: Option<String>,
+    pub description_template: Option<String>,
+    pub weekly_seeds: Option<Vec<String>>,
+    pub group: Option<Vec<String>>,
+    pub serve_address: Option<String>,
+    pub api_token: Option<String>,
+    pub metrics_address: Option<String>,
+    pub ensemble: Option<Vec<ProviderSettings>>,
+}
+
+// An LLM asked by --ensemble besides the configured one, as written in an [[ensemble]] entry of the config file
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderSettings {
+    // Shown in the comparison of the models, the model when not set
+    pub name: Option<String>,
+    // OpenAI-compatible chat completions endpoint, llm_api_url when not set
+    pub llm_api_url: Option<String>,
+    pub llm_model: String,
+    // API key of the provider, the LLM API key when not set
+    pub llm_client_secret: Option<String>,
+    // Price of the model, to estimate its cost in bench; unknown when not set
+    pub llm_price_per_million_tokens: Option<f64>,
+}
+
+// Retry policy of one endpoint, as written in a [retry.<endpoint>] table of the config file
+// What isn't set is taken from the retry_* settings.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct RetrySettings {
+    pub max_attempts: Option<u32>,
+    pub base_delay_ms: Option<u64>,
+    pub jitter: Option<f64>,
+    pub statuses: Option<Vec<u16>>,
+}
+
+// A run the daemon starts on a schedule, as written in a [[schedules]] entry of the config file
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduleSettings {
+    // Shown in the logs, the cron expression when not set
+    pub name: Option<String>,
+    // When to run, e.g. "0 8 * * mon" for Mondays at 8am, in the local time zone
+    pub cron: String,
+    // Playlist the songs are added to, the profile's playlist when not set
+    pub playlist: Option<String>,
+    // Number of songs to add
+    pub count: Option<usize>,
+    // Profile whose settings the run uses, the default settings when not set
+    pub profile: Option<String>,
+    // Other command line options of the run, e.g. ["--hybrid", "--max-per-artist", "2"]
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+// A playlist the daemon archives on a schedule, as written in an [[archives]] entry of the config file
+#[derive(Debug, Clone, Deserialize)]
+pub struct ArchiveSettings {
+    // When to take a snapshot, e.g. "0 3 * * *" for every night at 3am, in the local time zone
+    pub cron: String,
+    // Playlist archived, the profile's playlist when not set
+    pub playlist: Option<String>,
+    // Profile whose settings are used, the default settings when not set
+    pub profile: Option<String>,
+}
+
+// A named bundle of options for a moment or a context, as written in a [presets.<name>] entry of the config file
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct PresetSettings {
+    // Vibe the songs should fit, as with --vibe
+    pub vibe: Option<String>,
+    // Number of songs to add
+    pub count: Option<usize>,
+    // Playlist the songs are added to, the profile's playlist when not set
+    pub playlist: Option<String>,
+    // Other command line options of the preset, e.g. its filters: ["--instrumental", "--max-popularity", "40"]
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+// Content of the config file: default settings at the top level, named profiles under [profiles.<name>],
+// the runs of the daemon under [[schedules]], the playlists it archives under [[archives]] and the presets under [presets.<name>]
+#[derive(Debug, Default, Deserialize)]
+pub struct ConfigFile {
+    #[serde(flatten)]
+    pub default: ProfileSettings,
+    #[serde(default)]
+    pub profiles: HashMap<String, ProfileSettings>,
+    #[serde(default)]
+    pub schedules: Vec<ScheduleSettings>,
+    #[serde(default)]
+    pub archives: Vec<ArchiveSettings>,
+    #[serde(default)]
+    pub presets: HashMap<String, PresetSettings>,
+}
+
+// Fully resolved settings for the current run
+#[derive(Debug, Clone)]
+pub struct Config {
+    // Name of the selected profile, None for the default one
+    pub profile: Option<String>,
+    pub spotify_client_id: String,
+    pub spotify_client_secret: String,
+    pub spotify_redirect_uri: String,
+    // Base URL of the Spotify Web API, changed to point at a proxy or a test server
+    pub spotify_api_url: String,
+    // Base URL of the Spotify accounts service, changed like spotify_api_url
+    pub spotify_accounts_url: String,
+    // Most requests per second sent to Spotify, the searches and pages of every thread together; 0 for no limit
+    pub spotify_requests_per_second: f64,
+    // Seconds to wait for a connection to any server, and for a whole Spotify or LLM request
+    pub connect_timeout_secs: u64,
+    pub spotify_timeout_secs: u64,
+    pub llm_timeout_secs: u64,
+    // How failed calls are tried again, by endpoint
+    pub retry: RetryPolicies,
+    // Size of the model's context window in tokens, prompts are trimmed to fit; guessed from the model name when not set
+    pub llm_context_tokens: usize,
+    // Seconds an LLM answer is reused when the very same suggestion request is sent again; 0 never reuses one
+    pub llm_cache_ttl_secs: u64,
+    // Number of earlier runs on a playlist whose conversation with the LLM is sent again, so it keeps their taste; 0 starts cold
+    pub conversation_runs: usize,
+    // Proxy of every request (http://, https:// or socks5:// URL), the environment's HTTPS_PROXY when None
+    pub proxy: Option<String>,
+    // Proxy of the LLM and embeddings requests only, in place of `proxy`
+    pub llm_proxy: Option<String>,
+    pub llm_client_secret: Option<String>,
+    pub llm_api_url: String,
+    pub llm_model: String,
+    // Model of the --judge pass, usually a cheaper one; llm_model when None
+    pub judge_model: Option<String>,
+    // System message sent before every request to the LLM, its persona, tone and rules; none when empty
+    pub system_prompt: String,
+    // Other LLMs asked the same prompt by --ensemble
+    pub ensemble: Vec<ProviderSettings>,
+    // Playlist used when a command isn't given one, checked by the commands that fall back to it
+    pub playlist_id: Option<String>,
+    // Playlists a plain run suggests for one after the other, instead of playlist_id, when --playlist isn't given
+    pub playlists: Vec<String>,
+    // Matches scoring below this confidence are flagged for review instead of being added directly
+    pub min_confidence: f64,
+    // Country code (e.g. "FR") or "from_token" used to only match tracks playable in the user's market
+    pub market: Option<String>,
+    // Artists and tracks (Spotify URIs, links or titles) never suggested
+    pub exclude_artists: Vec<String>,
+    pub exclude_tracks: Vec<String>,
+    // OpenAI-compatible embeddings endpoint and model, called with the LLM API key
+    pub embeddings_api_url: String,
+    pub embeddings_model: String,
+    // OpenAI-compatible image generation endpoint and model, drawing the covers of --cover
+    pub image_api_url: String,
+    pub image_model: String,
+    // Key of the image API, the LLM API key when None
+    pub image_api_key: Option<String>,
+    // Key of the Last.fm API, needed by --lastfm
+    pub lastfm_api_key: Option<String>,
+    // Last.fm user whose scrobbles --throwback reads, None to only use the local history
+    pub lastfm_user: Option<String>,
+    // User token of ListenBrainz, needed by --listenbrainz
+    pub listenbrainz_token: Option<String>,
+    // Google OAuth client of the YouTube Data API, needed by --service ytmusic
+    pub google_client_id: Option<String>,
+    pub google_client_secret: Option<String>,
+    pub google_redirect_uri: String,
+    // MusicKit key of an Apple Developer account (team, key ID and .p8 file), needed by --service applemusic
+    pub apple_team_id: Option<String>,
+    pub apple_key_id: Option<String>,
+    pub apple_private_key_path: Option<String>,
+    // Music-User-Token of the Apple Music account, asked for in the browser when not set
+    pub apple_music_user_token: Option<String>,
+    // Country of the Apple Music catalog searched (e.g. "fr"), the account's own when not set
+    pub apple_storefront: Option<String>,
+    // Local address of the page asking for the Music-User-Token
+    pub apple_redirect_uri: String,
+    // Tidal API client allowed to use the device flow, needed by --service tidal (the secret only for confidential clients)
+    pub tidal_client_id: Option<String>,
+    pub tidal_client_secret: Option<String>,
+    // Deezer application, needed by --service deezer
+    pub deezer_app_id: Option<String>,
+    pub deezer_secret: Option<String>,
+    pub deezer_redirect_uri: String,
+    // Streaming service the suggestions go to when --service isn't given
+    pub service: Service,
+    // Discord or Slack webhook the tracks a run added are posted to
+    pub webhook_url: Option<String>,
+    // SMTP server the summary of a run is emailed through, to email_to, when both are set
+    pub smtp_host: Option<String>,
+    pub smtp_port: u16,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    // Sender of the summary, the SMTP username when not set
+    pub email_from: Option<String>,
+    pub email_to: Vec<String>,
+    // Price of the LLM, to estimate the cost of a run in the emailed summary
+    pub llm_price_per_million_tokens: Option<f64>,
+    // Directory where the Atom feed of each playlist's additions is kept up to date, none is when None
+    pub feed_dir: Option<String>,
+    // JSONL file every run appends a record of its inputs and decisions to, none is when None
+    pub audit_log: Option<String>,
+    // Description a Spotify playlist is given after a run adds to it, with {date} and {added} replaced; left as is when None
+    pub description_template: Option<String>,
+    // Playlists the weekly playlist is suggested from, the configured playlist when empty
+    pub weekly_seeds: Vec<String>,
+    // Profiles whose accounts take part in a group session, when the group command names none
+    pub group: Vec<String>,
+    // Address and port the web interface listens on
+    pub serve_address: String,
+    // Bearer token the JSON API of the web server requires, the API is off when None
+    pub api_token: Option<String>,
+    // Address the daemon serves its Prometheus metrics on, none is when None
+    pub metrics_address: Option<String>,
+}
+
+impl Config {
+    // Key under which this profile's tokens and secrets are stored
+    // The default profile keeps the plain name so existing caches stay valid.
+    pub fn storage_key(&self, name: &str) -> String {
+        match &self.profile {
+            Some(profile) => format!("{}:{}", profile, name),
+            None => name.to_string(),
+        }
+    }
+
+    // Function to get the default playlist, for the commands run without --playlist
+    pub fn default_playlist(&self) -> Result<String, PilotError> {
+        self.playlist_id.clone().ok_or_else(|| "playlist id not set".into())
+    }
+}
+
+// Function to find the config file: ./playlistpilot.toml, then the user config directory
+pub fn config_path() -> Option<PathBuf> {
+    let local = PathBuf::from("playlistpilot.toml");
+    if local.exists() {
+        return Some(local);
+    }
+    dirs::config_dir()
+        .map(|dir| dir.join("playlistpilot").join("config.toml"))
+        .filter(|path| path.exists())
+}
+
+// Function to read and parse the config file, if there is one
+pub fn load_config_file() -> Result<ConfigFile, PilotError> {
+    match config_path() {
+        Some(path) => {
+            let content = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            toml::from_str(&content).map_err(|e| format!("Invalid config file {}: {}", path.display(), e).into())
+        },
+        None => Ok(ConfigFile::default()),
+    }
+}
+
+// Reader of the environment variables settings are looked up in, by name
+pub type Environment = fn(&str) -> Option<String>;
+
+// Function to read a setting from the environment of the process
+fn process_env(key: &str) -> Option<String> {
+    env::var(key).ok()
+}
+
+// Function to look up one setting: the selected profile first, then the environment, then the file's top-level value
+// Environment values are strings and are parsed into the setting's type.
+fn lookup<T: Clone + FromStr>(
+    env: Environment,
+    selected: Option<&ProfileSettings>,
+    file: &ProfileSettings,
+    key: &str,
+    field: fn(&ProfileSettings) -> &Option<T>,
+) -> Result<Option<T>, PilotError> {
+    if let Some(value) = selected.and_then(|settings| field(settings).clone()) {
+        return Ok(Some(value));
+    }
+    if let Some(value) = env(key) {
+        return value.parse().map(Some).map_err(|_| format!("Invalid value for {}: {}", key, value).into());
+    }
+    Ok(field(file).clone())
+}
+
+// Function to look up a list setting, written as a comma-separated string in the environment
+fn lookup_list(
+    env: Environment,
+    selected: Option<&ProfileSettings>,
+    file: &ProfileSettings,
+    key: &str,
+    field: fn(&ProfileSettings) -> &Option<Vec<String>>,
+) -> Vec<String> {
+    if let Some(values) = selected.and_then(|settings| field(settings).clone()) {
+        return values;
+    }
+    if let Some(value) = env(key) {
+        return value.split(',').map(|item| item.trim().to_string()).filter(|item| !item.is_empty()).collect();
+    }
+    field(file).clone().unwrap_or_default()
+}
+
+// Function to check the values of a retry policy, `what` telling where they are set
+fn check_retry(what: &str, policy: RetryPolicy) -> Result<RetryPolicy, PilotError> {
+    if policy.max_attempts == 0 {
+        return Err(format!("Invalid {}: there must be at least 1 attempt", what).into());
+    }
+    if !(0.0..=1.0).contains(&policy.jitter) {
+        return Err(format!("Invalid {}: the jitter must be between 0 and 1", what).into());
+    }
+    Ok(policy)
+}
+
+// Function to resolve the retry policies: the retry_* settings make the default one, and each [retry.<endpoint>] table changes it
+// for one endpoint, a profile's table replacing the top-level one
+fn resolve_retry(env: Environment, selected: Option<&ProfileSettings>, file: &ProfileSettings) -> Result<RetryPolicies, PilotError> {
+    // Lists are comma-separated in the environment
+    let statuses = match (selected.and_then(|settings| settings.retry_statuses.clone()), env("retry_statuses")) {
+        (Some(statuses), _) => Some(statuses),
+        (None, Some(value)) => Some(
+            value
+                .split(',')
+                .map(|status| status.trim().parse())
+                .collect::<Result<Vec<u16>, _>>()
+                .map_err(|_| format!("Invalid value for retry_statuses: {}", value))?,
+        ),
+        (None, None) => file.retry_statuses.clone(),
+    };
+    let default = check_retry("retry settings", RetryPolicy {
+        max_attempts: lookup(env, selected, file, "retry_max_attempts", |s| &s.retry_max_attempts)?.unwrap_or(DEFAULT_RETRY_MAX_ATTEMPTS),
+        base_delay: Duration::from_millis(lookup(env, selected, file, "retry_base_delay_ms", |s| &s.retry_base_delay_ms)?.unwrap_or(DEFAULT_RETRY_BASE_DELAY_MS)),
+        jitter: lookup(env, selected, file, "retry_jitter", |s| &s.retry_jitter)?.unwrap_or(DEFAULT_RETRY_JITTER),
+        statuses: statuses.unwrap_or_else(|| DEFAULT_RETRY_STATUSES.to_vec()),
+    })?;
+
+    let own = selected.and_then(|settings| settings.retry.clone()).unwrap_or_default();
+    let top = file.retry.clone().unwrap_or_default();
+    if let Some(name) = own.keys().chain(top.keys()).find(|name| Endpoint::ALL.iter().all(|endpoint| endpoint.name() != name.as_str())) {
+        let names: Vec<&str> = Endpoint::ALL.iter().map(|endpoint| endpoint.name()).collect();
+        return Err(format!("Unknown endpoint in [retry.{}], the endpoints are {}", name, names.join(", ")).into());
+    }
+    let mut endpoints = HashMap::new();
+    for endpoint in Endpoint::ALL {
+        let Some(settings) = own.get(endpoint.name()).or_else(|| top.get(endpoint.name())) else { continue };
+        let policy = RetryPolicy {
+            max_attempts: settings.max_attempts.unwrap_or(default.max_attempts),
+            base_delay: settings.base_delay_ms.map_or(default.base_delay, Duration::from_millis),
+            jitter: settings.jitter.unwrap_or(default.jitter),
+            statuses: settings.statuses.clone().unwrap_or_else(|| default.statuses.clone()),
+        };
+        endpoints.insert(endpoint, check_retry(&format!("[retry.{}]", endpoint.name()), policy)?);
+    }
+    Ok(RetryPolicies { default, endpoints })
+}
+
+// Function to resolve the settings of the selected profile
+// The default profile reads the environment (.env) first and the config file second. A named profile
+// takes its own values first and falls back to the environment, then to the file's top-level values.
+pub fn load_config(profile: Option<&str>) -> Result<Config, PilotError> {
+    resolve_config(&load_config_file()?, profile)
+}
+
+// Function to read a preset of the config file by its name
+pub fn load_preset(name: &str) -> Result<PresetSettings, PilotError> {
+    let file = load_config_file()?;
+    file.presets.get(name).cloned().ok_or_else(|| {
+        let mut names: Vec<&str> = file.presets.keys().map(String::as_str).collect();
+        names.sort();
+        let known = if names.is_empty() { "there are none in the config file".to_string() } else { format!("presets: {}", names.join(", ")) };
+        PilotError::Usage(format!("Unknown preset {} ({})", name, known))
+    })
+}
+
+// Function to resolve the settings of a profile from an already read config file
+pub fn resolve_config(file: &ConfigFile, profile: Option<&str>) -> Result<Config, PilotError> {
+    resolve_config_from(file, profile, process_env)
+}
+
+// Function to resolve the settings of a profile from an already read config file and the given environment
+// Tests give an empty one, so the variables set where they run can't change them.
+pub fn resolve_config_from(file: &ConfigFile, profile: Option<&str>, env: Environment) -> Result<Config, PilotError> {
+    let selected = match profile {
+        Some(name) => Some(
+            file.profiles
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("Unknown profile: {}", name))?,
+        ),
+        None => None,
+    };
+    let selected = selected.as_ref();
+
+    Ok(Config {
+        profile: profile.map(|name| name.to_string()),
+        // Only needed when Spotify is used, checked when authorizing with it
+        spotify_client_id: lookup(env, selected, &file.default, "spotify_client_id", |s| &s.spotify_client_id)?.unwrap_or_default(),
+        spotify_client_secret: lookup(env, selected, &file.default, "spotify_client_secret", |s| &s.spotify_client_secret)?.unwrap_or_default(),
+        spotify_redirect_uri: lookup(env, selected, &file.default, "spotify_redirect_uri", |s| &s.spotify_redirect_uri)?.unwrap_or_default(),
+        spotify_api_url: lookup(env, selected, &file.default, "spotify_api_url", |s| &s.spotify_api_url)?
+            .unwrap_or_else(|| DEFAULT_SPOTIFY_API_URL.to_string()),
+        spotify_accounts_url: lookup(env, selected, &file.default, "spotify_accounts_url", |s| &s.spotify_accounts_url)?
+            .unwrap_or_else(|| DEFAULT_SPOTIFY_ACCOUNTS_URL.to_string()),
+        spotify_requests_per_second: lookup(env, selected, &file.default, "spotify_requests_per_second", |s| &s.spotify_requests_per_second)?
+            .unwrap_or(DEFAULT_SPOTIFY_REQUESTS_PER_SECOND),
+        connect_timeout_secs: lookup(env, selected, &file.default, "connect_timeout_secs", |s| &s.connect_timeout_secs)?
+            .unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS),
+        spotify_timeout_secs: lookup(env, selected, &file.default, "spotify_timeout_secs", |s| &s.spotify_timeout_secs)?
+            .unwrap_or(DEFAULT_SPOTIFY_TIMEOUT_SECS),
+        llm_timeout_secs: lookup(env, selected, &file.default, "llm_timeout_secs", |s| &s.llm_timeout_secs)?
+            .unwrap_or(DEFAULT_LLM_TIMEOUT_SECS),
+        retry: resolve_retry(env, selected, &file.default)?,
+        llm_context_tokens: match lookup(env, selected, &file.default, "llm_context_tokens", |s| &s.llm_context_tokens)? {
+            Some(tokens) => tokens,
+            None => context_window(lookup(env, selected, &file.default, "llm_model", |s| &s.llm_model)?.as_deref().unwrap_or(DEFAULT_LLM_MODEL)),
+        },
+        llm_cache_ttl_secs: lookup(env, selected, &file.default, "llm_cache_ttl_secs", |s| &s.llm_cache_ttl_secs)?
+            .unwrap_or(DEFAULT_LLM_CACHE_TTL_SECS),
+        conversation_runs: lookup(env, selected, &file.default, "conversation_runs", |s| &s.conversation_runs)?
+            .unwrap_or(DEFAULT_CONVERSATION_RUNS),
+        proxy: lookup(env, selected, &file.default, "proxy", |s| &s.proxy)?,
+        llm_proxy: lookup(env, selected, &file.default, "llm_proxy", |s| &s.llm_proxy)?,
+        llm_client_secret: lookup(env, selected, &file.default, "llm_client_secret", |s| &s.llm_client_secret)?,
+        llm_api_url: lookup(env, selected, &file.default, "llm_api_url", |s| &s.llm_api_url)?
+            .unwrap_or_else(|| DEFAULT_LLM_API_URL.to_string()),
+        llm_model: lookup(env, selected, &file.default, "llm_model", |s| &s.llm_model)?
+            .unwrap_or_else(|| DEFAULT_LLM_MODEL.to_string()),
+        judge_model: lookup(env, selected, &file.default, "judge_model", |s| &s.judge_model)?,
+        system_prompt: lookup(env, selected, &file.default, "system_prompt", |s| &s.system_prompt)?
+            .unwrap_or_else(|| DEFAULT_SYSTEM_PROMPT.to_string()),
+        // Tables can't be written in the environment, the profile's entries replace the top-level ones
+        ensemble: selected.and_then(|settings| settings.ensemble.clone()).or_else(|| file.default.ensemble.clone()).unwrap_or_default(),
+        playlist_id: lookup(env, selected, &file.default, "playlist_id", |s| &s.playlist_id)?,
+        playlists: lookup_list(env, selected, &file.default, "playlists", |s| &s.playlists),
+        min_confidence: lookup(env, selected, &file.default, "min_confidence", |s| &s.min_confidence)?
+            .unwrap_or(DEFAULT_MIN_CONFIDENCE),
+        market: lookup(env, selected, &file.default, "market", |s| &s.market)?,
+        exclude_artists: lookup_list(env, selected, &file.default, "exclude_artists", |s| &s.exclude_artists),
+        exclude_tracks: lookup_list(env, selected, &file.default, "exclude_tracks", |s| &s.exclude_tracks),
+        embeddings_api_url: lookup(env, selected, &file.default, "embeddings_api_url", |s| &s.embeddings_api_url)?
+            .unwrap_or_else(|| DEFAULT_EMBEDDINGS_API_URL.to_string()),
+        embeddings_model: lookup(env, selected, &file.default, "embeddings_model", |s| &s.embeddings_model)?
+            .unwrap_or_else(|| DEFAULT_EMBEDDINGS_MODEL.to_string()),
+        image_api_url: lookup(env, selected, &file.default, "image_api_url", |s| &s.image_api_url)?
+            .unwrap_or_else(|| DEFAULT_IMAGE_API_URL.to_string()),
+        image_model: lookup(env, selected, &file.default, "image_model", |s| &s.image_model)?
+            .unwrap_or_else(|| DEFAULT_IMAGE_MODEL.to_string()),
+        image_api_key: lookup(env, selected, &file.default, "image_api_key", |s| &s.image_api_key)?,
+        lastfm_api_key: lookup(env, selected, &file.default, "lastfm_api_key", |s| &s.lastfm_api_key)?,
+        lastfm_user: lookup(env, selected, &file.default, "lastfm_user", |s| &s.lastfm_user)?,
+        listenbrainz_token: lookup(env, selected, &file.default, "listenbrainz_token", |s| &s.listenbrainz_token)?,
+        google_client_id: lookup(env, selected, &file.default, "google_client_id", |s| &s.google_client_id)?,
+        google_client_secret: lookup(env, selected, &file.default, "google_client_secret", |s| &s.google_client_secret)?,
+        google_redirect_uri: lookup(env, selected, &file.default, "google_redirect_uri", |s| &s.google_redirect_uri)?
+            .unwrap_or_else(|| DEFAULT_GOOGLE_REDIRECT_URI.to_string()),
+        apple_team_id: lookup(env, selected, &file.default, "apple_team_id", |s| &s.apple_team_id)?,
+        apple_key_id: lookup(env, selected, &file.default, "apple_key_id", |s| &s.apple_key_id)?,
+        apple_private_key_path: lookup(env, selected, &file.default, "apple_private_key_path", |s| &s.apple_private_key_path)?,
+        apple_music_user_token: lookup(env, selected, &file.default, "apple_music_user_token", |s| &s.apple_music_user_token)?,
+        apple_storefront: lookup(env, selected, &file.default, "apple_storefront", |s| &s.apple_storefront)?,
+        apple_redirect_uri: lookup(env, selected, &file.default, "apple_redirect_uri", |s| &s.apple_redirect_uri)?
+            .unwrap_or_else(|| DEFAULT_APPLE_REDIRECT_URI.to_string()),
+        tidal_client_id: lookup(env, selected, &file.default, "tidal_client_id", |s| &s.tidal_client_id)?,
+        tidal_client_secret: lookup(env, selected, &file.default, "tidal_client_secret", |s| &s.tidal_client_secret)?,
+        deezer_app_id: lookup(env, selected, &file.default, "deezer_app_id", |s| &s.deezer_app_id)?,
+        deezer_secret: lookup(env, selected, &file.default, "deezer_secret", |s| &s.deezer_secret)?,
+        deezer_redirect_uri: lookup(env, selected, &file.default, "deezer_redirect_uri", |s| &s.deezer_redirect_uri)?
+            .unwrap_or_else(|| DEFAULT_DEEZER_REDIRECT_URI.to_string()),
+        service: lookup(env, selected, &file.default, "service", |s| &s.service)?
+            .map(|value| value.parse())
+            .transpose()?
+            .unwrap_or(Service::Spotify),
+        webhook_url: lookup(env, selected, &file.default, "webhook_url", |s| &s.webhook_url)?,
+        smtp_host: lookup(env, selected, &file.default, "smtp_host", |s| &s.smtp_host)?,
+        smtp_port: lookup(env, selected, &file.default, "smtp_port", |s| &s.smtp_port)?.unwrap_or(DEFAULT_SMTP_PORT),
+        smtp_username: lookup(env, selected, &file.default, "smtp_username", |s| &s.smtp_username)?,
+        smtp_password: lookup(env, selected, &file.default, "smtp_password", |s| &s.smtp_password)?,
+        email_from: lookup(env, selected, &file.default, "email_from", |s| &s.email_from)?,
+        email_to: lookup_list(env, selected, &file.default, "email_to", |s| &s.email_to),
+        llm_price_per_million_tokens: lookup(env, selected, &file.default, "llm_price_per_million_tokens", |s| &s.llm_price_per_million_tokens)?,
+        feed_dir: lookup(env, selected, &file.default, "feed_dir", |s| &s.feed_dir)?,
+        audit_log: lookup(env, selected, &file.default, "audit_log", |s| &s.audit_log)?,
+        description_template: lookup(env, selected, &file.default, "description_template", |s| &s.description_template)?,
+        weekly_seeds: lookup_list(env, selected, &file.default, "weekly_seeds", |s| &s.weekly_seeds),
+        group: lookup_list(env, selected, &file.default, "group", |s| &s.group),
+        serve_address: lookup(env, selected, &file.default, "serve_address", |s| &s.serve_address)?.unwrap_or_else(|| DEFAULT_SERVE_ADDRESS.to_string()),
+        api_token: lookup(env, selected, &file.default, "api_token", |s| &s.api_token)?,
+        metrics_address: lookup(env, selected, &file.default, "metrics_address", |s| &s.metrics_address)?,
+    })
+}
@@ -0,0 +1,13 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+
+use crate::config::Config;
+
+// State shared by every API call of a run
+pub struct Context {
+    // Single HTTP client reused by every call, so connections are pooled
+    pub client: Client,
+    pub config: Config,
+    // Spotify access token of the current user (or of the app in read-only runs)
+    pub access_token: String,
+}
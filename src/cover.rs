@@ -0,0 +1,117 @@
+// Import necessary modules and crates
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::http::send;
+use crate::models::*;
+use crate::spotify::upload_playlist_cover;
+
+// Sizes of the covers, in pixels: what Spotify shows them at, then smaller ones for images that compress badly
+const COVER_SIZES: [u32; 3] = [640, 480, 320];
+
+// Largest cover Spotify accepts, base64 encoded, in bytes
+pub const MAX_COVER_LENGTH: usize = 256 * 1024;
+
+// JPEG qualities tried in turn until the cover is small enough
+const QUALITIES: [u8; 6] = [90, 80, 70, 60, 50, 40];
+
+// Function to write the prompt of a playlist's cover from its name, a few of its artists and the vibe asked for
+pub fn cover_prompt(name: &str, artists: &[String], vibe: Option<&str>) -> String {
+    let mut prompt = format!("Square cover art for a music playlist named '{}'", name);
+    if !artists.is_empty() {
+        prompt.push_str(&format!(", with songs by {}", artists.join(", ")));
+    }
+    if let Some(vibe) = vibe {
+        prompt.push_str(&format!(", with this vibe: {}", vibe));
+    }
+    prompt.push_str(". Evocative and abstract, no text, no letters, no logos.");
+    prompt
+}
+
+// Function to generate an image with the configured image API, returning the image file (PNG or JPEG)
+pub fn generate_image(ctx: &Context, api_key: &str, prompt: &str) -> Result<Vec<u8>, PilotError> {
+    let request_body = ImageRequest {
+        model: ctx.config.image_model.clone(),
+        prompt: prompt.to_string(),
+        n: 1,
+        size: "1024x1024".to_string(),
+        response_format: "b64_json".to_string(),
+    };
+    let response = send(
+        ctx.client
+            .post(&ctx.config.image_api_url)
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json")
+            .json(&request_body)
+    )?;
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Error generating the cover: {}", response.status())));
+    }
+
+    let generated: ImageResponse = response.json().map_err(|e| format!("Failed to parse the generated image: {}", e))?;
+    let image = generated.data.into_iter().next().ok_or("The image API gave no image")?;
+    match (image.b64_json, image.url) {
+        (Some(data), _) => STANDARD.decode(data.trim()).map_err(|e| format!("Invalid generated image: {}", e).into()),
+        // Some providers only link to the image
+        (None, Some(url)) => {
+            let response = send(ctx.client.get(&url))?;
+            if !response.status().is_success() {
+                return Err(PilotError::from_status(response.status(), format!("Error downloading the cover: {}", response.status())));
+            }
+            Ok(response.bytes()?.to_vec())
+        },
+        (None, None) => Err("The image API gave no image".into()),
+    }
+}
+
+// Function to turn an image into the base64 JPEG Spotify accepts as a cover: square, and small enough
+// The quality goes down first, then the size.
+pub fn spotify_jpeg(image: &[u8]) -> Result<String, PilotError> {
+    let image = image::load_from_memory(image).map_err(|e| format!("Could not read the generated image: {}", e))?;
+    for size in COVER_SIZES {
+        let cover = image.resize_to_fill(size, size, FilterType::Lanczos3).to_rgb8();
+        for quality in QUALITIES {
+            let mut jpeg = Vec::new();
+            cover.write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, quality)).map_err(|e| format!("Could not encode the cover: {}", e))?;
+            let encoded = STANDARD.encode(&jpeg);
+            if encoded.len() <= MAX_COVER_LENGTH {
+                return Ok(encoded);
+            }
+        }
+    }
+    Err("The cover is too large for Spotify even small and at a low quality".into())
+}
+
+// Function to generate a cover for a playlist and upload it to Spotify
+pub fn make_cover(ctx: &Context, api_key: &str, playlist_id: &str, prompt: &str) -> Result<(), PilotError> {
+    let image = generate_image(ctx, api_key, prompt)?;
+    upload_playlist_cover(ctx, playlist_id, spotify_jpeg(&image)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgb, RgbImage};
+    use std::io::Cursor;
+
+    #[test]
+    fn spotify_jpeg_makes_a_small_square_cover() {
+        // Noise compresses badly, the quality and the size have to go down to fit
+        let mut seed: u32 = 7;
+        let noise = RgbImage::from_fn(800, 600, |_, _| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            Rgb([(seed >> 16) as u8, (seed >> 8) as u8, seed as u8])
+        });
+        let mut png = Vec::new();
+        noise.write_to(&mut Cursor::new(&mut png), ImageFormat::Png).unwrap();
+        let encoded = spotify_jpeg(&png).unwrap();
+        assert!(encoded.len() <= MAX_COVER_LENGTH);
+        let cover = image::load_from_memory_with_format(&STANDARD.decode(encoded).unwrap(), ImageFormat::Jpeg).unwrap();
+        assert_eq!(cover.width(), cover.height());
+        assert!(COVER_SIZES.contains(&cover.width()));
+    }
+}
@@ -0,0 +1,117 @@
+// Import necessary modules and crates
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::RngCore;
+use std::env;
+use std::fs;
+use std::sync::OnceLock;
+
+use crate::error::PilotError;
+use crate::tokens::data_dir;
+
+// Prefix marking an encrypted file, followed by base64(salt | nonce | ciphertext)
+const ENCRYPTED_PREFIX: &str = "playlistpilot-encrypted-v1:";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+// Passphrase used for every encrypted file during this run, set once at startup
+static PASSPHRASE: OnceLock<String> = OnceLock::new();
+
+// Function to check whether a file content was written encrypted
+pub fn is_encrypted(content: &str) -> bool {
+    content.starts_with(ENCRYPTED_PREFIX)
+}
+
+// Function to derive the 256-bit file key from the passphrase and a salt
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], PilotError> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Failed to derive the encryption key: {}", e))?;
+    Ok(key)
+}
+
+// Function to encrypt a file content with a passphrase
+fn encrypt_with(passphrase: &str, plaintext: &str) -> Result<String, PilotError> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let key = derive_key(passphrase, &salt)?;
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&key))
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+        .map_err(|_| "Failed to encrypt the file".to_string())?;
+
+    let mut payload = Vec::with_capacity(SALT_LEN + NONCE_LEN + ciphertext.len());
+    payload.extend_from_slice(&salt);
+    payload.extend_from_slice(&nonce);
+    payload.extend_from_slice(&ciphertext);
+    Ok(format!("{}{}", ENCRYPTED_PREFIX, STANDARD.encode(payload)))
+}
+
+// Function to decrypt a file content with a passphrase
+fn decrypt_with(passphrase: &str, content: &str) -> Result<String, PilotError> {
+    let encoded = content.trim().strip_prefix(ENCRYPTED_PREFIX).ok_or("The file is not encrypted")?;
+    let payload = STANDARD.decode(encoded).map_err(|_| "The encrypted file is corrupted".to_string())?;
+    if payload.len() < SALT_LEN + NONCE_LEN {
+        return Err("The encrypted file is corrupted".into());
+    }
+    let (salt, rest) = payload.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let key = derive_key(passphrase, salt)?;
+    let plaintext = ChaCha20Poly1305::new(Key::from_slice(&key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Wrong passphrase for the encrypted token cache".to_string())?;
+    String::from_utf8(plaintext).map_err(|e| e.to_string().into())
+}
+
+// Function to encrypt a file content with the passphrase of this run, or return it unchanged when encryption is off
+pub fn seal(plaintext: &str) -> Result<String, PilotError> {
+    match PASSPHRASE.get() {
+        Some(passphrase) => encrypt_with(passphrase, plaintext),
+        None => Ok(plaintext.to_string()),
+    }
+}
+
+// Function to decrypt a file content with the passphrase of this run, passing plaintext files through
+pub fn open(content: &str) -> Result<String, PilotError> {
+    if !is_encrypted(content) {
+        return Ok(content.to_string());
+    }
+    match PASSPHRASE.get() {
+        Some(passphrase) => decrypt_with(passphrase, content),
+        None => Err("The token cache is encrypted but no passphrase was given".into()),
+    }
+}
+
+// Function to set up encryption of the local token and secrets files at startup
+// The passphrase comes from `token_cache_passphrase`, or is prompted for when `encrypt_token_cache=true`
+// or when an already encrypted file is found. It is checked against existing files before anything is written.
+pub fn init_encryption() -> Result<(), PilotError> {
+    let existing: Vec<String> = ["tokens.json", "secrets.json"]
+        .iter()
+        .filter_map(|name| data_dir().ok().map(|dir| dir.join(name)))
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .filter(|content| is_encrypted(content))
+        .collect();
+
+    let requested = env::var("encrypt_token_cache").map(|value| value == "true").unwrap_or(false);
+    let passphrase = match env::var("token_cache_passphrase") {
+        Ok(passphrase) => passphrase,
+        Err(_) if requested || !existing.is_empty() => {
+            rpassword::prompt_password("Enter the passphrase for the token cache: ").map_err(|e| e.to_string())?
+        },
+        Err(_) => return Ok(()),
+    };
+
+    for content in &existing {
+        decrypt_with(&passphrase, content)?;
+    }
+    let _ = PASSPHRASE.set(passphrase);
+    Ok(())
+}
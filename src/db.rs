@@ -0,0 +1,118 @@
+// Import necessary modules and crates
+use rusqlite::Connection;
+use std::fs;
+
+use crate::error::PilotError;
+use crate::tokens::data_dir;
+
+// Tables of the local database, created on first use
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS search_cache (
+        query TEXT PRIMARY KEY,
+        uri TEXT NOT NULL,
+        cached_at INTEGER NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS llm_cache (
+        request TEXT PRIMARY KEY,
+        answer TEXT NOT NULL,
+        cached_at INTEGER NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS runs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        started_at INTEGER NOT NULL,
+        profile TEXT,
+        playlist_id TEXT NOT NULL,
+        prompt TEXT NOT NULL,
+        dry_run INTEGER NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS run_tracks (
+        run_id INTEGER NOT NULL REFERENCES runs (id),
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        artist TEXT NOT NULL,
+        uri TEXT,
+        matched_name TEXT,
+        matched_artists TEXT,
+        confidence REAL,
+        status TEXT NOT NULL,
+        source TEXT NOT NULL DEFAULT 'llm',
+        PRIMARY KEY (run_id, position)
+    );
+
+    CREATE TABLE IF NOT EXISTS embedding_cache (
+        uri TEXT NOT NULL,
+        model TEXT NOT NULL,
+        vector TEXT NOT NULL,
+        cached_at INTEGER NOT NULL,
+        PRIMARY KEY (uri, model)
+    );
+
+    CREATE TABLE IF NOT EXISTS feedback (
+        playlist_id TEXT NOT NULL,
+        uri TEXT NOT NULL,
+        name TEXT NOT NULL,
+        artists TEXT NOT NULL,
+        rating TEXT NOT NULL,
+        rated_at INTEGER NOT NULL,
+        PRIMARY KEY (playlist_id, uri)
+    );
+
+    CREATE TABLE IF NOT EXISTS weekly (
+        profile TEXT PRIMARY KEY,
+        playlist_id TEXT NOT NULL,
+        filled_at INTEGER,
+        archived INTEGER NOT NULL DEFAULT 0
+    );
+
+    CREATE TABLE IF NOT EXISTS conversations (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        playlist_id TEXT NOT NULL,
+        saved_at INTEGER NOT NULL,
+        messages TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS snapshots (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        playlist_id TEXT NOT NULL,
+        taken_at INTEGER NOT NULL,
+        snapshot_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        items TEXT NOT NULL
+    );
+";
+
+// Columns added to tables after their creation, as (table, column, definition), added to older databases on open
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("run_tracks", "source", "TEXT NOT NULL DEFAULT 'llm'"),
+    ("runs", "playlist_name", "TEXT"),
+    ("runs", "service", "TEXT"),
+    ("runs", "snapshot_id", "TEXT"),
+    ("run_tracks", "added_position", "INTEGER"),
+];
+
+// Function to open the local PlaylistPilot database, creating it and its tables if needed
+pub fn open_database() -> Result<Connection, PilotError> {
+    let dir = data_dir()?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+    let connection = Connection::open(dir.join("playlistpilot.db")).map_err(|e| e.to_string())?;
+    connection.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+    for (table, column, definition) in ADDED_COLUMNS {
+        let exists: bool = connection
+            .query_row(
+                &format!("SELECT COUNT(*) > 0 FROM pragma_table_info('{}') WHERE name = ?1", table),
+                [column],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if !exists {
+            connection
+                .execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(connection)
+}
@@ -0,0 +1,245 @@
+// Import necessary modules and crates
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::{Method, Url};
+use std::path::PathBuf;
+use tracing::warn;
+
+use crate::auth::{authorization_code, generate_state};
+use crate::backup::{save_backup, Backup, BackupItem};
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::matching::*;
+use crate::models::*;
+use crate::service::{MusicService, NewPlaylist, ServicePlaylist};
+use crate::tokens::*;
+
+// Endpoints of the Deezer authorization server and API
+const DEEZER_AUTH_URL: &str = "https://connect.deezer.com/oauth/auth.php";
+const DEEZER_TOKEN_URL: &str = "https://connect.deezer.com/oauth/access_token.php";
+const DEEZER_API_URL: &str = "https://api.deezer.com";
+
+// Permissions requested from the user: managing their playlists, with a token that doesn't expire
+const DEEZER_PERMS: &str = "basic_access,manage_library,offline_access";
+
+// Lifetime given to a token Deezer says never expires, in seconds
+const NEVER_EXPIRES: u64 = 10 * 365 * 24 * 60 * 60;
+
+// Prefix of the track URIs, to tell Deezer tracks apart from Spotify tracks in the history
+const URI_PREFIX: &str = "deezer:track:";
+
+// Number of playlist tracks read, and of tracks added, per call
+const PAGE_SIZE: &str = "100";
+const ADD_BATCH_SIZE: usize = 50;
+
+// Deezer, through its API
+pub struct Deezer;
+
+// Function to describe a Deezer track as a track
+fn deezer_track(track: DeezerTrack) -> Track {
+    Track {
+        name: track.title,
+        artists: vec![Artist { name: track.artist.name, id: None }],
+        uri: format!("{}{}", URI_PREFIX, track.id),
+        is_local: false,
+        external_ids: ExternalIds { isrc: track.isrc },
+        is_playable: track.readable,
+        duration_ms: track.duration * 1000,
+        popularity: None,
+        album: match track.album {
+            Some(album) => Album {
+                name: album.title,
+                release_date: track.release_date,
+                images: album.cover_small.map(|url| Image { url, width: Some(56) }).into_iter().collect(),
+            },
+            None => Album { release_date: track.release_date, ..Album::default() },
+        },
+        explicit: track.explicit_lyrics,
+    }
+}
+
+// Function to get the track ID of a track URI
+fn track_id(uri: &str) -> &str {
+    uri.strip_prefix(URI_PREFIX).unwrap_or(uri)
+}
+
+// Function to prepare a call to the Deezer API on behalf of the user
+fn request(ctx: &Context, method: Method, path: &str) -> RequestBuilder {
+    ctx.client
+        .request(method, format!("{}/{}", DEEZER_API_URL, path.trim_start_matches('/')))
+        .query(&[("access_token", ctx.access_token.as_str())])
+}
+
+// Function to send a call to the Deezer API and read its answer
+// Deezer answers errors with a 200 and an `error` object, which is turned into an error here.
+fn send<T: serde::de::DeserializeOwned>(builder: RequestBuilder) -> Result<T, PilotError> {
+    let response = crate::http::send(builder)?;
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Error calling Deezer: {}", response.status())));
+    }
+    let body: serde_json::Value = response.json().map_err(|e| format!("Failed to parse the Deezer response: {}", e))?;
+    if let Some(error) = body.get("error") {
+        let message = error.get("message").and_then(|message| message.as_str()).unwrap_or("unknown error");
+        return Err(format!("Deezer error: {}", message).into());
+    }
+    serde_json::from_value(body).map_err(|e| format!("Failed to parse the Deezer response: {}", e).into())
+}
+
+impl MusicService for Deezer {
+    fn name(&self) -> &'static str {
+        "Deezer"
+    }
+
+    // Deezer gives no refresh tokens, the offline_access permission makes its tokens last instead
+    fn access_token(&self, client: &Client, config: &Config, headless: bool, _read_only: bool) -> Result<String, PilotError> {
+        let app_id = config.deezer_app_id.as_deref().ok_or("deezer app id not set")?;
+        let secret = config.deezer_secret.as_deref().ok_or("deezer secret not set")?;
+        let cache_key = config.storage_key(&format!("deezer:{}", app_id));
+        if let Some(token) = load_token(&cache_key).filter(|token| token.scope == DEEZER_PERMS && token.expires_at > now() + 60) {
+            return Ok(token.access_token);
+        }
+
+        let auth = generate_state();
+        let redirect_uri = config.deezer_redirect_uri.as_str();
+        let auth_url = Url::parse_with_params(
+            DEEZER_AUTH_URL,
+            &[("app_id", app_id), ("redirect_uri", redirect_uri), ("perms", DEEZER_PERMS), ("state", &auth.state)],
+        )
+        .map_err(|e| e.to_string())?;
+        let code = authorization_code(auth_url.as_str(), redirect_uri, &auth, headless)?;
+        let response = crate::http::send(
+            client
+                .get(DEEZER_TOKEN_URL)
+                .query(&[("app_id", app_id), ("secret", secret), ("code", &code), ("output", "json")])
+        )?;
+        if !response.status().is_success() {
+            return Err(PilotError::Auth(format!("Failed to get a Deezer access token: {}", response.status())));
+        }
+        let response: DeezerAuthResponse = response.json().map_err(|_| "Deezer refused the authorization code")?;
+
+        let lifetime = if response.expires == 0 { NEVER_EXPIRES } else { response.expires };
+        let token = CachedToken {
+            access_token: response.access_token,
+            refresh_token: None,
+            expires_at: now() + lifetime,
+            scope: DEEZER_PERMS.to_string(),
+        };
+        if let Err(e) = save_token(&cache_key, &token) {
+            warn!("Could not save the access token: {}", e);
+        }
+        Ok(token.access_token)
+    }
+
+    // Tracks no longer available in the user's country are skipped
+    fn get_playlist(&self, ctx: &Context, playlist_id: &str) -> Result<ServicePlaylist, PilotError> {
+        let playlist: DeezerPlaylist = send(request(ctx, Method::GET, &format!("playlist/{}", playlist_id)))?;
+
+        let mut items = Vec::new();
+        let mut page: DeezerTracks =
+            send(request(ctx, Method::GET, &format!("playlist/{}/tracks", playlist_id)).query(&[("limit", PAGE_SIZE)]))?;
+        loop {
+            items.extend(page.data);
+            // The next page is a full URL, only its path and query are kept
+            let Some(next) = page.next.as_deref().and_then(|next| next.strip_prefix(DEEZER_API_URL)) else { break };
+            let next = next.to_string();
+            page = send(ctx.client.get(format!("{}{}", DEEZER_API_URL, next)).query(&[("access_token", ctx.access_token.as_str())]))?;
+        }
+
+        let length = items.len();
+        let tracks: Vec<Track> = items
+            .into_iter()
+            .filter(|track| track.readable != Some(false))
+            .map(deezer_track)
+            .collect();
+        let skipped = length - tracks.len();
+        Ok(ServicePlaylist { name: playlist.title, snapshot_id: None, length, tracks, skipped })
+    }
+
+    // A song with an ISRC is looked up by it first, then the catalog is searched by artist and title
+    // The advanced search syntax is tried first, a plain query only when it finds nothing confident enough.
+    fn search_song(&self, ctx: &Context, song: &Song, filter: &TrackFilter) -> Result<TrackMatch, PilotError> {
+        if let Some(isrc) = &song.isrc {
+            let found: Result<DeezerTrack, PilotError> = send(request(ctx, Method::GET, &format!("track/isrc:{}", isrc.trim())));
+            if let Ok(track) = found {
+                let candidates: Vec<Track> = vec![deezer_track(track)].into_iter().filter(|candidate| filter.allows(candidate)).collect();
+                if let Some(found) = best_candidate(&candidates, &song.artist, &song.name).filter(|found| found.confidence >= ctx.config.min_confidence) {
+                    return Ok(TrackMatch { confidence: 1.0, ..found });
+                }
+            }
+        }
+
+        let queries = [
+            format!("artist:\"{}\" track:\"{}\"", song.artist.replace('"', ""), song.name.replace('"', "")),
+            format!("{} {}", song.artist, song.name),
+        ];
+        let mut best: Option<TrackMatch> = None;
+        for query in queries {
+            let results: DeezerTracks = send(request(ctx, Method::GET, "search/track").query(&[("q", query.as_str()), ("limit", "10")]))?;
+            let candidates: Vec<Track> = results
+                .data
+                .into_iter()
+                .filter(|track| track.readable != Some(false))
+                .map(deezer_track)
+                .filter(|candidate| filter.allows(candidate) && !is_other_recording(candidate, &song.name))
+                .collect();
+            if let Some(found) = best_candidate(&candidates, &song.artist, &song.name) {
+                if found.confidence >= ctx.config.min_confidence {
+                    return Ok(found);
+                }
+                if best.as_ref().is_none_or(|best| found.confidence > best.confidence) {
+                    best = Some(found);
+                }
+            }
+        }
+
+        best.ok_or_else(|| {
+            if filter.is_active() {
+                "No result meeting the filters found for the specified artist and track.".into()
+            } else {
+                "No result found for the specified artist and track.".into()
+            }
+        })
+    }
+
+    // New Deezer playlists are public, so it is made private with its description right after
+    fn create_playlist(&self, ctx: &Context, name: &str, description: &str) -> Result<NewPlaylist, PilotError> {
+        let created: DeezerCreatedPlaylist = send(request(ctx, Method::POST, "user/me/playlists").query(&[("title", name)]))?;
+        let id = created.id.to_string();
+        let _: bool = send(request(ctx, Method::POST, &format!("playlist/{}", id)).query(&[("description", description), ("public", "false")]))?;
+        let url = format!("https://www.deezer.com/playlist/{}", id);
+        Ok(NewPlaylist { id, url })
+    }
+
+    // Deezer only appends to playlists, --position is refused before the run starts and here for the other callers
+    fn add_to_playlist(&self, ctx: &Context, playlist_id: &str, uris: Vec<String>, position: Option<usize>) -> Result<(), PilotError> {
+        if position.is_some() {
+            return Err(PilotError::Usage("Deezer only adds tracks at the end of a playlist, a position can't be given".to_string()));
+        }
+        // The IDs go in the URL, long lists are sent in batches
+        for batch in uris.chunks(ADD_BATCH_SIZE) {
+            let songs: Vec<&str> = batch.iter().map(|uri| track_id(uri)).collect();
+            let _: bool = send(request(ctx, Method::POST, &format!("playlist/{}/tracks", playlist_id)).query(&[("songs", songs.join(","))]))
+                .map_err(|e| format!("Failed to add tracks to playlist: {}", e))?;
+        }
+        Ok(())
+    }
+
+    fn backup_playlist(&self, ctx: &Context, playlist_id: &str) -> Result<PathBuf, PilotError> {
+        let playlist = self.get_playlist(ctx, playlist_id)?;
+        save_backup(&Backup {
+            playlist_id: playlist_id.to_string(),
+            snapshot_id: String::new(),
+            created_at: now(),
+            items: playlist
+                .tracks
+                .into_iter()
+                .map(|track| BackupItem {
+                    uri: track.uri,
+                    name: track.name,
+                    artists: track.artists.into_iter().map(|a| a.name).collect(),
+                    is_local: false,
+                })
+                .collect(),
+        })
+    }
+}
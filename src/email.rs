@@ -0,0 +1,237 @@
+// Import necessary modules and crates
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use openssl::ssl::{SslConnector, SslMethod};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::error::PilotError;
+use crate::history::{Status, Suggestion};
+use crate::report::track_link;
+use crate::tokens::{civil_date, now};
+
+// Port on which the connection is encrypted from the start instead of upgraded with STARTTLS
+const IMPLICIT_TLS_PORT: u16 = 465;
+
+// Time given to the mail server to answer each command
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Names of the days and months in the Date header, which doesn't depend on the locale
+const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+// Function to format a Unix time as an RFC 5322 date in UTC, e.g. "Tue, 21 May 2024 18:03:12 +0000"
+fn email_date(timestamp: u64) -> String {
+    let days = timestamp / 86400;
+    let seconds = timestamp % 86400;
+    let (year, month, day) = civil_date(days as i64);
+    format!(
+        "{}, {} {} {} {:02}:{:02}:{:02} +0000",
+        DAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+// Function to write the summary of a run: what was added, what couldn't be matched or added, and what the LLM used
+// The cost is only estimated when llm_price_per_million_tokens is set, and from the tokens the API reported.
+pub fn run_summary(playlist: &str, suggestions: &[Suggestion], tokens: u64, price_per_million: Option<f64>) -> (String, String) {
+    let added: Vec<&Suggestion> = suggestions.iter().filter(|s| s.status == Status::Added).collect();
+    let missed: Vec<&Suggestion> = suggestions.iter().filter(|s| matches!(s.status, Status::NotFound | Status::Failed)).collect();
+    let plural = if added.len() == 1 { "" } else { "s" };
+    let subject = format!("PlaylistPilot added {} song{} to {}", added.len(), plural, playlist);
+
+    let mut lines = vec![format!("{}.", subject), String::new()];
+    if !added.is_empty() {
+        lines.push("Added:".to_string());
+        for suggestion in &added {
+            let Some(found) = &suggestion.found else { continue };
+            let line = format!("- {} – {}", found.artists.join(", "), found.name);
+            lines.push(match track_link(&found.uri) {
+                Some(link) => format!("{} ({})", line, link),
+                None => line,
+            });
+        }
+        lines.push(String::new());
+    }
+    if !missed.is_empty() {
+        lines.push("Not added:".to_string());
+        for suggestion in &missed {
+            let reason = if suggestion.status == Status::NotFound { "not found" } else { "failed to add" };
+            lines.push(format!("- {} – {} ({})", suggestion.song.artist, suggestion.song.name, reason));
+        }
+        lines.push(String::new());
+    }
+    let others = suggestions.len() - added.len() - missed.len();
+    if others > 0 {
+        lines.push(format!("{} other suggestions were left out (duplicates, filters, blacklist...).", others));
+    }
+    match price_per_million {
+        Some(price) => lines.push(format!("LLM usage: {} tokens, about {:.4} in cost.", tokens, tokens as f64 * price / 1_000_000.0)),
+        None => lines.push(format!("LLM usage: {} tokens.", tokens)),
+    }
+    (subject, lines.join("\n"))
+}
+
+// A connection to an SMTP server, plain or encrypted
+struct Smtp<S: Read + Write> {
+    stream: BufReader<S>,
+}
+
+impl<S: Read + Write> Smtp<S> {
+    // Function to read a reply, which may span several "250-..." lines, and check its code
+    fn reply(&mut self, expected: &str) -> Result<String, PilotError> {
+        let mut text = String::new();
+        loop {
+            let mut line = String::new();
+            self.stream.read_line(&mut line).map_err(|e| format!("Mail server: {}", e))?;
+            if line.len() < 4 {
+                return Err(format!("Unexpected answer of the mail server: {}", line.trim()).into());
+            }
+            text.push_str(&line[4..]);
+            if line.as_bytes()[3] != b'-' {
+                if !line.starts_with(expected) {
+                    return Err(format!("The mail server refused the email: {}", line.trim()).into());
+                }
+                return Ok(text);
+            }
+        }
+    }
+
+    // Function to send a command and check the code of its reply
+    fn command(&mut self, command: &str, expected: &str) -> Result<String, PilotError> {
+        let stream = self.stream.get_mut();
+        stream.write_all(format!("{}\r\n", command).as_bytes()).map_err(|e| format!("Mail server: {}", e))?;
+        self.reply(expected)
+    }
+
+    // Function to authenticate, if needed, and send the email over a connection that was greeted
+    fn send(&mut self, config: &Config, from: &str, to: &[String], message: &str) -> Result<(), PilotError> {
+        if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+            let credentials = STANDARD.encode(format!("\0{}\0{}", username, password));
+            self.command(&format!("AUTH PLAIN {}", credentials), "235")?;
+        }
+        self.command(&format!("MAIL FROM:<{}>", from), "250")?;
+        for recipient in to {
+            self.command(&format!("RCPT TO:<{}>", recipient), "250")?;
+        }
+        self.command("DATA", "354")?;
+        self.command(&format!("{}\r\n.", message), "250")?;
+        let _ = self.command("QUIT", "221");
+        Ok(())
+    }
+}
+
+// Function to write an email as sent after DATA, lines starting with a dot being doubled
+fn email_message(from: &str, to: &[String], subject: &str, body: &str) -> String {
+    let headers = [
+        format!("From: PlaylistPilot <{}>", from),
+        format!("To: {}", to.join(", ")),
+        // Headers are ASCII, a subject with other characters (e.g. in the playlist name) is encoded
+        if subject.is_ascii() { format!("Subject: {}", subject) } else { format!("Subject: =?UTF-8?B?{}?=", STANDARD.encode(subject)) },
+        format!("Date: {}", email_date(now())),
+        "MIME-Version: 1.0".to_string(),
+        "Content-Type: text/plain; charset=utf-8".to_string(),
+        "Content-Transfer-Encoding: 8bit".to_string(),
+    ];
+    let body: Vec<String> = body.lines().map(|line| if line.starts_with('.') { format!(".{}", line) } else { line.to_string() }).collect();
+    format!("{}\r\n\r\n{}", headers.join("\r\n"), body.join("\r\n"))
+}
+
+// Function to email the summary of a run through the configured SMTP server
+// The connection is encrypted (directly on port 465, with STARTTLS otherwise); credentials are never sent in the clear.
+pub fn send_email(config: &Config, subject: &str, body: &str) -> Result<(), PilotError> {
+    let host = config.smtp_host.as_deref().ok_or("smtp host not set")?;
+    let from = config.email_from.as_deref().or(config.smtp_username.as_deref()).ok_or("email from not set")?;
+    let message = email_message(from, &config.email_to, subject, body);
+
+    let address = (host, config.smtp_port)
+        .to_socket_addrs()
+        .map_err(|e| format!("{}: {}", host, e))?
+        .next()
+        .ok_or_else(|| format!("{}: no address found", host))?;
+    let stream = TcpStream::connect_timeout(&address, SMTP_TIMEOUT).map_err(|e| format!("{}: {}", host, e))?;
+    stream.set_read_timeout(Some(SMTP_TIMEOUT))?;
+    let connector = SslConnector::builder(SslMethod::tls()).map_err(|e| e.to_string())?.build();
+    let ehlo = "EHLO playlistpilot";
+
+    if config.smtp_port == IMPLICIT_TLS_PORT {
+        let tls = connector.connect(host, stream).map_err(|e| format!("TLS with {}: {}", host, e))?;
+        let mut smtp = Smtp { stream: BufReader::new(tls) };
+        smtp.reply("220")?;
+        smtp.command(ehlo, "250")?;
+        return smtp.send(config, from, &config.email_to, &message);
+    }
+
+    let mut smtp = Smtp { stream: BufReader::new(stream) };
+    smtp.reply("220")?;
+    let extensions = smtp.command(ehlo, "250")?;
+    if extensions.lines().any(|line| line.trim().eq_ignore_ascii_case("STARTTLS")) {
+        smtp.command("STARTTLS", "220")?;
+        let tls = connector.connect(host, smtp.stream.into_inner()).map_err(|e| format!("TLS with {}: {}", host, e))?;
+        let mut smtp = Smtp { stream: BufReader::new(tls) };
+        smtp.command(ehlo, "250")?;
+        return smtp.send(config, from, &config.email_to, &message);
+    }
+    if config.smtp_username.is_some() {
+        return Err(format!("{} doesn't offer STARTTLS, the credentials weren't sent", host).into());
+    }
+    // A local relay without encryption or credentials
+    smtp.send(config, from, &config.email_to, &message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Source;
+    use crate::matching::TrackMatch;
+    use crate::models::Song;
+
+    fn suggestion(artist: &str, name: &str, status: Status) -> Suggestion {
+        Suggestion {
+            song: Song { name: name.to_string(), artist: artist.to_string(), isrc: None, mbid: None },
+            found: (status != Status::NotFound).then(|| TrackMatch {
+                uri: "spotify:track:abc".to_string(),
+                name: name.to_string(),
+                artists: vec![artist.to_string()],
+                artist_ids: Vec::new(),
+                confidence: 1.0,
+                image: None,
+                popularity: None,
+                tempo: None,
+            }),
+            status,
+            source: Source::Llm,
+        }
+    }
+
+    #[test]
+    fn run_summary_lists_added_and_missed_songs_with_the_cost() {
+        let suggestions = [
+            suggestion("The Who", "Baba O'Riley", Status::Added),
+            suggestion("Nobody", "Made Up", Status::NotFound),
+            suggestion("The Who", "My Generation", Status::Duplicate),
+        ];
+        let (subject, body) = run_summary("Rock", &suggestions, 12_000, Some(0.5));
+        assert_eq!(subject, "PlaylistPilot added 1 song to Rock");
+        assert_eq!(
+            body,
+            "PlaylistPilot added 1 song to Rock.\n\nAdded:\n- The Who – Baba O'Riley (https://open.spotify.com/track/abc)\n\n\
+             Not added:\n- Nobody – Made Up (not found)\n\n1 other suggestions were left out (duplicates, filters, blacklist...).\n\
+             LLM usage: 12000 tokens, about 0.0060 in cost."
+        );
+    }
+
+    #[test]
+    fn email_date_formats_utc_dates() {
+        assert_eq!(email_date(0), "Thu, 1 Jan 1970 00:00:00 +0000");
+        assert_eq!(email_date(1_716_314_592), "Tue, 21 May 2024 18:03:12 +0000");
+        assert_eq!(email_date(951_782_400), "Tue, 29 Feb 2000 00:00:00 +0000");
+    }
+}
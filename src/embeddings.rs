@@ -0,0 +1,91 @@
+// Import necessary modules and crates
+use tracing::warn;
+use crate::cache::EmbeddingCache;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::http::send;
+use crate::models::*;
+
+// Maximum number of texts sent in a single embeddings call
+const EMBEDDINGS_BATCH_SIZE: usize = 100;
+
+// Function to embed texts with the configured embeddings API, returning one vector per text in the same order
+pub fn embed(ctx: &Context, api_key: &str, texts: &[String]) -> Result<Vec<Vec<f64>>, PilotError> {
+    let mut vectors = Vec::with_capacity(texts.len());
+    for chunk in texts.chunks(EMBEDDINGS_BATCH_SIZE) {
+        let request_body = EmbeddingsRequest {
+            model: ctx.config.embeddings_model.clone(),
+            input: chunk.to_vec(),
+        };
+        let response = send(
+            ctx.client
+                .post(&ctx.config.embeddings_api_url)
+                .header("Authorization", format!("Bearer {}", api_key))
+                .header("Content-Type", "application/json")
+                .json(&request_body)
+        )?;
+        if !response.status().is_success() {
+            return Err(PilotError::from_status(response.status(), format!("Error fetching embeddings: {}", response.status())));
+        }
+
+        let mut page: EmbeddingsResponse = response.json().map_err(|e| format!("Failed to parse embeddings: {}", e))?;
+        if page.data.len() != chunk.len() {
+            return Err(format!("Expected {} embeddings, got {}", chunk.len(), page.data.len()).into());
+        }
+        page.data.sort_by_key(|embedding| embedding.index);
+        vectors.extend(page.data.into_iter().map(|embedding| embedding.embedding));
+    }
+    Ok(vectors)
+}
+
+// Function to embed tracks given as (URI, text) pairs, reusing and filling the cache when there is one
+pub fn embed_tracks(ctx: &Context, api_key: &str, cache: Option<&EmbeddingCache>, tracks: &[(String, String)]) -> Result<Vec<Vec<f64>>, PilotError> {
+    let model = &ctx.config.embeddings_model;
+    let mut vectors: Vec<Option<Vec<f64>>> = tracks.iter().map(|(uri, _)| cache.and_then(|cache| cache.get(uri, model))).collect();
+    let missing: Vec<usize> = (0..tracks.len()).filter(|index| vectors[*index].is_none()).collect();
+    if !missing.is_empty() {
+        let texts: Vec<String> = missing.iter().map(|index| tracks[*index].1.clone()).collect();
+        for (index, vector) in missing.into_iter().zip(embed(ctx, api_key, &texts)?) {
+            if let Some(cache) = cache {
+                if let Err(e) = cache.put(&tracks[index].0, model, &vector) {
+                    warn!("Could not cache the embedding: {}", e);
+                }
+            }
+            vectors[index] = Some(vector);
+        }
+    }
+    Ok(vectors.into_iter().flatten().collect())
+}
+
+// Function to average vectors of the same length, None when there are none
+pub fn centroid(vectors: &[Vec<f64>]) -> Option<Vec<f64>> {
+    let first = vectors.first()?;
+    let mut sum = vec![0.0; first.len()];
+    for vector in vectors {
+        for (total, value) in sum.iter_mut().zip(vector) {
+            *total += value;
+        }
+    }
+    Some(sum.into_iter().map(|total| total / vectors.len() as f64).collect())
+}
+
+// Function to compute the cosine similarity of two vectors, from -1 to 1 (0 when one of them is null)
+pub fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norms = a.iter().map(|x| x * x).sum::<f64>().sqrt() * b.iter().map(|y| y * y).sum::<f64>().sqrt();
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn similarity_to_the_centroid() {
+        let center = centroid(&[vec![1.0, 0.0], vec![0.0, 1.0]]).unwrap();
+        assert_eq!(center, vec![0.5, 0.5]);
+        assert!((cosine_similarity(&center, &[1.0, 1.0]) - 1.0).abs() < 1e-9);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-9);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+}
@@ -0,0 +1,96 @@
+// Import necessary modules and crates
+use reqwest::StatusCode;
+use std::process::ExitCode;
+
+// Error of any part of the application, telling callers what kind of failure it was
+// Messages are written for the user; the kind decides how callers react and the exit code of the CLI.
+#[derive(Debug, thiserror::Error)]
+pub enum PilotError {
+    // Wrong command line options or arguments
+    #[error("{0}")]
+    Usage(String),
+    // Authorization refused, expired or missing: logging in again usually fixes it
+    #[error("{0}")]
+    Auth(String),
+    // The API still answered 429 Too Many Requests after the retries
+    #[error("{0}")]
+    RateLimited(String),
+    // A playlist, track or other resource that doesn't exist
+    #[error("{0}")]
+    NotFound(String),
+    // The LLM answered something that couldn't be read as suggestions
+    #[error("{0}")]
+    LlmParse(String),
+    // The LLM failed too many times in a row, it isn't asked again for a while
+    #[error("{0}")]
+    LlmUnavailable(String),
+    // No answer from a server: DNS, connection, TLS or timeout
+    #[error("{0}")]
+    Network(#[from] reqwest::Error),
+    // A local file or terminal couldn't be read or written
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    // A JSON document that couldn't be read or written
+    #[error("Invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    // The history database failed
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    // Any other failure, described by its message
+    #[error("{0}")]
+    Other(String),
+}
+
+impl PilotError {
+    // Function to make the error of an API answering with an error status, its kind told by the status
+    pub fn from_status(status: StatusCode, message: String) -> PilotError {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => PilotError::Auth(message),
+            StatusCode::NOT_FOUND => PilotError::NotFound(message),
+            StatusCode::TOO_MANY_REQUESTS => PilotError::RateLimited(message),
+            _ => PilotError::Other(message),
+        }
+    }
+
+    // Function to get the exit code of the CLI for the error, documented in the Readme
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            PilotError::Usage(_) => 2,
+            PilotError::Auth(_) => 3,
+            PilotError::RateLimited(_) => 4,
+            PilotError::NotFound(_) => 5,
+            PilotError::LlmParse(_) => 6,
+            PilotError::Network(_) => 7,
+            PilotError::LlmUnavailable(_) => 8,
+            PilotError::Io(_) | PilotError::Json(_) | PilotError::Database(_) | PilotError::Other(_) => 1,
+        })
+    }
+}
+
+// Messages built with format! are other failures unless the caller picks a kind
+impl From<String> for PilotError {
+    fn from(message: String) -> PilotError {
+        PilotError::Other(message)
+    }
+}
+
+impl From<&str> for PilotError {
+    fn from(message: &str) -> PilotError {
+        PilotError::Other(message.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_statuses_give_their_kind() {
+        assert!(matches!(PilotError::from_status(StatusCode::UNAUTHORIZED, "expired".to_string()), PilotError::Auth(_)));
+        assert!(matches!(PilotError::from_status(StatusCode::NOT_FOUND, "gone".to_string()), PilotError::NotFound(_)));
+        assert!(matches!(PilotError::from_status(StatusCode::TOO_MANY_REQUESTS, "slow down".to_string()), PilotError::RateLimited(_)));
+        let other = PilotError::from_status(StatusCode::BAD_GATEWAY, "Error fetching playlist: 502 Bad Gateway".to_string());
+        assert_eq!(other.to_string(), "Error fetching playlist: 502 Bad Gateway");
+        assert_eq!(other.exit_code(), ExitCode::from(1));
+    }
+}
@@ -0,0 +1,99 @@
+// Import necessary modules and crates
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::config::Config;
+use crate::error::PilotError;
+use crate::history::{AddedEntry, History};
+use crate::report::{escape, track_link};
+use crate::service::Service;
+use crate::tokens::iso_date;
+
+// Number of additions kept in a feed, the most recent ones
+pub const FEED_ENTRIES: usize = 100;
+
+// Function to write the Atom feed of the tracks added to a playlist, one entry per track, newest first
+// The playlist ID stands in for its name when no run recorded it.
+pub fn render_feed(playlist_id: &str, name: Option<&str>, link: Option<&str>, entries: &[AddedEntry]) -> String {
+    let title = format!("Added to {} by PlaylistPilot", name.unwrap_or(playlist_id));
+    let updated = entries.first().map(|entry| entry.added_at).unwrap_or(0);
+    let mut lines = vec![
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>".to_string(),
+        "<feed xmlns=\"http://www.w3.org/2005/Atom\">".to_string(),
+        format!("  <id>urn:playlistpilot:playlist:{}</id>", escape(playlist_id)),
+        format!("  <title>{}</title>", escape(&title)),
+        format!("  <updated>{}</updated>", iso_date(updated)),
+        "  <generator>PlaylistPilot</generator>".to_string(),
+    ];
+    if let Some(link) = link {
+        lines.push(format!("  <link rel=\"alternate\" href=\"{}\"/>", escape(link)));
+    }
+    for entry in entries {
+        let track = &entry.track;
+        lines.push("  <entry>".to_string());
+        lines.push(format!("    <id>urn:playlistpilot:run:{}:{}</id>", entry.run_id, escape(&track.uri)));
+        lines.push(format!("    <title>{} – {}</title>", escape(&track.artists), escape(&track.name)));
+        lines.push(format!("    <updated>{}</updated>", iso_date(entry.added_at)));
+        lines.push("    <author><name>PlaylistPilot</name></author>".to_string());
+        if let Some(link) = track_link(&track.uri) {
+            lines.push(format!("    <link rel=\"alternate\" href=\"{}\"/>", escape(&link)));
+        }
+        lines.push(format!("    <summary>{} by {}, added to {}</summary>", escape(&track.name), escape(&track.artists), escape(name.unwrap_or(playlist_id))));
+        lines.push("  </entry>".to_string());
+    }
+    lines.push("</feed>".to_string());
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+// Function to get the web page of a playlist of the service, None for services without public playlist pages
+pub fn playlist_link(service: Service, playlist_id: &str) -> Option<String> {
+    match service {
+        Service::Spotify => Some(format!("https://open.spotify.com/playlist/{}", playlist_id)),
+        Service::YtMusic => Some(format!("https://music.youtube.com/playlist?list={}", playlist_id)),
+        Service::Tidal => Some(format!("https://tidal.com/browse/playlist/{}", playlist_id)),
+        Service::Deezer => Some(format!("https://www.deezer.com/playlist/{}", playlist_id)),
+        Service::AppleMusic => None,
+    }
+}
+
+// Function to write the feed of a playlist's additions from the run history to a file
+pub fn write_feed(config: &Config, playlist_id: &str, path: &Path) -> Result<usize, PilotError> {
+    let (name, entries) = History::open()?.added_entries(playlist_id, FEED_ENTRIES)?;
+    let link = playlist_link(config.service, playlist_id);
+    fs::write(path, render_feed(playlist_id, name.as_deref(), link.as_deref(), &entries)).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(entries.len())
+}
+
+// Function to refresh the feed of a playlist in feed_dir after a run added to it, a feed failure doesn't fail the run
+pub fn update_feed(config: &Config, playlist_id: &str) {
+    let Some(dir) = &config.feed_dir else { return };
+    let path = PathBuf::from(dir).join(format!("{}.xml", playlist_id));
+    let written = fs::create_dir_all(dir).map_err(|e| PilotError::from(format!("{}: {}", dir, e))).and_then(|_| write_feed(config, playlist_id, &path));
+    if let Err(e) = written {
+        warn!("Could not update the feed of the playlist: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::AddedTrack;
+
+    #[test]
+    fn render_feed_writes_an_entry_per_added_track() {
+        let entries = [AddedEntry {
+            run_id: 7,
+            added_at: 1_716_314_592,
+            track: AddedTrack { uri: "spotify:track:abc".to_string(), name: "Love <Reign>".to_string(), artists: "The Who".to_string() },
+        }];
+        let feed = render_feed("pl1", Some("Rock & Roll"), Some("https://open.spotify.com/playlist/pl1"), &entries);
+        assert!(feed.contains("<title>Added to Rock &amp; Roll by PlaylistPilot</title>"));
+        assert!(feed.contains("<link rel=\"alternate\" href=\"https://open.spotify.com/playlist/pl1\"/>"));
+        assert!(feed.contains("<id>urn:playlistpilot:run:7:spotify:track:abc</id>"));
+        assert!(feed.contains("<title>The Who – Love &lt;Reign&gt;</title>"));
+        assert!(feed.contains("<updated>2024-05-21T18:03:12Z</updated>"));
+        assert!(feed.contains("<link rel=\"alternate\" href=\"https://open.spotify.com/track/abc\"/>"));
+    }
+}
@@ -0,0 +1,537 @@
+// Import necessary modules and crates
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+
+use crate::backup::BackupItem;
+use crate::db::open_database;
+use crate::error::PilotError;
+use crate::matching::TrackMatch;
+use crate::models::{Message, Song};
+use crate::service::Service;
+use crate::tokens::now;
+
+// What happened to one suggestion of a run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    // Added to the playlist
+    Added,
+    // Would have been added, but the run was a dry run or the add step was not reached
+    Accepted,
+    // Low confidence match the user declined
+    Rejected,
+    // Low confidence match shown for review in a dry run
+    Review,
+    // Already in the playlist or the library
+    Duplicate,
+    // No Spotify track was found
+    NotFound,
+    // Spotify refused to add it
+    Failed,
+    // Added, then removed again by an undo
+    Undone,
+    // Not needed, the requested number of songs was already reached
+    Surplus,
+    // By a blacklisted artist, or a blacklisted track
+    Excluded,
+    // By an artist that already has --max-per-artist songs in the run
+    ArtistLimit,
+    // Added to or rejected for the same playlist by an earlier run
+    Previous,
+    // Left out by a filter checked after matching: an excluded genre, or audio features out of range
+    Filtered,
+}
+
+impl Status {
+    // Function to get the name stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Added => "added",
+            Status::Accepted => "accepted",
+            Status::Rejected => "rejected",
+            Status::Review => "review",
+            Status::Duplicate => "duplicate",
+            Status::NotFound => "not_found",
+            Status::Failed => "failed",
+            Status::Undone => "undone",
+            Status::Surplus => "surplus",
+            Status::Excluded => "excluded",
+            Status::ArtistLimit => "artist_limit",
+            Status::Filtered => "filtered",
+            Status::Previous => "previous",
+        }
+    }
+}
+
+// Where a suggestion came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    // Suggested by the LLM
+    Llm,
+    // Recommended by Spotify, in a hybrid run
+    Spotify,
+    // Associated with the seed by Last.fm's listeners
+    LastFm,
+    // Listed by the user, e.g. in an imported file
+    List,
+    // Played or added the same month some years ago, for --throwback
+    Throwback,
+}
+
+impl Source {
+    // Function to get the name stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Source::Llm => "llm",
+            Source::Spotify => "spotify",
+            Source::LastFm => "lastfm",
+            Source::List => "list",
+            Source::Throwback => "throwback",
+        }
+    }
+
+    // Function to get the name shown to the user
+    pub fn label(&self) -> &'static str {
+        match self {
+            Source::Llm => "the LLM",
+            Source::Spotify => "Spotify",
+            Source::LastFm => "Last.fm",
+            Source::List => "the list",
+            Source::Throwback => "your throwbacks",
+        }
+    }
+}
+
+// What the user thought of a track a run added
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rating {
+    // Worth keeping, more like it
+    Keeper,
+    // Not right for the playlist
+    Miss,
+}
+
+impl Rating {
+    // Function to get the name stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rating::Keeper => "keeper",
+            Rating::Miss => "miss",
+        }
+    }
+}
+
+// One song suggested by the LLM, with the track it matched and what was done with it
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub song: Song,
+    pub found: Option<TrackMatch>,
+    pub status: Status,
+    pub source: Source,
+}
+
+// Everything about one run worth keeping
+pub struct Run<'a> {
+    pub profile: Option<&'a str>,
+    pub playlist_id: &'a str,
+    // Name of the playlist when known, shown by the feed of its additions
+    pub playlist_name: Option<&'a str>,
+    pub prompt: &'a str,
+    pub dry_run: bool,
+    pub suggestions: &'a [Suggestion],
+    pub service: Service,
+    // Snapshot of the playlist once the songs were added, and the position of each added track in it, by URI
+    pub snapshot_id: Option<&'a str>,
+    pub positions: &'a HashMap<String, usize>,
+}
+
+// A track added by a past run
+pub struct AddedTrack {
+    pub uri: String,
+    pub name: String,
+    pub artists: String,
+}
+
+// A song an earlier run suggested for a playlist, with the track it matched when there was one
+pub struct PastSong {
+    pub uri: Option<String>,
+    pub name: String,
+    pub artists: String,
+}
+
+// A track added by a past run, with when it was added and the run that did
+pub struct AddedEntry {
+    pub run_id: i64,
+    pub added_at: u64,
+    pub track: AddedTrack,
+}
+
+// A past run that added tracks to a playlist
+pub struct AddedRun {
+    pub id: i64,
+    pub playlist_id: String,
+    // None for the runs recorded before the service was
+    pub service: Option<Service>,
+    // Snapshot the positions of the tracks are in, None when they weren't read
+    pub snapshot_id: Option<String>,
+    // With the position each track had in that snapshot
+    pub tracks: Vec<(AddedTrack, Option<usize>)>,
+}
+
+// A past run with how many songs it suggested and added
+pub struct RunSummary {
+    pub id: i64,
+    pub started_at: u64,
+    pub playlist_id: String,
+    pub playlist_name: Option<String>,
+    pub dry_run: bool,
+    pub suggested: usize,
+    pub added: usize,
+}
+
+// The weekly playlist of a profile, emptied into a dated archive and refilled by each weekly run
+pub struct WeeklyPlaylist {
+    pub playlist_id: String,
+    // When its current tracks were added, None until a run filled it
+    pub filled_at: Option<u64>,
+    // Its current tracks are already in an archive, so a failed refill doesn't archive them twice
+    pub archived: bool,
+}
+
+// The content of a playlist archived at one point in time
+pub struct PlaylistSnapshot {
+    pub taken_at: u64,
+    pub name: String,
+    pub items: Vec<BackupItem>,
+}
+
+// History of every run, stored in the local database
+pub struct History {
+    connection: Connection,
+}
+
+impl History {
+    // Function to open the run history stored in the local database
+    pub fn open() -> Result<History, PilotError> {
+        Ok(History { connection: open_database()? })
+    }
+
+    // Function to record a run and its suggestions, returning the run ID
+    pub fn record(&self, run: &Run) -> Result<i64, PilotError> {
+        let transaction = self.connection.unchecked_transaction().map_err(|e| e.to_string())?;
+        transaction
+            .execute(
+                "INSERT INTO runs (started_at, profile, playlist_id, playlist_name, prompt, dry_run, service, snapshot_id) \
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![now() as i64, run.profile, run.playlist_id, run.playlist_name, run.prompt, run.dry_run, run.service.as_str(), run.snapshot_id],
+            )
+            .map_err(|e| e.to_string())?;
+        let run_id = transaction.last_insert_rowid();
+
+        for (position, suggestion) in run.suggestions.iter().enumerate() {
+            let found = suggestion.found.as_ref();
+            transaction
+                .execute(
+                    "INSERT INTO run_tracks (run_id, position, name, artist, uri, matched_name, matched_artists, confidence, status, source, added_position) \
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                    params![
+                        run_id,
+                        position as i64,
+                        suggestion.song.name,
+                        suggestion.song.artist,
+                        found.map(|f| &f.uri),
+                        found.map(|f| &f.name),
+                        found.map(|f| f.artists.join(", ")),
+                        found.map(|f| f.confidence),
+                        suggestion.status.as_str(),
+                        suggestion.source.as_str(),
+                        found.filter(|_| suggestion.status == Status::Added).and_then(|f| run.positions.get(&f.uri)).map(|position| *position as i64),
+                    ],
+                )
+                .map_err(|e| e.to_string())?;
+        }
+
+        transaction.commit().map_err(|e| e.to_string())?;
+        Ok(run_id)
+    }
+
+    // Function to find the most recent run of a profile that still has added tracks, with those tracks
+    pub fn last_added_run(&self, profile: Option<&str>) -> Result<Option<AddedRun>, PilotError> {
+        let run = self
+            .connection
+            .query_row(
+                "SELECT id, playlist_id, service, snapshot_id FROM runs WHERE profile IS ?1 AND dry_run = 0 AND EXISTS \
+                (SELECT 1 FROM run_tracks WHERE run_id = runs.id AND status = ?2) ORDER BY id DESC LIMIT 1",
+                params![profile, Status::Added.as_str()],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get::<_, Option<String>>(2)?, row.get(3)?)),
+            )
+            .optional()?;
+        let Some((id, playlist_id, service, snapshot_id)) = run else { return Ok(None) };
+        let service = service.map(|service| service.parse()).transpose()?;
+
+        let mut statement = self
+            .connection
+            .prepare("SELECT uri, matched_name, matched_artists, added_position FROM run_tracks WHERE run_id = ?1 AND status = ?2 ORDER BY position")
+            .map_err(|e| e.to_string())?;
+        let tracks = statement
+            .query_map(params![id, Status::Added.as_str()], |row| {
+                let position: Option<i64> = row.get(3)?;
+                Ok((AddedTrack { uri: row.get(0)?, name: row.get(1)?, artists: row.get(2)? }, position.map(|position| position as usize)))
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<(AddedTrack, Option<usize>)>, _>>())
+            .map_err(|e| e.to_string())?;
+
+        Ok(Some(AddedRun { id, playlist_id, service, snapshot_id, tracks }))
+    }
+
+    // Function to list the latest runs of a profile, newest first
+    pub fn recent_runs(&self, profile: Option<&str>, limit: usize) -> Result<Vec<RunSummary>, PilotError> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT id, started_at, playlist_id, playlist_name, dry_run, \
+                (SELECT COUNT(*) FROM run_tracks WHERE run_id = runs.id), \
+                (SELECT COUNT(*) FROM run_tracks WHERE run_id = runs.id AND status = ?2) \
+                FROM runs WHERE profile IS ?1 ORDER BY id DESC LIMIT ?3",
+            )
+            .map_err(|e| e.to_string())?;
+        statement
+            .query_map(params![profile, Status::Added.as_str(), limit as i64], |row| {
+                Ok(RunSummary {
+                    id: row.get(0)?,
+                    started_at: row.get::<_, i64>(1)? as u64,
+                    playlist_id: row.get(2)?,
+                    playlist_name: row.get(3)?,
+                    dry_run: row.get(4)?,
+                    suggested: row.get::<_, i64>(5)? as usize,
+                    added: row.get::<_, i64>(6)? as usize,
+                })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<RunSummary>, _>>())
+            .map_err(PilotError::from)
+    }
+
+    // Function to list the songs earlier runs added to a playlist, or that the user rejected or undid, newest first
+    pub fn past_songs(&self, playlist_id: &str) -> Result<Vec<PastSong>, PilotError> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT run_tracks.uri, COALESCE(matched_name, name), COALESCE(matched_artists, artist) FROM run_tracks \
+                JOIN runs ON runs.id = run_tracks.run_id WHERE runs.playlist_id = ?1 AND runs.dry_run = 0 \
+                AND status IN (?2, ?3, ?4) ORDER BY runs.id DESC, position",
+            )
+            .map_err(|e| e.to_string())?;
+        statement
+            .query_map(
+                params![playlist_id, Status::Added.as_str(), Status::Rejected.as_str(), Status::Undone.as_str()],
+                |row| Ok(PastSong { uri: row.get(0)?, name: row.get(1)?, artists: row.get(2)? }),
+            )
+            .and_then(|rows| rows.collect::<Result<Vec<PastSong>, _>>())
+            .map_err(PilotError::from)
+    }
+
+    // Function to list the tracks runs added to a playlist, newest first, with the last name the playlist was recorded with
+    pub fn added_entries(&self, playlist_id: &str, limit: usize) -> Result<(Option<String>, Vec<AddedEntry>), PilotError> {
+        let name = self
+            .connection
+            .query_row(
+                "SELECT playlist_name FROM runs WHERE playlist_id = ?1 AND playlist_name IS NOT NULL ORDER BY id DESC LIMIT 1",
+                params![playlist_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT runs.id, runs.started_at, run_tracks.uri, matched_name, matched_artists FROM run_tracks \
+                JOIN runs ON runs.id = run_tracks.run_id WHERE runs.playlist_id = ?1 AND status = ?2 \
+                ORDER BY runs.id DESC, position LIMIT ?3",
+            )
+            .map_err(|e| e.to_string())?;
+        let entries = statement
+            .query_map(params![playlist_id, Status::Added.as_str(), limit as i64], |row| {
+                Ok(AddedEntry {
+                    run_id: row.get(0)?,
+                    added_at: row.get::<_, i64>(1)? as u64,
+                    track: AddedTrack { uri: row.get(2)?, name: row.get(3)?, artists: row.get(4)? },
+                })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<AddedEntry>, _>>())
+            .map_err(|e| e.to_string())?;
+        Ok((name, entries))
+    }
+
+    // Function to list the tracks runs of a profile added to any playlist between two Unix times, oldest first
+    pub fn added_between(&self, profile: Option<&str>, from: u64, to: u64) -> Result<Vec<AddedTrack>, PilotError> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT DISTINCT run_tracks.uri, matched_name, matched_artists FROM run_tracks \
+                JOIN runs ON runs.id = run_tracks.run_id WHERE runs.profile IS ?1 AND runs.started_at >= ?2 AND runs.started_at < ?3 \
+                AND status = ?4 ORDER BY runs.id, position",
+            )
+            .map_err(|e| e.to_string())?;
+        statement
+            .query_map(params![profile, from as i64, to as i64, Status::Added.as_str()], |row| {
+                Ok(AddedTrack { uri: row.get(0)?, name: row.get(1)?, artists: row.get(2)? })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<AddedTrack>, _>>())
+            .map_err(PilotError::from)
+    }
+
+    // Function to list the tracks runs added to a playlist that haven't been rated yet, oldest first
+    pub fn unrated_tracks(&self, playlist_id: &str) -> Result<Vec<AddedTrack>, PilotError> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT DISTINCT run_tracks.uri, matched_name, matched_artists FROM run_tracks \
+                JOIN runs ON runs.id = run_tracks.run_id WHERE runs.playlist_id = ?1 AND status = ?2 \
+                AND run_tracks.uri NOT IN (SELECT uri FROM feedback WHERE playlist_id = ?1) ORDER BY runs.id, position",
+            )
+            .map_err(|e| e.to_string())?;
+        statement
+            .query_map(params![playlist_id, Status::Added.as_str()], |row| {
+                Ok(AddedTrack { uri: row.get(0)?, name: row.get(1)?, artists: row.get(2)? })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<AddedTrack>, _>>())
+            .map_err(PilotError::from)
+    }
+
+    // Function to store the rating of a track added to a playlist
+    pub fn rate(&self, playlist_id: &str, track: &AddedTrack, rating: Rating) -> Result<(), PilotError> {
+        self.connection
+            .execute(
+                "INSERT OR REPLACE INTO feedback (playlist_id, uri, name, artists, rating, rated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![playlist_id, track.uri, track.name, track.artists, rating.as_str(), now() as i64],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    // Function to list the tracks of a playlist rated a given way, most recently rated first
+    pub fn rated_tracks(&self, playlist_id: &str, rating: Rating) -> Result<Vec<AddedTrack>, PilotError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT uri, name, artists FROM feedback WHERE playlist_id = ?1 AND rating = ?2 ORDER BY rated_at DESC")
+            .map_err(|e| e.to_string())?;
+        statement
+            .query_map(params![playlist_id, rating.as_str()], |row| {
+                Ok(AddedTrack { uri: row.get(0)?, name: row.get(1)?, artists: row.get(2)? })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<AddedTrack>, _>>())
+            .map_err(PilotError::from)
+    }
+
+    // Function to find the weekly playlist of a profile
+    pub fn weekly_playlist(&self, profile: Option<&str>) -> Result<Option<WeeklyPlaylist>, PilotError> {
+        self.connection
+            .query_row(
+                "SELECT playlist_id, filled_at, archived FROM weekly WHERE profile = ?1",
+                params![profile.unwrap_or_default()],
+                |row| {
+                    Ok(WeeklyPlaylist {
+                        playlist_id: row.get(0)?,
+                        filled_at: row.get::<_, Option<i64>>(1)?.map(|filled_at| filled_at as u64),
+                        archived: row.get(2)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(PilotError::from)
+    }
+
+    // Function to store the state of the weekly playlist of a profile
+    pub fn save_weekly_playlist(&self, profile: Option<&str>, weekly: &WeeklyPlaylist) -> Result<(), PilotError> {
+        self.connection
+            .execute(
+                "INSERT OR REPLACE INTO weekly (profile, playlist_id, filled_at, archived) VALUES (?1, ?2, ?3, ?4)",
+                params![profile.unwrap_or_default(), weekly.playlist_id, weekly.filled_at.map(|filled_at| filled_at as i64), weekly.archived],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    // Function to get the conversation with the LLM of the last `runs` runs on a playlist, oldest first
+    pub fn conversation(&self, playlist_id: &str, runs: usize) -> Result<Vec<Message>, PilotError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT messages FROM conversations WHERE playlist_id = ?1 ORDER BY id DESC LIMIT ?2")
+            .map_err(|e| e.to_string())?;
+        let saved = statement
+            .query_map(params![playlist_id, runs as i64], |row| row.get::<_, String>(0))
+            .and_then(|rows| rows.collect::<Result<Vec<String>, _>>())?;
+        let mut messages = Vec::new();
+        for run in saved.iter().rev() {
+            messages.extend(serde_json::from_str::<Vec<Message>>(run)?);
+        }
+        Ok(messages)
+    }
+
+    // Function to save the conversation with the LLM of a run on a playlist, for the next runs to continue it
+    pub fn save_conversation(&self, playlist_id: &str, messages: &[Message]) -> Result<(), PilotError> {
+        self.connection
+            .execute(
+                "INSERT INTO conversations (playlist_id, saved_at, messages) VALUES (?1, ?2, ?3)",
+                params![playlist_id, now() as i64, serde_json::to_string(messages)?],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    // Function to forget the conversations of a playlist, so the next run starts cold
+    pub fn forget_conversation(&self, playlist_id: &str) -> Result<(), PilotError> {
+        self.connection.execute("DELETE FROM conversations WHERE playlist_id = ?1", params![playlist_id]).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    // Function to archive the content of a playlist, returning false when it didn't change since the last snapshot
+    pub fn save_snapshot(&self, playlist_id: &str, snapshot_id: &str, name: &str, items: &[BackupItem]) -> Result<bool, PilotError> {
+        let latest: Option<String> = self
+            .connection
+            .query_row(
+                "SELECT snapshot_id FROM snapshots WHERE playlist_id = ?1 ORDER BY id DESC LIMIT 1",
+                params![playlist_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        if latest.as_deref() == Some(snapshot_id) {
+            return Ok(false);
+        }
+        self.connection
+            .execute(
+                "INSERT INTO snapshots (playlist_id, taken_at, snapshot_id, name, items) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![playlist_id, now() as i64, snapshot_id, name, serde_json::to_string(items)?],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+
+    // Function to get the archived snapshots of a playlist, oldest first
+    pub fn snapshots(&self, playlist_id: &str) -> Result<Vec<PlaylistSnapshot>, PilotError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT taken_at, name, items FROM snapshots WHERE playlist_id = ?1 ORDER BY id")
+            .map_err(|e| e.to_string())?;
+        let rows = statement
+            .query_map(params![playlist_id], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())?;
+        let mut snapshots = Vec::new();
+        for (taken_at, name, items) in rows {
+            snapshots.push(PlaylistSnapshot { taken_at: taken_at as u64, name, items: serde_json::from_str(&items)? });
+        }
+        Ok(snapshots)
+    }
+
+    // Function to change the status of a run's tracks, e.g. once they are undone
+    pub fn update_status(&self, run_id: i64, from: Status, to: Status) -> Result<(), PilotError> {
+        self.connection
+            .execute(
+                "UPDATE run_tracks SET status = ?3 WHERE run_id = ?1 AND status = ?2",
+                params![run_id, from.as_str(), to.as_str()],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
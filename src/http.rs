@@ -0,0 +1,548 @@
+// Import necessary modules and crates
+use reqwest::blocking::{Client, Request, RequestBuilder, Response};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Method, NoProxy, Proxy, ResponseBuilderExt, StatusCode, Url};
+use rand::Rng;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::config::{Config, DEFAULT_RETRY_BASE_DELAY_MS, DEFAULT_RETRY_JITTER, DEFAULT_RETRY_MAX_ATTEMPTS, DEFAULT_RETRY_STATUSES};
+use crate::error::PilotError;
+use crate::metrics::count_http_request;
+use crate::tokens::{iso_date, now};
+
+// User agent sent with every request
+const USER_AGENT: &str = concat!("PlaylistPilot/", env!("CARGO_PKG_VERSION"));
+
+// Timeout of a whole request to the APIs that don't have their own setting, in seconds
+const DEFAULT_TIMEOUT_SECS: u64 = 120;
+
+// Function to get the proxy the environment sets for HTTPS requests, every API being HTTPS
+fn env_proxy() -> Option<String> {
+    ["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"].into_iter().find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()))
+}
+
+// Function to get the host of an API URL
+fn host(url: &str) -> Option<String> {
+    Url::parse(url).ok()?.host_str().map(|host| host.to_string())
+}
+
+// Function to check a proxy URL of the config, SOCKS proxies included
+fn proxy_url(setting: &str, value: &str) -> Result<Url, PilotError> {
+    let url = Url::parse(value).map_err(|e| format!("Invalid value for {}: {}: {}", setting, value, e))?;
+    if !["http", "https", "socks5", "socks5h"].contains(&url.scheme()) {
+        return Err(format!("Invalid value for {}: {}: use an http://, https:// or socks5:// URL", setting, value).into());
+    }
+    Ok(url)
+}
+
+// Function to build the HTTP client shared by every call of a run
+// Connections are pooled and reused across calls. Spotify and LLM requests replace the overall timeout with their own.
+// Without proxy settings the environment's proxies (HTTPS_PROXY, NO_PROXY...) are used as is.
+pub fn build_client(config: &Config) -> Result<Client, PilotError> {
+    let mut builder = Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(Duration::from_secs(config.connect_timeout_secs))
+        .timeout(Duration::from_secs(DEFAULT_TIMEOUT_SECS))
+        .pool_idle_timeout(Duration::from_secs(90));
+    // The first proxy matching a request is used, the LLM's comes before the general one
+    if let Some(llm_proxy) = &config.llm_proxy {
+        let llm_proxy = proxy_url("llm_proxy", llm_proxy)?;
+        let hosts: Vec<String> = [&config.llm_api_url, &config.embeddings_api_url].into_iter().filter_map(|url| host(url)).collect();
+        builder = builder.proxy(Proxy::custom(move |url| url.host_str().is_some_and(|host| hosts.iter().any(|h| h == host)).then(|| llm_proxy.clone())));
+    }
+    // Setting a proxy turns off the environment's, so the environment's comes back for the other requests
+    if let Some(proxy) = config.proxy.clone().or_else(|| config.llm_proxy.as_ref().and_then(|_| env_proxy())) {
+        builder = builder.proxy(Proxy::all(proxy_url("proxy", &proxy)?)?.no_proxy(NoProxy::from_env()));
+    }
+    Ok(builder.build()?)
+}
+
+// File every request and its response are written to, one JSON line each, when --debug-http is set
+static DEBUG_HTTP: OnceLock<Mutex<File>> = OnceLock::new();
+
+// What a secret is replaced with in the recorded traffic
+const REDACTED: &str = "[redacted]";
+
+// Headers carrying credentials, compared in lowercase
+const SECRET_HEADERS: [&str; 6] = ["authorization", "proxy-authorization", "cookie", "set-cookie", "music-user-token", "x-api-key"];
+
+// Query and form parameters carrying credentials, compared in lowercase
+const SECRET_PARAMS: [&str; 12] = ["access_token", "refresh_token", "id_token", "client_secret", "secret", "api_key", "key", "token", "code", "code_verifier", "device_code", "password"];
+
+// Fields of JSON bodies carrying credentials, compared in lowercase
+// "key" is left out: it is the musical key of a track in some answers.
+const SECRET_FIELDS: [&str; 9] = ["access_token", "refresh_token", "id_token", "client_secret", "secret", "api_key", "token", "device_code", "password"];
+
+// Longest body recorded, in bytes; the rest is cut
+const MAX_RECORDED_BODY: usize = 100_000;
+
+// Function to record every request and response of the process to a file, appended to if it exists
+pub fn enable_debug_http(path: &str) -> Result<(), PilotError> {
+    let file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| format!("{}: {}", path, e))?;
+    DEBUG_HTTP.set(Mutex::new(file)).map_err(|_| "HTTP traffic is already being recorded".into())
+}
+
+// Function to cut a text to MAX_RECORDED_BODY bytes, on a character boundary
+fn truncate(text: &str) -> String {
+    if text.len() <= MAX_RECORDED_BODY {
+        return text.to_string();
+    }
+    let mut end = MAX_RECORDED_BODY;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}... [{} bytes cut]", &text[..end], text.len() - end)
+}
+
+// Function to hide the values of the secret parameters of a query string or form body
+fn redact_pairs(text: &str) -> String {
+    text.split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if SECRET_PARAMS.contains(&name.to_lowercase().as_str()) => format!("{}={}", name, REDACTED),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+// Function to write a URL with the values of its secret parameters hidden
+fn redact_url(url: &Url) -> String {
+    let mut url = url.clone();
+    if let Some(query) = url.query().map(redact_pairs) {
+        url.set_query(Some(&query));
+    }
+    url.to_string()
+}
+
+// Function to hide the values of the secret fields of a JSON body, at any depth
+fn redact_json(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                if SECRET_FIELDS.contains(&name.to_lowercase().as_str()) {
+                    *field = json!(REDACTED);
+                } else {
+                    redact_json(field);
+                }
+            }
+        },
+        Value::Array(items) => items.iter_mut().for_each(redact_json),
+        _ => {},
+    }
+}
+
+// Function to write headers as a JSON object, the values of the secret ones hidden
+fn record_headers(headers: &HeaderMap) -> Value {
+    let mut recorded = serde_json::Map::new();
+    for (name, value) in headers {
+        let value = if SECRET_HEADERS.contains(&name.as_str()) { REDACTED.to_string() } else { String::from_utf8_lossy(value.as_bytes()).into_owned() };
+        recorded.insert(name.as_str().to_string(), json!(value));
+    }
+    Value::Object(recorded)
+}
+
+// Function to write a body for the record: JSON as JSON, forms and text as text, secrets hidden and long bodies cut
+fn record_body(bytes: &[u8]) -> Value {
+    if bytes.is_empty() {
+        return Value::Null;
+    }
+    if let Ok(mut body) = serde_json::from_slice::<Value>(bytes) {
+        redact_json(&mut body);
+        if bytes.len() <= MAX_RECORDED_BODY {
+            return body;
+        }
+        return json!(truncate(&body.to_string()));
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) if text.contains('=') && !text.contains(char::is_whitespace) => json!(truncate(&redact_pairs(text))),
+        Ok(text) => json!(truncate(text)),
+        Err(_) => json!(format!("[{} bytes of binary data]", bytes.len())),
+    }
+}
+
+// Function to append an entry to the file of --debug-http
+fn write_record(entry: &Value) {
+    let Some(file) = DEBUG_HTTP.get() else { return };
+    if let Err(e) = writeln!(file.lock().unwrap(), "{}", entry) {
+        warn!("Could not record the HTTP traffic: {}", e);
+    }
+}
+
+// Function to read a whole response into its record, returning a response rebuilt around the body read
+// A body can only be read once, so the caller gets a copy holding the same status, headers and bytes.
+fn record_response(response: Response, entry: &mut Value) -> reqwest::Result<Response> {
+    let status = response.status();
+    let version = response.version();
+    let url = response.url().clone();
+    let headers = response.headers().clone();
+    entry["status"] = json!(status.as_u16());
+    entry["response_headers"] = record_headers(&headers);
+    let body = response.bytes()?;
+    entry["response_body"] = record_body(&body);
+    let mut rebuilt = ::http::Response::builder().url(url).body(body).expect("a response without headers is always valid");
+    *rebuilt.status_mut() = status;
+    *rebuilt.version_mut() = version;
+    *rebuilt.headers_mut() = headers;
+    Ok(rebuilt.into())
+}
+
+// Function to send a request once, counting it for the metrics and recording it when --debug-http is set
+fn execute(client: &Client, request: Request) -> reqwest::Result<Response> {
+    let host = request.url().host_str().unwrap_or("unknown").to_string();
+    let entry = DEBUG_HTTP.get().map(|_| json!({
+        "time": iso_date(now()),
+        "method": request.method().as_str(),
+        "url": redact_url(request.url()),
+        "request_headers": record_headers(request.headers()),
+        "request_body": request.body().and_then(|body| body.as_bytes()).map_or(Value::Null, record_body),
+    }));
+    let started = Instant::now();
+    let response = client.execute(request);
+    match &response {
+        Ok(response) => count_http_request(&host, response.status().as_str()),
+        Err(_) => count_http_request(&host, "error"),
+    }
+    let Some(mut entry) = entry else { return response };
+    let response = response.and_then(|response| record_response(response, &mut entry));
+    if let Err(e) = &response {
+        entry["error"] = json!(e.to_string());
+    }
+    entry["duration_ms"] = json!(started.elapsed().as_millis() as u64);
+    write_record(&entry);
+    response
+}
+
+// Function to send a request once, the way every API call without retries goes out
+pub fn send(request: RequestBuilder) -> reqwest::Result<Response> {
+    let (client, request) = request.build_split();
+    execute(&client, request?)
+}
+
+// Token bucket spacing out the requests to Spotify, shared by every thread
+// Tokens come back at `rate` per second up to `capacity`; a request takes one, and when none is left
+// it reserves the next one and waits for it, so concurrent requests queue up instead of all waiting the same time.
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, now: Instant) -> TokenBucket {
+        let capacity = rate.max(1.0);
+        TokenBucket { rate, capacity, tokens: capacity, updated: now }
+    }
+
+    // Function to take a token, returning how long to wait before sending the request
+    fn take(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity) - 1.0;
+        self.updated = now;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+// Kinds of outbound calls whose retries can be set apart from the others in the config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+    // Tokens from the Spotify accounts service
+    Auth,
+    // Reading a playlist and its pages of tracks
+    Playlist,
+    // Searching Spotify for a song
+    Search,
+    // Adding tracks to a playlist
+    Add,
+    // Asking the LLM
+    Llm,
+}
+
+impl Endpoint {
+    pub const ALL: [Endpoint; 5] = [Endpoint::Auth, Endpoint::Playlist, Endpoint::Search, Endpoint::Add, Endpoint::Llm];
+
+    // Function to get the name of the endpoint in the config file, as in [retry.search]
+    pub fn name(self) -> &'static str {
+        match self {
+            Endpoint::Auth => "auth",
+            Endpoint::Playlist => "playlist",
+            Endpoint::Search => "search",
+            Endpoint::Add => "add",
+            Endpoint::Llm => "llm",
+        }
+    }
+}
+
+// How a request that failed is tried again
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    // Attempts in all, the first one included; 1 never retries
+    pub max_attempts: u32,
+    // Wait before the first retry, doubled for every next one
+    pub base_delay: Duration,
+    // Share of each wait added or taken off at random, from 0 to 1, so clients failing together don't retry together
+    pub jitter: f64,
+    // Status codes of the answers worth trying again
+    pub statuses: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: DEFAULT_RETRY_MAX_ATTEMPTS,
+            base_delay: Duration::from_millis(DEFAULT_RETRY_BASE_DELAY_MS),
+            jitter: DEFAULT_RETRY_JITTER,
+            statuses: DEFAULT_RETRY_STATUSES.to_vec(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Function to tell whether an answer with this status is tried again
+    pub fn retries(&self, status: StatusCode) -> bool {
+        self.statuses.contains(&status.as_u16())
+    }
+
+    // Function to get how long to wait after the failed attempt number `attempt`, given the Retry-After delay of the answer and
+    // a random number from 0 to 1
+    // The backoff doubles from the base delay (1s, 2s, 4s...) and is jittered; a longer Retry-After is waited for as is.
+    fn delay(&self, attempt: u32, retry_after: Option<Duration>, random: f64) -> Duration {
+        let backoff = self.base_delay.saturating_mul(1 << attempt.saturating_sub(1).min(16));
+        let backoff = backoff.mul_f64((1.0 + self.jitter * (random * 2.0 - 1.0)).max(0.0));
+        retry_after.map_or(backoff, |delay| delay.max(backoff))
+    }
+}
+
+// Retry policies of the calls: one for each endpoint, and the default one of the other Spotify calls
+#[derive(Debug, Clone, Default)]
+pub struct RetryPolicies {
+    pub default: RetryPolicy,
+    pub endpoints: HashMap<Endpoint, RetryPolicy>,
+}
+
+impl RetryPolicies {
+    // Function to get the policy of an endpoint, the default one for the other calls
+    pub fn get(&self, endpoint: Option<Endpoint>) -> &RetryPolicy {
+        endpoint.and_then(|endpoint| self.endpoints.get(&endpoint)).unwrap_or(&self.default)
+    }
+}
+
+// Limit of the requests to Spotify, none until set from the config
+static SPOTIFY_LIMIT: Mutex<Option<TokenBucket>> = Mutex::new(None);
+
+// Timeout of a whole request to Spotify, the client's until set from the config
+static SPOTIFY_TIMEOUT: Mutex<Option<Duration>> = Mutex::new(None);
+
+// Retry policies of the calls, the defaults until set from the config
+static RETRY_POLICIES: Mutex<Option<RetryPolicies>> = Mutex::new(None);
+
+// Function to apply the config's limits to the outbound requests: the number of requests per second to Spotify (0 for no limit),
+// their timeout, and the retry policies of every call
+pub fn configure_requests(config: &Config) {
+    let rate = config.spotify_requests_per_second;
+    *SPOTIFY_LIMIT.lock().unwrap() = (rate > 0.0).then(|| TokenBucket::new(rate, Instant::now()));
+    *SPOTIFY_TIMEOUT.lock().unwrap() = Some(Duration::from_secs(config.spotify_timeout_secs));
+    *RETRY_POLICIES.lock().unwrap() = Some(config.retry.clone());
+}
+
+// Function to wait until the rate limit allows another request to Spotify
+fn throttle() {
+    let wait = SPOTIFY_LIMIT.lock().unwrap().as_mut().map_or(Duration::ZERO, |bucket| bucket.take(Instant::now()));
+    if !wait.is_zero() {
+        thread::sleep(wait);
+    }
+}
+
+// Function to read how long the server asks us to wait from the Retry-After header (in seconds)
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+// Function to send a request under the retry policy of its endpoint, trying it again while it is answered a status the policy retries
+// or gets no answer at all: a connection that failed is always tried again, since nothing was sent, but a request that timed out
+// only when it is idempotent, as a POST may have been applied (tracks added, a playlist created, LLM tokens paid) before the answer was lost.
+// Spotify requests wait their turn under the rate limit first and take the Spotify timeout, LLM requests keep their own.
+// After the last attempt the failed response or error is returned to the caller like any other.
+fn send_under_policy(endpoint: Option<Endpoint>, request: RequestBuilder) -> reqwest::Result<Response> {
+    let policy = RETRY_POLICIES.lock().unwrap().as_ref().map_or_else(RetryPolicy::default, |policies| policies.get(endpoint).clone());
+    let spotify = endpoint != Some(Endpoint::Llm);
+    let (client, request) = request.build_split();
+    let mut request = request?;
+    let idempotent = [Method::GET, Method::HEAD, Method::PUT, Method::DELETE].contains(request.method());
+    if let (true, Some(timeout)) = (spotify, *SPOTIFY_TIMEOUT.lock().unwrap()) {
+        *request.timeout_mut() = Some(timeout);
+    }
+    let mut attempt = 1;
+    loop {
+        // A cloned request loses its timeout, which is put back for the next attempt
+        let retry = request.try_clone().map(|mut retry| {
+            *retry.timeout_mut() = request.timeout().copied();
+            retry
+        });
+        if spotify {
+            throttle();
+        }
+        let result = execute(&client, request);
+        let retried = match &result {
+            Ok(response) => policy.retries(response.status()),
+            Err(e) => e.is_connect() || (e.is_timeout() && idempotent),
+        };
+        let retry = match retry {
+            Some(retry) if retried && attempt < policy.max_attempts => retry,
+            _ => return result,
+        };
+
+        let (failure, server_delay) = match &result {
+            Ok(response) => (format!("answered {}", response.status()), retry_after(response)),
+            Err(e) => (format!("could not be reached ({})", e), None),
+        };
+        let wait = policy.delay(attempt, server_delay, rand::thread_rng().gen());
+        let server = if spotify { "Spotify" } else { "The LLM API" };
+        warn!("{} {}, retrying in {:.1}s (attempt {}/{}).", server, failure, wait.as_secs_f64(), attempt + 1, policy.max_attempts);
+        thread::sleep(wait);
+
+        request = retry;
+        attempt += 1;
+    }
+}
+
+// Function to send a request to Spotify under the default retry policy, for the calls without an endpoint of their own
+pub fn send_with_retry(request: RequestBuilder) -> reqwest::Result<Response> {
+    send_under_policy(None, request)
+}
+
+// Function to send a request under the retry policy of an endpoint
+pub fn send_with_policy(endpoint: Endpoint, request: RequestBuilder) -> reqwest::Result<Response> {
+    send_under_policy(Some(endpoint), request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProfileSettings;
+    use crate::testing::{test_config, test_context, CannedServer};
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    #[test]
+    fn recorded_traffic_hides_secrets() {
+        let url = Url::parse("https://ws.audioscrobbler.com/2.0/?method=track.search&api_key=abc123&track=Hey").unwrap();
+        assert_eq!(redact_url(&url), "https://ws.audioscrobbler.com/2.0/?method=track.search&api_key=[redacted]&track=Hey");
+        let form = record_body(b"grant_type=authorization_code&code=xyz&redirect_uri=http%3A%2F%2Flocalhost");
+        assert_eq!(form, json!("grant_type=authorization_code&code=[redacted]&redirect_uri=http%3A%2F%2Flocalhost"));
+        let body = record_body(br#"{"access_token":"t0k","expires_in":3600,"items":[{"key":5,"refresh_token":"r"}]}"#);
+        assert_eq!(body, json!({ "access_token": "[redacted]", "expires_in": 3600, "items": [{ "key": 5, "refresh_token": "[redacted]" }] }));
+        let mut headers = HeaderMap::new();
+        headers.insert("Authorization", "Bearer t0k".parse().unwrap());
+        headers.insert("Content-Type", "application/json".parse().unwrap());
+        assert_eq!(record_headers(&headers), json!({ "authorization": "[redacted]", "content-type": "application/json" }));
+    }
+
+    #[test]
+    fn llm_requests_go_through_the_llm_proxy() {
+        let proxy = CannedServer::start(vec![(200, "{}".to_string())]);
+        let settings = ProfileSettings {
+            playlist_id: Some("pl1".to_string()),
+            llm_api_url: Some("http://llm.example/v1/chat/completions".to_string()),
+            llm_proxy: Some(proxy.url.clone()),
+            ..Default::default()
+        };
+        let client = build_client(&test_config(settings)).unwrap();
+        assert!(send(client.get("http://llm.example/v1/models")).unwrap().status().is_success());
+        // A proxy is sent the whole URL
+        assert_eq!(proxy.received()[0].path, "http://llm.example/v1/models");
+        assert!(proxy_url("proxy", "ftp://proxy.example").is_err());
+        assert!(proxy_url("proxy", "socks5://127.0.0.1:1080").is_ok());
+    }
+
+    #[test]
+    fn requests_without_an_answer_are_retried() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1/chat/completions", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            // The first connection is never answered, the next one is
+            let (_silent, _) = listener.accept().unwrap();
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            (&stream).write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}").unwrap();
+        });
+        // The test context retries right away
+        let ctx = test_context(&CannedServer::start(Vec::new()));
+        let response = send_with_policy(Endpoint::Llm, ctx.client.get(&url).timeout(Duration::from_millis(300))).unwrap();
+        assert!(response.status().is_success());
+    }
+
+    #[test]
+    fn timed_out_posts_are_not_sent_again() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1/chat/completions", listener.local_addr().unwrap());
+        let connections = Arc::new(Mutex::new(Vec::new()));
+        let accepted = Arc::clone(&connections);
+        thread::spawn(move || {
+            // No connection is ever answered
+            for stream in listener.incoming() {
+                accepted.lock().unwrap().push(stream.unwrap());
+            }
+        });
+        let ctx = test_context(&CannedServer::start(Vec::new()));
+        let result = send_with_policy(Endpoint::Llm, ctx.client.post(&url).body("{}").timeout(Duration::from_millis(300)));
+        assert!(result.unwrap_err().is_timeout());
+        assert_eq!(connections.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn retries_back_off_with_jitter_and_honor_retry_after() {
+        let policy = RetryPolicy { max_attempts: 4, base_delay: Duration::from_millis(500), jitter: 0.2, statuses: vec![429, 503] };
+        assert!(policy.retries(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!policy.retries(StatusCode::INTERNAL_SERVER_ERROR));
+        // 0.5 is the middle of the jitter, no wait added or taken off
+        assert_eq!(policy.delay(1, None, 0.5), Duration::from_millis(500));
+        assert_eq!(policy.delay(3, None, 0.5), Duration::from_secs(2));
+        assert_eq!(policy.delay(1, None, 0.0), Duration::from_millis(400));
+        assert_eq!(policy.delay(1, None, 1.0), Duration::from_millis(600));
+        // The server's delay wins when it is longer
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(3)), 0.5), Duration::from_secs(3));
+        assert_eq!(policy.delay(3, Some(Duration::from_secs(1)), 0.5), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn token_bucket_allows_a_burst_then_spaces_requests() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2.0, start);
+        assert_eq!(bucket.take(start), Duration::ZERO);
+        assert_eq!(bucket.take(start), Duration::ZERO);
+        // Reserved tokens queue up
+        assert_eq!(bucket.take(start), Duration::from_millis(500));
+        assert_eq!(bucket.take(start), Duration::from_secs(1));
+        // After a quiet while the bucket is full again, but holds no more than its capacity
+        let later = start + Duration::from_secs(10);
+        assert_eq!(bucket.take(later), Duration::ZERO);
+        assert_eq!(bucket.take(later), Duration::ZERO);
+        assert_eq!(bucket.take(later), Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,106 @@
+// Tests of whole flows against a local server standing in for Spotify and the LLM
+// They lock in what is sent and how answers, misses and failures are handled, across modules.
+use crate::auth::get_spotify_access;
+use crate::cli::Options;
+use crate::commands::suggest::find_suggestions;
+use crate::error::PilotError;
+use crate::history::{Source, Status};
+use crate::matching::TrackFilter;
+use crate::service::Spotify;
+use crate::spotify::{get_audio_features, get_playlist};
+use crate::testing::*;
+
+// Function to get the options of a dry run searching one song at a time, so the server sees the searches in order
+fn options() -> Options {
+    Options { dry_run: true, no_cache: true, no_llm_cache: true, parallelism: 1, ..Default::default() }
+}
+
+#[test]
+fn authorization_code_is_exchanged_for_tokens() {
+    let tokens = serde_json::json!({ "access_token": "access", "refresh_token": "refresh", "expires_in": 3600 });
+    let server = CannedServer::start(vec![(200, tokens.to_string())]);
+    let ctx = test_context(&server);
+    let response = get_spotify_access(&ctx.client, &ctx.config.spotify_accounts_url, "id", "secret", "the-code", "http://localhost:3000").unwrap();
+    assert_eq!(response.access_token, "access");
+    assert_eq!(response.refresh_token.as_deref(), Some("refresh"));
+    let received = server.received();
+    assert_eq!((received[0].method.as_str(), received[0].path.as_str()), ("POST", "/api/token"));
+    assert!(received[0].body.contains("grant_type=authorization_code"));
+    assert!(received[0].body.contains("code=the-code"));
+}
+
+#[test]
+fn refused_authorization_codes_are_auth_errors() {
+    let refused = serde_json::json!({ "error": "invalid_grant", "error_description": "Invalid authorization code" });
+    let server = CannedServer::start(vec![(400, refused.to_string())]);
+    let ctx = test_context(&server);
+    let result = get_spotify_access(&ctx.client, &ctx.config.spotify_accounts_url, "id", "secret", "used-code", "http://localhost:3000");
+    match result {
+        Err(PilotError::Auth(message)) => assert_eq!(message, "Spotify refused the authorization code: invalid_grant (Invalid authorization code)"),
+        other => panic!("expected an authorization error, got {:?}", other.map(|response| response.access_token)),
+    }
+}
+
+#[test]
+fn rate_limited_pages_are_retried() {
+    let first = serde_json::json!({
+        "name": "Road trip",
+        "snapshot_id": "snap1",
+        "tracks": { "items": [{ "track": track_json("a", "Song A", "Artist A") }], "next": "{server}/playlists/pl1/tracks?offset=1" },
+    });
+    let second = serde_json::json!({ "items": [{ "track": track_json("b", "Song B", "Artist B") }], "next": null });
+    let server = CannedServer::start(vec![(200, first.to_string()), (429, "{}".to_string()), (200, second.to_string())]);
+    let playlist = get_playlist(&test_context(&server), "pl1", None).unwrap();
+    assert_eq!(playlist.tracks.items.len(), 2);
+    let paths: Vec<String> = server.received().into_iter().map(|request| request.path).collect();
+    assert_eq!(paths, ["/playlists/pl1", "/playlists/pl1/tracks?offset=1", "/playlists/pl1/tracks?offset=1"]);
+}
+
+#[test]
+fn expired_tokens_are_auth_errors() {
+    let server = CannedServer::start(vec![(401, "{}".to_string())]);
+    let result = get_audio_features(&test_context(&server), &["spotify:track:a".to_string()]);
+    assert!(matches!(result, Err(PilotError::Auth(_))));
+}
+
+#[test]
+fn missing_songs_are_replaced_by_the_llm() {
+    let songs = r#"```{"songs": [{"name": "Karma Police", "artist": "Radiohead"}, {"name": "Made Up Song", "artist": "Nobody"}]}```"#;
+    let replacement = r#"{"songs": [{"name": "Teardrop", "artist": "Massive Attack"}]}"#;
+    let server = CannedServer::start(vec![
+        (200, llm_answer(songs)),
+        (200, search_answer(vec![track_json("k", "Karma Police", "Radiohead")])),
+        // The made up song isn't found by any of the three searches
+        (200, search_answer(vec![])),
+        (200, search_answer(vec![])),
+        (200, search_answer(vec![])),
+        (200, llm_answer(replacement)),
+        (200, search_answer(vec![track_json("t", "Teardrop", "Massive Attack")])),
+    ]);
+    let ctx = test_context(&server);
+    let suggestions = find_suggestions(&ctx, &Spotify, &options(), "key", "Suggest songs", &TrackFilter::default()).unwrap();
+    let outcome: Vec<(&str, Status)> = suggestions.iter().map(|s| (s.song.name.as_str(), s.status)).collect();
+    assert_eq!(outcome, [("Karma Police", Status::Accepted), ("Made Up Song", Status::NotFound), ("Teardrop", Status::Accepted)]);
+    assert!(suggestions.iter().all(|s| s.source == Source::Llm));
+    assert_eq!(suggestions[2].found.as_ref().unwrap().uri, "spotify:track:t");
+    let replacement_prompt: serde_json::Value = serde_json::from_str(&server.received()[5].body).unwrap();
+    assert!(replacement_prompt["messages"][1]["content"].as_str().unwrap().starts_with("The song 'Made Up Song' by Nobody isn't on Spotify."));
+}
+
+#[test]
+fn failed_llm_requests_give_no_suggestions() {
+    let server = CannedServer::start(vec![(401, r#"{"error": {"message": "Invalid API key"}}"#.to_string())]);
+    let ctx = test_context(&server);
+    let suggestions = find_suggestions(&ctx, &Spotify, &options(), "key", "Suggest songs", &TrackFilter::default()).unwrap();
+    assert!(suggestions.is_empty());
+    assert_eq!(server.received().len(), 1);
+}
+
+#[test]
+fn malformed_llm_answers_are_parse_errors() {
+    let server = CannedServer::start(vec![(200, llm_answer("Here are some great songs: Karma Police by Radiohead"))]);
+    let ctx = test_context(&server);
+    let result = find_suggestions(&ctx, &Spotify, &options(), "key", "Suggest songs", &TrackFilter::default());
+    assert!(matches!(result, Err(PilotError::LlmParse(_))));
+    assert_eq!(server.received().len(), 1);
+}
@@ -0,0 +1,116 @@
+// Import necessary modules and crates
+use serde::de::DeserializeOwned;
+
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::http::send;
+use crate::models::*;
+
+// Base URL of every Last.fm API method
+const LASTFM_API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+
+// Function to call a Last.fm API method with its parameters
+// Last.fm reports some errors (unknown artist or track) in a JSON body with a success status, they are returned as errors too.
+fn lastfm_get<T: DeserializeOwned>(ctx: &Context, api_key: &str, method: &str, params: &[(&str, &str)]) -> Result<T, PilotError> {
+    let response = send(
+        ctx.client
+            .get(LASTFM_API_URL)
+            .query(&[("method", method), ("api_key", api_key), ("format", "json")])
+            .query(params)
+    )?;
+    let status = response.status();
+    let body = response.text()?;
+    if let Ok(error) = serde_json::from_str::<LastfmError>(&body) {
+        return Err(PilotError::from_status(status, format!("Last.fm error {}: {}", error.error, error.message)));
+    }
+    if !status.is_success() {
+        return Err(PilotError::from_status(status, format!("Error calling Last.fm: {}", status)));
+    }
+    serde_json::from_str(&body).map_err(|e| format!("Failed to parse the Last.fm response: {}", e).into())
+}
+
+// Function to turn Last.fm tracks into songs to search on Spotify
+fn songs(tracks: Vec<LastfmTrack>) -> Vec<Song> {
+    tracks
+        .into_iter()
+        .map(|track| Song { name: track.name, artist: track.artist.name, isrc: None, mbid: None })
+        .collect()
+}
+
+// Function to get the artists Last.fm's listeners associate with an artist, most similar first
+pub fn get_similar_artists(ctx: &Context, api_key: &str, artist: &str, limit: usize) -> Result<Vec<String>, PilotError> {
+    let limit = limit.to_string();
+    let response: LastfmSimilarArtistsResponse =
+        lastfm_get(ctx, api_key, "artist.getsimilar", &[("artist", artist), ("limit", &limit), ("autocorrect", "1")])?;
+    Ok(response.similarartists.artist.into_iter().map(|artist| artist.name).collect())
+}
+
+// Function to get the most played tracks of an artist on Last.fm
+pub fn get_artist_top_tracks(ctx: &Context, api_key: &str, artist: &str, limit: usize) -> Result<Vec<Song>, PilotError> {
+    let limit = limit.to_string();
+    let response: LastfmTopTracksResponse =
+        lastfm_get(ctx, api_key, "artist.gettoptracks", &[("artist", artist), ("limit", &limit), ("autocorrect", "1")])?;
+    Ok(songs(response.toptracks.track))
+}
+
+// Function to get the tracks a user scrobbled last, most recent first, without repeats
+pub fn get_user_recent_tracks(ctx: &Context, api_key: &str, user: &str, limit: usize) -> Result<Vec<Song>, PilotError> {
+    let limit = limit.to_string();
+    // The extended format names the artist like the other methods do
+    let response: LastfmRecentTracksResponse =
+        lastfm_get(ctx, api_key, "user.getrecenttracks", &[("user", user), ("limit", &limit), ("extended", "1")])?;
+    let mut songs = songs(response.recenttracks.track);
+    let mut seen = std::collections::HashSet::new();
+    songs.retain(|song| seen.insert((song.name.clone(), song.artist.clone())));
+    Ok(songs)
+}
+
+// Function to get the tracks a user scrobbled between two Unix times, the most played first
+// Only the last 200 scrobbles of the span are counted.
+pub fn get_user_tracks_between(ctx: &Context, api_key: &str, user: &str, from: u64, to: u64) -> Result<Vec<Song>, PilotError> {
+    let (from, to) = (from.to_string(), to.to_string());
+    let response: LastfmRecentTracksResponse = lastfm_get(
+        ctx,
+        api_key,
+        "user.getrecenttracks",
+        &[("user", user), ("from", &from), ("to", &to), ("limit", "200"), ("extended", "1")],
+    )?;
+    let mut plays: Vec<(Song, usize)> = Vec::new();
+    for song in songs(response.recenttracks.track) {
+        match plays.iter_mut().find(|(played, _)| played.name == song.name && played.artist == song.artist) {
+            Some((_, count)) => *count += 1,
+            None => plays.push((song, 1)),
+        }
+    }
+    // Stable, so equally played songs keep the order they were last played in
+    plays.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    Ok(plays.into_iter().map(|(song, _)| song).collect())
+}
+
+// Function to get the tracks a user scrobbled the most over a period (1month, 6month, 12month or overall)
+pub fn get_user_top_tracks(ctx: &Context, api_key: &str, user: &str, period: &str, limit: usize) -> Result<Vec<Song>, PilotError> {
+    let limit = limit.to_string();
+    let response: LastfmTopTracksResponse =
+        lastfm_get(ctx, api_key, "user.gettoptracks", &[("user", user), ("period", period), ("limit", &limit)])?;
+    Ok(songs(response.toptracks.track))
+}
+
+// Function to get the artists a user scrobbled the most over a period
+pub fn get_user_top_artists(ctx: &Context, api_key: &str, user: &str, period: &str, limit: usize) -> Result<Vec<String>, PilotError> {
+    let limit = limit.to_string();
+    let response: LastfmTopArtistsResponse =
+        lastfm_get(ctx, api_key, "user.gettopartists", &[("user", user), ("period", period), ("limit", &limit)])?;
+    Ok(response.topartists.artist.into_iter().map(|artist| artist.name).collect())
+}
+
+// Function to get the tracks Last.fm's listeners associate with a track, most similar first
+pub fn get_similar_tracks(ctx: &Context, api_key: &str, name: &str, artist: &str, limit: usize) -> Result<Vec<Song>, PilotError> {
+    let limit = limit.to_string();
+    let response: LastfmSimilarTracksResponse = lastfm_get(
+        ctx,
+        api_key,
+        "track.getsimilar",
+        &[("track", name), ("artist", artist), ("limit", &limit), ("autocorrect", "1")],
+    )?;
+    Ok(songs(response.similartracks.track))
+}
@@ -0,0 +1,106 @@
+// Import necessary modules and crates
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::http::send;
+use crate::models::*;
+
+// Base URL of every ListenBrainz API endpoint
+const LISTENBRAINZ_API_URL: &str = "https://api.listenbrainz.org/1";
+
+// Function to read a ListenBrainz endpoint, None when ListenBrainz has nothing for it yet (e.g. statistics not computed)
+fn listenbrainz_get<T: DeserializeOwned>(ctx: &Context, path: &str, params: &[(&str, &str)]) -> Result<Option<T>, PilotError> {
+    let response = send(
+        ctx.client
+            .get(format!("{}/{}", LISTENBRAINZ_API_URL, path))
+            .query(params)
+    )?;
+    match response.status() {
+        StatusCode::NO_CONTENT | StatusCode::NOT_FOUND => Ok(None),
+        status if status.is_success() => {
+            response.json().map(Some).map_err(|e| format!("Failed to parse the ListenBrainz response: {}", e).into())
+        },
+        status => Err(PilotError::from_status(status, format!("Error calling ListenBrainz: {}", status))),
+    }
+}
+
+// Function to get the last listens of a user, most recent first, without repeats
+pub fn get_listens(ctx: &Context, user: &str, count: usize) -> Result<Vec<Song>, PilotError> {
+    let count = count.to_string();
+    let response: Option<ListensResponse> = listenbrainz_get(ctx, &format!("user/{}/listens", user), &[("count", &count)])?;
+    let mut songs: Vec<Song> = response
+        .map(|response| response.payload.listens)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|listen| Song {
+            name: listen.track_metadata.track_name,
+            artist: listen.track_metadata.artist_name,
+            isrc: listen.track_metadata.additional_info.isrc,
+            mbid: None,
+        })
+        .collect();
+    let mut seen = std::collections::HashSet::new();
+    songs.retain(|song| seen.insert((song.name.clone(), song.artist.clone())));
+    Ok(songs)
+}
+
+// Function to get the recordings a user listened to the most over a range (month, half_yearly, year or all_time)
+pub fn get_top_recordings(ctx: &Context, user: &str, range: &str, count: usize) -> Result<Vec<Song>, PilotError> {
+    let count = count.to_string();
+    let response: Option<TopRecordingsResponse> =
+        listenbrainz_get(ctx, &format!("stats/user/{}/recordings", user), &[("range", range), ("count", &count)])?;
+    Ok(response
+        .map(|response| response.payload.recordings)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|recording| Song { name: recording.track_name, artist: recording.artist_name, isrc: None, mbid: None })
+        .collect())
+}
+
+// Function to get the artists a user listened to the most over a range
+pub fn get_top_artists(ctx: &Context, user: &str, range: &str, count: usize) -> Result<Vec<String>, PilotError> {
+    let count = count.to_string();
+    let response: Option<ListenBrainzTopArtistsResponse> =
+        listenbrainz_get(ctx, &format!("stats/user/{}/artists", user), &[("range", range), ("count", &count)])?;
+    Ok(response
+        .map(|response| response.payload.artists)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|artist| artist.artist_name)
+        .collect())
+}
+
+// Function to find the MusicBrainz recording ID of a song, None when ListenBrainz doesn't know it
+pub fn lookup_recording(ctx: &Context, name: &str, artist: &str) -> Result<Option<String>, PilotError> {
+    let response: Option<MetadataLookupResponse> =
+        listenbrainz_get(ctx, "metadata/lookup/", &[("recording_name", name), ("artist_name", artist)])?;
+    Ok(response.and_then(|response| response.recording_mbid))
+}
+
+// Function to create a private ListenBrainz playlist of recordings, returning its URL
+pub fn create_playlist(ctx: &Context, token: &str, title: &str, recording_ids: &[String]) -> Result<String, PilotError> {
+    let request_body = CreateListenBrainzPlaylistRequest {
+        playlist: JspfPlaylist {
+            title: title.to_string(),
+            track: recording_ids
+                .iter()
+                .map(|id| JspfTrack { identifier: format!("https://musicbrainz.org/recording/{}", id) })
+                .collect(),
+            extension: JspfPlaylistExtension { playlist: JspfPlaylistSettings { public: false } },
+        },
+    };
+    let response = send(
+        ctx.client
+            .post(format!("{}/playlist/create", LISTENBRAINZ_API_URL))
+            .header("Authorization", format!("Token {}", token))
+            .json(&request_body)
+    )?;
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Error creating the ListenBrainz playlist: {}", response.status())));
+    }
+    let created: CreatedListenBrainzPlaylist =
+        response.json().map_err(|e| format!("Failed to parse the ListenBrainz response: {}", e))?;
+    Ok(format!("https://listenbrainz.org/playlist/{}", created.playlist_mbid))
+}
@@ -0,0 +1,398 @@
+// Import necessary modules and crates
+use reqwest::StatusCode;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::http::{send_with_policy, Endpoint};
+use crate::metrics::observe_llm_request;
+use crate::models::*;
+use crate::secrets::*;
+use crate::spotify::MAX_DESCRIPTION_LENGTH;
+
+// Tokens used by the LLM requests of this run, prompts and answers together
+static TOKENS_USED: AtomicU64 = AtomicU64::new(0);
+
+// Function to get the number of tokens the LLM requests of this run used so far
+pub fn tokens_used() -> u64 {
+    TOKENS_USED.load(Ordering::Relaxed)
+}
+
+// Context window assumed for models whose name isn't known, small enough for most of them
+const DEFAULT_CONTEXT_TOKENS: usize = 8192;
+
+// Context windows of the models whose name contains a key, in tokens; the first match wins, so longer names come first
+const CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("gpt-4.1", 1_000_000),
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4", 8192),
+    ("gpt-3.5", 16_385),
+    ("claude", 200_000),
+    ("gemini", 1_000_000),
+    ("llama-3.1", 128_000),
+    ("llama-3.2", 128_000),
+    ("llama-3.3", 128_000),
+    ("llama3", 8192),
+    ("mistral-large", 128_000),
+    ("mixtral", 32_768),
+    ("qwen", 32_768),
+    ("deepseek", 64_000),
+];
+
+// Function to guess the context window of a model from its name, e.g. 128,000 tokens for "nvidia/llama-3.1-nemotron-70b-instruct"
+pub fn context_window(model: &str) -> usize {
+    let model = model.to_lowercase();
+    CONTEXT_WINDOWS.iter().find(|(key, _)| model.contains(key)).map_or(DEFAULT_CONTEXT_TOKENS, |(_, tokens)| *tokens)
+}
+
+// Function to estimate the number of tokens of a text the way BPE tokenizers such as tiktoken's split it
+// Words are about one token per four letters, numbers one per three digits, and every other symbol is a token of its own.
+// It errs on the high side, which is the safe side for a budget.
+pub fn estimate_tokens(text: &str) -> usize {
+    let mut tokens = 0;
+    let mut letters: usize = 0;
+    let mut digits: usize = 0;
+    for c in text.chars().chain([' ']) {
+        if c.is_alphabetic() {
+            letters += 1;
+            continue;
+        }
+        tokens += letters.div_ceil(4);
+        letters = 0;
+        if c.is_ascii_digit() {
+            digits += 1;
+            continue;
+        }
+        tokens += digits.div_ceil(3);
+        digits = 0;
+        if !c.is_whitespace() {
+            tokens += 1;
+        }
+    }
+    tokens
+}
+
+// Function to estimate the tokens of messages sent to the LLM, with the few each message costs besides its content
+pub fn estimate_message_tokens(messages: &[Message]) -> usize {
+    messages.iter().map(|message| estimate_tokens(&message.content) + 4).sum()
+}
+
+// Number of failures in a row after which the LLM isn't asked anymore
+const BREAKER_THRESHOLD: u32 = 3;
+
+// How long the LLM isn't asked once the breaker tripped; the next request after that is a single try
+const BREAKER_COOLDOWN: Duration = Duration::from_secs(300);
+
+// Circuit breaker of the LLM API, so a provider that is down fails the next requests right away
+// instead of each of them waiting for its timeout. Only failures a retry could fix count: no answer,
+// a server error or a rate limit. An unreadable answer or a refused key don't.
+struct Breaker {
+    failures: u32,
+    opened_at: Option<Instant>,
+}
+
+static LLM_BREAKER: Mutex<Breaker> = Mutex::new(Breaker { failures: 0, opened_at: None });
+
+impl Breaker {
+    // Function to tell whether requests are refused at the given time
+    fn is_open(&self, now: Instant) -> bool {
+        self.opened_at.is_some_and(|opened_at| now.duration_since(opened_at) < BREAKER_COOLDOWN)
+    }
+
+    // Function to record the outcome of a request, tripping the breaker after too many failures
+    fn record(&mut self, succeeded: bool, now: Instant) {
+        if succeeded {
+            self.failures = 0;
+            self.opened_at = None;
+        } else {
+            self.failures += 1;
+            if self.failures >= BREAKER_THRESHOLD {
+                self.opened_at = Some(now);
+            }
+        }
+    }
+}
+
+// Function to tell whether the LLM can be asked, false while its breaker is open
+pub fn llm_available() -> bool {
+    !LLM_BREAKER.lock().unwrap().is_open(Instant::now())
+}
+
+// Function to forget the failures of the LLM, when the next requests go to another provider
+pub fn reset_breaker() {
+    let mut breaker = LLM_BREAKER.lock().unwrap();
+    breaker.failures = 0;
+    breaker.opened_at = None;
+}
+
+// Helper function to parse the LLM response
+// Cleans the response by trimming and removing surrounding backticks (`) if present.
+pub fn parse_llm_response(response: &str) -> Result<String, PilotError> {
+    let cleaned_response = response.trim().trim_matches('`');
+    Ok(cleaned_response.to_string())
+}
+
+// Function to read the cleaned JSON answer of the LLM into the type the prompt asked for
+pub fn read_llm_json<T: serde::de::DeserializeOwned>(cleaned_response: &str) -> Result<T, PilotError> {
+    serde_json::from_str(cleaned_response).map_err(|e| PilotError::LlmParse(format!("The LLM answer isn't the JSON that was asked for: {}", e)))
+}
+
+// Function to interact with an LLM API to generate new song suggestions
+pub fn ask_llm(ctx: &Context, api_key: &str, prompt: &str) -> Result<String, PilotError> {
+    ask_llm_conversation(ctx, api_key, vec![Message { role: "user".to_string(), content: prompt.to_string() }])
+}
+
+// Function to send a whole conversation to the LLM API, the earlier turns and its earlier answers, and get its next answer
+pub fn ask_llm_conversation(ctx: &Context, api_key: &str, mut messages: Vec<Message>) -> Result<String, PilotError> {
+    if !llm_available() {
+        return Err(PilotError::LlmUnavailable(format!("The LLM failed {} times in a row, it isn't asked again for now", BREAKER_THRESHOLD)));
+    }
+
+    // Prepare the request body with model and conversation, after the system message of the config
+    if !ctx.config.system_prompt.is_empty() {
+        messages.insert(0, Message { role: "system".to_string(), content: ctx.config.system_prompt.clone() });
+    }
+    let request_body = LlmRequest {
+        model: ctx.config.llm_model.clone(),
+        messages,
+    };
+
+    // Send the request to the LLM API, tried again under its retry policy
+    let started = Instant::now();
+    let response = send_with_policy(
+        Endpoint::Llm,
+        ctx.client
+            .post(&ctx.config.llm_api_url)
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json")
+            .timeout(Duration::from_secs(ctx.config.llm_timeout_secs))
+            .json(&request_body)
+    );
+    observe_llm_request(started.elapsed().as_secs_f64(), response.as_ref().is_ok_and(|response| response.status().is_success()));
+    let transient = response.as_ref().map_or(true, |response| response.status().is_server_error() || response.status() == StatusCode::TOO_MANY_REQUESTS);
+    LLM_BREAKER.lock().unwrap().record(!transient, Instant::now());
+    let response = response?;
+
+    // Parse the response
+    if response.status().is_success() {
+        let llm_response: LlmResponse = response.json().map_err(|e| PilotError::LlmParse(format!("Failed to parse response: {}", e)))?;
+        if let Some(usage) = &llm_response.usage {
+            TOKENS_USED.fetch_add(usage.total_tokens, Ordering::Relaxed);
+        }
+        if let Some(choice) = llm_response.choices.first() {
+            Ok(choice.message.content.clone())
+        } else {
+            Err(PilotError::LlmParse("No response choices available".to_string()))
+        }
+    } else {
+        Err(PilotError::from_status(response.status(), format!("The LLM API answered {}", response.status())))
+    }
+}
+
+// Number of songs shown to the LLM when it names a playlist, enough to get its feel
+const SONGS_TO_NAME: usize = 30;
+
+// Longest playlist name asked for, in characters
+const MAX_NAME_LENGTH: usize = 60;
+
+// Function to ask the LLM for the name and description of a new playlist from what it is about and its songs ("title by artist")
+// The answer is cut to what Spotify accepts.
+pub fn name_playlist(ctx: &Context, api_key: &str, about: &str, songs: &[String]) -> Result<PlaylistDetails, PilotError> {
+    let listed: Vec<&str> = songs.iter().take(SONGS_TO_NAME).map(|song| song.as_str()).collect();
+    let prompt = format!(
+        "Name a new playlist. It is {}. Its songs include: {}. Give it a short, fitting name of at most {} characters \
+        and a description of one or two sentences. The format of your answer will be a JSON object with the keys 'name' and \
+        'description', nothing more.",
+        about,
+        listed.join("; "),
+        MAX_NAME_LENGTH
+    );
+    let mut details: PlaylistDetails = read_llm_json(&parse_llm_response(&ask_llm(ctx, api_key, &prompt)?)?)?;
+    details.name = details.name.trim().chars().take(MAX_NAME_LENGTH).collect();
+    details.description = details.description.trim().chars().take(MAX_DESCRIPTION_LENGTH).collect();
+    if details.name.is_empty() {
+        return Err(PilotError::LlmParse("The LLM gave an empty name".to_string()));
+    }
+    Ok(details)
+}
+
+// Function to ask the LLM which songs of a list don't truly fit a playlist, returning their indexes with the reason it gives
+// `summary` tells what the playlist is like; numbers that aren't in the list are left out of the answer.
+pub fn find_misfits(ctx: &Context, api_key: &str, summary: &str, songs: &[String]) -> Result<Vec<(usize, String)>, PilotError> {
+    let listed: Vec<String> = songs.iter().enumerate().map(|(index, song)| format!("{}. {}", index + 1, song)).collect();
+    let prompt = format!(
+        "{} These songs were suggested for it: {}. Check each of them: does it truly fit the genre, era and mood of the playlist? \
+        The format of your answer will be a JSON object with the key 'misfits', a list of objects with the keys 'number' (of a song \
+        that clearly doesn't fit) and 'reason' (a few words), nothing more. Give an empty list when every song fits.",
+        summary,
+        listed.join("; ")
+    );
+    let answer: Misfits = read_llm_json(&parse_llm_response(&ask_llm(ctx, api_key, &prompt)?)?)?;
+    let mut misfits: Vec<(usize, String)> = Vec::new();
+    for misfit in answer.misfits.into_iter().filter(|misfit| (1..=songs.len()).contains(&misfit.number)) {
+        if !misfits.iter().any(|(index, _)| *index == misfit.number - 1) {
+            misfits.push((misfit.number - 1, misfit.reason));
+        }
+    }
+    Ok(misfits)
+}
+
+// Function to ask the LLM to pick the best `wanted` of a list of candidate songs for a playlist, returning their indexes, best first
+// `summary` tells what the playlist is like; numbers that aren't in the list and songs picked twice are left out of the answer.
+pub fn pick_candidates(ctx: &Context, api_key: &str, summary: &str, candidates: &[String], wanted: usize) -> Result<Vec<usize>, PilotError> {
+    let listed: Vec<String> = candidates.iter().enumerate().map(|(index, candidate)| format!("{}. {}", index + 1, candidate)).collect();
+    let prompt = format!(
+        "{} These songs were found for it: {}. Pick the {} that fit the playlist best and order them the way they should be \
+        added, the best first. The format of your answer will be a JSON object with the key 'picks', the list of the numbers of \
+        the songs picked, nothing more.",
+        summary,
+        listed.join("; "),
+        wanted
+    );
+    let answer: PickedCandidates = read_llm_json(&parse_llm_response(&ask_llm(ctx, api_key, &prompt)?)?)?;
+    let mut picks: Vec<usize> = Vec::new();
+    for index in answer.picks.into_iter().filter(|number| (1..=candidates.len()).contains(number)).map(|number| number - 1) {
+        if picks.len() < wanted && !picks.contains(&index) {
+            picks.push(index);
+        }
+    }
+    Ok(picks)
+}
+
+// Function to tell whether the LLM API key is known, so getting it won't ask the user
+pub fn has_llm_secret(config: &Config) -> bool {
+    config.llm_client_secret.is_some() || load_secret(&config.storage_key("llm-api-key")).is_some()
+}
+
+// Function to get the LLM API key of the selected profile
+// Taken from the config when set, otherwise from the secret store; if neither has it, the user is asked once and it is stored.
+pub fn get_llm_secret(config: &Config) -> Result<String, PilotError> {
+    if let Some(secret) = &config.llm_client_secret {
+        return Ok(secret.clone());
+    }
+    let secret_name = config.storage_key("llm-api-key");
+    if let Some(secret) = load_secret(&secret_name) {
+        return Ok(secret);
+    }
+
+    println!("Enter your LLM API key (it will be stored in your OS keyring):");
+    let mut secret = String::new();
+    std::io::stdin().read_line(&mut secret)?;
+    let secret = secret.trim().to_string();
+    save_secret(&secret_name, &secret)?;
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{llm_answer, test_context, CannedServer};
+
+    #[test]
+    fn ask_llm_returns_the_first_choice() {
+        let answer = serde_json::json!({ "choices": [{ "message": { "content": "```{\"songs\": []}```" } }], "usage": { "total_tokens": 42 } });
+        let server = CannedServer::start(vec![(200, answer.to_string()), (200, "{\"choices\": []}".to_string())]);
+        let ctx = test_context(&server);
+        let response = ask_llm(&ctx, "key", "Suggest songs").unwrap();
+        assert_eq!(parse_llm_response(&response).unwrap(), "{\"songs\": []}");
+        let request: serde_json::Value = serde_json::from_str(&server.received()[0].body).unwrap();
+        assert_eq!(request["model"], "test-model");
+        assert_eq!(request["messages"][0]["role"], "system");
+        assert_eq!(request["messages"][1]["content"], "Suggest songs");
+        assert!(matches!(ask_llm(&ctx, "key", "Again"), Err(PilotError::LlmParse(_))));
+    }
+
+    #[test]
+    fn ask_llm_conversation_sends_every_turn_in_order() {
+        let server = CannedServer::start(vec![(200, llm_answer("{\"songs\": []}")), (200, llm_answer("{\"songs\": []}"))]);
+        let mut ctx = test_context(&server);
+        ctx.config.system_prompt = "You are a DJ.".to_string();
+        let turns = [("user", "Suggest songs"), ("assistant", "{\"songs\": []}"), ("user", "More upbeat")];
+        let messages = || turns.iter().map(|(role, content)| Message { role: role.to_string(), content: content.to_string() }).collect();
+        ask_llm_conversation(&ctx, "key", messages()).unwrap();
+        // An empty system prompt sends none
+        ctx.config.system_prompt = String::new();
+        ask_llm_conversation(&ctx, "key", messages()).unwrap();
+        let sent = |index: usize| -> Vec<(String, String)> {
+            let request: serde_json::Value = serde_json::from_str(&server.received()[index].body).unwrap();
+            request["messages"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|message| (message["role"].as_str().unwrap().to_string(), message["content"].as_str().unwrap().to_string()))
+                .collect()
+        };
+        let expected: Vec<(String, String)> = turns.iter().map(|(role, content)| (role.to_string(), content.to_string())).collect();
+        assert_eq!(sent(0)[0], ("system".to_string(), "You are a DJ.".to_string()));
+        assert_eq!(sent(0)[1..], expected[..]);
+        assert_eq!(sent(1), expected);
+    }
+
+    #[test]
+    fn name_playlist_reads_and_cuts_the_answer() {
+        let details = serde_json::json!({ "name": format!(" {} ", "n".repeat(80)), "description": "Rainy trip-hop." }).to_string();
+        let server = CannedServer::start(vec![(200, llm_answer(&details))]);
+        let songs = vec!["Teardrop by Massive Attack".to_string()];
+        let named = name_playlist(&test_context(&server), "key", "made from this description: rainy trip-hop", &songs).unwrap();
+        assert_eq!(named.name.len(), MAX_NAME_LENGTH);
+        assert_eq!(named.description, "Rainy trip-hop.");
+        let request: serde_json::Value = serde_json::from_str(&server.received()[0].body).unwrap();
+        assert!(request["messages"][1]["content"].as_str().unwrap().contains("Its songs include: Teardrop by Massive Attack."));
+    }
+
+    #[test]
+    fn find_misfits_reads_the_flagged_songs() {
+        let answer = r#"{"misfits": [{"number": 2, "reason": "a 2010s pop hit"}, {"number": 5}, {"number": 2, "reason": "again"}]}"#;
+        let server = CannedServer::start(vec![(200, llm_answer(answer))]);
+        let songs = ["Teardrop by Massive Attack", "Happy by Pharrell Williams", "Roads by Portishead"].map(String::from);
+        let misfits = find_misfits(&test_context(&server), "key", "The playlist 'Rain' is 90s trip-hop.", &songs).unwrap();
+        assert_eq!(misfits, vec![(1, "a 2010s pop hit".to_string())]);
+        let request: serde_json::Value = serde_json::from_str(&server.received()[0].body).unwrap();
+        assert!(request["messages"][1]["content"].as_str().unwrap().contains("suggested for it: 1. Teardrop by Massive Attack; 2. Happy"));
+    }
+
+    #[test]
+    fn pick_candidates_keeps_the_valid_picks_in_order() {
+        let server = CannedServer::start(vec![(200, llm_answer("{\"picks\": [3, 0, 1, 3, 7, 2]}"))]);
+        let candidates = ["Teardrop by Massive Attack (popularity 70)", "Glory Box by Portishead", "Roads by Portishead"].map(String::from);
+        let picks = pick_candidates(&test_context(&server), "key", "The playlist 'Rain' is trip-hop.", &candidates, 2).unwrap();
+        assert_eq!(picks, vec![2, 0]);
+        let request: serde_json::Value = serde_json::from_str(&server.received()[0].body).unwrap();
+        let prompt = request["messages"][1]["content"].as_str().unwrap();
+        assert!(prompt.starts_with("The playlist 'Rain' is trip-hop. These songs were found for it: 1. Teardrop by Massive Attack (popularity 70); 2."));
+        assert!(prompt.contains("Pick the 2 that fit"));
+    }
+
+    #[test]
+    fn tokens_are_estimated_and_windows_guessed() {
+        assert_eq!(estimate_tokens(""), 0);
+        // A token per four letters or less of each word, one for the comma and two for the four digits
+        assert_eq!(estimate_tokens("Karma Police by Radiohead, 1997"), 2 + 2 + 1 + 3 + 1 + 2);
+        assert_eq!(context_window("nvidia/llama-3.1-nemotron-70b-instruct"), 128_000);
+        assert_eq!(context_window("GPT-4o-mini"), 128_000);
+        assert_eq!(context_window("gpt-4"), 8192);
+        assert_eq!(context_window("some-local-model"), 8192);
+    }
+
+    #[test]
+    fn breaker_opens_after_failures_in_a_row_and_retries_after_the_cooldown() {
+        let start = Instant::now();
+        let mut breaker = Breaker { failures: 0, opened_at: None };
+        breaker.record(false, start);
+        breaker.record(true, start);
+        breaker.record(false, start);
+        breaker.record(false, start);
+        assert!(!breaker.is_open(start));
+        breaker.record(false, start);
+        assert!(breaker.is_open(start + Duration::from_secs(10)));
+        // A single try after the cooldown, which trips it again when it fails
+        let later = start + BREAKER_COOLDOWN;
+        assert!(!breaker.is_open(later));
+        breaker.record(false, later);
+        assert!(breaker.is_open(later));
+    }
+}
@@ -0,0 +1,23 @@
+// Import necessary modules and crates
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter, Layer};
+
+use crate::audit::AuditLayer;
+
+// Level of the messages shown when RUST_LOG isn't set: warnings and the progress of a run
+const DEFAULT_LOG_FILTER: &str = "info";
+
+// Function to send the log messages to the error output, filtered by RUST_LOG (e.g. RUST_LOG=debug or RUST_LOG=one=warn)
+// As JSON lines when asked, e.g. for a log collector; otherwise as short lines for the terminal. Warnings and errors are also kept
+// for the audit log, whatever RUST_LOG shows.
+pub fn init_logging(json: bool) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let output = fmt::layer().with_writer(std::io::stderr);
+    let output = if json {
+        output.json().with_current_span(true).with_filter(filter).boxed()
+    } else {
+        output.without_time().with_target(false).with_filter(filter).boxed()
+    };
+    tracing_subscriber::registry().with(output).with(AuditLayer).init();
+}
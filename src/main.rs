@@ -1,266 +1,164 @@
 // Import necessary modules and crates
-use reqwest::blocking::Client;
-use reqwest::StatusCode;
 use std::env;
+use std::process::ExitCode;
 use dotenv::dotenv;
-use std::collections::HashMap;
+use tracing::warn;
 
 // Import models
+mod applemusic;
+mod audit;
+mod auth;
+mod backup;
+mod cache;
+mod cli;
+mod commands;
+mod config;
+mod context;
+mod cover;
+mod crypto;
+mod db;
+mod deezer;
+mod email;
+mod embeddings;
+mod error;
+mod feed;
+mod history;
+mod http;
+#[cfg(test)]
+mod integration_tests;
+mod lastfm;
+mod listenbrainz;
+mod llm;
+mod logging;
+mod matching;
+mod metrics;
 mod models;
-use models::*;
-
-// Helper function to parse the LLM response
-// Cleans the response by trimming and removing surrounding backticks (`) if present.
-fn parse_llm_response(response: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let cleaned_response = response.trim().trim_matches('`');
-    Ok(cleaned_response.to_string())
-}
-
-// Function to exchange the authorization code for an access token
-fn get_spotify_access(
-    client_id: &str, 
-    client_secret: &str, 
-    code: &str, 
-    redirect_uri: &str
-) -> Result<String, Box<dyn std::error::Error>> {
-    let client = Client::new();
-    let auth_url = "https://accounts.spotify.com/api/token";
-
-    // Prepare the request body as a HashMap
-    let mut body = HashMap::new();
-    body.insert("grant_type", "authorization_code");
-    body.insert("code", code);
-    body.insert("redirect_uri", redirect_uri);
-    body.insert("client_id", client_id);
-    body.insert("client_secret", client_secret);
-
-    // Send POST request to the Spotify token endpoint
-    let auth_response: SpotifyAuthResponse = client
-        .post(auth_url)
-        .header("Content-Type", "application/x-www-form-urlencoded")
-        .form(&body)
-        .send()?
-        .json()?;
-
-    // Return the access token from the response
-    Ok(auth_response.access_token)
-}
-
-// Function to generate the Spotify authorization URL
-fn get_authorization_url(client_id: &str, redirect_uri: &str) -> String {
-    let scopes = "playlist-modify-public playlist-modify-private";
-    format!(
-        "https://accounts.spotify.com/authorize?response_type=code&client_id={}&scope={}&redirect_uri={}",
-        client_id, scopes, redirect_uri
-    )
-}
-
-// Function to fetch a playlist from Spotify using its ID and an access token
-fn get_playlist(access_token: &str, playlist_id: &str) -> Result<PlaylistResponse, String> {
-    let client = Client::new();
-    let playlist_url = format!("https://api.spotify.com/v1/playlists/{}", playlist_id);
-
-    let response = client
-        .get(&playlist_url)
-        .header("Authorization", format!("Bearer {}", access_token))
-        .send();
-
-    // Handle the response and map to PlaylistResponse
-    match response {
-        Ok(resp) => {
-            match resp.status() {
-                StatusCode::OK => {
-                    let playlist_response: PlaylistResponse = resp.json().map_err(|e| e.to_string())?;
-                    Ok(playlist_response)
-                },
-                StatusCode::NOT_FOUND => Err("Invalid Playlist ID: The playlist could not be found.".into()),
-                _ => Err(format!("Error fetching playlist: {}", resp.status()).into()),
-            }
+mod musicbrainz;
+mod notify;
+mod ordering;
+mod preview;
+mod report;
+mod schedule;
+mod secrets;
+mod service;
+mod spotify;
+#[cfg(test)]
+mod testing;
+mod tidal;
+mod tokens;
+mod ytmusic;
+use cli::*;
+use config::*;
+use crate::error::PilotError;
+use crate::http::*;
+
+// Main function, the exit code tells scripts what kind of error stopped the run
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            e.exit_code()
         },
-        Err(e) => Err(format!("{}", e)),
     }
 }
 
-// Function to interact with an LLM API to generate new song suggestions
-fn ask_llm(api_key: &str, prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let client = Client::new();
-    let api_url = "https://integrate.api.nvidia.com/v1/chat/completions";
-
-    // Prepare the request body with model and prompt
-    let request_body = LlmRequest {
-        model: "nvidia/llama-3.1-nemotron-70b-instruct".to_string(),
-        messages: vec![Message {
-            role: "user".to_string(),
-            content: prompt.to_string(),
-        }],
-    };
-
-    // Send the request to the LLM API
-    let response = client
-        .post(api_url)
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .map_err(|e| format!("{}", e))?;
+// Function to handle user input and the entire process flow
+fn run() -> Result<(), PilotError> {
+    // Load environment variables from .env file
+    dotenv().ok();
 
-    // Parse the response
-    if response.status().is_success() {
-        let llm_response: LlmResponse = response.json().map_err(|e| format!("Failed to parse response: {}", e))?;
-        if let Some(choice) = llm_response.choices.get(0) {
-            Ok(choice.message.content.clone())
-        } else {
-            Err("No response choices available".into())
-        }
-    } else {
-        Err(format!("{}", response.status()).into())
+    // Parse the command line options
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut options = parse_args(args.iter().cloned()).map_err(PilotError::Usage)?;
+    let preset = options.preset.as_deref().map(load_preset).transpose()?;
+    if let Some(preset) = &preset {
+        options = apply_preset(&args, preset).map_err(PilotError::Usage)?;
     }
-}
-
-// Function to search for a specific song by artist and track name on Spotify
-fn search_song(access_token: &str, artist: &str, track: &str) -> Result<String, String> {
-    let client = Client::new();
-    let search_url = format!(
-        "https://api.spotify.com/v1/search?q=artist:{}+track:{}&type=track&limit=1",
-        artist, track
-    );
-
-    let response = client
-        .get(&search_url)
-        .header("Authorization", format!("Bearer {}", access_token))
-        .send();
-
-    // Handle the response and return the first track's URI if found
-    match response {
-        Ok(resp) => {
-            match resp.status() {
-                StatusCode::OK => {
-                    let search_response: SearchResponse = resp.json().map_err(|e| e.to_string())?;
-                    if let Some(track) = search_response.tracks.items.get(0) {
-                        Ok(track.uri.clone())
-                    } else {
-                        Err("No result found for the specified artist and track.".into())
-                    }
-                },
-                StatusCode::NOT_FOUND => Err("No results found for the specified artist and track.".into()),
-                _ => Err(format!("{}", resp.status()).into()),
-            }
-        },
-        Err(e) => Err(format!("{}", e)),
+    logging::init_logging(options.log_json);
+    if let Some(path) = &options.debug_http {
+        enable_debug_http(path)?;
+        warn!("Recording the HTTP traffic to {}, tokens and keys hidden.", path);
     }
-}
 
-// Function to add tracks to a playlist by their URIs
-fn add_to_playlist(access_token: &str, playlist_id: &str, uris: Vec<String>) -> Result<(), String> {
-    let client = Client::new();
-    let playlist_url = format!("https://api.spotify.com/v1/playlists/{playlist_id}/tracks");
+    // Unlock the encrypted token cache, if one is used
+    crypto::init_encryption()?;
 
-    let body = AddTracksRequest { uris };
-
-    // Send POST request to add tracks to the playlist
-    let response = client
-        .post(&playlist_url)
-        .header("Authorization", format!("Bearer {}", access_token))
-        .header("Content-Type", "application/json")
-        .json(&body)
-        .send();
-
-    // Check if the operation was successful
-    match response {
-        Ok(resp) => {
-            if resp.status().is_success() {
-                Ok(())
-            } else {
-                Err(format!("Failed to add tracks to playlist: {}", resp.status()))
-            }
-        },
-        Err(e) => Err(format!("{}", e)),
+    // Resolve the settings of the selected profile, command line options taking precedence
+    let mut config = load_config(options.profile.as_deref())?;
+    if let Some(min_confidence) = options.min_confidence {
+        config.min_confidence = min_confidence;
     }
-}
-
-// Main function to handle user input and the entire process flow
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Load environment variables from .env file
-    dotenv().ok();
-
-    // Read necessary environment variables
-    let spotify_client_id = env::var("spotify_client_id").expect("spotify client id not set");
-    let spotify_client_secret = env::var("spotify_client_secret").expect("spotify client secret key not set");
-    let spotify_redirect_uri = env::var("spotify_redirect_uri").expect("spotify redirect uri not set");
-    let llm_client_secret = env::var("llm_client_secret").expect("llm client secret key not set");
-    let playlist_id = env::var("playlist_id").expect("playlist id not set");
-
-    // Ask the user how many songs they want to add
-    println!("Enter the number of songs you want to add to the playlist:");
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input)
-        .expect("Failed to read input");
-
-    let number: i32 = input.trim().parse()
-        .expect("Please enter a valid number");
-
-    // Generate Spotify authorization URL and instruct the user to visit it
-    let auth_url = get_authorization_url(&spotify_client_id, &spotify_redirect_uri);
-    println!("Go to this URL to authorize: {}", auth_url);
-
-    // Get the authorization code from the user
-    let mut code = String::new();
-    println!("Enter the authorization code:");
-    std::io::stdin().read_line(&mut code)?;
-    let code = code.trim();
-
-    // Obtain access token using the authorization code
-    let access_token = get_spotify_access(&spotify_client_id, &spotify_client_secret, &code, &spotify_redirect_uri)?;
-
-    // Fetch the playlist and format the output for the LLM prompt
-    let mut output = String::new();
-    match get_playlist(&access_token, &playlist_id) {
-        Ok(playlist_response) => {
-            for item in playlist_response.tracks.items {
-                let track = item.track;
-                let artist_names: Vec<String> = track.artists.iter().map(|a| a.name.clone()).collect();
-                output.push_str(&format!("{} by {}, ", track.name, artist_names.join(", ")));
-            }
-        },
-        Err(e) => {
-            println!("{}", e);
-        }
+    if let Some(service) = options.service {
+        config.service = service;
+    }
+    if let Some(playlist) = preset.as_ref().and_then(|preset| preset.playlist.as_deref()) {
+        config.playlist_id = Some(commands::playlist_argument(playlist)?);
+    }
+    // A single --playlist replaces the configured playlist, several (or the playlists setting) are suggested for in turn
+    let mut playlists = options.playlists.clone();
+    let suggests_for_playlist = matches!(options.command, Command::Suggest) && !options.from_clipboard;
+    if playlists.is_empty() && preset.as_ref().is_none_or(|preset| preset.playlist.is_none()) && suggests_for_playlist {
+        playlists = config.playlists.clone();
     }
+    if let [playlist] = playlists.as_slice() {
+        config.playlist_id = Some(commands::playlist_argument(playlist)?);
+    }
+    if playlists.len() > 1 && !suggests_for_playlist {
+        return Err(PilotError::Usage("Several playlists can only be given to suggest".to_string()));
+    }
+    configure_requests(&config);
 
-    // Prepare prompt for the LLM to generate similar songs
-    let prompt = &format!(
-        "I will give you a playlist, give me {number} songs that are similar to the songs in the playlist, \
-        no songs that you give me should be the same as the songs in the playlist. Your goal is to give me songs that fit the vibe of the playlist. \
-        You are only allowed to give me the songs nothing more. The format of your answer will be a JSON object \
-        with the key 'songs' and the value being a list of song objects. Each song object should have the keys 'name' and 'artist'. Here is the playlist: {output}"
-    );
+    // Build the HTTP client shared by every call of this run
+    let client = build_client(&config)?;
 
-    // Ask the LLM for song suggestions and search for their URIs on Spotify
-    let mut uris_to_add = Vec::new();
-    match ask_llm(&llm_client_secret, prompt) {
-        Ok(response) => {
-            match parse_llm_response(&response) {
-                Ok(cleaned_response) => {
-                    let llm_songs: LlmSongsResponse = serde_json::from_str(&cleaned_response)?;
-                    for song in llm_songs.songs {
-                        match search_song(&access_token, &song.artist, &song.name) {
-                            Ok(uri) => uris_to_add.push(uri),
-                            Err(e) => println!("Error finding song '{} - {}': {}", song.name, song.artist, e),
-                        }
-                    }
-                },
-                Err(e) => println!("{}", e),
-            }
-        },
-        Err(e) => println!("{}", e),
+    // Other services only have what the suggestion pipeline needs
+    let suggests = matches!(
+        options.command,
+        Command::Suggest | Command::Blend(..) | Command::Generate(_) | Command::DeepDive(_) | Command::Import(_) | Command::Daemon
+    );
+    if config.service != service::Service::Spotify && !suggests && !matches!(options.command, Command::Open(_)) {
+        return Err(PilotError::Usage(format!("{} only works with suggest, blend, generate, deep-dive, import, daemon and open", service::music_service(config.service).name())));
     }
 
-    // If songs are found, add them to the playlist
-    if !uris_to_add.is_empty() {
-        match add_to_playlist(&access_token, &playlist_id, uris_to_add) {
-            Ok(_) => println!("Successfully added songs to the playlist."),
-            Err(e) => println!("{}", e),
-        }
+    match options.command.clone() {
+        Command::Suggest if options.from_clipboard => commands::import::run_clipboard(client, config, &options),
+        Command::Suggest if playlists.len() > 1 => commands::suggest::run_playlists(client, config, &options, &playlists).map(|_| ()),
+        Command::Suggest => commands::suggest::run(client, config, &options).map(|_| ()),
+        Command::Undo => commands::undo::run(client, config, &options),
+        Command::Restore(backup) => commands::restore::run(client, config, &options, &backup),
+        Command::Remove(selectors) => commands::remove::run(client, config, &options, &selectors),
+        Command::Dedupe(playlist) => commands::dedupe::run(client, config, &options, playlist.as_deref()),
+        Command::History(playlist) => commands::history::run(&config, playlist.as_deref()),
+        Command::Cleanup(playlist) => commands::cleanup::run(client, config, &options, playlist.as_deref()),
+        Command::Clone(source) => commands::clone::run(client, config, &options, &source),
+        Command::Merge(sources) => commands::merge::run(client, config, &options, &sources),
+        Command::Split(source) => commands::split::run(client, config, &options, source.as_deref()),
+        Command::Diff(a, b) => commands::diff::run(client, config, &options, &a, &b),
+        Command::Sync(source) => commands::sync::run(client, config, &options, &source),
+        Command::Reorder(playlist) => commands::reorder::run(client, config, &options, playlist.as_deref()),
+        Command::Shuffle(playlist) => commands::shuffle::run(client, config, &options, playlist.as_deref()),
+        Command::Sort(order, playlist) => commands::sort::run(client, config, &options, order, playlist.as_deref()),
+        Command::Stats(playlist) => commands::stats::run(client, config, &options, playlist.as_deref()),
+        Command::Genres(playlist) => commands::genres::run(client, config, &options, playlist.as_deref()),
+        Command::Releases(playlist) => commands::releases::run(client, config, &options, playlist.as_deref()),
+        Command::Health(playlist) => commands::health::run(client, config, &options, playlist.as_deref()),
+        Command::Analyze(what) => commands::analyze::run(client, config, &options, what.as_deref()),
+        Command::Bench(playlist) => commands::bench::run(client, config, &options, playlist.as_deref()),
+        Command::Blend(a, b) => commands::suggest::run_blend(client, config, &options, &a, &b).map(|_| ()),
+        Command::Generate(description) => commands::suggest::run_generate(client, config, &options, &description).map(|_| ()),
+        Command::DeepDive(artist) => commands::suggest::run_deep_dive(client, config, &options, &artist).map(|_| ()),
+        Command::Journey(playlist) => commands::suggest::run_journey(client, config, &options, playlist.as_deref()).map(|_| ()),
+        Command::Feedback(playlist) => commands::feedback::run(config, playlist.as_deref()),
+        Command::CacheClear => commands::cache::run_clear(),
+        Command::Export(playlist) => commands::export::run(client, config, &options, playlist.as_deref()),
+        Command::Import(path) => commands::import::run(client, config, &options, &path),
+        Command::Feed(playlist) => commands::feed::run(config, &options, playlist.as_deref()),
+        Command::Open(what) => commands::open::run(&config, what.as_deref()),
+        Command::Daemon => commands::daemon::run(client, &config),
+        Command::Weekly(seeds) => commands::suggest::run_weekly(client, config, &options, &seeds).map(|_| ()),
+        Command::Group(members) => commands::suggest::run_group(client, config, &options, &members).map(|_| ()),
+        Command::Serve => commands::serve::run(client, config, &options),
+        Command::Mcp => commands::mcp::run(client, config, &options),
     }
-    Ok(())
 }
@@ -0,0 +1,621 @@
+// Import necessary modules and crates
+use std::collections::HashSet;
+use strsim::normalized_levenshtein;
+
+use crate::models::*;
+use crate::ordering::intensity;
+use crate::spotify::parse_spotify_id;
+
+// Words marking a parenthesised or dashed part of a title as a credit or release tag rather than the title itself
+const TAG_WORDS: [&str; 5] = ["feat", "ft", "featuring", "remaster", "remastered"];
+
+// Words marking a part of a title as another recording than the studio one
+const OTHER_RECORDING_WORDS: [&str; 4] = ["live", "karaoke", "tribute", "acoustic"];
+
+// Phrases marking an artist or an album as a karaoke or tribute act rather than the original artist
+const COVER_ACT_PHRASES: [&str; 5] = ["karaoke", "tribute", "made famous", "originally performed", "in the style of"];
+
+// Share of the confidence given to the title, the rest goes to the artist
+const TITLE_WEIGHT: f64 = 0.6;
+
+// Title similarity from which a track is the requested song, whoever recorded it
+const COVER_TITLE_SIMILARITY: f64 = 0.9;
+
+// Artist similarity under which the requested song by another artist is a cover rather than a misspelled credit
+const COVER_ARTIST_SIMILARITY: f64 = 0.5;
+
+// A Spotify track matched for a suggested song, with how confident the match is
+#[derive(Debug, Clone)]
+pub struct TrackMatch {
+    pub uri: String,
+    pub name: String,
+    pub artists: Vec<String>,
+    // Spotify IDs of the artists, empty when unknown (e.g. for a cached match)
+    pub artist_ids: Vec<String>,
+    // Between 0 and 1, 1 meaning the normalized title and artist are identical
+    pub confidence: f64,
+    // URL of the smallest cover art of the album, None when unknown (e.g. for a cached match)
+    pub image: Option<String>,
+    // Between 0 and 100, None when unknown (e.g. for a cached match)
+    pub popularity: Option<u32>,
+    // Beats per minute as felt at the --bpm cadence (double an 85 BPM track for 170), None when it wasn't checked
+    pub tempo: Option<f64>,
+}
+
+impl TrackMatch {
+    // Function to build a match from a Spotify track and its confidence
+    pub fn from_track(track: &Track, confidence: f64) -> TrackMatch {
+        TrackMatch {
+            uri: track.uri.clone(),
+            name: track.name.clone(),
+            artists: track.artists.iter().map(|a| a.name.clone()).collect(),
+            artist_ids: track.artists.iter().filter_map(|a| a.id.clone()).collect(),
+            confidence,
+            image: track.album.images.iter().min_by_key(|image| image.width.unwrap_or(u32::MAX)).map(|image| image.url.clone()),
+            popularity: track.popularity,
+            tempo: None,
+        }
+    }
+}
+
+// Function to normalize a name for comparison: lowercase, letters and digits only, single spaces
+pub fn normalize(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+// Function to check whether a part of a title is a credit or release tag, e.g. "feat. X" or "2011 Remaster"
+fn is_tag(part: &str) -> bool {
+    normalize(part).split(' ').any(|word| TAG_WORDS.contains(&word))
+}
+
+// Function to get the parts of a title that aren't the title itself: bracketed parts and a dashed suffix
+fn tag_parts(name: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    for (open, close) in [('(', ')'), ('[', ']')] {
+        let mut rest = name;
+        while let Some(start) = rest.find(open) {
+            let Some(length) = rest[start..].find(close) else { break };
+            parts.push(normalize(&rest[start..start + length]));
+            rest = &rest[start + length..];
+        }
+    }
+    if let Some(index) = name.find(" - ") {
+        parts.push(normalize(&name[index..]));
+    }
+    parts
+}
+
+// Function to check whether any tag part of a title contains one of the words, unless the requested title has it too
+fn tagged_with(candidate: &str, requested: &str, words: &[&str]) -> bool {
+    let requested = normalize(requested);
+    let requested: Vec<&str> = requested.split(' ').collect();
+    tag_parts(candidate)
+        .iter()
+        .any(|part| part.split(' ').any(|word| words.contains(&word) && !requested.contains(&word)))
+}
+
+// Function to check whether a track is another recording of the requested song: live, acoustic, karaoke or by a tribute act
+// Remasters are the same recording and are handled by is_remaster.
+pub fn is_other_recording(candidate: &Track, requested: &str) -> bool {
+    let cover_act = |name: &str| {
+        let name = format!(" {} ", normalize(name));
+        COVER_ACT_PHRASES.iter().any(|phrase| name.contains(&format!(" {} ", phrase)))
+    };
+    tagged_with(&candidate.name, requested, &OTHER_RECORDING_WORDS)
+        || candidate.artists.iter().any(|artist| cover_act(&artist.name))
+        || cover_act(&candidate.album.name)
+}
+
+// Function to check whether a track is a remaster the requested title doesn't ask for
+pub fn is_remaster(candidate: &Track, requested: &str) -> bool {
+    tagged_with(&candidate.name, requested, &["remaster", "remastered"])
+}
+
+// Function to strip featured artists and remaster tags from a title or artist name, then normalize it
+// "Song (feat. X) - 2011 Remaster" and "Song ft. X" both become "song".
+pub fn clean_name(name: &str) -> String {
+    let mut name = name.to_string();
+
+    // Bracketed parts: "(feat. X)", "[Remastered 2009]"
+    for (open, close) in [('(', ')'), ('[', ']')] {
+        while let Some(start) = name.find(open) {
+            let Some(length) = name[start..].find(close) else { break };
+            let end = start + length + close.len_utf8();
+            if !is_tag(&name[start..end]) {
+                // Keep meaningful parts such as "(Don't Fear) The Reaper", but stop looking at this bracket
+                name.replace_range(start..start + open.len_utf8(), " ");
+                name.replace_range(end - close.len_utf8()..end, " ");
+                continue;
+            }
+            name.replace_range(start..end, " ");
+        }
+    }
+
+    // Dashed suffixes: "Song - 2011 Remaster", "Song - feat. X"
+    if let Some(index) = name.find(" - ") {
+        if is_tag(&name[index..]) {
+            name.truncate(index);
+        }
+    }
+
+    // Inline credits: "Song feat. X", "Artist ft. Y"
+    let normalized = normalize(&name);
+    let words: Vec<&str> = normalized.split(' ').collect();
+    let end = words
+        .iter()
+        .position(|word| ["feat", "ft", "featuring"].contains(word))
+        .unwrap_or(words.len());
+    words[..end].join(" ")
+}
+
+// Function to compute how confident we are that a Spotify track is the requested song
+// Title and artist similarities are normalized Levenshtein similarities of the cleaned names; the artist
+// similarity is the best one among the track's artists, so a featured artist can match too.
+pub fn match_confidence(candidate: &Track, artist: &str, track: &str) -> f64 {
+    let title_similarity = normalized_levenshtein(&clean_name(&candidate.name), &clean_name(track));
+    TITLE_WEIGHT * title_similarity + (1.0 - TITLE_WEIGHT) * artist_similarity(candidate, artist)
+}
+
+// Function to compute how similar the closest of a track's artists is to the requested artist, between 0 and 1
+fn artist_similarity(candidate: &Track, artist: &str) -> f64 {
+    let wanted_artist = clean_name(artist);
+    candidate
+        .artists
+        .iter()
+        .map(|a| normalized_levenshtein(&clean_name(&a.name), &wanted_artist))
+        .fold(0.0, f64::max)
+}
+
+// Function to check whether a track is the requested song recorded by someone else: a cover, a tribute band or a "made famous by" version
+// Unlike is_other_recording, it doesn't rely on the names saying so, only on the artist being far from the one asked for.
+pub fn is_cover(candidate: &Track, artist: &str, track: &str) -> bool {
+    normalized_levenshtein(&clean_name(&candidate.name), &clean_name(track)) >= COVER_TITLE_SIMILARITY
+        && artist_similarity(candidate, artist) < COVER_ARTIST_SIMILARITY
+}
+
+// Function to pick the search result that best matches the requested song, with its confidence
+// Spotify returns results by relevance, so the first of equally scored candidates is kept.
+pub fn best_candidate(candidates: &[Track], artist: &str, track: &str) -> Option<TrackMatch> {
+    candidates
+        .iter()
+        .map(|candidate| (match_confidence(candidate, artist, track), candidate))
+        .fold(None, |best: Option<(f64, &Track)>, (confidence, candidate)| match best {
+            Some((best_confidence, _)) if best_confidence >= confidence => best,
+            _ => Some((confidence, candidate)),
+        })
+        .map(|(confidence, candidate)| TrackMatch::from_track(candidate, confidence))
+}
+
+// Function to check whether every word of a query appears in a track's title or artists, ignoring case and punctuation
+pub fn matches_query(track: &Track, query: &str) -> bool {
+    let artists: Vec<&str> = track.artists.iter().map(|a| a.name.as_str()).collect();
+    let haystack = normalize(&format!("{} {}", track.name, artists.join(" ")));
+    let words: Vec<&str> = haystack.split(' ').collect();
+    let query = normalize(query);
+    !query.is_empty() && query.split(' ').all(|word| words.contains(&word))
+}
+
+// Function to build the key identifying a song regardless of its release, from its title and main artist
+pub fn song_key(name: &str, artist: &str) -> String {
+    format!("{}|{}", clean_name(name), clean_name(artist))
+}
+
+// Audio feature scores above which a track counts as instrumental or acoustic, Spotify's own cut-off
+const INSTRUMENTAL_THRESHOLD: f64 = 0.5;
+const ACOUSTIC_THRESHOLD: f64 = 0.5;
+
+// Words of a kind of song to avoid that say nothing about it, e.g. the "music" of "christmas music"
+const AVOID_FILLER: [&str; 6] = ["music", "songs", "song", "tracks", "track", "tunes"];
+
+// Title words and genre tags that give away common kinds of songs to avoid, beyond the words of the kind itself
+const AVOID_HINTS: &[(&str, &[&str], &[&str])] = &[
+    ("christmas", &["xmas", "santa", "jingle bells", "sleigh", "mistletoe"], &["christmas"]),
+    ("holiday", &["christmas", "xmas", "santa", "hanukkah"], &["christmas"]),
+    ("soundtrack", &["from the motion picture", "original score", "theme from"], &["movie tunes", "show tunes", "broadway", "video game music"]),
+    ("novelty", &["parody"], &["comedy", "parody"]),
+    ("children", &["nursery rhyme"], &["children s music", "kids"]),
+];
+
+// Function to check whether a phrase appears as whole words in a text, both normalized
+fn has_phrase(text: &str, phrase: &str) -> bool {
+    format!(" {} ", normalize(text)).contains(&format!(" {} ", phrase))
+}
+
+// Function to get the phrases a kind of song to avoid is spotted by, in titles and in genre tags
+// The kind's own words count for both, in the singular as well ("soundtracks" matches the "soundtrack" tag).
+fn avoid_phrases(kind: &str) -> (Vec<String>, Vec<String>) {
+    let normalized = normalize(kind);
+    let words: Vec<&str> = normalized.split(' ').filter(|word| !word.is_empty() && !AVOID_FILLER.contains(word)).collect();
+    if words.is_empty() {
+        return (Vec::new(), Vec::new());
+    }
+    let mut own = vec![words.join(" ")];
+    if let Some(singular) = own[0].strip_suffix('s').filter(|singular| !singular.ends_with('s')) {
+        own.push(singular.to_string());
+    }
+    let (mut titles, mut genres) = (own.clone(), own.clone());
+    for (key, title_hints, genre_hints) in AVOID_HINTS {
+        if own.iter().any(|phrase| phrase.split(' ').any(|word| word == *key)) {
+            titles.extend(title_hints.iter().map(|hint| hint.to_string()));
+            genres.extend(genre_hints.iter().map(|hint| hint.to_string()));
+        }
+    }
+    (titles, genres)
+}
+
+// Conditions a Spotify track must meet to be suggested
+#[derive(Debug, Default)]
+pub struct TrackFilter {
+    pub no_explicit: bool,
+    // First and last release years allowed, both included
+    pub years: Option<(i32, i32)>,
+    // Earliest release date allowed, as "YYYY-MM-DD"
+    pub released_since: Option<String>,
+    // Spotify popularity bounds, from 0 to 100, both included
+    pub min_popularity: Option<u32>,
+    pub max_popularity: Option<u32>,
+    // Genres none of the track's artists may be tagged with
+    pub exclude_genres: Vec<String>,
+    // Only instrumental or acoustic tracks, from their audio features
+    pub instrumental: bool,
+    pub acoustic: bool,
+    // Kinds of songs never to suggest, as written by the user (e.g. "christmas music"), spotted in titles and genre tags
+    pub avoid: Vec<String>,
+    // Genres of the seed's artists, one of which the track's artists must be tagged with (when they have tags); none when empty
+    pub seed_genres: Vec<String>,
+    // Lowest and highest intensity allowed, from the audio features, for the moods of an --arc transition
+    pub intensity: Option<(f64, f64)>,
+    // Lowest and highest tempo allowed in BPM, a track at half or double the tempo fitting too
+    pub bpm: Option<(f64, f64)>,
+}
+
+impl TrackFilter {
+    // Function to check whether any condition is set
+    pub fn is_active(&self) -> bool {
+        self.no_explicit
+            || self.years.is_some()
+            || self.released_since.is_some()
+            || self.min_popularity.is_some()
+            || self.max_popularity.is_some()
+            || !self.exclude_genres.is_empty()
+            || !self.avoid.is_empty()
+            || !self.seed_genres.is_empty()
+            || self.needs_features()
+    }
+
+    // Function to check whether audio features are needed to apply the conditions
+    pub fn needs_features(&self) -> bool {
+        self.instrumental || self.acoustic || self.intensity.is_some() || self.bpm.is_some()
+    }
+
+    // Function to check whether a tempo fits the --bpm range, at half or double time too (e.g. 85 BPM for a 170 BPM cadence)
+    pub fn allows_tempo(&self, tempo: f64) -> bool {
+        self.bpm.is_none() || self.cadence(tempo).is_some()
+    }
+
+    // Function to get the tempo a track is felt at in the --bpm range: its own, or double or half of it; None when none fits
+    pub fn cadence(&self, tempo: f64) -> Option<f64> {
+        let (low, high) = self.bpm?;
+        [tempo, tempo * 2.0, tempo / 2.0].into_iter().find(|tempo| (low..=high).contains(tempo))
+    }
+
+    // Function to check whether a track's audio features meet the conditions
+    pub fn allows_features(&self, features: &AudioFeatures) -> bool {
+        (!self.instrumental || features.instrumentalness >= INSTRUMENTAL_THRESHOLD)
+            && (!self.acoustic || features.acousticness >= ACOUSTIC_THRESHOLD)
+            && self.intensity.is_none_or(|(low, high)| (low..=high).contains(&intensity(features)))
+            && self.allows_tempo(features.tempo)
+    }
+
+    // Function to describe the conditions for the LLM prompt, None when there are none
+    pub fn prompt(&self) -> Option<String> {
+        let mut rules = Vec::new();
+        if self.no_explicit {
+            rules.push("Only give songs without explicit lyrics.".to_string());
+        }
+        if let Some((first, last)) = self.years {
+            rules.push(format!("Only give songs released between {} and {}.", first, last));
+        }
+        if let Some(since) = &self.released_since {
+            rules.push(format!("Only give songs released on {} or later, new music rather than older favourites.", since));
+        }
+        // The LLM doesn't know Spotify's scores, only whether a song is a hit or a deep cut
+        if self.max_popularity.is_some_and(|max| max < 50) {
+            rules.push("Prefer lesser-known songs and deep cuts over hits.".to_string());
+        }
+        if self.min_popularity.is_some_and(|min| min > 50) {
+            rules.push("Prefer well-known songs and hits.".to_string());
+        }
+        if !self.exclude_genres.is_empty() {
+            rules.push(format!("Never suggest songs in these genres: {}.", self.exclude_genres.join(", ")));
+        }
+        if self.instrumental {
+            rules.push("Only give instrumental songs, without vocals.".to_string());
+        }
+        if self.acoustic {
+            rules.push("Only give acoustic songs.".to_string());
+        }
+        if let Some((low, high)) = self.bpm {
+            rules.push(format!("Only give songs with a tempo between {:.0} and {:.0} BPM, or half or double that with a steady beat.", low, high));
+        }
+        if !self.avoid.is_empty() {
+            rules.push(format!("This is a hard rule: never suggest any of these, not even one: {}.", self.avoid.join(", ")));
+        }
+        (!rules.is_empty()).then(|| rules.join(" "))
+    }
+
+    // Function to find the first excluded genre among an artist's genre tags
+    // An excluded genre matches the tags containing it as whole words, e.g. "country" matches "contemporary country".
+    pub fn excluded_genre(&self, tags: &[String]) -> Option<&str> {
+        self.exclude_genres
+            .iter()
+            .find(|excluded| {
+                let excluded = format!(" {} ", normalize(excluded));
+                tags.iter().any(|tag| format!(" {} ", normalize(tag)).contains(&excluded))
+            })
+            .map(|excluded| excluded.as_str())
+    }
+
+    // Function to check whether a track's genre tags share one with the seed, untagged tracks and seeds always do
+    pub fn shares_seed_genre(&self, tags: &[String]) -> bool {
+        self.seed_genres.is_empty() || tags.is_empty() || tags.iter().any(|tag| self.seed_genres.iter().any(|genre| normalize(genre) == normalize(tag)))
+    }
+
+    // Function to find the kind of song to avoid a title gives away, e.g. "christmas music" for "Santa Baby"
+    pub fn avoided_title(&self, title: &str) -> Option<&str> {
+        self.avoid.iter().find(|kind| avoid_phrases(kind).0.iter().any(|phrase| has_phrase(title, phrase))).map(|kind| kind.as_str())
+    }
+
+    // Function to find the kind of song to avoid an artist's genre tags give away, e.g. "soundtracks" for "video game music"
+    pub fn avoided_genre(&self, tags: &[String]) -> Option<&str> {
+        self.avoid
+            .iter()
+            .find(|kind| avoid_phrases(kind).1.iter().any(|phrase| tags.iter().any(|tag| has_phrase(tag, phrase))))
+            .map(|kind| kind.as_str())
+    }
+
+    // Function to check whether a track meets every condition that can be checked on the track itself
+    pub fn allows(&self, track: &Track) -> bool {
+        if self.no_explicit && track.explicit {
+            return false;
+        }
+        // Tracks without a known release date can't be placed in the window
+        if let Some((first, last)) = self.years {
+            if !track.album.release_year().is_some_and(|year| (first..=last).contains(&year)) {
+                return false;
+            }
+        }
+        // A date only known to the year or month counts as the last day of it, so this year's albums aren't all left out
+        if let Some(since) = &self.released_since {
+            let latest = track.album.release_date.as_deref().map(|date| match date.len() {
+                4 => format!("{}-12-31", date),
+                7 => format!("{}-31", date),
+                _ => date.to_string(),
+            });
+            if latest.is_none_or(|latest| latest < *since) {
+                return false;
+            }
+        }
+        if self.min_popularity.is_some() || self.max_popularity.is_some() {
+            let Some(popularity) = track.popularity else { return false };
+            if self.min_popularity.is_some_and(|min| popularity < min) || self.max_popularity.is_some_and(|max| popularity > max) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// Set of songs already in a playlist, to detect duplicates by URI or by normalized title and artist
+#[derive(Clone)]
+pub struct Duplicates {
+    uris: HashSet<String>,
+    keys: HashSet<String>,
+}
+
+impl Duplicates {
+    // Function to build the set from the tracks currently in the playlist
+    pub fn new(tracks: &[Track]) -> Duplicates {
+        let mut duplicates = Duplicates { uris: HashSet::new(), keys: HashSet::new() };
+        duplicates.extend(tracks);
+        duplicates
+    }
+
+    // Function to add more existing tracks to the set, e.g. the user's saved tracks
+    pub fn extend(&mut self, tracks: &[Track]) {
+        for track in tracks {
+            self.uris.insert(track.uri.clone());
+            for artist in &track.artists {
+                self.keys.insert(song_key(&track.name, &artist.name));
+            }
+        }
+    }
+
+    // Function to add a song known by its name and artists (comma-separated), and its URI when known
+    pub fn add(&mut self, uri: Option<&str>, name: &str, artists: &str) {
+        if let Some(uri) = uri {
+            self.uris.insert(uri.to_string());
+        }
+        for artist in artists.split(", ") {
+            self.keys.insert(song_key(name, artist));
+        }
+    }
+
+    // Function to check whether a match is in the set, without adding it
+    pub fn contains(&self, found: &TrackMatch) -> bool {
+        self.uris.contains(&found.uri) || found.artists.iter().any(|artist| self.keys.contains(&song_key(&found.name, artist)))
+    }
+
+    // Function to add a match to the set, returning false when it is a duplicate
+    pub fn insert(&mut self, found: &TrackMatch) -> bool {
+        let keys: Vec<String> = found.artists.iter().map(|artist| song_key(&found.name, artist)).collect();
+        if self.uris.contains(&found.uri) || keys.iter().any(|key| self.keys.contains(key)) {
+            return false;
+        }
+        self.uris.insert(found.uri.clone());
+        self.keys.extend(keys);
+        true
+    }
+}
+
+// Artists and tracks the user never wants suggested
+pub struct Blacklist {
+    // As written by the user, for the prompt
+    artists: Vec<String>,
+    titles: Vec<String>,
+    // Normalized, for matching
+    artist_keys: HashSet<String>,
+    title_keys: HashSet<String>,
+    uris: HashSet<String>,
+}
+
+impl Blacklist {
+    // Function to build the blacklist, each excluded track being a Spotify URI or link, or a title
+    pub fn new(artists: &[String], tracks: &[String]) -> Blacklist {
+        let mut blacklist = Blacklist {
+            artists: artists.to_vec(),
+            titles: Vec::new(),
+            artist_keys: artists.iter().map(|artist| clean_name(artist)).collect(),
+            title_keys: HashSet::new(),
+            uris: HashSet::new(),
+        };
+        for track in tracks {
+            match parse_spotify_id(track, "track") {
+                // Only URIs and links, a single word is taken as a title rather than a bare ID
+                Some(id) if track.contains(':') || track.contains('/') => {
+                    blacklist.uris.insert(format!("spotify:track:{}", id));
+                },
+                _ => {
+                    blacklist.titles.push(track.clone());
+                    blacklist.title_keys.insert(clean_name(track));
+                },
+            }
+        }
+        blacklist
+    }
+
+    // Function to describe the blacklist for the LLM prompt, None when there is nothing the LLM can act on
+    pub fn prompt(&self) -> Option<String> {
+        let mut rules = Vec::new();
+        if !self.artists.is_empty() {
+            rules.push(format!("Never suggest songs by {}.", self.artists.join(", ")));
+        }
+        if !self.titles.is_empty() {
+            rules.push(format!("Never suggest these songs: {}.", self.titles.join(", ")));
+        }
+        (!rules.is_empty()).then(|| rules.join(" "))
+    }
+
+    // Function to check whether a match is blacklisted, by URI, title or any of its artists
+    pub fn blocks(&self, found: &TrackMatch) -> bool {
+        self.uris.contains(&found.uri)
+            || self.title_keys.contains(&clean_name(&found.name))
+            || found.artists.iter().any(|artist| self.artist_keys.contains(&clean_name(artist)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Function to build a track with one artist on an album
+    fn track(name: &str, artist: &str, album: &str) -> Track {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "artists": [{ "name": artist }],
+            "uri": "spotify:track:0",
+            "album": { "name": album },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn other_recordings_are_detected() {
+        assert!(is_other_recording(&track("Creep - Live at Glastonbury", "Radiohead", "Creep"), "Creep"));
+        assert!(is_other_recording(&track("Creep", "Karaoke Hits Band", "Karaoke Hits 90s"), "Creep"));
+        assert!(is_other_recording(&track("Creep", "Radio Heads", "A Tribute to Radiohead"), "Creep"));
+        // "Live" is part of the title here, and a live version that was asked for is fine
+        assert!(!is_other_recording(&track("Live Forever", "Oasis", "Definitely Maybe"), "Live Forever"));
+        assert!(!is_other_recording(&track("Creep (Live)", "Radiohead", "Creep"), "Creep (Live)"));
+    }
+
+    #[test]
+    fn avoided_kinds_are_spotted_in_titles_and_genres() {
+        let filter = TrackFilter { avoid: vec!["christmas music".to_string(), "soundtracks".to_string()], ..Default::default() };
+        assert_eq!(filter.avoided_title("Last Christmas - Remastered"), Some("christmas music"));
+        assert_eq!(filter.avoided_title("Jingle Bells"), Some("christmas music"));
+        assert_eq!(filter.avoided_title("Main Theme (From the Motion Picture \"Jaws\")"), Some("soundtracks"));
+        assert_eq!(filter.avoided_genre(&["video game music".to_string()]), Some("soundtracks"));
+        assert_eq!(filter.avoided_genre(&["orchestral soundtrack".to_string()]), Some("soundtracks"));
+        // Whole words only
+        assert_eq!(filter.avoided_title("Santana Groove"), None);
+        assert_eq!(filter.avoided_genre(&["indie rock".to_string()]), None);
+    }
+
+    #[test]
+    fn seed_genres_are_shared_by_any_tag() {
+        let filter = TrackFilter { seed_genres: vec!["shoegaze".to_string(), "Dream Pop".to_string()], ..Default::default() };
+        assert!(filter.is_active());
+        assert!(filter.shares_seed_genre(&["dream pop".to_string(), "indie".to_string()]));
+        assert!(!filter.shares_seed_genre(&["dance pop".to_string()]));
+        // Artists Spotify has no genres for can't be told apart
+        assert!(filter.shares_seed_genre(&[]));
+        assert!(TrackFilter::default().shares_seed_genre(&["dance pop".to_string()]));
+    }
+
+    #[test]
+    fn tempos_fit_the_bpm_range_at_half_or_double_time() {
+        let filter = TrackFilter { bpm: Some((165.0, 180.0)), ..Default::default() };
+        assert!(filter.needs_features());
+        assert!(filter.allows_tempo(172.0));
+        assert!(filter.allows_tempo(86.0));
+        assert!(!filter.allows_tempo(120.0));
+        assert_eq!(filter.cadence(86.0), Some(172.0));
+        assert_eq!(filter.cadence(120.0), None);
+        let features: AudioFeatures = serde_json::from_value(serde_json::json!({
+            "id": "a", "energy": 0.9, "valence": 0.5, "danceability": 0.7, "acousticness": 0.1, "tempo": 340.0, "key": 0, "mode": 1
+        }))
+        .unwrap();
+        assert!(filter.allows_features(&features));
+        assert!(TrackFilter::default().allows_tempo(120.0));
+    }
+
+    #[test]
+    fn only_tracks_released_since_the_date_are_allowed() {
+        let filter = TrackFilter { released_since: Some("2026-04-14".to_string()), ..Default::default() };
+        let released = |date: Option<&str>| {
+            let mut track = track("Song", "Artist", "Album");
+            track.album.release_date = date.map(str::to_string);
+            filter.allows(&track)
+        };
+        assert!(released(Some("2026-05-01")));
+        assert!(released(Some("2026-04-14")));
+        assert!(!released(Some("2026-04-13")));
+        assert!(released(Some("2026")));
+        assert!(released(Some("2026-04")));
+        assert!(!released(Some("2025")));
+        assert!(!released(None));
+    }
+
+    #[test]
+    fn covers_are_the_song_by_a_far_artist() {
+        assert!(is_cover(&track("Creep", "Vitamin String Quartet", "Strung Out on Radiohead"), "Radiohead", "Creep"));
+        assert!(is_cover(&track("Creep", "The Sound-Alikes", "Hits of the 90s"), "Radiohead", "Creep"));
+        // A slightly different credit is still the artist, and another song isn't a cover
+        assert!(!is_cover(&track("Creep", "Radiohead.", "Pablo Honey"), "Radiohead", "Creep"));
+        assert!(!is_cover(&track("Creep", "The Beatles", "Pablo Honey"), "Beatles", "Creep"));
+        assert!(!is_cover(&track("No Surprises", "Vitamin String Quartet", "Strung Out on Radiohead"), "Radiohead", "Creep"));
+    }
+
+    #[test]
+    fn remasters_are_detected() {
+        assert!(is_remaster(&track("Karma Police - Remastered 2017", "Radiohead", "OK Computer"), "Karma Police"));
+        assert!(!is_remaster(&track("Karma Police", "Radiohead", "OK Computer"), "Karma Police"));
+    }
+}
@@ -0,0 +1,180 @@
+// Import necessary modules and crates
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::error::PilotError;
+use crate::history::Suggestion;
+use crate::llm::tokens_used;
+
+// Upper bounds of the buckets of the LLM latency histogram, in seconds
+const LLM_BUCKETS: [f64; 9] = [0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0];
+
+// Content type of the Prometheus text format
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+// Distribution of durations, counted in cumulative buckets as Prometheus expects
+struct Histogram {
+    // Observations at most as long as each bucket's bound
+    buckets: [u64; LLM_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+// Counters and histograms of the process since it started
+struct Metrics {
+    // HTTP requests to the APIs, retries included, by host and status code ("error" when no answer came)
+    http_requests: BTreeMap<(String, String), u64>,
+    // LLM requests by outcome, and how long they took
+    llm_requests: BTreeMap<&'static str, u64>,
+    llm_latency: Histogram,
+    // Suggestions of the runs that wrote to a playlist, by what was done with them
+    suggestions: BTreeMap<&'static str, u64>,
+    // Scheduled runs of the daemon by outcome
+    runs: BTreeMap<&'static str, u64>,
+}
+
+static METRICS: Mutex<Metrics> = Mutex::new(Metrics::new());
+
+impl Metrics {
+    const fn new() -> Metrics {
+        Metrics {
+            http_requests: BTreeMap::new(),
+            llm_requests: BTreeMap::new(),
+            llm_latency: Histogram { buckets: [0; LLM_BUCKETS.len()], sum: 0.0, count: 0 },
+            suggestions: BTreeMap::new(),
+            runs: BTreeMap::new(),
+        }
+    }
+
+    // Function to write the metrics in the Prometheus text format
+    fn render(&self, tokens: u64) -> String {
+        let mut lines = Vec::new();
+        lines.push("# HELP playlistpilot_http_requests_total HTTP requests to the APIs, retries included.".to_string());
+        lines.push("# TYPE playlistpilot_http_requests_total counter".to_string());
+        for ((host, status), count) in &self.http_requests {
+            lines.push(format!("playlistpilot_http_requests_total{{host=\"{}\",status=\"{}\"}} {}", label(host), label(status), count));
+        }
+        lines.push("# HELP playlistpilot_llm_requests_total LLM requests by outcome.".to_string());
+        lines.push("# TYPE playlistpilot_llm_requests_total counter".to_string());
+        for (status, count) in &self.llm_requests {
+            lines.push(format!("playlistpilot_llm_requests_total{{status=\"{}\"}} {}", status, count));
+        }
+        lines.push("# HELP playlistpilot_llm_request_duration_seconds Time the LLM took to answer.".to_string());
+        lines.push("# TYPE playlistpilot_llm_request_duration_seconds histogram".to_string());
+        for (bound, count) in LLM_BUCKETS.iter().zip(self.llm_latency.buckets) {
+            lines.push(format!("playlistpilot_llm_request_duration_seconds_bucket{{le=\"{}\"}} {}", bound, count));
+        }
+        lines.push(format!("playlistpilot_llm_request_duration_seconds_bucket{{le=\"+Inf\"}} {}", self.llm_latency.count));
+        lines.push(format!("playlistpilot_llm_request_duration_seconds_sum {}", self.llm_latency.sum));
+        lines.push(format!("playlistpilot_llm_request_duration_seconds_count {}", self.llm_latency.count));
+        lines.push("# HELP playlistpilot_llm_tokens_total Tokens the LLM requests used, prompts and answers together.".to_string());
+        lines.push("# TYPE playlistpilot_llm_tokens_total counter".to_string());
+        lines.push(format!("playlistpilot_llm_tokens_total {}", tokens));
+        lines.push("# HELP playlistpilot_suggestions_total Suggestions of the runs that wrote to a playlist, by status (added, failed...).".to_string());
+        lines.push("# TYPE playlistpilot_suggestions_total counter".to_string());
+        for (status, count) in &self.suggestions {
+            lines.push(format!("playlistpilot_suggestions_total{{status=\"{}\"}} {}", status, count));
+        }
+        lines.push("# HELP playlistpilot_runs_total Scheduled runs of the daemon by outcome.".to_string());
+        lines.push("# TYPE playlistpilot_runs_total counter".to_string());
+        for (status, count) in &self.runs {
+            lines.push(format!("playlistpilot_runs_total{{status=\"{}\"}} {}", status, count));
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+}
+
+// Function to escape a label value of the text format
+fn label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+// Function to count an HTTP request to an API
+pub fn count_http_request(host: &str, status: &str) {
+    let mut metrics = METRICS.lock().unwrap();
+    *metrics.http_requests.entry((host.to_string(), status.to_string())).or_insert(0) += 1;
+}
+
+// Function to count an LLM request and how long it took
+pub fn observe_llm_request(seconds: f64, succeeded: bool) {
+    let mut metrics = METRICS.lock().unwrap();
+    *metrics.llm_requests.entry(if succeeded { "ok" } else { "error" }).or_insert(0) += 1;
+    let latency = &mut metrics.llm_latency;
+    for (bucket, bound) in latency.buckets.iter_mut().zip(LLM_BUCKETS) {
+        if seconds <= bound {
+            *bucket += 1;
+        }
+    }
+    latency.sum += seconds;
+    latency.count += 1;
+}
+
+// Function to count the suggestions of a run that wrote to a playlist
+pub fn count_suggestions(suggestions: &[Suggestion]) {
+    let mut metrics = METRICS.lock().unwrap();
+    for suggestion in suggestions {
+        *metrics.suggestions.entry(suggestion.status.as_str()).or_insert(0) += 1;
+    }
+}
+
+// Function to count a scheduled run of the daemon
+pub fn count_run(succeeded: bool) {
+    *METRICS.lock().unwrap().runs.entry(if succeeded { "ok" } else { "error" }).or_insert(0) += 1;
+}
+
+// Function to write the metrics of the process in the Prometheus text format
+pub fn render_metrics() -> String {
+    METRICS.lock().unwrap().render(tokens_used())
+}
+
+// Function to serve GET /metrics on an address from a background thread, for processes without a web server (the daemon)
+pub fn serve_metrics(address: &str) -> Result<(), PilotError> {
+    let listener = TcpListener::bind(address).map_err(|e| format!("Could not serve the metrics on {}: {}", address, e))?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // A client that never sends its request doesn't hold up the next scrape
+            let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+            let mut request_line = String::new();
+            if BufReader::new(&stream).read_line(&mut request_line).is_err() {
+                continue;
+            }
+            let response = match request_line.split_whitespace().nth(1) {
+                Some("/metrics") => {
+                    let body = render_metrics();
+                    format!("HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", METRICS_CONTENT_TYPE, body.len(), body)
+                },
+                _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+            };
+            let _ = (&stream).write_all(response.as_bytes());
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_writes_counters_and_cumulative_buckets() {
+        let mut metrics = Metrics::new();
+        metrics.http_requests.insert(("api.spotify.com".to_string(), "200".to_string()), 12);
+        metrics.llm_requests.insert("ok", 2);
+        metrics.llm_latency = Histogram { buckets: [0, 1, 1, 2, 2, 2, 2, 2, 2], sum: 4.5, count: 2 };
+        metrics.suggestions.insert("added", 5);
+        let text = metrics.render(1500);
+        assert!(text.contains("playlistpilot_http_requests_total{host=\"api.spotify.com\",status=\"200\"} 12\n"));
+        assert!(text.contains("playlistpilot_llm_request_duration_seconds_bucket{le=\"0.5\"} 0\n"));
+        assert!(text.contains("playlistpilot_llm_request_duration_seconds_bucket{le=\"2.5\"} 1\n"));
+        assert!(text.contains("playlistpilot_llm_request_duration_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(text.contains("playlistpilot_llm_request_duration_seconds_sum 4.5\n"));
+        assert!(text.contains("playlistpilot_llm_tokens_total 1500\n"));
+        assert!(text.contains("playlistpilot_suggestions_total{status=\"added\"} 5\n"));
+        assert!(text.contains("# TYPE playlistpilot_runs_total counter\n"));
+    }
+}
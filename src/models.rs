@@ -3,32 +3,261 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Deserialize)]
 pub struct SpotifyAuthResponse {
     pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_in: u64,
 }
 
 #[derive(Debug, Deserialize)]
+pub struct SpotifyAuthError {
+    // e.g. "invalid_grant" for an authorization code that was already used or has expired
+    pub error: String,
+    pub error_description: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CachedToken {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    // Unix timestamp (seconds) after which the access token is no longer valid
+    pub expires_at: u64,
+    // Scopes the token was granted for
+    pub scope: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Track {
     pub name: String,
     pub artists: Vec<Artist>,
     pub uri: String,
+    // Local files are listed in playlists but don't exist on Spotify
+    #[serde(default)]
+    pub is_local: bool,
+    #[serde(default)]
+    pub external_ids: ExternalIds,
+    // Only sent when a market was requested: false when the track can't be played there
+    pub is_playable: Option<bool>,
+    #[serde(default)]
+    pub duration_ms: u64,
+    // Between 0 and 100, missing for local files
+    pub popularity: Option<u32>,
+    #[serde(default)]
+    pub album: Album,
+    #[serde(default)]
+    pub explicit: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Album {
+    #[serde(default)]
+    pub name: String,
+    // "1997", "1997-05" or "1997-05-21" depending on how precisely Spotify knows it
+    pub release_date: Option<String>,
+    // Cover art in several sizes
+    #[serde(default)]
+    pub images: Vec<Image>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Image {
+    pub url: String,
+    pub width: Option<u32>,
+}
+
+impl Album {
+    // Function to get the release year, when known
+    pub fn release_year(&self) -> Option<i32> {
+        self.release_date.as_deref()?.get(..4)?.parse().ok()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ExternalIds {
+    // International Standard Recording Code, identifies one exact recording
+    pub isrc: Option<String>,
+}
+
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Artist {
     pub name: String,
+    // Missing for the artists of local files
+    pub id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArtistsResponse {
+    // null for unknown IDs
+    pub artists: Vec<Option<FullArtist>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FullArtist {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub genres: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct PlaylistResponse {
+    pub name: String,
+    // Version of the playlist, changes whenever its contents change
+    pub snapshot_id: String,
+    // Whether other users can add tracks to it
+    #[serde(default)]
+    pub collaborative: bool,
     pub tracks: PlaylistTracks,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PlaylistSnapshot {
+    pub snapshot_id: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PlaylistTracks {
     pub items: Vec<TrackItem>,
+    // URL of the next page of items, None on the last page
+    pub next: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct TrackItem {
+    // null when the track was removed from Spotify
+    pub track: Option<PlaylistItem>,
+    // When the item was added, e.g. "2024-05-21T18:03:12Z", null for very old playlists
+    pub added_at: Option<String>,
+    // User who added the item, null for very old playlists
+    #[serde(default)]
+    pub added_by: Option<AddedBy>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddedBy {
+    pub id: String,
+}
+
+// A playlist entry is either a track or a podcast episode, anything else is kept as Other
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PlaylistItem {
+    Track(Track),
+    Episode(Episode),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Episode {
+    pub name: String,
+    pub uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SavedTracksPage {
+    pub items: Vec<SavedTrackItem>,
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SavedTrackItem {
+    pub track: Track,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserPlaylistsPage {
+    pub items: Vec<UserPlaylist>,
+    pub next: Option<String>,
+}
+
+// A playlist followed or owned by the user, as listed without its tracks
+#[derive(Debug, Deserialize)]
+pub struct UserPlaylist {
+    pub id: String,
+    pub name: String,
+    // None when Spotify leaves it out
+    #[serde(default)]
+    pub owner: Option<PlaylistOwner>,
+    pub tracks: PlaylistTracksCount,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlaylistOwner {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlaylistTracksCount {
+    pub total: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TopTracksResponse {
+    pub items: Vec<Track>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TopArtistsResponse {
+    pub items: Vec<FullArtist>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FollowedArtistsResponse {
+    pub artists: FollowedArtistsPage,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FollowedArtistsPage {
+    pub items: Vec<FullArtist>,
+    // Link to the next page, which starts after the last artist of this one
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArtistAlbumsResponse {
+    pub items: Vec<Release>,
+}
+
+// An album or single of an artist, without its tracks
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    // "1997", "1997-05" or "1997-05-21" depending on how precisely Spotify knows it
+    pub release_date: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeveralAlbumsResponse {
+    // null for albums Spotify doesn't know
+    pub albums: Vec<Option<AlbumWithTracks>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlbumWithTracks {
+    #[serde(default)]
+    pub name: String,
+    pub release_date: Option<String>,
+    #[serde(default)]
+    pub images: Vec<Image>,
+    pub tracks: AlbumTracks,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlbumTracks {
+    // Without their album, which is the one they are listed in
+    pub items: Vec<Track>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecentlyPlayedResponse {
+    pub items: Vec<PlayHistoryItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlayHistoryItem {
     pub track: Track,
 }
 
@@ -48,7 +277,7 @@ pub struct LlmRequest {
     pub messages: Vec<Message>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Message {
     pub role: String,
     pub content: String,
@@ -57,6 +286,13 @@ pub struct Message {
 #[derive(Debug, Deserialize)]
 pub struct LlmResponse {
     pub choices: Vec<Choice>,
+    // Tokens the request used, not every OpenAI-compatible API gives it
+    pub usage: Option<LlmUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LlmUsage {
+    pub total_tokens: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -69,18 +305,803 @@ pub struct MessageResponse {
     pub content: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct LlmBucketsResponse {
+    pub buckets: Vec<LlmBucket>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LlmBucket {
+    pub name: String,
+    // Numbers of the tracks in the list sent to the LLM, starting from 1
+    pub tracks: Vec<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImageRequest {
+    pub model: String,
+    pub prompt: String,
+    pub n: u32,
+    pub size: String,
+    pub response_format: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImageResponse {
+    pub data: Vec<GeneratedImage>,
+}
+
+// A generated image, given inline or as a link depending on the provider
+#[derive(Debug, Deserialize)]
+pub struct GeneratedImage {
+    pub b64_json: Option<String>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmbeddingsRequest {
+    pub model: String,
+    pub input: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingsResponse {
+    pub data: Vec<Embedding>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Embedding {
+    // Position of the input this embedding is for
+    pub index: usize,
+    pub embedding: Vec<f64>,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct LlmSongsResponse {
     pub songs: Vec<Song>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+// Link of the short clip of a track, when Spotify has one
+#[derive(Debug, Deserialize)]
+pub struct TrackPreview {
+    pub preview_url: Option<String>,
+}
+
+// Whether a track can be played in the requested market, and the track it stands for when Spotify relinked it
+#[derive(Debug, Deserialize)]
+pub struct PlayableTrack {
+    pub uri: String,
+    pub is_playable: Option<bool>,
+    // The requested track, when this is the version of it available in the market
+    pub linked_from: Option<TrackLink>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TrackLink {
+    pub uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlayableTracksResponse {
+    // null for IDs Spotify doesn't know
+    pub tracks: Vec<Option<PlayableTrack>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeveralTracksResponse {
+    // null for IDs Spotify doesn't know
+    pub tracks: Vec<Option<Track>>,
+}
+
+// Name and description the LLM gives a new playlist
+#[derive(Debug, Deserialize)]
+pub struct PlaylistDetails {
+    pub name: String,
+    pub description: String,
+}
+
+// A suggestion the LLM finds doesn't fit a playlist, by its number in the list it was shown
+#[derive(Debug, Deserialize)]
+pub struct Misfit {
+    pub number: usize,
+    #[serde(default)]
+    pub reason: String,
+}
+
+// Suggestions the LLM finds don't fit a playlist
+#[derive(Debug, Deserialize)]
+pub struct Misfits {
+    pub misfits: Vec<Misfit>,
+}
+
+// Candidates the LLM picks for a playlist, by their numbers in the list it was shown, best first
+#[derive(Debug, Deserialize)]
+pub struct PickedCandidates {
+    pub picks: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Song {
     pub name: String,
     pub artist: String,
+    // Known when the LLM or an imported file provides it, allows matching the exact recording
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub isrc: Option<String>,
+    // MusicBrainz recording ID, known once the song was found on MusicBrainz
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mbid: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct AddTracksRequest {
-    pub uris: Vec<String>, 
-}
\ No newline at end of file
+    pub uris: Vec<String>,
+    // Index the tracks are inserted at, appended at the end when None
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<usize>,
+}
+#[derive(Debug, Serialize)]
+pub struct RemoveTracksRequest {
+    pub tracks: Vec<TrackUri>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrackUri {
+    pub uri: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RemoveOccurrencesRequest {
+    pub tracks: Vec<TrackOccurrence>,
+    // Version of the playlist the positions are counted in
+    pub snapshot_id: String,
+}
+
+// Occurrences of a track to remove, by their positions in the playlist
+#[derive(Debug, Serialize)]
+pub struct TrackOccurrence {
+    pub uri: String,
+    pub positions: Vec<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserProfile {
+    pub id: String,
+    // Name shown on the profile, None when the user didn't set one
+    #[serde(default)]
+    pub display_name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlaylistDetailsRequest {
+    pub description: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SaveTracksRequest {
+    // Spotify IDs of the tracks, not their URIs
+    pub ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatePlaylistRequest {
+    pub name: String,
+    pub description: String,
+    pub public: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatedPlaylist {
+    pub id: String,
+    pub external_urls: ExternalUrls,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExternalUrls {
+    pub spotify: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AudioFeaturesResponse {
+    // null for tracks Spotify has no features for
+    pub audio_features: Vec<Option<AudioFeatures>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AudioFeatures {
+    pub id: String,
+    // Between 0 and 1
+    pub energy: f64,
+    pub valence: f64,
+    pub danceability: f64,
+    pub acousticness: f64,
+    #[serde(default)]
+    pub instrumentalness: f64,
+    // Average loudness in decibels, usually between -60 and 0
+    #[serde(default)]
+    pub loudness: f64,
+    // Beats per minute
+    pub tempo: f64,
+    // Pitch class of the key (0 for C, 1 for C#...), -1 when unknown
+    pub key: i32,
+    // 1 for major, 0 for minor
+    pub mode: i32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AudioAnalysis {
+    pub track: AnalysisTrack,
+    // The parts of the track (intro, verse, chorus...), in order
+    pub sections: Vec<AnalysisSection>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AnalysisTrack {
+    // In seconds
+    pub duration: f64,
+    // In decibels
+    pub loudness: f64,
+    pub tempo: f64,
+    pub key: i32,
+    pub mode: i32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AnalysisSection {
+    // In seconds from the start of the track
+    pub start: f64,
+    pub duration: f64,
+    pub loudness: f64,
+    pub tempo: f64,
+    // Pitch class and mode as in the audio features, -1 when unknown
+    pub key: i32,
+    pub mode: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReorderTracksRequest {
+    pub range_start: usize,
+    pub insert_before: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecommendationsResponse {
+    pub tracks: Vec<Track>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LastfmError {
+    pub error: i32,
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LastfmSimilarArtistsResponse {
+    pub similarartists: LastfmArtists,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LastfmArtists {
+    #[serde(default)]
+    pub artist: Vec<LastfmArtist>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LastfmArtist {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LastfmTopTracksResponse {
+    pub toptracks: LastfmTracks,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LastfmTopArtistsResponse {
+    pub topartists: LastfmArtists,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LastfmRecentTracksResponse {
+    pub recenttracks: LastfmTracks,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LastfmSimilarTracksResponse {
+    pub similartracks: LastfmTracks,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LastfmTracks {
+    #[serde(default)]
+    pub track: Vec<LastfmTrack>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LastfmTrack {
+    pub name: String,
+    pub artist: LastfmArtist,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListensResponse {
+    pub payload: ListensPayload,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListensPayload {
+    pub listens: Vec<Listen>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Listen {
+    pub track_metadata: TrackMetadata,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TrackMetadata {
+    pub artist_name: String,
+    pub track_name: String,
+    #[serde(default)]
+    pub additional_info: AdditionalInfo,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AdditionalInfo {
+    // Sent by the players that know it
+    pub isrc: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TopRecordingsResponse {
+    pub payload: TopRecordingsPayload,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TopRecordingsPayload {
+    pub recordings: Vec<TopRecording>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TopRecording {
+    pub artist_name: String,
+    pub track_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListenBrainzTopArtistsResponse {
+    pub payload: ListenBrainzTopArtistsPayload,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListenBrainzTopArtistsPayload {
+    pub artists: Vec<ListenBrainzArtist>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListenBrainzArtist {
+    pub artist_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MetadataLookupResponse {
+    // Missing when no recording matches
+    pub recording_mbid: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateListenBrainzPlaylistRequest {
+    pub playlist: JspfPlaylist,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JspfPlaylist {
+    pub title: String,
+    pub track: Vec<JspfTrack>,
+    pub extension: JspfPlaylistExtension,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JspfTrack {
+    // URL of the MusicBrainz recording
+    pub identifier: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JspfPlaylistExtension {
+    #[serde(rename = "https://musicbrainz.org/doc/jspf#playlist")]
+    pub playlist: JspfPlaylistSettings,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JspfPlaylistSettings {
+    pub public: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatedListenBrainzPlaylist {
+    pub playlist_mbid: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MusicBrainzSearchResponse {
+    pub recordings: Vec<MusicBrainzRecording>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MusicBrainzRecording {
+    // MusicBrainz recording ID (MBID)
+    pub id: String,
+    // How well the recording matches the search, from 0 to 100
+    pub score: u32,
+    pub title: String,
+    #[serde(rename = "artist-credit", default)]
+    pub artist_credit: Vec<ArtistCredit>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArtistCredit {
+    // Name the artist is credited under on this recording
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GoogleAuthResponse {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_in: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubePlaylists {
+    pub items: Vec<YoutubePlaylist>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubePlaylist {
+    pub snippet: YoutubePlaylistSnippet,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct YoutubePlaylistSnippet {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YoutubePlaylistItems {
+    pub items: Vec<YoutubePlaylistItem>,
+    // Token of the next page of items, None on the last page
+    pub next_page_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubePlaylistItem {
+    pub snippet: YoutubeItemSnippet,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YoutubeItemSnippet {
+    pub title: String,
+    // Missing for deleted and private videos
+    pub video_owner_channel_title: Option<String>,
+    pub resource_id: YoutubeResourceId,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YoutubeResourceId {
+    pub kind: String,
+    pub video_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubeSearchResponse {
+    pub items: Vec<YoutubeSearchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubeSearchResult {
+    pub id: YoutubeSearchId,
+    pub snippet: YoutubeVideoSnippet,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YoutubeSearchId {
+    // Only set for videos
+    pub video_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YoutubeVideoSnippet {
+    pub title: String,
+    pub channel_title: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct YoutubePlaylistRequest {
+    pub snippet: YoutubePlaylistSnippet,
+    pub status: YoutubeStatus,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YoutubeStatus {
+    pub privacy_status: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YoutubeCreatedPlaylist {
+    pub id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct YoutubePlaylistItemRequest {
+    pub snippet: YoutubePlaylistItemSnippet,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YoutubePlaylistItemSnippet {
+    pub playlist_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<usize>,
+    pub resource_id: YoutubeResourceId,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AppleStorefronts {
+    pub data: Vec<AppleStorefront>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AppleStorefront {
+    // Country code of the catalog, e.g. "us"
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AppleSearchResponse {
+    pub results: AppleSearchResults,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AppleSearchResults {
+    // Missing when no song was found
+    pub songs: Option<AppleSongs>,
+}
+
+// A page of catalog songs, or of the songs of a library playlist
+#[derive(Debug, Deserialize)]
+pub struct AppleSongs {
+    pub data: Vec<AppleSong>,
+    // Path of the next page, None on the last page
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AppleSong {
+    pub id: String,
+    pub attributes: AppleSongAttributes,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppleSongAttributes {
+    pub name: String,
+    #[serde(default)]
+    pub artist_name: String,
+    #[serde(default)]
+    pub album_name: String,
+    // Only set for catalog songs
+    pub isrc: Option<String>,
+    pub release_date: Option<String>,
+    // "explicit" or "clean", missing when the song has no rating
+    pub content_rating: Option<String>,
+    #[serde(default)]
+    pub duration_in_millis: u64,
+    pub play_params: Option<ApplePlayParams>,
+    pub artwork: Option<AppleArtwork>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AppleArtwork {
+    // Template with {w} and {h} in place of the size, e.g. ".../{w}x{h}bb.jpg"
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplePlayParams {
+    // ID of the catalog song a library song stands for, missing for uploaded songs
+    pub catalog_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AppleLibraryPlaylists {
+    pub data: Vec<AppleLibraryPlaylist>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AppleLibraryPlaylist {
+    pub id: String,
+    pub attributes: ApplePlaylistAttributes,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ApplePlaylistAttributes {
+    pub name: String,
+    #[serde(default, skip_deserializing)]
+    pub description: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AppleCreatePlaylistRequest {
+    pub attributes: ApplePlaylistAttributes,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AppleTracksRequest {
+    pub data: Vec<AppleResource>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AppleResource {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TidalDeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri_complete: String,
+    // Seconds until the device code expires, and to wait between two polls
+    pub expires_in: u64,
+    pub interval: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TidalAuthResponse {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_in: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TidalAuthError {
+    // "authorization_pending" while the user hasn't approved the device yet
+    pub error: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TidalSession {
+    pub user_id: u64,
+    pub country_code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TidalTracks {
+    pub items: Vec<TidalTrack>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TidalTrack {
+    pub id: u64,
+    pub title: String,
+    // e.g. "Remastered 2011", shown after the title
+    pub version: Option<String>,
+    // In seconds
+    #[serde(default)]
+    pub duration: u64,
+    #[serde(default)]
+    pub explicit: bool,
+    pub popularity: Option<u32>,
+    pub isrc: Option<String>,
+    #[serde(default)]
+    pub artists: Vec<TidalArtist>,
+    pub album: Option<TidalAlbum>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TidalArtist {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TidalAlbum {
+    pub title: String,
+    pub release_date: Option<String>,
+    // ID of the cover art, e.g. "4e1a-..."
+    pub cover: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TidalPlaylist {
+    pub title: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TidalPlaylistItems {
+    pub items: Vec<TidalPlaylistItem>,
+    pub total_number_of_items: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TidalPlaylistItem {
+    // "track" or "video"
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub item: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TidalCreatedPlaylist {
+    pub uuid: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeezerAuthResponse {
+    pub access_token: String,
+    // Seconds the token lasts, 0 when it never expires (with the offline_access permission)
+    #[serde(default)]
+    pub expires: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeezerTracks {
+    pub data: Vec<DeezerTrack>,
+    // URL of the next page, None on the last page
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeezerTrack {
+    pub id: u64,
+    pub title: String,
+    // In seconds
+    #[serde(default)]
+    pub duration: u64,
+    #[serde(default)]
+    pub explicit_lyrics: bool,
+    // False when the track can't be played in the user's country
+    pub readable: Option<bool>,
+    // Only given by the track endpoint, not by searches and playlists
+    pub isrc: Option<String>,
+    pub release_date: Option<String>,
+    pub artist: DeezerArtist,
+    pub album: Option<DeezerAlbum>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeezerArtist {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeezerAlbum {
+    pub title: String,
+    // URL of the 56x56 cover art
+    pub cover_small: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeezerPlaylist {
+    pub title: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeezerCreatedPlaylist {
+    pub id: u64,
+}
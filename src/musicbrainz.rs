@@ -0,0 +1,53 @@
+// Import necessary modules and crates
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::http::send;
+use crate::models::*;
+
+// Recording search of the MusicBrainz API
+const MUSICBRAINZ_SEARCH_URL: &str = "https://musicbrainz.org/ws/2/recording";
+
+// MusicBrainz allows one request per second and per client
+const MUSICBRAINZ_INTERVAL: Duration = Duration::from_secs(1);
+
+// Search score (0 to 100) under which a recording isn't trusted to be the song asked for
+const MUSICBRAINZ_MIN_SCORE: u32 = 90;
+
+// Time of the last request, so the next one waits for its turn
+static LAST_REQUEST: Mutex<Option<Instant>> = Mutex::new(None);
+
+// Function to quote a value for the MusicBrainz search syntax
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Function to wait until the next request is allowed
+fn wait_for_turn() {
+    let mut last = LAST_REQUEST.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(elapsed) = last.map(|time| time.elapsed()) {
+        if elapsed < MUSICBRAINZ_INTERVAL {
+            thread::sleep(MUSICBRAINZ_INTERVAL - elapsed);
+        }
+    }
+    *last = Some(Instant::now());
+}
+
+// Function to find the MusicBrainz recording of a song, None when no recording matches well enough
+pub fn find_recording(ctx: &Context, name: &str, artist: &str) -> Result<Option<MusicBrainzRecording>, PilotError> {
+    wait_for_turn();
+    let query = format!("recording:{} AND artist:{}", quote(name), quote(artist));
+    let response = send(
+        ctx.client
+            .get(MUSICBRAINZ_SEARCH_URL)
+            .query(&[("query", query.as_str()), ("fmt", "json"), ("limit", "1")])
+    )?;
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Error searching MusicBrainz: {}", response.status())));
+    }
+    let result: MusicBrainzSearchResponse = response.json().map_err(|e| format!("Failed to parse the MusicBrainz response: {}", e))?;
+    Ok(result.recordings.into_iter().next().filter(|recording| recording.score >= MUSICBRAINZ_MIN_SCORE))
+}
@@ -0,0 +1,130 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use serde_json::json;
+
+use crate::error::PilotError;
+use crate::history::{Status, Suggestion};
+use crate::http::send;
+use crate::matching::TrackMatch;
+use crate::report::track_link;
+
+// Longest message Discord accepts, in characters
+const DISCORD_MAX_LENGTH: usize = 2000;
+
+// Longest message sent to Slack, which folds longer ones behind a "Show more"
+const SLACK_MAX_LENGTH: usize = 3000;
+
+// Chat service a webhook posts to, each having its own message format
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Webhook {
+    Discord,
+    // Slack, and the services accepting Slack-compatible webhooks (e.g. Mattermost)
+    Slack,
+}
+
+impl Webhook {
+    // Function to tell the service of a webhook from its URL
+    fn of(url: &str) -> Webhook {
+        let host = url.split("://").nth(1).unwrap_or(url).split('/').next().unwrap_or_default();
+        if host.ends_with("discord.com") || host.ends_with("discordapp.com") {
+            Webhook::Discord
+        } else {
+            Webhook::Slack
+        }
+    }
+}
+
+// Function to write one added track, linked to its page when it has one
+fn track_line(webhook: Webhook, found: &TrackMatch) -> String {
+    let title = format!("{} – {}", found.artists.join(", "), found.name);
+    match (webhook, track_link(&found.uri)) {
+        // The angle brackets keep Discord from embedding a preview of every track
+        (Webhook::Discord, Some(link)) => format!("• [{}](<{}>)", title.replace(['[', ']'], ""), link),
+        (Webhook::Slack, Some(link)) => format!("• <{}|{}>", link, title.replace(['<', '>', '|'], "")),
+        (_, None) => format!("• {}", title),
+    }
+}
+
+// Function to write the summary of the tracks a run added, cut to the length the service accepts
+fn summary(webhook: Webhook, playlist: &str, added: &[&TrackMatch]) -> String {
+    let max_length = match webhook {
+        Webhook::Discord => DISCORD_MAX_LENGTH,
+        Webhook::Slack => SLACK_MAX_LENGTH,
+    };
+    let plural = if added.len() == 1 { "" } else { "s" };
+    let mut message = format!("PlaylistPilot added {} song{} to {}:", added.len(), plural, playlist);
+    for (index, found) in added.iter().enumerate() {
+        let line = track_line(webhook, found);
+        // Room is kept for the line telling how many were left out
+        let more = format!("\n…and {} more", added.len() - index);
+        if message.chars().count() + 1 + line.chars().count() + more.chars().count() > max_length {
+            message.push_str(&more);
+            break;
+        }
+        message.push('\n');
+        message.push_str(&line);
+    }
+    message
+}
+
+// Function to post the tracks a run added to the Discord or Slack webhook, nothing is posted when none were added
+pub fn notify_webhook(client: &Client, url: &str, playlist: &str, suggestions: &[Suggestion]) -> Result<(), PilotError> {
+    let added: Vec<&TrackMatch> =
+        suggestions.iter().filter(|s| s.status == Status::Added).filter_map(|s| s.found.as_ref()).collect();
+    if added.is_empty() {
+        return Ok(());
+    }
+    let webhook = Webhook::of(url);
+    let message = summary(webhook, playlist, &added);
+    let body = match webhook {
+        Webhook::Discord => json!({ "content": message }),
+        Webhook::Slack => json!({ "text": message }),
+    };
+    let response = send(client.post(url).json(&body)).map_err(|e| format!("Could not post to the webhook: {}", e))?;
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("The webhook refused the summary: {}", response.status())));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found(uri: &str, name: &str) -> TrackMatch {
+        TrackMatch {
+            uri: uri.to_string(),
+            name: name.to_string(),
+            artists: vec!["The Who".to_string()],
+            artist_ids: Vec::new(),
+            confidence: 1.0,
+            image: None,
+            popularity: None,
+            tempo: None,
+        }
+    }
+
+    #[test]
+    fn summary_links_tracks_in_the_webhook_format() {
+        assert_eq!(Webhook::of("https://discord.com/api/webhooks/1/abc"), Webhook::Discord);
+        assert_eq!(Webhook::of("https://hooks.slack.com/services/T/B/x"), Webhook::Slack);
+
+        let tracks = [found("spotify:track:abc", "Baba O'Riley"), found("applemusic:song:1", "Love, Reign o'er Me")];
+        let added: Vec<&TrackMatch> = tracks.iter().collect();
+        assert_eq!(
+            summary(Webhook::Discord, "Rock", &added),
+            "PlaylistPilot added 2 songs to Rock:\n• [The Who – Baba O'Riley](<https://open.spotify.com/track/abc>)\n\
+             • The Who – Love, Reign o'er Me"
+        );
+        assert_eq!(
+            summary(Webhook::Slack, "Rock", &added[..1]),
+            "PlaylistPilot added 1 song to Rock:\n• <https://open.spotify.com/track/abc|The Who – Baba O'Riley>"
+        );
+
+        let many: Vec<TrackMatch> = (0..100).map(|i| found("spotify:track:abc", &format!("Song {}", i))).collect();
+        let added: Vec<&TrackMatch> = many.iter().collect();
+        let message = summary(Webhook::Discord, "Rock", &added);
+        assert!(message.chars().count() <= DISCORD_MAX_LENGTH);
+        assert!(message.ends_with("more"));
+    }
+}
@@ -0,0 +1,435 @@
+// Import necessary modules and crates
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::str::FromStr;
+
+use crate::models::{AudioFeatures, PlaylistItem, TrackItem};
+
+// Number of tracks per rise or fall of a wave
+const WAVE_LENGTH: usize = 12;
+
+// Shape the intensity of a reordered playlist follows from start to end
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arc {
+    // From calm to intense
+    BuildUp,
+    // From intense to calm
+    WindDown,
+    // Rising and falling again several times
+    Wave,
+    // Moving from one mood to another, written as "calm->energetic"
+    Transition(Mood, Mood),
+}
+
+impl FromStr for Arc {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Some((from, to)) = value.split_once("->") {
+            return Ok(Arc::Transition(from.trim().parse()?, to.trim().parse()?));
+        }
+        match value {
+            "build-up" => Ok(Arc::BuildUp),
+            "wind-down" => Ok(Arc::WindDown),
+            "wave" => Ok(Arc::Wave),
+            _ => Err(format!("Unknown arc: {} (expected build-up, wind-down, wave or a transition such as calm->energetic)", value)),
+        }
+    }
+}
+
+// Mood a transition starts or ends on, each standing for a level of intensity
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mood {
+    Calm,
+    Melancholic,
+    Chill,
+    Mellow,
+    Happy,
+    Upbeat,
+    Energetic,
+    Intense,
+}
+
+// Names of the moods, from the calmest to the most intense
+const MOODS: [(&str, Mood); 8] = [
+    ("calm", Mood::Calm),
+    ("melancholic", Mood::Melancholic),
+    ("chill", Mood::Chill),
+    ("mellow", Mood::Mellow),
+    ("happy", Mood::Happy),
+    ("upbeat", Mood::Upbeat),
+    ("energetic", Mood::Energetic),
+    ("intense", Mood::Intense),
+];
+
+impl Mood {
+    // Function to get the name of the mood, as written in --arc
+    pub fn name(&self) -> &'static str {
+        MOODS.iter().find(|(_, mood)| mood == self).map(|(name, _)| *name).unwrap_or_default()
+    }
+
+    // Function to get the intensity a track of this mood typically has, between 0 and 1, as computed by `intensity`
+    pub fn intensity(&self) -> f64 {
+        match self {
+            Mood::Calm => 0.25,
+            Mood::Melancholic => 0.3,
+            Mood::Chill => 0.35,
+            Mood::Mellow => 0.4,
+            Mood::Happy => 0.6,
+            Mood::Upbeat => 0.7,
+            Mood::Energetic => 0.8,
+            Mood::Intense => 0.9,
+        }
+    }
+}
+
+impl FromStr for Mood {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let names: Vec<&str> = MOODS.iter().map(|(name, _)| *name).collect();
+        MOODS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(value))
+            .map(|(_, mood)| *mood)
+            .ok_or_else(|| format!("Unknown mood: {} (expected {})", value, names.join(", ")))
+    }
+}
+
+// What the sort command sorts a playlist by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    // When the item was added to the playlist
+    Added,
+    Title,
+    // The first artist credited
+    Artist,
+    Album,
+    Release,
+    Duration,
+    Popularity,
+}
+
+// Key of the sort command with its direction, written as "popularity" or "popularity:desc"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortOrder {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (key, direction) = value.split_once(':').unwrap_or((value, "asc"));
+        let key = match key {
+            "added" => SortKey::Added,
+            "title" => SortKey::Title,
+            "artist" => SortKey::Artist,
+            "album" => SortKey::Album,
+            "release" => SortKey::Release,
+            "duration" => SortKey::Duration,
+            "popularity" => SortKey::Popularity,
+            _ => return Err(format!("Unknown sort key: {} (expected added, title, artist, album, release, duration or popularity)", key)),
+        };
+        let descending = match direction {
+            "asc" => false,
+            "desc" => true,
+            _ => return Err(format!("Unknown sort direction: {} (expected asc or desc)", direction)),
+        };
+        Ok(SortOrder { key, descending })
+    }
+}
+
+// Function to get the value an item is sorted by, None when it has none (e.g. the popularity of a local file)
+// Numbers are compared as numbers, text ignoring case; dates are ISO strings, which sort as text.
+fn sort_value(item: &TrackItem, key: SortKey) -> Option<(u64, String)> {
+    let track = match &item.track {
+        Some(PlaylistItem::Track(track)) => Some(track),
+        _ => None,
+    };
+    match key {
+        SortKey::Added => item.added_at.clone().map(|added_at| (0, added_at)),
+        SortKey::Title => track.map(|track| (0, track.name.to_lowercase())),
+        SortKey::Artist => track?.artists.first().map(|artist| (0, artist.name.to_lowercase())),
+        SortKey::Album => track.map(|track| (0, track.album.name.to_lowercase())).filter(|(_, name)| !name.is_empty()),
+        SortKey::Release => track?.album.release_date.clone().map(|date| (0, date)),
+        SortKey::Duration => track.map(|track| (track.duration_ms, String::new())).filter(|(duration, _)| *duration > 0),
+        SortKey::Popularity => track?.popularity.map(|popularity| (popularity as u64, String::new())),
+    }
+}
+
+// Function to get the order of playlist items sorted by a key, as indexes of the items
+// Items with the same value keep their order, and items without one go last whichever the direction.
+pub fn sorted_order(items: &[TrackItem], order: SortOrder) -> Vec<usize> {
+    let values: Vec<Option<(u64, String)>> = items.iter().map(|item| sort_value(item, order.key)).collect();
+    let mut indexes: Vec<usize> = (0..items.len()).collect();
+    indexes.sort_by(|a, b| match (&values[*a], &values[*b]) {
+        (Some(a), Some(b)) if order.descending => b.cmp(a),
+        (Some(a), Some(b)) => a.cmp(b),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    });
+    indexes
+}
+
+// Function to sum up how intense a track feels, between 0 and 1
+// Energy weighs the most, a happy or danceable track feels more intense than a sad or still one with the same energy.
+pub fn intensity(features: &AudioFeatures) -> f64 {
+    0.6 * features.energy + 0.2 * features.valence + 0.2 * features.danceability
+}
+
+// Function to order items by intensity along an arc, returning their indexes in the new order
+pub fn arrange(intensities: &[f64], arc: Arc) -> Vec<usize> {
+    let mut ascending: Vec<usize> = (0..intensities.len()).collect();
+    ascending.sort_by(|a, b| intensities[*a].total_cmp(&intensities[*b]));
+
+    match arc {
+        Arc::BuildUp => ascending,
+        Arc::WindDown => ascending.into_iter().rev().collect(),
+        Arc::Transition(from, to) if from.intensity() <= to.intensity() => ascending,
+        Arc::Transition(..) => ascending.into_iter().rev().collect(),
+        Arc::Wave => {
+            // Deal the sorted items into segments so each one spans the whole range, then alternate rising and falling segments
+            let segments = intensities.len().div_ceil(WAVE_LENGTH).max(1);
+            let mut order = Vec::with_capacity(intensities.len());
+            for segment in 0..segments {
+                let mut items: Vec<usize> = ascending.iter().skip(segment).step_by(segments).copied().collect();
+                if segment % 2 == 1 {
+                    items.reverse();
+                }
+                order.extend(items);
+            }
+            order
+        },
+    }
+}
+
+// Function to measure how far apart two tempos feel when mixing, in BPM
+// A track at half or twice the tempo of another mixes as well as one at the same tempo.
+pub fn tempo_distance(a: f64, b: f64) -> f64 {
+    [(a - b).abs(), (a * 2.0 - b).abs(), (a - b * 2.0).abs()].into_iter().fold(f64::MAX, f64::min)
+}
+
+// Position of a key on the Camelot wheel used by DJs: 1 to 12, A for minor keys and B for major keys
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camelot {
+    pub number: u8,
+    pub minor: bool,
+}
+
+impl Camelot {
+    // Function to place a key given as a pitch class (0 for C) and a mode (1 for major) on the wheel, None when unknown
+    pub fn from_key(key: i32, mode: i32) -> Option<Camelot> {
+        if !(0..12).contains(&key) {
+            return None;
+        }
+        // A minor key sits on the same number as its relative major, three semitones up
+        let minor = mode == 0;
+        let major_key = if minor { (key + 3) % 12 } else { key };
+        // Each step around the wheel is a fifth, C major being 8B
+        let number = ((major_key * 7 + 7) % 12 + 1) as u8;
+        Some(Camelot { number, minor })
+    }
+
+    // Function to count the steps between two keys: one per number around the wheel, one to switch between A and B
+    pub fn distance(&self, other: &Camelot) -> u8 {
+        let difference = self.number.abs_diff(other.number);
+        difference.min(12 - difference) + u8::from(self.minor != other.minor)
+    }
+
+    // Function to check whether two keys mix harmonically: the same key, a neighbour number, or the relative key
+    pub fn is_compatible(&self, other: &Camelot) -> bool {
+        self.distance(other) <= 1
+    }
+}
+
+// Function to chain items so each one fits the previous, staying as close as possible to `order`
+// Each next item is the first remaining one in `order` that fits the current one; when none does, the one at the
+// smallest distance is taken and counted as a jump. Returns the new order and the number of jumps.
+pub fn chain(order: &[usize], fits: impl Fn(usize, usize) -> bool, distance: impl Fn(usize, usize) -> f64) -> (Vec<usize>, usize) {
+    let mut remaining: Vec<usize> = order.to_vec();
+    let mut result = Vec::with_capacity(order.len());
+    let mut jumps = 0;
+    while !remaining.is_empty() {
+        let next = match result.last() {
+            None => 0,
+            Some(&current) => match remaining.iter().position(|item| fits(current, *item)) {
+                Some(position) => position,
+                None => {
+                    jumps += 1;
+                    (0..remaining.len())
+                        .min_by(|a, b| distance(current, remaining[*a]).total_cmp(&distance(current, remaining[*b])))
+                        .unwrap_or(0)
+                },
+            },
+        };
+        result.push(remaining.remove(next));
+    }
+    (result, jumps)
+}
+
+// Function to reorder items so consecutive tempos stay within `max_delta` BPM, staying as close as possible to `order`
+pub fn limit_tempo_jumps(order: &[usize], tempos: &[f64], max_delta: f64) -> (Vec<usize>, usize) {
+    chain(
+        order,
+        |a, b| tempo_distance(tempos[a], tempos[b]) <= max_delta,
+        |a, b| tempo_distance(tempos[a], tempos[b]),
+    )
+}
+
+// Function to plan the single-item moves turning the current order into `order`, as (range_start, insert_before) pairs
+// Items already in place are left alone, so a nearly sorted playlist only needs a few calls.
+pub fn plan_moves(order: &[usize]) -> Vec<(usize, usize)> {
+    let mut current: Vec<usize> = (0..order.len()).collect();
+    let mut moves = Vec::new();
+    for (position, wanted) in order.iter().enumerate() {
+        let index = current.iter().position(|item| item == wanted).unwrap_or(position);
+        if index != position {
+            // Everything before `position` is final, so the item always moves up
+            let item = current.remove(index);
+            current.insert(position, item);
+            moves.push((index, position));
+        }
+    }
+    moves
+}
+
+// Function to get a random order of `length` items, always the same for the same seed
+pub fn shuffled_order(length: usize, seed: u64) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..length).collect();
+    order.shuffle(&mut StdRng::seed_from_u64(seed));
+    order
+}
+
+// Characters of a sparkline, from the lowest level to the highest
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+// Function to get how many values each character of a sparkline at most `width` characters wide stands for
+pub fn sparkline_step(count: usize, width: usize) -> usize {
+    count.div_ceil(width.max(1)).max(1)
+}
+
+// Function to draw values between 0 and 1 as a sparkline, averaging them when there are more than `width`
+// A character whose values are all unknown is left blank.
+pub fn sparkline(values: &[Option<f64>], width: usize) -> String {
+    values
+        .chunks(sparkline_step(values.len(), width))
+        .map(|chunk| {
+            let known: Vec<f64> = chunk.iter().flatten().copied().collect();
+            if known.is_empty() {
+                return ' ';
+            }
+            let average = known.iter().sum::<f64>() / known.len() as f64;
+            SPARKS[(average.clamp(0.0, 1.0) * (SPARKS.len() - 1) as f64).round() as usize]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Function to apply moves the way Spotify does, insert_before being counted before the move
+    fn apply(moves: &[(usize, usize)], length: usize) -> Vec<usize> {
+        let mut items: Vec<usize> = (0..length).collect();
+        for (range_start, insert_before) in moves {
+            let item = items.remove(*range_start);
+            let target = if insert_before > range_start { insert_before - 1 } else { *insert_before };
+            items.insert(target, item);
+        }
+        items
+    }
+
+    #[test]
+    fn transitions_go_from_one_mood_to_the_other() {
+        assert_eq!("calm->Energetic".parse::<Arc>(), Ok(Arc::Transition(Mood::Calm, Mood::Energetic)));
+        assert!("calm->loud".parse::<Arc>().is_err());
+        let intensities = [0.5, 0.9, 0.2];
+        assert_eq!(arrange(&intensities, "calm->energetic".parse().unwrap()), vec![2, 0, 1]);
+        assert_eq!(arrange(&intensities, "intense -> chill".parse().unwrap()), vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn sparklines_average_values_past_their_width() {
+        assert_eq!(sparkline(&[Some(0.0), Some(1.0), None, Some(0.5)], 10), "▁█ ▅");
+        assert_eq!(sparkline(&[Some(0.0), Some(1.0), Some(1.0), Some(1.0), None], 3), "▅█ ");
+        assert_eq!(sparkline_step(5, 3), 2);
+    }
+
+    #[test]
+    fn plan_moves_reaches_the_wanted_order() {
+        let order = vec![3, 0, 4, 1, 2];
+        assert_eq!(apply(&plan_moves(&order), order.len()), order);
+        assert!(plan_moves(&[0, 1, 2]).is_empty());
+    }
+
+    #[test]
+    fn sorted_order_puts_items_without_a_value_last() {
+        let track = |name: &str, popularity: Option<u32>| {
+            let mut json = crate::testing::track_json(name, name, "Artist");
+            json["popularity"] = serde_json::json!(popularity);
+            serde_json::json!({ "track": json, "added_at": format!("2024-0{}-01T00:00:00Z", name.len()) })
+        };
+        let items: Vec<TrackItem> = serde_json::from_value(serde_json::json!([
+            track("b", Some(40)),
+            track("Aaa", None),
+            { "track": null, "added_at": null },
+            track("cc", Some(90)),
+            track("dd", Some(40)),
+        ]))
+        .unwrap();
+        let order = |value: &str| sorted_order(&items, value.parse().unwrap());
+        assert_eq!(order("popularity"), vec![0, 4, 3, 1, 2]);
+        assert_eq!(order("popularity:desc"), vec![3, 0, 4, 1, 2]);
+        assert_eq!(order("title"), vec![1, 0, 3, 4, 2]);
+        assert_eq!(order("added:desc"), vec![1, 3, 4, 0, 2]);
+        assert!("bpm".parse::<SortOrder>().is_err());
+        assert!("title:up".parse::<SortOrder>().is_err());
+    }
+
+    #[test]
+    fn shuffled_order_is_reproducible() {
+        let order = shuffled_order(20, 42);
+        assert_eq!(order, shuffled_order(20, 42));
+        assert_ne!(order, shuffled_order(20, 43));
+        let mut sorted = order.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..20).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn arrange_follows_the_arc() {
+        let intensities = [0.5, 0.1, 0.9, 0.3];
+        assert_eq!(arrange(&intensities, Arc::BuildUp), vec![1, 3, 0, 2]);
+        assert_eq!(arrange(&intensities, Arc::WindDown), vec![2, 0, 3, 1]);
+
+        let intensities: Vec<f64> = (0..24).map(|i| i as f64).collect();
+        let wave = arrange(&intensities, Arc::Wave);
+        assert!(wave[..12].windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(wave[12..].windows(2).all(|pair| pair[0] > pair[1]));
+    }
+
+    #[test]
+    fn limit_tempo_jumps_keeps_transitions_small() {
+        let tempos = [120.0, 170.0, 124.0, 60.0, 128.0];
+        let (order, jumps) = limit_tempo_jumps(&[0, 1, 2, 3, 4], &tempos, 8.0);
+        // 60 BPM mixes with 120 as half time, 170 is only reachable with a jump
+        assert_eq!(order, vec![0, 2, 3, 4, 1]);
+        assert_eq!(jumps, 1);
+    }
+
+    #[test]
+    fn camelot_follows_the_wheel() {
+        let c_major = Camelot::from_key(0, 1).unwrap();
+        let a_minor = Camelot::from_key(9, 0).unwrap();
+        let g_major = Camelot::from_key(7, 1).unwrap();
+        let f_sharp_major = Camelot::from_key(6, 1).unwrap();
+        assert_eq!(c_major, Camelot { number: 8, minor: false });
+        assert_eq!(a_minor, Camelot { number: 8, minor: true });
+        assert_eq!(g_major, Camelot { number: 9, minor: false });
+        assert!(c_major.is_compatible(&a_minor) && c_major.is_compatible(&g_major));
+        assert!(!c_major.is_compatible(&f_sharp_major));
+        assert_eq!(Camelot::from_key(-1, 1), None);
+    }
+}
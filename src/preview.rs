@@ -0,0 +1,84 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use std::io;
+use tracing::warn;
+
+use crate::auth::open_in_browser;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::report::track_link;
+use crate::service::Service;
+use crate::spotify::get_preview_url;
+
+// Whether this build can play previews, only with the `preview` feature
+pub const CAN_PLAY: bool = cfg!(feature = "preview");
+
+// Function to read the answer to a question from the terminal, in lowercase
+fn read_answer() -> Option<String> {
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).ok()?;
+    Some(answer.trim().to_lowercase())
+}
+
+// Function to download a clip and play it until it ends or the user presses Enter
+#[cfg(feature = "preview")]
+fn play(client: &Client, url: &str) -> Result<(), PilotError> {
+    use crate::http::send;
+    use rodio::{Decoder, OutputStream, Sink};
+    use std::io::Cursor;
+
+    let response = send(client.get(url))?;
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Error downloading the preview: {}", response.status())));
+    }
+    let clip = Decoder::new(Cursor::new(response.bytes()?.to_vec())).map_err(|e| format!("Could not read the preview: {}", e))?;
+    // The stream plays as long as it is kept
+    let (_stream, handle) = OutputStream::try_default().map_err(|e| format!("No audio output: {}", e))?;
+    let sink = Sink::try_new(&handle).map_err(|e| format!("No audio output: {}", e))?;
+    sink.append(clip);
+    println!("Playing the preview, press Enter to stop.");
+    read_answer();
+    sink.stop();
+    Ok(())
+}
+
+#[cfg(not(feature = "preview"))]
+fn play(_client: &Client, _url: &str) -> Result<(), PilotError> {
+    Err("PlaylistPilot was built without the preview feature".into())
+}
+
+// Function to ask whether to add a low confidence match, opening it in the browser or app each time the user answers o,
+// and playing its 30 second preview each time they answer p
+// Only Spotify tracks have a preview to play, and only in builds with the `preview` feature.
+pub fn confirm_with_preview(ctx: &Context, question: &str, uri: &str) -> bool {
+    let playable = CAN_PLAY && ctx.config.service == Service::Spotify;
+    let link = track_link(uri);
+    let choices = match (link.is_some(), playable) {
+        (true, true) => "[y/N/o to open/p to play a preview]",
+        (true, false) => "[y/N/o to open]",
+        (false, true) => "[y/N/p to play a preview]",
+        (false, false) => "[y/N]",
+    };
+    loop {
+        println!("{} {}", question, choices);
+        let Some(answer) = read_answer() else { return false };
+        if let (Some(link), "o" | "open") = (&link, answer.as_str()) {
+            if let Err(e) = open_in_browser(link) {
+                warn!("Could not open the browser ({}), the track is at {}", e, link);
+            }
+            continue;
+        }
+        if !(playable && matches!(answer.as_str(), "p" | "play")) {
+            return matches!(answer.as_str(), "y" | "yes");
+        }
+        match get_preview_url(ctx, uri) {
+            Ok(Some(url)) => {
+                if let Err(e) = play(&ctx.client, &url) {
+                    warn!("Could not play the preview: {}", e);
+                }
+            },
+            Ok(None) => println!("Spotify has no preview of this track."),
+            Err(e) => warn!("Could not get the preview: {}", e),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+// Import necessary modules and crates
+use std::fs;
+
+use crate::error::PilotError;
+use crate::history::{Status, Suggestion};
+
+// Style of the report, kept inline so the file can be opened or shared on its own
+pub const STYLE: &str = "body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: 0.4em 0.6em; border-bottom: 1px solid #ddd; vertical-align: middle; }
+td.art { width: 64px; }
+img { width: 56px; height: 56px; object-fit: cover; }
+.added, .accepted { color: #1a7f37; }
+.not_found, .failed { color: #cf222e; }
+.muted { color: #777; }";
+
+// Everything shown in the report of one run
+pub struct Report<'a> {
+    // Service the run read and wrote, e.g. "Spotify"
+    pub service: &'a str,
+    // Names of the seeds with their number of tracks, or what the songs were read from for an import
+    pub seeds: Vec<(String, usize)>,
+    // Name of the playlist the songs were written to
+    pub target: String,
+    pub dry_run: bool,
+    pub suggestions: &'a [Suggestion],
+}
+
+// Function to escape text for HTML, or XML
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Function to get the web page of a track from its URI, None for services without public track pages
+pub fn track_link(uri: &str) -> Option<String> {
+    let pages = [
+        ("spotify:track:", "https://open.spotify.com/track/"),
+        ("ytmusic:video:", "https://music.youtube.com/watch?v="),
+        ("tidal:track:", "https://tidal.com/browse/track/"),
+        ("deezer:track:", "https://www.deezer.com/track/"),
+    ];
+    pages.iter().find_map(|(prefix, page)| uri.strip_prefix(prefix).map(|id| format!("{}{}", page, id)))
+}
+
+// Function to describe what was done with a suggestion
+fn status_label(status: Status, dry_run: bool) -> &'static str {
+    match status {
+        Status::Added => "Added",
+        Status::Accepted if dry_run => "Would be added",
+        Status::Accepted => "Not written",
+        Status::Rejected => "Rejected",
+        Status::Review => "To review",
+        Status::Duplicate => "Already in the playlist",
+        Status::NotFound => "Not found",
+        Status::Failed => "Failed to add",
+        Status::Undone => "Undone",
+        Status::Surplus => "Not needed",
+        Status::Excluded => "Blacklisted",
+        Status::ArtistLimit => "Too many by this artist",
+        Status::Previous => "Suggested before",
+        Status::Filtered => "Filtered out",
+    }
+}
+
+// Function to write one row of the suggestions table
+fn render_row(suggestion: &Suggestion, dry_run: bool) -> String {
+    let art = match suggestion.found.as_ref().and_then(|found| found.image.as_deref()) {
+        Some(image) => format!("<img src=\"{}\" alt=\"\">", escape(image)),
+        None => String::new(),
+    };
+    let matched = match &suggestion.found {
+        Some(found) => {
+            let title = format!("{} – {}", escape(&found.artists.join(", ")), escape(&found.name));
+            let title = match track_link(&found.uri) {
+                Some(link) => format!("<a href=\"{}\">{}</a>", escape(&link), title),
+                None => title,
+            };
+            format!("{} <span class=\"muted\">({:.0}%)</span>", title, found.confidence * 100.0)
+        },
+        None => "<span class=\"muted\">–</span>".to_string(),
+    };
+    format!(
+        "<tr><td class=\"art\">{}</td><td>{} – {}</td><td>{}</td><td class=\"{}\">{}</td><td>{}</td></tr>",
+        art,
+        escape(&suggestion.song.artist),
+        escape(&suggestion.song.name),
+        suggestion.source.label(),
+        suggestion.status.as_str(),
+        status_label(suggestion.status, dry_run),
+        matched,
+    )
+}
+
+// Function to write the report of a run as a standalone HTML page
+pub fn render_report(report: &Report) -> String {
+    let title = format!("PlaylistPilot: {}", report.target);
+    let seeds: Vec<String> =
+        report.seeds.iter().map(|(name, tracks)| format!("<li>{} <span class=\"muted\">({} tracks)</span></li>", escape(name), tracks)).collect();
+    let added_status = if report.dry_run { Status::Accepted } else { Status::Added };
+    let added = report.suggestions.iter().filter(|s| s.status == added_status).count();
+    let summary = if report.dry_run {
+        format!("Dry run on {}, {} of {} suggestions would be added to {}.", report.service, added, report.suggestions.len(), escape(&report.target))
+    } else {
+        format!("{} of {} suggestions were added to {} on {}.", added, report.suggestions.len(), escape(&report.target), report.service)
+    };
+    let rows: Vec<String> = report.suggestions.iter().map(|suggestion| render_row(suggestion, report.dry_run)).collect();
+
+    let mut html = vec![
+        "<!DOCTYPE html>".to_string(),
+        "<html><head><meta charset=\"utf-8\">".to_string(),
+        format!("<title>{}</title>", escape(&title)),
+        format!("<style>\n{}\n</style>", STYLE),
+        "</head><body>".to_string(),
+        format!("<h1>{}</h1>", escape(&title)),
+        format!("<p>{}</p>", summary),
+    ];
+    if !seeds.is_empty() {
+        html.push("<h2>Seeds</h2>".to_string());
+        html.push(format!("<ul>{}</ul>", seeds.join("")));
+    }
+    html.push("<h2>Suggestions</h2>".to_string());
+    html.push("<table><tr><th></th><th>Suggested</th><th>From</th><th>Status</th><th>Matched track</th></tr>".to_string());
+    html.extend(rows);
+    html.push("</table>".to_string());
+    html.push("</body></html>".to_string());
+    html.push(String::new());
+    html.join("\n")
+}
+
+// Function to write the report of a run to a file
+pub fn write_report(path: &str, report: &Report) -> Result<(), PilotError> {
+    fs::write(path, render_report(report)).map_err(|e| format!("Could not write the report to {}: {}", path, e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Source;
+    use crate::matching::TrackMatch;
+    use crate::models::Song;
+
+    #[test]
+    fn render_report_escapes_and_links_suggestions() {
+        let suggestions = vec![
+            Suggestion {
+                song: Song { name: "Love <Reign>".to_string(), artist: "The Who".to_string(), isrc: None, mbid: None },
+                found: Some(TrackMatch {
+                    uri: "spotify:track:abc".to_string(),
+                    name: "Love, Reign o'er Me".to_string(),
+                    artists: vec!["The Who".to_string()],
+                    artist_ids: Vec::new(),
+                    confidence: 0.9,
+                    image: Some("https://i.scdn.co/image/small".to_string()),
+                    popularity: None,
+                    tempo: None,
+                }),
+                status: Status::Added,
+                source: Source::Llm,
+            },
+            Suggestion {
+                song: Song { name: "Made Up".to_string(), artist: "Nobody".to_string(), isrc: None, mbid: None },
+                found: None,
+                status: Status::NotFound,
+                source: Source::Llm,
+            },
+        ];
+        let report = Report {
+            service: "Spotify",
+            seeds: vec![("Rock & Roll".to_string(), 42)],
+            target: "Rock & Roll".to_string(),
+            dry_run: false,
+            suggestions: &suggestions,
+        };
+        let html = render_report(&report);
+        assert!(html.contains("<li>Rock &amp; Roll <span class=\"muted\">(42 tracks)</span></li>"));
+        assert!(html.contains("1 of 2 suggestions were added to Rock &amp; Roll on Spotify."));
+        assert!(html.contains("The Who – Love &lt;Reign&gt;"));
+        assert!(html.contains("<a href=\"https://open.spotify.com/track/abc\">The Who – Love, Reign o&#39;er Me</a> <span class=\"muted\">(90%)</span>"));
+        assert!(html.contains("<img src=\"https://i.scdn.co/image/small\" alt=\"\">"));
+        assert!(html.contains("<td class=\"not_found\">Not found</td>"));
+    }
+}
@@ -0,0 +1,192 @@
+// Import necessary modules and crates
+use std::str::FromStr;
+
+// Names accepted for the months and the days of the week, in order
+const MONTH_NAMES: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const DAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+// A moment in the local time zone, to the minute
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LocalTime {
+    pub minute: u32,
+    pub hour: u32,
+    // 1 to 31
+    pub day: u32,
+    // 1 to 12
+    pub month: u32,
+    // 0 for Sunday to 6 for Saturday
+    pub weekday: u32,
+}
+
+// Function to get the local time of a Unix timestamp, from the time zone of the system
+#[cfg(unix)]
+pub fn local_time(timestamp: u64) -> LocalTime {
+    let time = timestamp as libc::time_t;
+    // SAFETY: localtime_r only writes the tm it is given, which is zeroed and owned here
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&time, &mut tm) };
+    LocalTime {
+        minute: tm.tm_min as u32,
+        hour: tm.tm_hour as u32,
+        day: tm.tm_mday as u32,
+        month: tm.tm_mon as u32 + 1,
+        weekday: tm.tm_wday as u32,
+    }
+}
+
+// Function to get the local time of a Unix timestamp, in UTC where the time zone of the system isn't read
+#[cfg(not(unix))]
+pub fn local_time(timestamp: u64) -> LocalTime {
+    use crate::tokens::civil_date;
+
+    let days = timestamp / 86400;
+    let seconds = timestamp % 86400;
+    let (_, month, day) = civil_date(days as i64);
+    // The epoch was a Thursday
+    LocalTime { minute: (seconds % 3600 / 60) as u32, hour: (seconds / 3600) as u32, day, month, weekday: ((days + 4) % 7) as u32 }
+}
+
+// A cron expression: minute, hour, day of the month, month and day of the week, e.g. "0 8 * * mon"
+// Each field takes *, numbers, names (jan, mon...), ranges (1-5), lists (1,15) and steps (*/15, 8-18/2).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // As in cron, when both days are restricted a time matching either of them matches
+    any_day: bool,
+    any_weekday: bool,
+}
+
+// Function to read one value of a cron field, a number or a name
+fn cron_value(value: &str, names: &[&str], offset: u32) -> Result<u32, String> {
+    if let Some(index) = names.iter().position(|name| value.eq_ignore_ascii_case(name)) {
+        return Ok(index as u32 + offset);
+    }
+    value.parse().map_err(|_| format!("Invalid cron value: {}", value))
+}
+
+// Function to read a cron field into a bit set of the values it matches, between min and max
+fn cron_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let mut set = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| format!("Invalid cron step: {}", part))?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (cron_value(start, names, min)?, cron_value(end, names, min)?),
+                // "5/15" runs from 5 to the end of the field
+                None if part.contains('/') => (cron_value(range, names, min)?, max),
+                None => {
+                    let value = cron_value(range, names, min)?;
+                    (value, value)
+                },
+            },
+        };
+        if start > end && start <= max && end >= min {
+            return Err(format!("Invalid cron range: {} (ranges can't wrap around the end of the field, split it in two, e.g. fri-sat,sun)", range));
+        }
+        if step == 0 || start < min || end > max || start > end {
+            return Err(format!("Invalid cron field: {} (values go from {} to {})", field, min, max));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+impl FromStr for Cron {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = value.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("Invalid cron expression: {} (expected minute, hour, day, month and day of the week)", value));
+        };
+        let mut weekdays = cron_field(weekday, 0, 7, &DAY_NAMES)?;
+        // Sunday is both 0 and 7
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Cron {
+            minutes: cron_field(minute, 0, 59, &[])?,
+            hours: cron_field(hour, 0, 23, &[])?,
+            days: cron_field(day, 1, 31, &[])?,
+            months: cron_field(month, 1, 12, &MONTH_NAMES)?,
+            weekdays,
+            // "*/2" restricts the days but is still a star: only the other field is checked, as cron does
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+}
+
+impl Cron {
+    // Function to tell whether the expression matches a minute
+    pub fn matches(&self, time: &LocalTime) -> bool {
+        let day = self.days & (1 << time.day) != 0;
+        let weekday = self.weekdays & (1 << time.weekday) != 0;
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        self.minutes & (1 << time.minute) != 0 && self.hours & (1 << time.hour) != 0 && self.months & (1 << time.month) != 0 && day_matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hour: u32, minute: u32, day: u32, weekday: u32) -> LocalTime {
+        LocalTime { minute, hour, day, month: 5, weekday }
+    }
+
+    #[test]
+    fn cron_matches_fields_names_ranges_and_steps() {
+        let weekly: Cron = "0 8 * * MON".parse().unwrap();
+        assert!(weekly.matches(&time(8, 0, 20, 1)));
+        assert!(!weekly.matches(&time(8, 0, 21, 2)));
+        assert!(!weekly.matches(&time(8, 1, 20, 1)));
+
+        let working_hours: Cron = "*/15 9-17 * * 1-5".parse().unwrap();
+        assert!(working_hours.matches(&time(9, 45, 21, 2)));
+        assert!(!working_hours.matches(&time(18, 0, 21, 2)));
+        assert!(!working_hours.matches(&time(9, 10, 21, 2)));
+
+        // Either the first of the month or a Sunday (7 being Sunday too)
+        let either: Cron = "30 6 1 * 7".parse().unwrap();
+        assert!(either.matches(&time(6, 30, 1, 3)));
+        assert!(either.matches(&time(6, 30, 19, 0)));
+        assert!(!either.matches(&time(6, 30, 20, 1)));
+
+        assert!("0 8 * *".parse::<Cron>().is_err());
+        assert!("60 8 * * *".parse::<Cron>().is_err());
+        assert!("0 8 * * someday".parse::<Cron>().is_err());
+    }
+
+    #[test]
+    fn stepped_stars_keep_the_other_day_field_required() {
+        // Every other day of the month, and on Mondays
+        let cron: Cron = "0 8 */2 * MON".parse().unwrap();
+        assert!(cron.matches(&time(8, 0, 3, 1)));
+        assert!(!cron.matches(&time(8, 0, 4, 1)));
+        assert!(!cron.matches(&time(8, 0, 3, 2)));
+    }
+
+    #[test]
+    fn wrapping_ranges_are_refused() {
+        let error = "0 8 * * fri-sun".parse::<Cron>().unwrap_err();
+        assert!(error.contains("can't wrap around"), "{}", error);
+        assert!("0 22-2 * * *".parse::<Cron>().unwrap_err().contains("can't wrap around"));
+        let weekend: Cron = "0 8 * * fri-sat,sun".parse().unwrap();
+        assert!(weekend.matches(&time(8, 0, 19, 0)));
+        assert!(!weekend.matches(&time(8, 0, 20, 1)));
+    }
+}
@@ -0,0 +1,95 @@
+// Import necessary modules and crates
+use keyring::Entry;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::crypto;
+use crate::error::PilotError;
+use crate::tokens::data_dir;
+
+// Service name under which secrets are stored in the OS keyring
+const KEYRING_SERVICE: &str = "playlistpilot";
+
+// Function to get the path of the fallback secrets file
+fn secrets_file() -> Result<PathBuf, PilotError> {
+    Ok(data_dir()?.join("secrets.json"))
+}
+
+// Function to read every secret stored in the fallback file
+fn load_file_secrets() -> HashMap<String, String> {
+    secrets_file()
+        .ok()
+        .and_then(|path| read_private_file(&path))
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+// Function to write the fallback secrets file, readable by the current user only
+fn save_file_secrets(secrets: &HashMap<String, String>) -> Result<(), PilotError> {
+    let path = secrets_file()?;
+    write_private_file(&path, &serde_json::to_string_pretty(secrets).map_err(|e| e.to_string())?)
+}
+
+// Function to read a file holding credentials, decrypting it if it was written encrypted
+pub fn read_private_file(path: &PathBuf) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    crypto::open(&content).ok()
+}
+
+// Function to write a file holding credentials, creating its directory if needed
+// The content is encrypted when a token cache passphrase was set up at startup.
+pub fn write_private_file(path: &PathBuf, content: &str) -> Result<(), PilotError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let sealed = crypto::seal(content)?;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    // The file is created private, so the credentials are never readable by others, even briefly
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|e| e.to_string())?;
+    // A file written by an older version may still be readable by others
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600)).map_err(|e| e.to_string())?;
+    }
+    file.write_all(sealed.as_bytes()).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Function to read a secret, looking in the OS keyring first and in the fallback file otherwise
+pub fn load_secret(name: &str) -> Option<String> {
+    let from_keyring = Entry::new(KEYRING_SERVICE, name)
+        .and_then(|entry| entry.get_password())
+        .ok();
+    from_keyring.or_else(|| load_file_secrets().remove(name))
+}
+
+// Function to store a secret in the OS keyring (macOS Keychain, Secret Service, Windows Credential Manager)
+// Falls back to the secrets file in the data directory when no keyring is available.
+pub fn save_secret(name: &str, value: &str) -> Result<(), PilotError> {
+    let stored = Entry::new(KEYRING_SERVICE, name).and_then(|entry| entry.set_password(value));
+
+    let mut secrets = load_file_secrets();
+    match stored {
+        Ok(_) => {
+            // Don't leave a stale plaintext copy behind once the keyring holds the secret
+            if secrets.remove(name).is_some() {
+                save_file_secrets(&secrets)?;
+            }
+        },
+        Err(_) => {
+            secrets.insert(name.to_string(), value.to_string());
+            save_file_secrets(&secrets)?;
+        },
+    }
+    Ok(())
+}
@@ -0,0 +1,221 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use tracing::warn;
+
+use crate::applemusic::AppleMusic;
+use crate::auth::{get_access_token, get_read_only_token};
+use crate::backup::backup_playlist;
+use crate::cache::SearchCache;
+use crate::config::Config;
+use crate::context::Context;
+use crate::deezer::Deezer;
+use crate::error::PilotError;
+use crate::matching::{TrackFilter, TrackMatch};
+use crate::models::*;
+use crate::spotify;
+use crate::tidal::Tidal;
+use crate::ytmusic::YtMusic;
+
+// Streaming service the suggestions are searched on and written to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Service {
+    Spotify,
+    YtMusic,
+    AppleMusic,
+    Tidal,
+    Deezer,
+}
+
+impl FromStr for Service {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "spotify" => Ok(Service::Spotify),
+            "ytmusic" => Ok(Service::YtMusic),
+            "applemusic" => Ok(Service::AppleMusic),
+            "tidal" => Ok(Service::Tidal),
+            "deezer" => Ok(Service::Deezer),
+            _ => Err(format!("Unknown service: {} (expected spotify, ytmusic, applemusic, tidal or deezer)", value)),
+        }
+    }
+}
+
+impl Service {
+    // Function to get the name of the service in the config and the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Service::Spotify => "spotify",
+            Service::YtMusic => "ytmusic",
+            Service::AppleMusic => "applemusic",
+            Service::Tidal => "tidal",
+            Service::Deezer => "deezer",
+        }
+    }
+}
+
+// A playlist read from a service, with its tracks in playlist order
+pub struct ServicePlaylist {
+    pub name: String,
+    // Version of the playlist, for the services that have one
+    pub snapshot_id: Option<String>,
+    // Number of items, including those that aren't tracks
+    pub length: usize,
+    pub tracks: Vec<Track>,
+    // Number of items that aren't tracks of the service (local files, episodes, removed tracks)
+    pub skipped: usize,
+}
+
+// A playlist created on a service
+pub struct NewPlaylist {
+    pub id: String,
+    pub url: String,
+}
+
+// What the suggestion pipeline needs from a streaming service: reading playlists, searching songs and writing the result
+// Track URIs are the service's own (e.g. spotify:track:ID), and tracks it can't describe fully leave the optional fields empty.
+pub trait MusicService: Sync {
+    // Function to get the name of the service shown to the user
+    fn name(&self) -> &'static str;
+
+    // Function to get an access token for the service, only able to read public data when `read_only` (if the service allows it)
+    fn access_token(&self, client: &Client, config: &Config, headless: bool, read_only: bool) -> Result<String, PilotError>;
+
+    // Function to read a playlist with all its tracks
+    fn get_playlist(&self, ctx: &Context, playlist_id: &str) -> Result<ServicePlaylist, PilotError>;
+
+    // Function to find the best track for a song, with its confidence
+    fn search_song(&self, ctx: &Context, song: &Song, filter: &TrackFilter) -> Result<TrackMatch, PilotError>;
+
+    // Function to create a private playlist for the current user
+    fn create_playlist(&self, ctx: &Context, name: &str, description: &str) -> Result<NewPlaylist, PilotError>;
+
+    // Function to add tracks to a playlist by their URIs, at `position` or at the end
+    fn add_to_playlist(&self, ctx: &Context, playlist_id: &str, uris: Vec<String>, position: Option<usize>) -> Result<(), PilotError>;
+
+    // Function to save the current content of a playlist before it is written to, returning the backup path
+    fn backup_playlist(&self, ctx: &Context, playlist_id: &str) -> Result<PathBuf, PilotError>;
+}
+
+// Function to get the implementation of a service
+pub fn music_service(service: Service) -> Box<dyn MusicService> {
+    match service {
+        Service::Spotify => Box::new(Spotify),
+        Service::YtMusic => Box::new(YtMusic),
+        Service::AppleMusic => Box::new(AppleMusic::default()),
+        Service::Tidal => Box::new(Tidal::default()),
+        Service::Deezer => Box::new(Deezer),
+    }
+}
+
+// Spotify, through its Web API
+pub struct Spotify;
+
+impl MusicService for Spotify {
+    fn name(&self) -> &'static str {
+        "Spotify"
+    }
+
+    fn access_token(&self, client: &Client, config: &Config, headless: bool, read_only: bool) -> Result<String, PilotError> {
+        if read_only {
+            get_read_only_token(client, config)
+        } else {
+            get_access_token(client, config, headless)
+        }
+    }
+
+    fn get_playlist(&self, ctx: &Context, playlist_id: &str) -> Result<ServicePlaylist, PilotError> {
+        // Seeds and targets only need the names of the tracks, the rest is fetched for the tracks that need it
+        let playlist = spotify::get_lean_playlist(ctx, playlist_id)?;
+        let length = playlist.tracks.items.len();
+        let (tracks, skipped) = spotify::spotify_tracks(playlist.tracks.items);
+        Ok(ServicePlaylist { name: playlist.name, snapshot_id: Some(playlist.snapshot_id), length, tracks, skipped })
+    }
+
+    fn search_song(&self, ctx: &Context, song: &Song, filter: &TrackFilter) -> Result<TrackMatch, PilotError> {
+        spotify::search_song(ctx, song, filter)
+    }
+
+    fn create_playlist(&self, ctx: &Context, name: &str, description: &str) -> Result<NewPlaylist, PilotError> {
+        let created = spotify::create_playlist(ctx, name, description)?;
+        Ok(NewPlaylist { id: created.id, url: created.external_urls.spotify })
+    }
+
+    fn add_to_playlist(&self, ctx: &Context, playlist_id: &str, uris: Vec<String>, position: Option<usize>) -> Result<(), PilotError> {
+        spotify::add_to_playlist(ctx, playlist_id, uris, position)
+    }
+
+    fn backup_playlist(&self, ctx: &Context, playlist_id: &str) -> Result<PathBuf, PilotError> {
+        backup_playlist(ctx, playlist_id)
+    }
+}
+
+// Function to search several songs at once, with at most `parallelism` requests in flight
+// Songs found in the cache are not searched again, and new confident matches are added to it.
+// Results are returned in the same order as the songs.
+pub fn search_songs(
+    service: &dyn MusicService,
+    ctx: &Context,
+    songs: &[Song],
+    parallelism: usize,
+    cache: Option<&SearchCache>,
+    filter: &TrackFilter,
+) -> Vec<Result<TrackMatch, PilotError>> {
+    let cached: Vec<Option<String>> = songs
+        .iter()
+        .map(|song| cache.and_then(|cache| cache.get(&song.artist, &song.name)))
+        .collect();
+    let pending: Vec<usize> = (0..songs.len()).filter(|&index| cached[index].is_none()).collect();
+
+    let next = AtomicUsize::new(0);
+    let searched = Mutex::new((0..songs.len()).map(|_| None).collect::<Vec<Option<Result<TrackMatch, PilotError>>>>());
+
+    // Each worker takes the next song that hasn't been searched yet until none are left
+    thread::scope(|scope| {
+        for _ in 0..parallelism.clamp(1, pending.len().max(1)) {
+            scope.spawn(|| {
+                while let Some(&index) = pending.get(next.fetch_add(1, Ordering::SeqCst)) {
+                    let song = &songs[index];
+                    let result = service.search_song(ctx, song, filter);
+                    searched.lock().unwrap()[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    let searched = searched.into_inner().unwrap();
+    songs
+        .iter()
+        .zip(cached)
+        .zip(searched)
+        .map(|((song, cached), searched)| match (cached, searched) {
+            // Only confident matches are cached, so a cached URI is trusted as is
+            (Some(uri), _) => Ok(TrackMatch {
+                uri,
+                name: song.name.clone(),
+                artists: vec![song.artist.clone()],
+                artist_ids: Vec::new(),
+                confidence: 1.0,
+                image: None,
+                popularity: None,
+                tempo: None,
+            }),
+            (None, Some(result)) => {
+                if let (Some(cache), Ok(found)) = (cache, &result) {
+                    if found.confidence >= ctx.config.min_confidence {
+                        if let Err(e) = cache.put(&song.artist, &song.name, &found.uri) {
+                            warn!("Could not cache the search result: {}", e);
+                        }
+                    }
+                }
+                result
+            },
+            (None, None) => Err("Search was not run.".into()),
+        })
+        .collect()
+}
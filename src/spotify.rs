@@ -0,0 +1,1147 @@
+// Import necessary modules and crates
+use rand::Rng;
+use reqwest::{StatusCode, Url};
+use std::collections::HashMap;
+
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::http::{send_with_policy, send_with_retry, Endpoint};
+use crate::matching::*;
+use crate::models::*;
+
+// Function to read the ID of a Spotify object of the given kind ("track", "playlist") from an URI, an open.spotify.com link
+// or the bare ID, e.g. "spotify:track:ID" or "https://open.spotify.com/track/ID?si=...".
+pub fn parse_spotify_id(value: &str, kind: &str) -> Option<String> {
+    let value = value.trim();
+    let id = if let Some(id) = value.strip_prefix(&format!("spotify:{}:", kind)) {
+        id.to_string()
+    } else if let Ok(url) = Url::parse(value) {
+        if url.host_str() != Some("open.spotify.com") {
+            return None;
+        }
+        let mut segments = url.path_segments()?.filter(|segment| !segment.is_empty() && !segment.starts_with("intl-"));
+        if segments.next() != Some(kind) {
+            return None;
+        }
+        segments.next()?.to_string()
+    } else {
+        value.to_string()
+    };
+    let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric());
+    valid.then_some(id)
+}
+
+// Function to keep only the Spotify tracks of playlist items
+// Removed tracks (`track: null`), local files and podcast episodes are dropped, and how many were dropped is returned.
+pub fn spotify_tracks(items: Vec<TrackItem>) -> (Vec<Track>, usize) {
+    let total = items.len();
+    let tracks: Vec<Track> = items
+        .into_iter()
+        .filter_map(|item| match item.track {
+            Some(PlaylistItem::Track(track)) if !track.is_local => Some(track),
+            _ => None,
+        })
+        .collect();
+    let skipped = total - tracks.len();
+    (tracks, skipped)
+}
+
+// Fields of a playlist read by get_lean_playlist, and of its following pages: what the suggestions need to write the prompt and
+// spot duplicates. The type tells tracks from episodes.
+const LEAN_PLAYLIST_FIELDS: &str = "name,snapshot_id,tracks(next,items(track(type,name,uri,is_local,artists(name,id))))";
+const LEAN_PAGE_FIELDS: &str = "next,items(track(type,name,uri,is_local,artists(name,id)))";
+
+// Function to fetch a playlist from Spotify using its ID
+// Spotify returns at most 100 items per page, so the following pages are fetched until `max_tracks` items are read (all of them by default).
+pub fn get_playlist(ctx: &Context, playlist_id: &str, max_tracks: Option<usize>) -> Result<PlaylistResponse, PilotError> {
+    fetch_playlist(ctx, playlist_id, max_tracks, None)
+}
+
+// Function to fetch a playlist with only the name, URI and artists of its tracks, for the suggestions
+// Large playlists weigh megabytes with every album, market and popularity; what else a run needs of some tracks is fetched later,
+// as get_tracks does.
+pub fn get_lean_playlist(ctx: &Context, playlist_id: &str) -> Result<PlaylistResponse, PilotError> {
+    fetch_playlist(ctx, playlist_id, None, Some((LEAN_PLAYLIST_FIELDS, LEAN_PAGE_FIELDS)))
+}
+
+// Function to fetch a playlist and its following pages, whole or only the given fields of the playlist and of its pages
+fn fetch_playlist(ctx: &Context, playlist_id: &str, max_tracks: Option<usize>, fields: Option<(&str, &str)>) -> Result<PlaylistResponse, PilotError> {
+    let playlist_url = format!("{}/playlists/{}", ctx.config.spotify_api_url, playlist_id);
+    let mut request = ctx.client.get(&playlist_url).header("Authorization", format!("Bearer {}", ctx.access_token));
+    if let Some((playlist_fields, _)) = fields {
+        request = request.query(&[("fields", playlist_fields)]);
+    }
+
+    let response = send_with_policy(Endpoint::Playlist, request);
+
+    // Handle the response and map to PlaylistResponse
+    let mut playlist_response: PlaylistResponse = match response {
+        Ok(resp) => {
+            match resp.status() {
+                StatusCode::OK => resp.json().map_err(|e| e.to_string())?,
+                StatusCode::NOT_FOUND => return Err(PilotError::NotFound("Invalid Playlist ID: The playlist could not be found.".to_string())),
+                _ => return Err(PilotError::from_status(resp.status(), format!("Error fetching playlist: {}", resp.status()))),
+            }
+        },
+        Err(e) => return Err(e.into()),
+    };
+
+    // Follow the `next` links until every item (or the cap) is read
+    let limit = max_tracks.unwrap_or(usize::MAX);
+    while playlist_response.tracks.items.len() < limit {
+        let next_url = match playlist_response.tracks.next.take() {
+            Some(url) => url,
+            None => break,
+        };
+        // The next link doesn't keep the fields asked for
+        let mut request = ctx.client.get(&next_url).header("Authorization", format!("Bearer {}", ctx.access_token));
+        if let Some((_, page_fields)) = fields {
+            request = request.query(&[("fields", page_fields)]);
+        }
+        let resp = send_with_policy(Endpoint::Playlist, request)?;
+        if !resp.status().is_success() {
+            return Err(PilotError::from_status(resp.status(), format!("Error fetching playlist page: {}", resp.status())));
+        }
+        let page: PlaylistTracks = resp.json().map_err(|e| e.to_string())?;
+        playlist_response.tracks.items.extend(page.items);
+        playlist_response.tracks.next = page.next;
+    }
+    playlist_response.tracks.items.truncate(limit);
+
+    Ok(playlist_response)
+}
+
+// Function to fetch every track saved in the user's library (Liked Songs)
+// Requires a user token with the `user-library-read` scope; pages of 50 are read until the last one.
+pub fn get_saved_tracks(ctx: &Context) -> Result<Vec<Track>, PilotError> {
+    let mut tracks = Vec::new();
+    let mut next_url = Some(format!("{}/me/tracks?limit=50", ctx.config.spotify_api_url));
+
+    while let Some(url) = next_url {
+        let response = send_with_retry(
+            ctx.client
+                .get(&url)
+                .header("Authorization", format!("Bearer {}", ctx.access_token))
+        )?;
+        if !response.status().is_success() {
+            return Err(PilotError::from_status(response.status(), format!("Error fetching saved tracks: {}", response.status())));
+        }
+        let page: SavedTracksPage = response.json().map_err(|e| e.to_string())?;
+        tracks.extend(page.items.into_iter().map(|item| item.track));
+        next_url = page.next;
+    }
+
+    Ok(tracks)
+}
+
+// Function to list the playlists the user owns or follows, in the order of their library
+// Requires a user token with the `playlist-read-private` scope for the private ones.
+pub fn get_user_playlists(ctx: &Context) -> Result<Vec<UserPlaylist>, PilotError> {
+    let mut playlists = Vec::new();
+    let mut next_url = Some(format!("{}/me/playlists?limit=50", ctx.config.spotify_api_url));
+
+    while let Some(url) = next_url {
+        let response = send_with_retry(
+            ctx.client
+                .get(&url)
+                .header("Authorization", format!("Bearer {}", ctx.access_token))
+        )?;
+        if !response.status().is_success() {
+            return Err(PilotError::from_status(response.status(), format!("Error fetching your playlists: {}", response.status())));
+        }
+        let page: UserPlaylistsPage = response.json().map_err(|e| e.to_string())?;
+        playlists.extend(page.items);
+        next_url = page.next;
+    }
+
+    Ok(playlists)
+}
+
+// Function to get the tracks the user played most recently, newest first and without repeats
+// Spotify only keeps the last 50 plays.
+pub fn get_recently_played(ctx: &Context) -> Result<Vec<Track>, PilotError> {
+    let response = send_with_retry(
+        ctx.client
+            .get(format!("{}/me/player/recently-played?limit=50", ctx.config.spotify_api_url))
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+    )?;
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Error fetching recently played tracks: {}", response.status())));
+    }
+    let history: RecentlyPlayedResponse = response.json().map_err(|e| e.to_string())?;
+
+    let mut tracks: Vec<Track> = Vec::new();
+    for item in history.items {
+        if !tracks.iter().any(|track| track.uri == item.track.uri) {
+            tracks.push(item.track);
+        }
+    }
+    Ok(tracks)
+}
+
+// Function to get the user's 50 top tracks over a period ("short_term", "medium_term" or "long_term"), most listened first
+pub fn get_top_tracks(ctx: &Context, time_range: &str) -> Result<Vec<Track>, PilotError> {
+    let response = send_with_retry(
+        ctx.client
+            .get(format!("{}/me/top/tracks", ctx.config.spotify_api_url))
+            .query(&[("limit", "50"), ("time_range", time_range)])
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+    )?;
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Error fetching top tracks: {}", response.status())));
+    }
+    let top: TopTracksResponse = response.json().map_err(|e| e.to_string())?;
+    Ok(top.items)
+}
+
+// Function to get the user's 50 top artists over a period, most listened first
+pub fn get_top_artists(ctx: &Context, time_range: &str) -> Result<Vec<FullArtist>, PilotError> {
+    let response = send_with_retry(
+        ctx.client
+            .get(format!("{}/me/top/artists", ctx.config.spotify_api_url))
+            .query(&[("limit", "50"), ("time_range", time_range)])
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+    )?;
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Error fetching top artists: {}", response.status())));
+    }
+    let top: TopArtistsResponse = response.json().map_err(|e| e.to_string())?;
+    Ok(top.items)
+}
+
+// Function to list the artists the user follows
+// Requires a user token with the `user-follow-read` scope; each page links to the next one.
+pub fn get_followed_artists(ctx: &Context) -> Result<Vec<FullArtist>, PilotError> {
+    let mut artists = Vec::new();
+    let mut next_url = Some(format!("{}/me/following?type=artist&limit=50", ctx.config.spotify_api_url));
+
+    while let Some(url) = next_url {
+        let response = send_with_retry(
+            ctx.client
+                .get(&url)
+                .header("Authorization", format!("Bearer {}", ctx.access_token))
+        )?;
+        if !response.status().is_success() {
+            return Err(PilotError::from_status(response.status(), format!("Error fetching followed artists: {}", response.status())));
+        }
+        let page: FollowedArtistsResponse = response.json().map_err(|e| e.to_string())?;
+        artists.extend(page.artists.items);
+        next_url = page.artists.next;
+    }
+
+    Ok(artists)
+}
+
+// Function to list the latest albums and singles of an artist, leaving out compilations and appearances on other records
+pub fn get_artist_releases(ctx: &Context, artist_id: &str) -> Result<Vec<Release>, PilotError> {
+    let response = send_with_retry(
+        ctx.client
+            .get(format!("{}/artists/{}/albums", ctx.config.spotify_api_url, artist_id))
+            .query(&[("include_groups", "album,single"), ("limit", "20")])
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+    )?;
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Error fetching the releases of an artist: {}", response.status())));
+    }
+    let releases: ArtistAlbumsResponse = response.json().map_err(|e| e.to_string())?;
+    Ok(releases.items)
+}
+
+// Maximum number of IDs Spotify accepts in a single several-albums call
+pub const ALBUMS_BATCH_SIZE: usize = 20;
+
+// Function to fetch the tracks of albums, album after album, each track given the album it is on
+// Only the first 50 tracks of an album are read.
+pub fn get_album_tracks(ctx: &Context, album_ids: &[String]) -> Result<Vec<Track>, PilotError> {
+    let mut tracks = Vec::new();
+    for chunk in album_ids.chunks(ALBUMS_BATCH_SIZE) {
+        let response = send_with_retry(
+            ctx.client
+                .get(format!("{}/albums", ctx.config.spotify_api_url))
+                .query(&[("ids", chunk.join(","))])
+                .header("Authorization", format!("Bearer {}", ctx.access_token))
+        )?;
+        if !response.status().is_success() {
+            return Err(PilotError::from_status(response.status(), format!("Error fetching albums: {}", response.status())));
+        }
+        let page: SeveralAlbumsResponse = response.json().map_err(|e| e.to_string())?;
+        for album in page.albums.into_iter().flatten() {
+            let on = Album { name: album.name, release_date: album.release_date, images: album.images };
+            tracks.extend(album.tracks.items.into_iter().map(|track| Track { album: on.clone(), ..track }));
+        }
+    }
+    Ok(tracks)
+}
+
+// Function to fetch only the current snapshot ID of a playlist
+pub fn get_snapshot_id(ctx: &Context, playlist_id: &str) -> Result<String, PilotError> {
+    let playlist_url = format!("{}/playlists/{}?fields=snapshot_id", ctx.config.spotify_api_url, playlist_id);
+
+    let response = send_with_policy(
+        Endpoint::Playlist,
+        ctx.client
+            .get(&playlist_url)
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+    )?;
+
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Error fetching playlist snapshot: {}", response.status())));
+    }
+    let snapshot: PlaylistSnapshot = response.json().map_err(|e| e.to_string())?;
+    Ok(snapshot.snapshot_id)
+}
+
+// Function to get the Spotify ID of the user the token belongs to
+pub fn get_current_user_id(ctx: &Context) -> Result<String, PilotError> {
+    let response = send_with_retry(
+        ctx.client
+            .get(format!("{}/me", ctx.config.spotify_api_url))
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+    )?;
+
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Error fetching the user profile: {}", response.status())));
+    }
+    let profile: UserProfile = response.json().map_err(|e| e.to_string())?;
+    Ok(profile.id)
+}
+
+// Function to get the names shown on the profiles of users, by ID
+// A profile that can't be read is named by its ID, so members are still told apart.
+pub fn member_names(ctx: &Context, user_ids: &[String]) -> HashMap<String, String> {
+    let mut names = HashMap::new();
+    for user_id in user_ids {
+        let profile = send_with_retry(
+            ctx.client
+                .get(format!("{}/users/{}", ctx.config.spotify_api_url, user_id))
+                .header("Authorization", format!("Bearer {}", ctx.access_token))
+        )
+        .ok()
+        .filter(|response| response.status().is_success())
+        .and_then(|response| response.json::<UserProfile>().ok());
+        let name = profile.and_then(|profile| profile.display_name).filter(|name| !name.is_empty()).unwrap_or_else(|| user_id.clone());
+        names.insert(user_id.clone(), name);
+    }
+    names
+}
+
+// Function to find the IDs of the users who added items to a playlist, in the order they first did
+pub fn playlist_members(items: &[TrackItem]) -> Vec<String> {
+    let mut members: Vec<String> = Vec::new();
+    for added_by in items.iter().filter_map(|item| item.added_by.as_ref()).filter(|added_by| !added_by.id.is_empty()) {
+        if !members.contains(&added_by.id) {
+            members.push(added_by.id.clone());
+        }
+    }
+    members
+}
+
+// Function to create an empty private playlist owned by the current user
+pub fn create_playlist(ctx: &Context, name: &str, description: &str) -> Result<CreatedPlaylist, PilotError> {
+    let user_id = get_current_user_id(ctx)?;
+    let body = CreatePlaylistRequest { name: name.to_string(), description: description.to_string(), public: false };
+
+    let response = send_with_retry(
+        ctx.client
+            .post(format!("{}/users/{}/playlists", ctx.config.spotify_api_url, user_id))
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+            .header("Content-Type", "application/json")
+            .json(&body)
+    )?;
+
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Failed to create playlist '{}': {}", name, response.status())));
+    }
+    response.json().map_err(PilotError::from)
+}
+
+// Longest playlist description Spotify accepts, in characters
+pub const MAX_DESCRIPTION_LENGTH: usize = 300;
+
+// Function to change the description of a playlist, cut to the length Spotify accepts
+pub fn update_playlist_description(ctx: &Context, playlist_id: &str, description: &str) -> Result<(), PilotError> {
+    let body = PlaylistDetailsRequest { description: description.chars().take(MAX_DESCRIPTION_LENGTH).collect() };
+
+    let response = send_with_retry(
+        ctx.client
+            .put(format!("{}/playlists/{}", ctx.config.spotify_api_url, playlist_id))
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+            .header("Content-Type", "application/json")
+            .json(&body)
+    )?;
+
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Failed to update the playlist description: {}", response.status())));
+    }
+    Ok(())
+}
+
+// Function to replace the cover of a playlist with a JPEG image, given base64 encoded as Spotify expects
+pub fn upload_playlist_cover(ctx: &Context, playlist_id: &str, jpeg_base64: String) -> Result<(), PilotError> {
+    let response = send_with_retry(
+        ctx.client
+            .put(format!("{}/playlists/{}/images", ctx.config.spotify_api_url, playlist_id))
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+            .header("Content-Type", "image/jpeg")
+            .body(jpeg_base64)
+    )?;
+
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Failed to upload the playlist cover: {}", response.status())));
+    }
+    Ok(())
+}
+
+// Function to get the link of a track's 30 second preview, None when Spotify has none (e.g. for apps registered since late 2024)
+pub fn get_preview_url(ctx: &Context, uri: &str) -> Result<Option<String>, PilotError> {
+    let id = parse_spotify_id(uri, "track").ok_or_else(|| format!("Not a Spotify track: {}", uri))?;
+    let response = send_with_retry(
+        ctx.client
+            .get(format!("{}/tracks/{}", ctx.config.spotify_api_url, id))
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+    )?;
+
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Error fetching the track: {}", response.status())));
+    }
+    let track: TrackPreview = response.json().map_err(|e| e.to_string())?;
+    Ok(track.preview_url)
+}
+
+// Function to add a track to the queue of the user's active device
+// Spotify answers 404 when no device is playing, and 403 for accounts without Premium.
+pub fn add_to_queue(ctx: &Context, uri: &str) -> Result<(), PilotError> {
+    let mut queue_url = Url::parse(&format!("{}/me/player/queue", ctx.config.spotify_api_url)).map_err(|e| e.to_string())?;
+    queue_url.query_pairs_mut().append_pair("uri", uri);
+
+    let response = send_with_retry(
+        ctx.client
+            .post(queue_url)
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+            .header("Content-Length", "0")
+    )?;
+
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        StatusCode::NOT_FOUND => Err(PilotError::NotFound("No active device, start playing something on Spotify first".to_string())),
+        StatusCode::FORBIDDEN => Err(PilotError::Auth("Spotify only lets Premium accounts add to the queue".to_string())),
+        status => Err(PilotError::from_status(status, format!("Failed to add to the queue: {}", status))),
+    }
+}
+
+// Maximum number of tracks Spotify saves to the library in a single call
+pub const SAVE_TRACKS_BATCH_SIZE: usize = 50;
+
+// Function to save tracks to the user's library (Liked Songs) by their URIs
+// Requires the `user-library-modify` scope; the tracks are sent in batches of 50 and the first failure stops the saving.
+pub fn save_tracks(ctx: &Context, uris: &[String]) -> Result<(), PilotError> {
+    let ids: Vec<String> = uris.iter().filter_map(|uri| parse_spotify_id(uri, "track")).collect();
+    for chunk in ids.chunks(SAVE_TRACKS_BATCH_SIZE) {
+        let response = send_with_retry(
+            ctx.client
+                .put(format!("{}/me/tracks", ctx.config.spotify_api_url))
+                .header("Authorization", format!("Bearer {}", ctx.access_token))
+                .header("Content-Type", "application/json")
+                .json(&SaveTracksRequest { ids: chunk.to_vec() })
+        )?;
+        if !response.status().is_success() {
+            return Err(PilotError::from_status(response.status(), format!("Failed to save tracks to the library: {}", response.status())));
+        }
+    }
+    Ok(())
+}
+
+// Function to build a search URL for tracks
+// The query is percent-encoded, so titles with `&`, `#`, quotes or non-ASCII characters reach Spotify intact.
+pub fn search_url(api_url: &str, query: &str, limit: u32, market: Option<&str>) -> Url {
+    let mut url = Url::parse_with_params(
+        &format!("{}/search", api_url),
+        &[("q", query), ("type", "track"), ("limit", &limit.to_string())],
+    )
+    .expect("search endpoint is a valid URL");
+    if let Some(market) = market {
+        url.query_pairs_mut().append_pair("market", market);
+    }
+    url
+}
+
+// Function to build the strict field-filtered query for a song
+pub fn field_query(artist: &str, track: &str) -> String {
+    format!("artist:{} track:{}", artist, track)
+}
+
+// Function to run a track search on Spotify and return the tracks found
+// With a market configured, tracks that can't be played in it are left out.
+fn run_search(ctx: &Context, query: &str, limit: u32) -> Result<Vec<Track>, PilotError> {
+    let response = send_with_policy(
+        Endpoint::Search,
+        ctx.client
+            .get(search_url(&ctx.config.spotify_api_url, query, limit, ctx.config.market.as_deref()))
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+    );
+
+    match response {
+        Ok(resp) => {
+            match resp.status() {
+                StatusCode::OK => {
+                    let search_response: SearchResponse = resp.json().map_err(|e| e.to_string())?;
+                    Ok(search_response
+                        .tracks
+                        .items
+                        .into_iter()
+                        .filter(|track| track.is_playable != Some(false))
+                        .collect())
+                },
+                StatusCode::NOT_FOUND => Ok(Vec::new()),
+                _ => Err(PilotError::from_status(resp.status(), format!("{}", resp.status()))),
+            }
+        },
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Function to search a recording by its ISRC
+// The result must still resemble the requested song, so a wrong ISRC can't pull in an unrelated track.
+fn search_isrc(ctx: &Context, isrc: &str, artist: &str, track: &str, filter: &TrackFilter) -> Result<Option<TrackMatch>, PilotError> {
+    let isrc = isrc.trim();
+    let candidates: Vec<Track> = run_search(ctx, &format!("isrc:{}", isrc), 1)?
+        .into_iter()
+        .filter(|candidate| candidate.external_ids.isrc.as_deref().is_some_and(|found| found.eq_ignore_ascii_case(isrc)))
+        .filter(|candidate| filter.allows(candidate))
+        .collect();
+    Ok(best_candidate(&candidates, artist, track)
+        .filter(|found| found.confidence >= ctx.config.min_confidence)
+        .map(|found| TrackMatch { confidence: 1.0, ..found }))
+}
+
+// Function to search for a specific song on Spotify
+// A song with an ISRC is looked up by it first to get the exact recording. Otherwise (or when that fails) the strict
+// `artist:X track:Y` query is tried; when it finds nothing confident enough, a plain free-text query and then
+// the artist's tracks are searched. The best scored candidate overall is returned with its confidence.
+// Candidates the filter doesn't allow are ignored, so e.g. a clean version is picked over an explicit one. Covers by other artists
+// are never returned: when they are all that was found, the title alone is searched for the original artist's recording.
+pub fn search_song(ctx: &Context, song: &Song, filter: &TrackFilter) -> Result<TrackMatch, PilotError> {
+    let (artist, track) = (song.artist.as_str(), song.name.as_str());
+    if let Some(isrc) = &song.isrc {
+        if let Some(found) = search_isrc(ctx, isrc, artist, track, filter)? {
+            return Ok(found);
+        }
+    }
+
+    let queries = [
+        (field_query(artist, track), 5),
+        (format!("{} {}", artist, track), 10),
+        (format!("artist:{}", artist), 50),
+    ];
+
+    let mut best: Option<TrackMatch> = None;
+    let mut cover: Option<Track> = None;
+    for (query, limit) in queries {
+        // Live, karaoke and tribute versions are never what was asked for, and the original release beats its remasters
+        let (covers, candidates): (Vec<Track>, Vec<Track>) = run_search(ctx, &query, limit)?
+            .into_iter()
+            .filter(|candidate| filter.allows(candidate) && !is_other_recording(candidate, track))
+            .partition(|candidate| is_cover(candidate, artist, track));
+        cover = cover.or(covers.into_iter().next());
+        let (originals, remasters): (Vec<Track>, Vec<Track>) = candidates.into_iter().partition(|candidate| !is_remaster(candidate, track));
+        for candidates in [originals, remasters] {
+            if let Some(found) = best_candidate(&candidates, artist, track) {
+                if found.confidence >= ctx.config.min_confidence {
+                    return Ok(found);
+                }
+                if best.as_ref().is_none_or(|best| found.confidence > best.confidence) {
+                    best = Some(found);
+                }
+            }
+        }
+    }
+
+    // The original often hides behind covers that spell the title exactly, among the songs of that title
+    if let Some(cover) = cover.filter(|_| best.as_ref().is_none_or(|best| best.confidence < ctx.config.min_confidence)) {
+        let candidates: Vec<Track> = run_search(ctx, &format!("track:{}", clean_name(track)), 50)?
+            .into_iter()
+            .filter(|candidate| filter.allows(candidate) && !is_other_recording(candidate, track) && !is_cover(candidate, artist, track))
+            .collect();
+        if let Some(found) = best_candidate(&candidates, artist, track) {
+            if best.as_ref().is_none_or(|best| found.confidence > best.confidence) {
+                best = Some(found);
+            }
+        }
+        if best.is_none() {
+            let covered_by: Vec<&str> = cover.artists.iter().map(|a| a.name.as_str()).collect();
+            return Err(format!("Only covers by other artists found, e.g. by {}, not the original recording.", covered_by.join(", ")).into());
+        }
+    }
+
+    best.ok_or_else(|| {
+        if filter.is_active() {
+            "No result meeting the filters found for the specified artist and track.".into()
+        } else {
+            "No result found for the specified artist and track.".into()
+        }
+    })
+}
+
+// Maximum number of URIs Spotify accepts in a single add-tracks or remove-tracks call
+pub const ADD_TRACKS_BATCH_SIZE: usize = 100;
+
+// Function to add tracks to a playlist by their URIs
+// The URIs are sent in sequential batches of 100; a failed batch doesn't stop the following ones,
+// and every failure is reported in the returned error.
+pub fn add_to_playlist(ctx: &Context, playlist_id: &str, uris: Vec<String>, position: Option<usize>) -> Result<(), PilotError> {
+    let playlist_url = format!("{}/playlists/{playlist_id}/tracks", ctx.config.spotify_api_url);
+
+    let mut failures = Vec::new();
+    for (index, chunk) in uris.chunks(ADD_TRACKS_BATCH_SIZE).enumerate() {
+        // Each batch goes right after the previous one, so the tracks keep their order
+        let body = AddTracksRequest {
+            uris: chunk.to_vec(),
+            position: position.map(|position| position + index * ADD_TRACKS_BATCH_SIZE),
+        };
+
+        // Send POST request to add this batch of tracks to the playlist
+        let response = send_with_policy(
+            Endpoint::Add,
+            ctx.client
+                .post(&playlist_url)
+                .header("Authorization", format!("Bearer {}", ctx.access_token))
+                .header("Content-Type", "application/json")
+                .json(&body)
+        );
+
+        // Check if the operation was successful
+        let first = index * ADD_TRACKS_BATCH_SIZE + 1;
+        let last = first + chunk.len() - 1;
+        match response {
+            Ok(resp) if resp.status().is_success() => {},
+            Ok(resp) => failures.push(format!("tracks {}-{}: {}", first, last, resp.status())),
+            Err(e) => failures.push(format!("tracks {}-{}: {}", first, last, e)),
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Failed to add tracks to playlist: {}", failures.join(", ")).into())
+    }
+}
+
+// Function to pick where each new track goes when spreading them through a playlist of `length` items
+// Positions are random, sorted, and account for the tracks inserted before them.
+pub fn shuffle_in_positions(length: usize, count: usize) -> Vec<usize> {
+    let mut rng = rand::thread_rng();
+    let mut positions: Vec<usize> = (0..count).map(|_| rng.gen_range(0..=length)).collect();
+    positions.sort_unstable();
+    positions.iter().enumerate().map(|(index, position)| position + index).collect()
+}
+
+// Function to insert tracks at random places in a playlist of `length` items instead of appending them
+pub fn shuffle_into_playlist(ctx: &Context, playlist_id: &str, uris: Vec<String>, length: usize) -> Result<(), PilotError> {
+    let positions = shuffle_in_positions(length, uris.len());
+    let mut failures = Vec::new();
+    for (uri, position) in uris.into_iter().zip(positions) {
+        if let Err(e) = add_to_playlist(ctx, playlist_id, vec![uri.clone()], Some(position)) {
+            failures.push(format!("{}: {}", uri, e));
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures.join(", ").into())
+    }
+}
+
+// Maximum number of IDs Spotify accepts in a single audio-features call
+pub const AUDIO_FEATURES_BATCH_SIZE: usize = 100;
+
+// Function to fetch the audio features (energy, valence...) of tracks, by track ID
+// Tracks without features, and local files or episodes, are missing from the result.
+pub fn get_audio_features(ctx: &Context, uris: &[String]) -> Result<HashMap<String, AudioFeatures>, PilotError> {
+    let ids: Vec<&str> = uris.iter().filter_map(|uri| uri.strip_prefix("spotify:track:")).collect();
+
+    let mut features = HashMap::new();
+    for chunk in ids.chunks(AUDIO_FEATURES_BATCH_SIZE) {
+        let response = send_with_retry(
+            ctx.client
+                .get(format!("{}/audio-features", ctx.config.spotify_api_url))
+                .query(&[("ids", chunk.join(","))])
+                .header("Authorization", format!("Bearer {}", ctx.access_token))
+        )?;
+
+        match response.status() {
+            status if status.is_success() => {},
+            // Apps registered since late 2024 are refused access to this endpoint
+            StatusCode::FORBIDDEN => return Err("Spotify refused access to audio features for this app (403 Forbidden).".into()),
+            status => return Err(PilotError::from_status(status, format!("Error fetching audio features: {}", status))),
+        }
+        let page: AudioFeaturesResponse = response.json().map_err(|e| e.to_string())?;
+        for item in page.audio_features.into_iter().flatten() {
+            features.insert(item.id.clone(), item);
+        }
+    }
+    Ok(features)
+}
+
+// Function to fetch the audio analysis of a track: its loudness, tempo and key, overall and section by section
+pub fn get_audio_analysis(ctx: &Context, track_id: &str) -> Result<AudioAnalysis, PilotError> {
+    let response = send_with_retry(
+        ctx.client
+            .get(format!("{}/audio-analysis/{}", ctx.config.spotify_api_url, track_id))
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+    )?;
+
+    match response.status() {
+        status if status.is_success() => {},
+        // Refused like the audio features for apps registered since late 2024
+        StatusCode::FORBIDDEN => return Err("Spotify refused access to audio analysis for this app (403 Forbidden).".into()),
+        status => return Err(PilotError::from_status(status, format!("Error fetching the audio analysis: {}", status))),
+    }
+    response.json().map_err(|e| e.to_string().into())
+}
+
+// Maximum number of IDs Spotify accepts in a single several-tracks call
+pub const TRACKS_BATCH_SIZE: usize = 50;
+
+// Function to fetch whole tracks by URI, for the tracks of a lean playlist whose popularity or album is needed
+// Tracks Spotify doesn't know are left out, and URIs that aren't Spotify tracks are skipped.
+pub fn get_tracks(ctx: &Context, uris: &[String]) -> Result<Vec<Track>, PilotError> {
+    let ids: Vec<&str> = uris.iter().filter_map(|uri| uri.strip_prefix("spotify:track:")).collect();
+
+    let mut tracks = Vec::new();
+    for chunk in ids.chunks(TRACKS_BATCH_SIZE) {
+        let response = send_with_retry(
+            ctx.client
+                .get(format!("{}/tracks", ctx.config.spotify_api_url))
+                .query(&[("ids", chunk.join(","))])
+                .header("Authorization", format!("Bearer {}", ctx.access_token))
+        )?;
+        if !response.status().is_success() {
+            return Err(PilotError::from_status(response.status(), format!("Error fetching tracks: {}", response.status())));
+        }
+        let page: SeveralTracksResponse = response.json().map_err(|e| e.to_string())?;
+        tracks.extend(page.tracks.into_iter().flatten());
+    }
+    Ok(tracks)
+}
+
+// Function to check which version of tracks can be played in a market, by URI
+// Each track maps to the URI to add there: its own, or the one of the version Spotify relinks it to; None when it can't be
+// played at all. Tracks Spotify doesn't know are missing from the result.
+pub fn playable_versions(ctx: &Context, uris: &[String], market: &str) -> Result<HashMap<String, Option<String>>, PilotError> {
+    let ids: Vec<&str> = uris.iter().filter_map(|uri| uri.strip_prefix("spotify:track:")).collect();
+
+    let mut versions = HashMap::new();
+    for chunk in ids.chunks(TRACKS_BATCH_SIZE) {
+        let response = send_with_retry(
+            ctx.client
+                .get(format!("{}/tracks", ctx.config.spotify_api_url))
+                .query(&[("ids", chunk.join(",")), ("market", market.to_string())])
+                .header("Authorization", format!("Bearer {}", ctx.access_token))
+        )?;
+        if !response.status().is_success() {
+            return Err(PilotError::from_status(response.status(), format!("Error fetching tracks: {}", response.status())));
+        }
+        // Tracks come back in the order of the IDs, relinked ones with the URI of their market version
+        let page: PlayableTracksResponse = response.json().map_err(|e| e.to_string())?;
+        for (id, track) in chunk.iter().zip(page.tracks) {
+            let Some(track) = track else { continue };
+            let requested = track.linked_from.as_ref().map_or_else(|| format!("spotify:track:{}", id), |link| link.uri.clone());
+            let playable = track.is_playable != Some(false);
+            versions.insert(requested, playable.then_some(track.uri));
+        }
+    }
+    Ok(versions)
+}
+
+// Maximum number of seeds (tracks and artists together) Spotify accepts for recommendations
+pub const RECOMMENDATION_SEEDS: usize = 5;
+
+// Function to get Spotify's recommendations for seed tracks and artists, given by ID
+pub fn get_recommendations(ctx: &Context, seed_tracks: &[String], seed_artists: &[String], limit: usize) -> Result<Vec<Track>, PilotError> {
+    let mut query = vec![
+        ("seed_tracks", seed_tracks.join(",")),
+        ("seed_artists", seed_artists.join(",")),
+        ("limit", limit.clamp(1, 100).to_string()),
+    ];
+    if let Some(market) = &ctx.config.market {
+        query.push(("market", market.clone()));
+    }
+
+    let response = send_with_retry(
+        ctx.client
+            .get(format!("{}/recommendations", ctx.config.spotify_api_url))
+            .query(&query)
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+    )?;
+
+    match response.status() {
+        status if status.is_success() => {},
+        // Apps registered since late 2024 are refused access to this endpoint
+        StatusCode::FORBIDDEN | StatusCode::NOT_FOUND => {
+            return Err(PilotError::Auth(format!("Spotify refused access to recommendations for this app ({}).", response.status())))
+        },
+        status => return Err(PilotError::from_status(status, format!("Error fetching recommendations: {}", status))),
+    }
+    let recommendations: RecommendationsResponse = response.json().map_err(|e| e.to_string())?;
+    Ok(recommendations.tracks.into_iter().filter(|track| track.is_playable != Some(false)).collect())
+}
+
+// Maximum number of IDs Spotify accepts in a single artists call
+pub const ARTISTS_BATCH_SIZE: usize = 50;
+
+// Function to fetch the genre tags of artists, by artist ID
+pub fn get_artist_genres(ctx: &Context, artist_ids: &[String]) -> Result<HashMap<String, Vec<String>>, PilotError> {
+    let mut genres = HashMap::new();
+    for chunk in artist_ids.chunks(ARTISTS_BATCH_SIZE) {
+        let response = send_with_retry(
+            ctx.client
+                .get(format!("{}/artists", ctx.config.spotify_api_url))
+                .query(&[("ids", chunk.join(","))])
+                .header("Authorization", format!("Bearer {}", ctx.access_token))
+        )?;
+
+        if !response.status().is_success() {
+            return Err(PilotError::from_status(response.status(), format!("Error fetching artists: {}", response.status())));
+        }
+        let page: ArtistsResponse = response.json().map_err(|e| e.to_string())?;
+        for artist in page.artists.into_iter().flatten() {
+            genres.insert(artist.id, artist.genres);
+        }
+    }
+    Ok(genres)
+}
+
+// Function to count how many tracks have each genre, a track having the genres of all its artists
+// Genres are sorted from the most to the least common.
+pub fn genre_counts(ctx: &Context, tracks: &[Track]) -> Result<Vec<(String, usize)>, PilotError> {
+    let mut artist_ids: Vec<String> = tracks.iter().flat_map(|track| track.artists.iter().filter_map(|a| a.id.clone())).collect();
+    artist_ids.sort();
+    artist_ids.dedup();
+    let genres = get_artist_genres(ctx, &artist_ids)?;
+
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for track in tracks {
+        let mut track_genres: Vec<&str> = track
+            .artists
+            .iter()
+            .filter_map(|artist| artist.id.as_ref().and_then(|id| genres.get(id)))
+            .flatten()
+            .map(|genre| genre.as_str())
+            .collect();
+        track_genres.sort();
+        track_genres.dedup();
+        for genre in track_genres {
+            *counts.entry(genre).or_default() += 1;
+        }
+    }
+
+    let mut counts: Vec<(String, usize)> = counts.into_iter().map(|(genre, count)| (genre.to_string(), count)).collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(counts)
+}
+
+// Function to move one playlist item to another position, returning the new snapshot ID
+// `insert_before` is the position the item is put in front of, counted before the move.
+pub fn move_in_playlist(ctx: &Context, playlist_id: &str, range_start: usize, insert_before: usize, snapshot_id: Option<String>) -> Result<String, PilotError> {
+    let body = ReorderTracksRequest { range_start, insert_before, snapshot_id };
+    let response = send_with_retry(
+        ctx.client
+            .put(format!("{}/playlists/{playlist_id}/tracks", ctx.config.spotify_api_url))
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+            .header("Content-Type", "application/json")
+            .json(&body)
+    )?;
+
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Failed to reorder playlist tracks: {}", response.status())));
+    }
+    let snapshot: PlaylistSnapshot = response.json().map_err(|e| e.to_string())?;
+    Ok(snapshot.snapshot_id)
+}
+
+// Function to remove specific occurrences of tracks, given as (URI, position), leaving their other copies in place
+// Batches go from the end of the playlist, so the positions of the next batches don't move; each batch is counted in the
+// snapshot the previous one left. The final snapshot ID is returned.
+pub fn remove_occurrences(ctx: &Context, playlist_id: &str, occurrences: &[(String, usize)], snapshot_id: String) -> Result<String, PilotError> {
+    let mut occurrences = occurrences.to_vec();
+    occurrences.sort_by_key(|(_, position)| std::cmp::Reverse(*position));
+
+    let mut snapshot_id = snapshot_id;
+    for chunk in occurrences.chunks(ADD_TRACKS_BATCH_SIZE) {
+        let body = RemoveOccurrencesRequest {
+            tracks: chunk.iter().map(|(uri, position)| TrackOccurrence { uri: uri.clone(), positions: vec![*position] }).collect(),
+            snapshot_id,
+        };
+        let response = send_with_retry(
+            ctx.client
+                .delete(format!("{}/playlists/{playlist_id}/tracks", ctx.config.spotify_api_url))
+                .header("Authorization", format!("Bearer {}", ctx.access_token))
+                .header("Content-Type", "application/json")
+                .json(&body)
+        )?;
+        if !response.status().is_success() {
+            return Err(PilotError::from_status(response.status(), format!("Failed to remove the tracks: {}", response.status())));
+        }
+        let snapshot: PlaylistSnapshot = response.json().map_err(|e| e.to_string())?;
+        snapshot_id = snapshot.snapshot_id;
+    }
+    Ok(snapshot_id)
+}
+
+// Function to replace the whole content of a playlist, in order
+// Spotify only replaces up to ADD_TRACKS_BATCH_SIZE tracks at once, the rest is appended after.
+pub fn replace_playlist(ctx: &Context, playlist_id: &str, uris: Vec<String>) -> Result<(), PilotError> {
+    let playlist_url = format!("{}/playlists/{playlist_id}/tracks", ctx.config.spotify_api_url);
+    let split = uris.len().min(ADD_TRACKS_BATCH_SIZE);
+    let body = AddTracksRequest { uris: uris[..split].to_vec(), position: None };
+
+    // Send PUT request to replace the playlist with the first batch of tracks
+    let response = send_with_retry(
+        ctx.client
+            .put(&playlist_url)
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+            .header("Content-Type", "application/json")
+            .json(&body)
+    )?;
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Failed to replace playlist tracks: {}", response.status())));
+    }
+
+    if uris.len() > split {
+        add_to_playlist(ctx, playlist_id, uris[split..].to_vec(), None)?;
+    }
+    Ok(())
+}
+
+// Function to remove tracks from a playlist, every occurrence of each URI is removed
+pub fn remove_from_playlist(ctx: &Context, playlist_id: &str, uris: Vec<String>) -> Result<(), PilotError> {
+    let playlist_url = format!("{}/playlists/{playlist_id}/tracks", ctx.config.spotify_api_url);
+
+    let mut failures = Vec::new();
+    for (index, chunk) in uris.chunks(ADD_TRACKS_BATCH_SIZE).enumerate() {
+        let body = RemoveTracksRequest {
+            tracks: chunk.iter().map(|uri| TrackUri { uri: uri.clone() }).collect(),
+        };
+
+        // Send DELETE request to remove this batch of tracks from the playlist
+        let response = send_with_retry(
+            ctx.client
+                .delete(&playlist_url)
+                .header("Authorization", format!("Bearer {}", ctx.access_token))
+                .header("Content-Type", "application/json")
+                .json(&body)
+        );
+
+        let first = index * ADD_TRACKS_BATCH_SIZE + 1;
+        let last = first + chunk.len() - 1;
+        match response {
+            Ok(resp) if resp.status().is_success() => {},
+            Ok(resp) => failures.push(format!("tracks {}-{}: {}", first, last, resp.status())),
+            Err(e) => failures.push(format!("tracks {}-{}: {}", first, last, e)),
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Failed to remove tracks from playlist: {}", failures.join(", ")).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_SPOTIFY_API_URL;
+    use crate::testing::{test_context, track_json, CannedServer};
+
+    // Function to read back the `q` parameter the way Spotify decodes it
+    fn decoded_query(url: &Url) -> String {
+        url.query_pairs()
+            .find(|(key, _)| key == "q")
+            .map(|(_, value)| value.into_owned())
+            .unwrap()
+    }
+
+    #[test]
+    fn search_url_keeps_ampersands_and_hashes_in_the_query() {
+        let url = search_url(DEFAULT_SPOTIFY_API_URL, &field_query("Simon & Garfunkel", "Song #1"), 1, None);
+        assert_eq!(decoded_query(&url), "artist:Simon & Garfunkel track:Song #1");
+        assert_eq!(url.query_pairs().count(), 3);
+        assert!(url.fragment().is_none());
+    }
+
+    #[test]
+    fn search_url_keeps_apostrophes_and_non_ascii_titles() {
+        let url = search_url(DEFAULT_SPOTIFY_API_URL, &field_query("Sigur Rós", "Hoppípolla (Don't Stop)"), 1, None);
+        assert_eq!(decoded_query(&url), "artist:Sigur Rós track:Hoppípolla (Don't Stop)");
+        assert!(url.as_str().is_ascii());
+    }
+
+    #[test]
+    fn search_url_does_not_let_the_title_override_other_parameters() {
+        let url = search_url(DEFAULT_SPOTIFY_API_URL, &field_query("Artist", "Title&type=album&limit=50"), 1, Some("FR"));
+        let limits: Vec<String> = url
+            .query_pairs()
+            .filter(|(key, _)| key == "limit")
+            .map(|(_, value)| value.into_owned())
+            .collect();
+        assert_eq!(limits, vec!["1"]);
+    }
+
+    #[test]
+    fn get_playlist_follows_the_next_pages() {
+        let first = serde_json::json!({
+            "name": "Road trip",
+            "snapshot_id": "snap1",
+            "tracks": { "items": [{ "track": track_json("a", "Song A", "Artist A") }], "next": "{server}/playlists/pl1/tracks?offset=1" },
+        });
+        let second = serde_json::json!({ "items": [{ "track": track_json("b", "Song B", "Artist B") }], "next": null });
+        let server = CannedServer::start(vec![(200, first.to_string()), (200, second.to_string())]);
+        let playlist = get_playlist(&test_context(&server), "pl1", None).unwrap();
+        assert_eq!(playlist.name, "Road trip");
+        assert_eq!(spotify_tracks(playlist.tracks.items).0.len(), 2);
+        let paths: Vec<String> = server.received().into_iter().map(|request| request.path).collect();
+        assert_eq!(paths, vec!["/playlists/pl1", "/playlists/pl1/tracks?offset=1"]);
+    }
+
+    #[test]
+    fn lean_playlists_ask_every_page_for_the_fields_used() {
+        let first = serde_json::json!({
+            "name": "Road trip",
+            "snapshot_id": "snap1",
+            "tracks": { "items": [{ "track": { "type": "track", "name": "Song A", "uri": "spotify:track:a", "artists": [{ "name": "Artist A" }] } }], "next": "{server}/playlists/pl1/tracks?offset=1" },
+        });
+        let second = serde_json::json!({ "items": [{ "track": { "type": "episode", "name": "Episode", "uri": "spotify:episode:e" } }], "next": null });
+        let server = CannedServer::start(vec![(200, first.to_string()), (200, second.to_string())]);
+        let playlist = get_lean_playlist(&test_context(&server), "pl1").unwrap();
+        let (tracks, skipped) = spotify_tracks(playlist.tracks.items);
+        assert_eq!((tracks.len(), skipped), (1, 1));
+        assert_eq!(tracks[0].popularity, None);
+        let received = server.received();
+        let first_fields = Url::parse(&format!("http://localhost{}", received[0].path)).unwrap();
+        assert_eq!(first_fields.query_pairs().find(|(key, _)| key == "fields").unwrap().1, LEAN_PLAYLIST_FIELDS);
+        assert!(received[1].path.starts_with("/playlists/pl1/tracks?offset=1&fields=next%2Citems"));
+    }
+
+    #[test]
+    fn followed_artists_follow_the_next_pages() {
+        let first = serde_json::json!({ "artists": { "items": [{ "id": "a1", "name": "Portishead" }], "next": "{server}/me/following?type=artist&after=a1" } });
+        let second = serde_json::json!({ "artists": { "items": [{ "id": "a2", "name": "Massive Attack" }], "next": null } });
+        let server = CannedServer::start(vec![(200, first.to_string()), (200, second.to_string())]);
+        let artists = get_followed_artists(&test_context(&server)).unwrap();
+        let names: Vec<&str> = artists.iter().map(|artist| artist.name.as_str()).collect();
+        assert_eq!(names, vec!["Portishead", "Massive Attack"]);
+        assert_eq!(server.received()[1].path, "/me/following?type=artist&after=a1");
+    }
+
+    #[test]
+    fn members_are_named_by_their_profile() {
+        let items: Vec<TrackItem> = serde_json::from_value(serde_json::json!([
+            { "track": track_json("a", "Song A", "Artist A"), "added_by": { "id": "u1" } },
+            { "track": track_json("b", "Song B", "Artist B"), "added_by": { "id": "u2" } },
+            { "track": track_json("c", "Song C", "Artist C"), "added_by": { "id": "u1" } },
+            { "track": track_json("d", "Song D", "Artist D"), "added_by": null },
+        ]))
+        .unwrap();
+        let members = playlist_members(&items);
+        assert_eq!(members, vec!["u1", "u2"]);
+        let profile = serde_json::json!({ "id": "u1", "display_name": "Anna" });
+        let server = CannedServer::start(vec![(200, profile.to_string()), (404, "{}".to_string())]);
+        let names = member_names(&test_context(&server), &members);
+        assert_eq!((names["u1"].as_str(), names["u2"].as_str()), ("Anna", "u2"));
+        assert_eq!(server.received()[0].path, "/users/u1");
+    }
+
+    #[test]
+    fn save_tracks_sends_ids_in_batches() {
+        let server = CannedServer::start(vec![(200, String::new()), (200, String::new())]);
+        let uris: Vec<String> = (0..60).map(|i| format!("spotify:track:t{}", i)).collect();
+        save_tracks(&test_context(&server), &uris).unwrap();
+        let received = server.received();
+        assert_eq!((received[0].method.as_str(), received[0].path.as_str()), ("PUT", "/me/tracks"));
+        let batches: Vec<usize> = received
+            .iter()
+            .map(|request| serde_json::from_str::<serde_json::Value>(&request.body).unwrap()["ids"].as_array().unwrap().len())
+            .collect();
+        assert_eq!(batches, vec![50, 10]);
+        assert!(received[0].body.starts_with(r#"{"ids":["t0","t1""#));
+    }
+
+    #[test]
+    fn playable_versions_follow_relinking() {
+        let tracks = serde_json::json!({ "tracks": [
+            { "uri": "spotify:track:a", "is_playable": true },
+            { "uri": "spotify:track:b2", "is_playable": true, "linked_from": { "uri": "spotify:track:b" } },
+            { "uri": "spotify:track:c", "is_playable": false },
+            null,
+        ] });
+        let server = CannedServer::start(vec![(200, tracks.to_string())]);
+        let uris: Vec<String> = ["a", "b", "c", "d"].iter().map(|id| format!("spotify:track:{}", id)).collect();
+        let versions = playable_versions(&test_context(&server), &uris, "FR").unwrap();
+        assert_eq!(versions["spotify:track:a"].as_deref(), Some("spotify:track:a"));
+        assert_eq!(versions["spotify:track:b"].as_deref(), Some("spotify:track:b2"));
+        assert_eq!(versions["spotify:track:c"], None);
+        assert!(!versions.contains_key("spotify:track:d"));
+        assert_eq!(server.received()[0].path, "/tracks?ids=a%2Cb%2Cc%2Cd&market=FR");
+    }
+
+    #[test]
+    fn search_song_returns_the_confident_match() {
+        let results = serde_json::json!({ "tracks": { "items": [track_json("x", "Karma Police (Live)", "Radiohead"), track_json("y", "Karma Police", "Radiohead")] } });
+        let server = CannedServer::start(vec![(200, results.to_string())]);
+        let song = Song { name: "Karma Police".to_string(), artist: "Radiohead".to_string(), isrc: None, mbid: None };
+        let found = search_song(&test_context(&server), &song, &TrackFilter::default()).unwrap();
+        assert_eq!(found.uri, "spotify:track:y");
+        assert!(found.confidence >= 0.8);
+        assert!(server.received()[0].path.starts_with("/search?q=artist%3ARadiohead"));
+    }
+
+    #[test]
+    fn search_song_looks_for_the_original_behind_covers() {
+        let covers = serde_json::json!({ "tracks": { "items": [track_json("x", "Creep", "Vitamin String Quartet")] } });
+        let empty = serde_json::json!({ "tracks": { "items": [] } });
+        let titled = serde_json::json!({ "tracks": { "items": [track_json("x", "Creep", "Vitamin String Quartet"), track_json("y", "Creep", "Radiohead")] } });
+        let server = CannedServer::start(vec![(200, covers.to_string()), (200, covers.to_string()), (200, empty.to_string()), (200, titled.to_string())]);
+        let song = Song { name: "Creep".to_string(), artist: "Radiohead".to_string(), isrc: None, mbid: None };
+        let found = search_song(&test_context(&server), &song, &TrackFilter::default()).unwrap();
+        assert_eq!(found.uri, "spotify:track:y");
+        assert!(server.received()[3].path.starts_with("/search?q=track%3Acreep"));
+    }
+
+    #[test]
+    fn update_playlist_description_cuts_long_descriptions() {
+        let server = CannedServer::start(vec![(200, "{}".to_string())]);
+        update_playlist_description(&test_context(&server), "pl1", &"a".repeat(400)).unwrap();
+        let received = &server.received()[0];
+        assert_eq!((received.method.as_str(), received.path.as_str()), ("PUT", "/playlists/pl1"));
+        let body: serde_json::Value = serde_json::from_str(&received.body).unwrap();
+        assert_eq!(body["description"].as_str().unwrap().len(), MAX_DESCRIPTION_LENGTH);
+    }
+
+    #[test]
+    fn add_to_playlist_sends_batches_of_100_in_order() {
+        let server = CannedServer::start(vec![(201, "{}".to_string()), (201, "{}".to_string())]);
+        let uris: Vec<String> = (0..150).map(|index| format!("spotify:track:{}", index)).collect();
+        add_to_playlist(&test_context(&server), "pl1", uris, Some(3)).unwrap();
+        let received = server.received();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].method, "POST");
+        assert_eq!(received[0].path, "/playlists/pl1/tracks");
+        let second: serde_json::Value = serde_json::from_str(&received[1].body).unwrap();
+        assert_eq!(second["uris"].as_array().unwrap().len(), 50);
+        assert_eq!(second["uris"][0], "spotify:track:100");
+        assert_eq!(second["position"], 103);
+    }
+
+    #[test]
+    fn parse_spotify_id_reads_uris_links_and_bare_ids() {
+        let id = Some("4uLU6hMCjMI75M1A2tKUQC".to_string());
+        assert_eq!(parse_spotify_id("spotify:track:4uLU6hMCjMI75M1A2tKUQC", "track"), id);
+        assert_eq!(parse_spotify_id("https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC?si=abc", "track"), id);
+        assert_eq!(parse_spotify_id("https://open.spotify.com/intl-fr/track/4uLU6hMCjMI75M1A2tKUQC", "track"), id);
+        assert_eq!(parse_spotify_id("4uLU6hMCjMI75M1A2tKUQC", "track"), id);
+        assert_eq!(parse_spotify_id("https://open.spotify.com/album/4uLU6hMCjMI75M1A2tKUQC", "track"), None);
+        assert_eq!(parse_spotify_id("https://example.com/track/4uLU6hMCjMI75M1A2tKUQC", "track"), None);
+    }
+}
@@ -0,0 +1,126 @@
+// Import necessary modules and crates
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::{resolve_config_from, Config, ConfigFile, ProfileSettings};
+use crate::context::Context;
+use crate::http::{build_client, configure_requests, RetryPolicies, RetryPolicy};
+
+// A request the canned server received, for tests to check what was sent
+#[derive(Debug, Clone)]
+pub struct Received {
+    pub method: String,
+    // Path and query, e.g. "/playlists/pl1/tracks?offset=100"
+    pub path: String,
+    pub body: String,
+}
+
+// Local HTTP server answering each request with the next canned response, standing in for the real APIs
+pub struct CannedServer {
+    // Base URL of the server, e.g. "http://127.0.0.1:40123"
+    pub url: String,
+    received: Arc<Mutex<Vec<Received>>>,
+}
+
+impl CannedServer {
+    // Function to start a server answering the given statuses and JSON bodies in order, one per request
+    // "{server}" in a body is replaced with the server's URL, e.g. for the next page links.
+    pub fn start(responses: Vec<(u16, String)>) -> CannedServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        let base = url.clone();
+        thread::spawn(move || {
+            for (status, body) in responses {
+                let Ok((stream, _)) = listener.accept() else { return };
+                let body = body.replace("{server}", &base);
+                let mut reader = BufReader::new(&stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut request_body = vec![0; length];
+                reader.read_exact(&mut request_body).unwrap();
+                let mut parts = request_line.split_whitespace();
+                log.lock().unwrap().push(Received {
+                    method: parts.next().unwrap_or_default().to_string(),
+                    path: parts.next().unwrap_or_default().to_string(),
+                    body: String::from_utf8_lossy(&request_body).into_owned(),
+                });
+                let response = format!(
+                    "HTTP/1.1 {} Canned\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                (&stream).write_all(response.as_bytes()).unwrap();
+            }
+        });
+        CannedServer { url, received }
+    }
+
+    // Function to get the requests received so far, in order
+    pub fn received(&self) -> Vec<Received> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+// Function to resolve test settings, without reading the environment the tests run in
+pub fn test_config(settings: ProfileSettings) -> Config {
+    let file = ConfigFile { profiles: HashMap::from([("test".to_string(), settings)]), ..Default::default() };
+    resolve_config_from(&file, Some("test"), |_| None).unwrap()
+}
+
+// Function to build a context whose Spotify, Spotify accounts and LLM APIs are the canned server
+// Failed requests are retried right away, so the tests of retries don't wait for the real delays.
+pub fn test_context(server: &CannedServer) -> Context {
+    let settings = ProfileSettings {
+        playlist_id: Some("pl1".to_string()),
+        spotify_api_url: Some(server.url.clone()),
+        spotify_accounts_url: Some(server.url.clone()),
+        llm_api_url: Some(format!("{}/chat/completions", server.url)),
+        llm_model: Some("test-model".to_string()),
+        ..Default::default()
+    };
+    let mut config = test_config(settings);
+    config.retry = RetryPolicies { default: RetryPolicy { base_delay: Duration::ZERO, ..RetryPolicy::default() }, endpoints: HashMap::new() };
+    configure_requests(&config);
+    Context { client: build_client(&config).unwrap(), config, access_token: "test-token".to_string() }
+}
+
+// Function to write a Spotify track as the API returns it
+pub fn track_json(id: &str, name: &str, artist: &str) -> serde_json::Value {
+    serde_json::json!({
+        "type": "track",
+        "name": name,
+        "uri": format!("spotify:track:{}", id),
+        "artists": [{ "name": artist, "id": format!("artist-{}", id) }],
+        "popularity": 50,
+    })
+}
+
+// Function to write the answer of a Spotify search
+pub fn search_answer(tracks: Vec<serde_json::Value>) -> String {
+    serde_json::json!({ "tracks": { "items": tracks } }).to_string()
+}
+
+// Function to write the answer of the LLM API, its message being the given content
+pub fn llm_answer(content: &str) -> String {
+    serde_json::json!({ "choices": [{ "message": { "content": content } }], "usage": { "total_tokens": 10 } }).to_string()
+}
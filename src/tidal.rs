@@ -0,0 +1,388 @@
+// Import necessary modules and crates
+use reqwest::blocking::{Client, Response};
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::auth::open_in_browser;
+use crate::backup::{save_backup, Backup, BackupItem};
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::http::send;
+use crate::matching::*;
+use crate::models::*;
+use crate::service::{MusicService, NewPlaylist, ServicePlaylist};
+use crate::tokens::*;
+
+// Endpoints of the Tidal authorization server and API
+const TIDAL_DEVICE_URL: &str = "https://auth.tidal.com/v1/oauth2/device_authorization";
+const TIDAL_TOKEN_URL: &str = "https://auth.tidal.com/v1/oauth2/token";
+const TIDAL_API_URL: &str = "https://api.tidal.com/v1";
+const TIDAL_IMAGES_URL: &str = "https://resources.tidal.com/images";
+
+// Scopes requested from the user: reading and writing their collection and playlists
+const TIDAL_SCOPE: &str = "r_usr w_usr";
+
+// Grant type of the device flow (RFC 8628)
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+// Prefix of the track URIs, to tell Tidal tracks apart from Spotify tracks in the history
+const URI_PREFIX: &str = "tidal:track:";
+
+// Number of playlist items read, and of tracks added, per call
+const PAGE_SIZE: usize = 100;
+const ADD_BATCH_SIZE: usize = 50;
+
+// Tidal, through its API: the user and the country of their catalog are kept here once authorized
+#[derive(Default)]
+pub struct Tidal {
+    session: OnceLock<TidalSession>,
+}
+
+// Function to describe a Tidal track as a track, its version (e.g. "Remastered 2011") being part of the title
+fn tidal_track(track: TidalTrack) -> Track {
+    let name = match track.version.filter(|version| !version.is_empty()) {
+        Some(version) => format!("{} ({})", track.title, version),
+        None => track.title,
+    };
+    let album = track
+        .album
+        .map(|album| Album {
+            images: album
+                .cover
+                .map(|cover| Image { url: format!("{}/{}/160x160.jpg", TIDAL_IMAGES_URL, cover.replace('-', "/")), width: Some(160) })
+                .into_iter()
+                .collect(),
+            name: album.title,
+            release_date: album.release_date,
+        })
+        .unwrap_or_default();
+    Track {
+        name,
+        artists: track.artists.into_iter().map(|artist| Artist { name: artist.name, id: None }).collect(),
+        uri: format!("{}{}", URI_PREFIX, track.id),
+        is_local: false,
+        external_ids: ExternalIds { isrc: track.isrc },
+        is_playable: None,
+        duration_ms: track.duration * 1000,
+        popularity: track.popularity,
+        album,
+        explicit: track.explicit,
+    }
+}
+
+// Function to get the track ID of a track URI
+fn track_id(uri: &str) -> &str {
+    uri.strip_prefix(URI_PREFIX).unwrap_or(uri)
+}
+
+// Function to call the Tidal authorization server
+fn request_token(client: &Client, config: &Config, grant: &[(&str, &str)]) -> Result<Response, PilotError> {
+    let client_id = config.tidal_client_id.as_deref().ok_or("tidal client id not set")?;
+    let mut body: HashMap<&str, &str> = grant.iter().copied().collect();
+    body.insert("client_id", client_id);
+    body.insert("scope", TIDAL_SCOPE);
+    if let Some(client_secret) = config.tidal_client_secret.as_deref() {
+        body.insert("client_secret", client_secret);
+    }
+    Ok(send(client.post(TIDAL_TOKEN_URL).form(&body))?)
+}
+
+// Function to store a token response in the cache and return its access token
+fn cache_token(cache_key: &str, response: TidalAuthResponse, previous_refresh_token: Option<String>) -> String {
+    let token = CachedToken {
+        access_token: response.access_token,
+        refresh_token: response.refresh_token.or(previous_refresh_token),
+        expires_at: now() + response.expires_in,
+        scope: TIDAL_SCOPE.to_string(),
+    };
+    if let Err(e) = save_token(cache_key, &token) {
+        warn!("Could not save the access token: {}", e);
+    }
+    token.access_token
+}
+
+// Function to run the device flow: the user approves a code on any device while this one polls for the token
+fn authorize_device(client: &Client, config: &Config, headless: bool) -> Result<TidalAuthResponse, PilotError> {
+    let client_id = config.tidal_client_id.as_deref().ok_or("tidal client id not set")?;
+    let response = send(client.post(TIDAL_DEVICE_URL).form(&[("client_id", client_id), ("scope", TIDAL_SCOPE)]))?;
+    if !response.status().is_success() {
+        return Err(PilotError::Auth(format!("Failed to start the Tidal authorization: {}", response.status())));
+    }
+    let device: TidalDeviceAuthorization = response.json()?;
+
+    // The verification URI is often given without its scheme
+    let url = match device.verification_uri_complete.starts_with("http") {
+        true => device.verification_uri_complete.clone(),
+        false => format!("https://{}", device.verification_uri_complete),
+    };
+    if headless || open_in_browser(&url).is_err() {
+        println!("Open this URL on any device to authorize Tidal: {}", url);
+    } else {
+        println!("Opened the Tidal authorization page in your browser. If nothing happened, go to this URL: {}", url);
+    }
+    println!("Check that it shows the code {}.", device.user_code);
+
+    let deadline = Instant::now() + Duration::from_secs(device.expires_in);
+    let mut interval = Duration::from_secs(device.interval.max(1));
+    while Instant::now() < deadline {
+        thread::sleep(interval);
+        let response = request_token(client, config, &[("grant_type", DEVICE_CODE_GRANT), ("device_code", &device.device_code)])?;
+        if response.status().is_success() {
+            println!("Authorization received.");
+            return Ok(response.json()?);
+        }
+        let status = response.status();
+        match response.json::<TidalAuthError>().map(|e| e.error).as_deref() {
+            Ok("authorization_pending") => {},
+            Ok("slow_down") => interval += Duration::from_secs(5),
+            Ok(error) => return Err(format!("Tidal refused the authorization: {}", error).into()),
+            Err(_) => return Err(PilotError::Auth(format!("Failed to get a Tidal access token: {}", status))),
+        }
+    }
+    Err("Timed out waiting for the Tidal authorization.".into())
+}
+
+// Function to check the answer of a write call
+fn check_write(response: Result<Response, reqwest::Error>, action: &str) -> Result<Response, PilotError> {
+    match response {
+        Ok(resp) if resp.status().is_success() => Ok(resp),
+        Ok(resp) => Err(PilotError::from_status(resp.status(), format!("Error {}: {}", action, resp.status()))),
+        Err(e) => Err(format!("Error {}: {}", action, e).into()),
+    }
+}
+
+impl Tidal {
+    // Function to get the country of the user's catalog, which every catalog call is made in
+    fn country_code(&self) -> &str {
+        self.session.get().map(|session| session.country_code.as_str()).unwrap_or("US")
+    }
+
+    // Function to call the Tidal API on behalf of the user, returning the whole response
+    fn get_response(&self, ctx: &Context, path: &str, params: &[(&str, &str)]) -> Result<Response, PilotError> {
+        let response = send(
+            ctx.client
+                .get(format!("{}/{}", TIDAL_API_URL, path))
+                .query(&[("countryCode", self.country_code())])
+                .query(params)
+                .header("Authorization", format!("Bearer {}", ctx.access_token))
+        )?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(PilotError::NotFound(format!("Not found on Tidal: {}", path)));
+        }
+        if !response.status().is_success() {
+            return Err(PilotError::from_status(response.status(), format!("Error calling Tidal: {}", response.status())));
+        }
+        Ok(response)
+    }
+
+    // Function to call the Tidal API on behalf of the user
+    fn get<T: serde::de::DeserializeOwned>(&self, ctx: &Context, path: &str, params: &[(&str, &str)]) -> Result<T, PilotError> {
+        self.get_response(ctx, path, params)?
+            .json()
+            .map_err(|e| format!("Failed to parse the Tidal response: {}", e).into())
+    }
+
+    // Function to get the current version of a playlist, which every write to it must name
+    fn playlist_etag(&self, ctx: &Context, playlist_id: &str) -> Result<String, PilotError> {
+        let response = self.get_response(ctx, &format!("playlists/{}", playlist_id), &[])?;
+        response
+            .headers()
+            .get("ETag")
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string())
+            .ok_or_else(|| "Tidal did not give the playlist version".into())
+    }
+}
+
+impl MusicService for Tidal {
+    fn name(&self) -> &'static str {
+        "Tidal"
+    }
+
+    // Tidal has no app-only access to playlists, so even a dry run needs the user's authorization
+    fn access_token(&self, client: &Client, config: &Config, headless: bool, _read_only: bool) -> Result<String, PilotError> {
+        let client_id = config.tidal_client_id.as_deref().ok_or("tidal client id not set")?;
+        let cache_key = config.storage_key(&format!("tidal:{}", client_id));
+        let mut access_token = None;
+        if let Some(token) = load_token(&cache_key).filter(|token| token.scope == TIDAL_SCOPE) {
+            if token.expires_at > now() + 60 {
+                access_token = Some(token.access_token);
+            } else if let Some(refresh_token) = &token.refresh_token {
+                let response = request_token(client, config, &[("grant_type", "refresh_token"), ("refresh_token", refresh_token)])?;
+                if response.status().is_success() {
+                    access_token = Some(cache_token(&cache_key, response.json()?, Some(refresh_token.clone())));
+                } else {
+                    warn!("Failed to refresh the Tidal access token: {}, authorizing again.", response.status());
+                }
+            }
+        }
+        let access_token = match access_token {
+            Some(token) => token,
+            None => cache_token(&cache_key, authorize_device(client, config, headless)?, None),
+        };
+
+        // The session tells which user playlists are created for and which country's catalog is searched
+        let response = send(
+            client
+                .get(format!("{}/sessions", TIDAL_API_URL))
+                .header("Authorization", format!("Bearer {}", access_token))
+        )?;
+        if !response.status().is_success() {
+            return Err(PilotError::from_status(response.status(), format!("Failed to read the Tidal session: {}", response.status())));
+        }
+        let _ = self.session.set(response.json()?);
+        Ok(access_token)
+    }
+
+    // Videos and tracks no longer available are skipped
+    fn get_playlist(&self, ctx: &Context, playlist_id: &str) -> Result<ServicePlaylist, PilotError> {
+        let playlist: TidalPlaylist = self.get(ctx, &format!("playlists/{}", playlist_id), &[])?;
+
+        let mut items = Vec::new();
+        loop {
+            let offset = items.len().to_string();
+            let limit = PAGE_SIZE.to_string();
+            let page: TidalPlaylistItems =
+                self.get(ctx, &format!("playlists/{}/items", playlist_id), &[("offset", &offset), ("limit", &limit)])?;
+            let received = page.items.len();
+            items.extend(page.items);
+            if received == 0 || items.len() >= page.total_number_of_items {
+                break;
+            }
+        }
+
+        let length = items.len();
+        let tracks: Vec<Track> = items
+            .into_iter()
+            .filter(|item| item.kind == "track")
+            .filter_map(|item| serde_json::from_value::<TidalTrack>(item.item).ok())
+            .map(tidal_track)
+            .collect();
+        let skipped = length - tracks.len();
+        Ok(ServicePlaylist { name: playlist.title, snapshot_id: None, length, tracks, skipped })
+    }
+
+    // Search results carry their ISRC, so a song with one is matched exactly when the recording shows up
+    fn search_song(&self, ctx: &Context, song: &Song, filter: &TrackFilter) -> Result<TrackMatch, PilotError> {
+        let query = format!("{} {}", song.artist, song.name);
+        let results: TidalTracks = self.get(ctx, "search/tracks", &[("query", &query), ("limit", "10")])?;
+        let candidates: Vec<Track> = results
+            .items
+            .into_iter()
+            .map(tidal_track)
+            .filter(|candidate| filter.allows(candidate))
+            .collect();
+
+        if let Some(isrc) = song.isrc.as_deref().map(str::trim) {
+            let exact: Vec<Track> = candidates
+                .iter()
+                .filter(|candidate| candidate.external_ids.isrc.as_deref().is_some_and(|found| found.eq_ignore_ascii_case(isrc)))
+                .cloned()
+                .collect();
+            if let Some(found) = best_candidate(&exact, &song.artist, &song.name).filter(|found| found.confidence >= ctx.config.min_confidence) {
+                return Ok(TrackMatch { confidence: 1.0, ..found });
+            }
+        }
+
+        let candidates: Vec<Track> = candidates.into_iter().filter(|candidate| !is_other_recording(candidate, &song.name)).collect();
+        best_candidate(&candidates, &song.artist, &song.name).ok_or_else(|| {
+            if filter.is_active() {
+                "No result meeting the filters found for the specified artist and track.".into()
+            } else {
+                "No result found for the specified artist and track.".into()
+            }
+        })
+    }
+
+    fn create_playlist(&self, ctx: &Context, name: &str, description: &str) -> Result<NewPlaylist, PilotError> {
+        let user_id = self.session.get().map(|session| session.user_id).ok_or("No Tidal session")?;
+        let response = check_write(
+            send(
+                ctx.client
+                    .post(format!("{}/users/{}/playlists", TIDAL_API_URL, user_id))
+                    .query(&[("countryCode", self.country_code())])
+                    .header("Authorization", format!("Bearer {}", ctx.access_token))
+                    .form(&[("title", name), ("description", description)])
+            ),
+            "creating playlist",
+        )?;
+        let created: TidalCreatedPlaylist = response.json().map_err(|e| format!("Failed to parse the Tidal response: {}", e))?;
+        let url = format!("https://tidal.com/browse/playlist/{}", created.uuid);
+        Ok(NewPlaylist { id: created.uuid, url })
+    }
+
+    // Each write names the playlist version it applies to, so the version is read again before every batch
+    fn add_to_playlist(&self, ctx: &Context, playlist_id: &str, uris: Vec<String>, position: Option<usize>) -> Result<(), PilotError> {
+        for (index, batch) in uris.chunks(ADD_BATCH_SIZE).enumerate() {
+            let etag = self.playlist_etag(ctx, playlist_id)?;
+            let track_ids: Vec<&str> = batch.iter().map(|uri| track_id(uri)).collect();
+            let mut form = vec![("trackIds", track_ids.join(",")), ("onDupes", "ADD".to_string())];
+            if let Some(position) = position {
+                form.push(("toIndex", (position + index * ADD_BATCH_SIZE).to_string()));
+            }
+            check_write(
+                send(
+                    ctx.client
+                        .post(format!("{}/playlists/{}/items", TIDAL_API_URL, playlist_id))
+                        .query(&[("countryCode", self.country_code())])
+                        .header("Authorization", format!("Bearer {}", ctx.access_token))
+                        .header("If-None-Match", etag)
+                        .form(&form)
+                ),
+                "adding tracks to playlist",
+            )?;
+        }
+        Ok(())
+    }
+
+    fn backup_playlist(&self, ctx: &Context, playlist_id: &str) -> Result<PathBuf, PilotError> {
+        let playlist = self.get_playlist(ctx, playlist_id)?;
+        save_backup(&Backup {
+            playlist_id: playlist_id.to_string(),
+            snapshot_id: String::new(),
+            created_at: now(),
+            items: playlist
+                .tracks
+                .into_iter()
+                .map(|track| BackupItem {
+                    uri: track.uri,
+                    name: track.name,
+                    artists: track.artists.into_iter().map(|a| a.name).collect(),
+                    is_local: false,
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tidal_track_keeps_the_version_in_the_title() {
+        let track: TidalTrack = serde_json::from_value(serde_json::json!({
+            "id": 42,
+            "title": "Come Together",
+            "version": "Remastered 2009",
+            "duration": 259,
+            "explicit": false,
+            "popularity": 80,
+            "isrc": "GBAYE0601690",
+            "artists": [{ "name": "The Beatles" }],
+            "album": { "title": "Abbey Road", "releaseDate": "1969-09-26" }
+        }))
+        .unwrap();
+        let track = tidal_track(track);
+        assert_eq!(track.name, "Come Together (Remastered 2009)");
+        assert_eq!(track.uri, "tidal:track:42");
+        assert_eq!((track.duration_ms, track.album.release_year()), (259_000, Some(1969)));
+        assert_eq!(track_id(&track.uri), "42");
+    }
+}
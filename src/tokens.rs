@@ -0,0 +1,107 @@
+// Import necessary modules and crates
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::PilotError;
+use crate::models::*;
+use crate::secrets::*;
+
+// Function to get the current time as a Unix timestamp in seconds
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+// Function to get the year, month and day of a number of days since the Unix epoch, after Howard Hinnant's civil_from_days
+pub fn civil_date(days: i64) -> (i64, u32, u32) {
+    let shifted = days + 719468;
+    let era = shifted.div_euclid(146097);
+    let day_of_era = shifted.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// Function to get the number of days since the Unix epoch of a year, month and day, the inverse of civil_date
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_index = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let day_of_year = (153 * month_index + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// Function to format a Unix time as an RFC 3339 date in UTC, e.g. "2024-05-21T18:03:12Z"
+pub fn iso_date(timestamp: u64) -> String {
+    let (year, month, day) = civil_date((timestamp / 86400) as i64);
+    let seconds = timestamp % 86400;
+    format!("{}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, seconds / 3600, seconds % 3600 / 60, seconds % 60)
+}
+
+// Function to get the date a number of months before a Unix time, as "YYYY-MM-DD"
+// Days past the 28th become the 28th, so the date exists in every month.
+pub fn months_before(timestamp: u64, months: u32) -> String {
+    let (year, month, day) = civil_date((timestamp / 86400) as i64);
+    let index = year * 12 + month as i64 - 1 - months as i64;
+    format!("{}-{:02}-{:02}", index.div_euclid(12), index.rem_euclid(12) + 1, day.min(28))
+}
+
+// Function to get the directory where PlaylistPilot keeps its local state
+// Follows the platform convention, e.g. $XDG_DATA_HOME/playlistpilot on Linux.
+pub fn data_dir() -> Result<PathBuf, PilotError> {
+    dirs::data_dir()
+        .map(|dir| dir.join("playlistpilot"))
+        .ok_or_else(|| "Could not determine the data directory".into())
+}
+
+// Function to get the path of the token cache file
+fn token_file() -> Result<PathBuf, PilotError> {
+    Ok(data_dir()?.join("tokens.json"))
+}
+
+// Function to read every cached token, keyed by profile and Spotify client ID
+fn load_tokens() -> HashMap<String, CachedToken> {
+    token_file()
+        .ok()
+        .and_then(|path| read_private_file(&path))
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+// Function to get the name under which a client's refresh token is kept in the secret store
+fn refresh_token_secret(cache_key: &str) -> String {
+    format!("spotify-refresh-token:{}", cache_key)
+}
+
+// Function to get the cached token for a Spotify client ID, if any
+// The refresh token lives in the secret store, the rest of the token in the cache file.
+pub fn load_token(cache_key: &str) -> Option<CachedToken> {
+    let mut token = load_tokens().remove(cache_key)?;
+    if token.refresh_token.is_none() {
+        token.refresh_token = load_secret(&refresh_token_secret(cache_key));
+    }
+    Some(token)
+}
+
+// Function to store the token for a Spotify client ID, keeping the other entries
+pub fn save_token(cache_key: &str, token: &CachedToken) -> Result<(), PilotError> {
+    let mut token = token.clone();
+    if let Some(refresh_token) = token.refresh_token.take() {
+        save_secret(&refresh_token_secret(cache_key), &refresh_token)?;
+    }
+
+    let mut tokens = load_tokens();
+    tokens.insert(cache_key.to_string(), token);
+
+    let content = serde_json::to_string_pretty(&tokens).map_err(|e| e.to_string())?;
+    write_private_file(&token_file()?, &content)
+}
@@ -0,0 +1,325 @@
+// Import necessary modules and crates
+use reqwest::blocking::Client;
+use reqwest::Url;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tracing::warn;
+
+use crate::auth::{authorization_code, generate_state};
+use crate::backup::{save_backup, Backup, BackupItem};
+use crate::config::Config;
+use crate::context::Context;
+use crate::error::PilotError;
+use crate::http::send;
+use crate::matching::*;
+use crate::models::*;
+use crate::service::{MusicService, NewPlaylist, ServicePlaylist};
+use crate::tokens::*;
+
+// Endpoints of the Google authorization server and of the YouTube Data API
+const GOOGLE_AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const YOUTUBE_API_URL: &str = "https://www.googleapis.com/youtube/v3";
+
+// Scope requested from the user: managing their YouTube (and so YouTube Music) playlists
+const YOUTUBE_SCOPE: &str = "https://www.googleapis.com/auth/youtube";
+
+// Prefix of the track URIs, to tell YouTube videos apart from Spotify tracks in the history
+const URI_PREFIX: &str = "ytmusic:video:";
+
+// YouTube category of music videos
+const MUSIC_CATEGORY: &str = "10";
+
+// Suffix of the channels YouTube Music generates for an artist's official tracks, e.g. "Portishead - Topic"
+const TOPIC_SUFFIX: &str = " - Topic";
+
+// Words marking a bracketed part of a video title as a video tag rather than part of the song title
+const VIDEO_TAG_WORDS: [&str; 6] = ["official", "video", "audio", "lyric", "lyrics", "visualizer"];
+
+// YouTube Music, through the YouTube Data API: its playlists are YouTube playlists of music videos
+pub struct YtMusic;
+
+// Function to decode the HTML entities YouTube leaves in titles, e.g. "&amp;" or "&#39;"
+fn decode_entities(text: &str) -> String {
+    text.replace("&quot;", "\"").replace("&#39;", "'").replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+}
+
+// Function to remove the video tags of a title, e.g. "Glory Box (Official Video)" becomes "Glory Box"
+fn strip_video_tags(title: &str) -> String {
+    let mut result = title.to_string();
+    for (open, close) in [('(', ')'), ('[', ']')] {
+        while let Some(start) = result.find(open) {
+            let Some(length) = result[start..].find(close) else { break };
+            let part = normalize(&result[start..start + length]);
+            if !part.split(' ').any(|word| VIDEO_TAG_WORDS.contains(&word)) {
+                break;
+            }
+            result.replace_range(start..start + length + 1, "");
+        }
+    }
+    result.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// Function to describe a video as a track: its title and artist, guessed from the channel and the video title
+// Topic channels give both exactly; other videos are usually titled "Artist - Title (Official Video)".
+fn video_track(video_id: &str, title: &str, channel: &str) -> Track {
+    let title = decode_entities(title);
+    let (artist, name) = match channel.strip_suffix(TOPIC_SUFFIX) {
+        Some(artist) => (artist.to_string(), title),
+        None => match title.split_once(" - ") {
+            Some((artist, name)) => (artist.to_string(), strip_video_tags(name)),
+            None => (channel.trim_end_matches("VEVO").to_string(), strip_video_tags(&title)),
+        },
+    };
+    Track {
+        name,
+        artists: vec![Artist { name: artist, id: None }],
+        uri: format!("{}{}", URI_PREFIX, video_id),
+        is_local: false,
+        external_ids: ExternalIds::default(),
+        is_playable: None,
+        duration_ms: 0,
+        popularity: None,
+        album: Album::default(),
+        explicit: false,
+    }
+}
+
+// Function to get the video ID of a track URI
+fn video_id(uri: &str) -> &str {
+    uri.strip_prefix(URI_PREFIX).unwrap_or(uri)
+}
+
+// Function to call the YouTube Data API on behalf of the user
+fn youtube_get<T: serde::de::DeserializeOwned>(ctx: &Context, path: &str, params: &[(&str, &str)]) -> Result<T, PilotError> {
+    let response = send(
+        ctx.client
+            .get(format!("{}/{}", YOUTUBE_API_URL, path))
+            .query(params)
+            .header("Authorization", format!("Bearer {}", ctx.access_token))
+    )?;
+    if !response.status().is_success() {
+        return Err(PilotError::from_status(response.status(), format!("Error calling YouTube: {}", response.status())));
+    }
+    response.json().map_err(|e| format!("Failed to parse the YouTube response: {}", e).into())
+}
+
+// Function to exchange an authorization code or a refresh token for an access token
+fn request_token(client: &Client, config: &Config, grant: &[(&str, &str)]) -> Result<GoogleAuthResponse, PilotError> {
+    let client_id = config.google_client_id.as_deref().ok_or("google client id not set")?;
+    let client_secret = config.google_client_secret.as_deref().ok_or("google client secret not set")?;
+    let mut body: HashMap<&str, &str> = grant.iter().copied().collect();
+    body.insert("client_id", client_id);
+    body.insert("client_secret", client_secret);
+    let response = send(client.post(GOOGLE_TOKEN_URL).form(&body))?;
+    if !response.status().is_success() {
+        return Err(PilotError::Auth(format!("Failed to get a Google access token: {}", response.status())));
+    }
+    Ok(response.json()?)
+}
+
+// Function to store a token response in the cache and return its access token
+fn cache_token(cache_key: &str, response: GoogleAuthResponse, previous_refresh_token: Option<String>) -> String {
+    let token = CachedToken {
+        access_token: response.access_token,
+        refresh_token: response.refresh_token.or(previous_refresh_token),
+        expires_at: now() + response.expires_in,
+        scope: YOUTUBE_SCOPE.to_string(),
+    };
+    if let Err(e) = save_token(cache_key, &token) {
+        warn!("Could not save the access token: {}", e);
+    }
+    token.access_token
+}
+
+impl MusicService for YtMusic {
+    fn name(&self) -> &'static str {
+        "YouTube Music"
+    }
+
+    // YouTube has no app-only access to playlists, so even a dry run needs the user's authorization
+    fn access_token(&self, client: &Client, config: &Config, headless: bool, _read_only: bool) -> Result<String, PilotError> {
+        let client_id = config.google_client_id.as_deref().ok_or("google client id not set")?;
+        let cache_key = config.storage_key(&format!("google:{}", client_id));
+        if let Some(token) = load_token(&cache_key).filter(|token| token.scope == YOUTUBE_SCOPE) {
+            if token.expires_at > now() + 60 {
+                return Ok(token.access_token);
+            }
+            if let Some(refresh_token) = &token.refresh_token {
+                match request_token(client, config, &[("grant_type", "refresh_token"), ("refresh_token", refresh_token)]) {
+                    Ok(response) => return Ok(cache_token(&cache_key, response, Some(refresh_token.clone()))),
+                    Err(e) => warn!("{}, authorizing again.", e),
+                }
+            }
+        }
+
+        // Offline access with an explicit consent is the only way Google gives a refresh token
+        let auth = generate_state();
+        let redirect_uri = config.google_redirect_uri.as_str();
+        let auth_url = Url::parse_with_params(
+            GOOGLE_AUTH_URL,
+            &[
+                ("response_type", "code"),
+                ("client_id", client_id),
+                ("scope", YOUTUBE_SCOPE),
+                ("redirect_uri", redirect_uri),
+                ("state", &auth.state),
+                ("access_type", "offline"),
+                ("prompt", "consent"),
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        let code = authorization_code(auth_url.as_str(), redirect_uri, &auth, headless)?;
+        let response = request_token(
+            client,
+            config,
+            &[("grant_type", "authorization_code"), ("code", &code), ("redirect_uri", redirect_uri)],
+        )?;
+        Ok(cache_token(&cache_key, response, None))
+    }
+
+    // Videos that were deleted or made private are listed without an owner channel, they are skipped
+    fn get_playlist(&self, ctx: &Context, playlist_id: &str) -> Result<ServicePlaylist, PilotError> {
+        let playlists: YoutubePlaylists = youtube_get(ctx, "playlists", &[("part", "snippet"), ("id", playlist_id)])?;
+        let name = playlists
+            .items
+            .into_iter()
+            .next()
+            .map(|playlist| playlist.snippet.title)
+            .ok_or_else(|| format!("Playlist not found: {}", playlist_id))?;
+
+        let mut items = Vec::new();
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut params = vec![("part", "snippet"), ("playlistId", playlist_id), ("maxResults", "50")];
+            if let Some(token) = &page_token {
+                params.push(("pageToken", token));
+            }
+            let page: YoutubePlaylistItems = youtube_get(ctx, "playlistItems", &params)?;
+            items.extend(page.items);
+            match page.next_page_token {
+                Some(token) => page_token = Some(token),
+                None => break,
+            }
+        }
+
+        let length = items.len();
+        let tracks: Vec<Track> = items
+            .into_iter()
+            .filter_map(|item| {
+                let channel = item.snippet.video_owner_channel_title?;
+                Some(video_track(&item.snippet.resource_id.video_id, &item.snippet.title, &channel))
+            })
+            .collect();
+        let skipped = length - tracks.len();
+        Ok(ServicePlaylist { name, snapshot_id: None, length, tracks, skipped })
+    }
+
+    // Each search costs 100 units of the API's daily quota of 10,000, so a single search of music videos is run
+    fn search_song(&self, ctx: &Context, song: &Song, filter: &TrackFilter) -> Result<TrackMatch, PilotError> {
+        let query = format!("{} {}", song.artist, song.name);
+        let results: YoutubeSearchResponse = youtube_get(
+            ctx,
+            "search",
+            &[("part", "snippet"), ("q", &query), ("type", "video"), ("videoCategoryId", MUSIC_CATEGORY), ("maxResults", "10")],
+        )?;
+        let candidates: Vec<Track> = results
+            .items
+            .iter()
+            .filter_map(|item| Some(video_track(item.id.video_id.as_deref()?, &item.snippet.title, &item.snippet.channel_title)))
+            .filter(|candidate| filter.allows(candidate) && !is_other_recording(candidate, &song.name))
+            .collect();
+        best_candidate(&candidates, &song.artist, &song.name)
+            .ok_or_else(|| "No result found for the specified artist and track.".into())
+    }
+
+    fn create_playlist(&self, ctx: &Context, name: &str, description: &str) -> Result<NewPlaylist, PilotError> {
+        let body = YoutubePlaylistRequest {
+            snippet: YoutubePlaylistSnippet { title: name.to_string(), description: description.to_string() },
+            status: YoutubeStatus { privacy_status: "private".to_string() },
+        };
+        let response = send(
+            ctx.client
+                .post(format!("{}/playlists", YOUTUBE_API_URL))
+                .query(&[("part", "snippet,status")])
+                .header("Authorization", format!("Bearer {}", ctx.access_token))
+                .json(&body)
+        )?;
+        if !response.status().is_success() {
+            return Err(PilotError::from_status(response.status(), format!("Error creating playlist: {}", response.status())));
+        }
+        let created: YoutubeCreatedPlaylist = response.json().map_err(|e| format!("Failed to parse the YouTube response: {}", e))?;
+        let url = format!("https://music.youtube.com/playlist?list={}", created.id);
+        Ok(NewPlaylist { id: created.id, url })
+    }
+
+    // Videos are added one per call, a failed one doesn't stop the following ones
+    fn add_to_playlist(&self, ctx: &Context, playlist_id: &str, uris: Vec<String>, position: Option<usize>) -> Result<(), PilotError> {
+        let mut failures = Vec::new();
+        for (index, uri) in uris.iter().enumerate() {
+            let body = YoutubePlaylistItemRequest {
+                snippet: YoutubePlaylistItemSnippet {
+                    playlist_id: playlist_id.to_string(),
+                    position: position.map(|position| position + index),
+                    resource_id: YoutubeResourceId { kind: "youtube#video".to_string(), video_id: video_id(uri).to_string() },
+                },
+            };
+            let response = send(
+                ctx.client
+                    .post(format!("{}/playlistItems", YOUTUBE_API_URL))
+                    .query(&[("part", "snippet")])
+                    .header("Authorization", format!("Bearer {}", ctx.access_token))
+                    .json(&body)
+            );
+            match response {
+                Ok(resp) if resp.status().is_success() => {},
+                Ok(resp) => failures.push(format!("{}: {}", uri, resp.status())),
+                Err(e) => failures.push(format!("{}: {}", uri, e)),
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Failed to add tracks to playlist: {}", failures.join(", ")).into())
+        }
+    }
+
+    fn backup_playlist(&self, ctx: &Context, playlist_id: &str) -> Result<PathBuf, PilotError> {
+        let playlist = self.get_playlist(ctx, playlist_id)?;
+        save_backup(&Backup {
+            playlist_id: playlist_id.to_string(),
+            snapshot_id: String::new(),
+            created_at: now(),
+            items: playlist
+                .tracks
+                .into_iter()
+                .map(|track| BackupItem {
+                    uri: track.uri,
+                    name: track.name,
+                    artists: track.artists.into_iter().map(|a| a.name).collect(),
+                    is_local: false,
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn video_track_reads_topic_channels_and_video_titles() {
+        let topic = video_track("a", "Glory Box", "Portishead - Topic");
+        assert_eq!((topic.name.as_str(), topic.artists[0].name.as_str()), ("Glory Box", "Portishead"));
+
+        let video = video_track("b", "Massive Attack - Teardrop (Official Video)", "MassiveAttackVEVO");
+        assert_eq!((video.name.as_str(), video.artists[0].name.as_str()), ("Teardrop", "Massive Attack"));
+        assert_eq!(video.uri, "ytmusic:video:b");
+
+        let untitled = video_track("c", "Roads [Official Audio] (Live)", "PortisheadVEVO");
+        assert_eq!((untitled.name.as_str(), untitled.artists[0].name.as_str()), ("Roads (Live)", "Portishead"));
+        assert_eq!(decode_entities("Simon &amp; Garfunkel&#39;s"), "Simon & Garfunkel's");
+    }
+}